@@ -102,6 +102,28 @@ pub fn get_encryption_key(password: &str, salt: &str) -> Result<LessSafeKey> {
     Ok(LessSafeKey::new(key))
 }
 
+/// ChaCha20-Poly1305 key for 32 bytes of uniformly random key material, e.g.
+/// the hash of a shared secret, that needs no key stretching
+pub fn get_key(key: &[u8; 32]) -> LessSafeKey {
+    LessSafeKey::new(
+        UnboundKey::new(&ring::aead::CHACHA20_POLY1305, key).expect("Key has the correct length"),
+    )
+}
+
+/// Hashes a password for storage with Argon2, so that an attacker who reads the
+/// hash still has to spend memory-hard work on every guess.
+///
+/// * `salt` - Random bytes, at least 8 and unique per stored password
+pub fn hash_password(password: &str, salt: &[u8]) -> Result<[u8; 32]> {
+    let mut hash = [0u8; 32];
+
+    argon2()
+        .hash_password_into(password.as_bytes(), salt, &mut hash)
+        .map_err(|e| format_err!("could not hash password").context(e))?;
+
+    Ok(hash)
+}
+
 /// Generates a B64-encoded random salt string of the recommended 16 byte length
 pub fn random_salt() -> String {
     SaltString::generate(OsRng).to_string()
@@ -119,7 +141,7 @@ fn argon2() -> Argon2<'static> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{decrypt, encrypt, get_encryption_key};
+    use crate::{decrypt, encrypt, get_encryption_key, hash_password};
 
     #[test]
     fn encrypts_and_decrypts() {
@@ -133,4 +155,13 @@ mod tests {
 
         assert_eq!(decrypted, message.as_bytes());
     }
+
+    #[test]
+    fn hashes_passwords_with_salt() {
+        let hash = hash_password("test123", b"salt1235").unwrap();
+
+        assert_eq!(hash, hash_password("test123", b"salt1235").unwrap());
+        assert_ne!(hash, hash_password("test124", b"salt1235").unwrap());
+        assert_ne!(hash, hash_password("test123", b"salt1236").unwrap());
+    }
 }
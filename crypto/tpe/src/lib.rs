@@ -1,20 +1,47 @@
 use std::collections::BTreeMap;
+use std::fmt;
 use std::ops::Mul;
 
 use bitcoin_hashes::{sha256, Hash};
-use bls12_381::{pairing, G1Projective, G2Projective, Scalar};
+use bls12_381::{multi_miller_loop, pairing, G1Projective, G2Prepared, G2Projective, Gt, Scalar};
 pub use bls12_381::{G1Affine, G2Affine};
+use fedimint_aead::LessSafeKey;
 use fedimint_core::bls12_381_serde;
 use fedimint_core::encoding::{Decodable, Encodable};
 use group::ff::Field;
 use group::{Curve, Group};
+use rand::rngs::OsRng;
 use rand_chacha::rand_core::SeedableRng;
 use rand_chacha::ChaChaRng;
 use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Encodable, Decodable, Serialize, Deserialize)]
+/// The secret key share of a peer is zeroized when dropped and redacted from
+/// the debug output. Comparing shares and multiplying points with them runs
+/// in constant time.
+#[derive(Clone, Eq, PartialEq, Encodable, Decodable, Serialize, Deserialize)]
 pub struct SecretKeyShare(#[serde(with = "bls12_381_serde::scalar")] pub Scalar);
 
+impl fmt::Debug for SecretKeyShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretKeyShare(<redacted>)")
+    }
+}
+
+impl Zeroize for SecretKeyShare {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Drop for SecretKeyShare {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SecretKeyShare {}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Encodable, Decodable, Serialize, Deserialize)]
 pub struct PublicKeyShare(#[serde(with = "bls12_381_serde::g1")] pub G1Affine);
 
@@ -41,6 +68,26 @@ pub struct CipherText {
     pub signature: EphemeralSignature,
 }
 
+/// Reason a ciphertext or decryption key received from the network or the
+/// input to a resharing is rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum TpeError {
+    #[error("The ciphertext is invalid")]
+    InvalidCiphertext,
+    #[error("The decryption key share is invalid")]
+    InvalidShare,
+    #[error("The aggregate decryption key is invalid")]
+    InvalidAggregate,
+    #[error("The threshold has to be at least one and at most the number of new peers")]
+    InvalidThreshold,
+    #[error("The index of a peer has to be at least one")]
+    InvalidIndex,
+    #[error("There are no shares to combine")]
+    NoShares,
+    #[error("The commitments are for different thresholds")]
+    MismatchedCommitments,
+}
+
 pub fn verify_ciphertext(ct: &CipherText, commitment: &sha256::Hash) -> bool {
     let message = hash_to_message(&ct.encrypted_preimage, &ct.pk.0, commitment);
 
@@ -55,12 +102,11 @@ pub fn derive_agg_decryption_key(
     agg_pk: &AggregatePublicKey,
     encryption_seed: &[u8; 32],
 ) -> AggregateDecryptionKey {
-    AggregateDecryptionKey(
-        agg_pk
-            .0
-            .mul(derive_ephemeral_sk(encryption_seed))
-            .to_affine(),
-    )
+    let mut ephemeral_sk = derive_ephemeral_sk(encryption_seed);
+    let agg_dk = AggregateDecryptionKey(agg_pk.0.mul(ephemeral_sk).to_affine());
+    ephemeral_sk.zeroize();
+
+    agg_dk
 }
 
 fn derive_ephemeral_sk(encryption_seed: &[u8; 32]) -> Scalar {
@@ -76,11 +122,12 @@ pub fn encrypt_preimage(
     let agg_dk = derive_agg_decryption_key(agg_pk, encryption_seed);
     let encrypted_preimage = xor_with_hash(*preimage, &agg_dk);
 
-    let ephemeral_sk = derive_ephemeral_sk(encryption_seed);
+    let mut ephemeral_sk = derive_ephemeral_sk(encryption_seed);
     let ephemeral_pk = G1Projective::generator().mul(ephemeral_sk).to_affine();
     let ephemeral_signature = hash_to_message(&encrypted_preimage, &ephemeral_pk, commitment)
         .mul(ephemeral_sk)
         .to_affine();
+    ephemeral_sk.zeroize();
 
     CipherText {
         encrypted_preimage,
@@ -100,6 +147,31 @@ pub fn verify_agg_decryption_key(
     pairing(&agg_dk.0, &message) == pairing(&agg_pk.0, &ct.signature.0)
 }
 
+/// Checks the ciphertext before the aggregate decryption key, since a key can
+/// only be verified for a valid ciphertext
+pub fn check_agg_decryption_key(
+    agg_pk: &AggregatePublicKey,
+    agg_dk: &AggregateDecryptionKey,
+    ct: &CipherText,
+    commitment: &sha256::Hash,
+) -> Result<(), TpeError> {
+    check_ciphertext(ct, commitment)?;
+
+    if !verify_agg_decryption_key(agg_pk, agg_dk, ct, commitment) {
+        return Err(TpeError::InvalidAggregate);
+    }
+
+    Ok(())
+}
+
+pub fn check_ciphertext(ct: &CipherText, commitment: &sha256::Hash) -> Result<(), TpeError> {
+    if !verify_ciphertext(ct, commitment) {
+        return Err(TpeError::InvalidCiphertext);
+    }
+
+    Ok(())
+}
+
 pub fn create_decryption_key_share(sks: &SecretKeyShare, ct: &CipherText) -> DecryptionKeyShare {
     DecryptionKeyShare(ct.pk.0.mul(sks.0).to_affine())
 }
@@ -115,6 +187,82 @@ pub fn verify_decryption_key_share(
     pairing(&dks.0, &message) == pairing(&pks.0, &ct.signature.0)
 }
 
+/// Checks the ciphertext before the decryption key share, since a share can
+/// only be verified for a valid ciphertext
+pub fn check_decryption_key_share(
+    pks: &PublicKeyShare,
+    dks: &DecryptionKeyShare,
+    ct: &CipherText,
+    commitment: &sha256::Hash,
+) -> Result<(), TpeError> {
+    check_ciphertext(ct, commitment)?;
+
+    if !verify_decryption_key_share(pks, dks, ct, commitment) {
+        return Err(TpeError::InvalidShare);
+    }
+
+    Ok(())
+}
+
+/// Verifies the decryption key shares of one ciphertext with two pairings in
+/// total instead of two per share by checking a random linear combination of
+/// the shares. If this fails at least one share is invalid, which has to be
+/// found with [`verify_decryption_key_share`]. An empty slice of shares is
+/// rejected, since the check would pass trivially.
+pub fn verify_decryption_key_shares(
+    shares: &[(PublicKeyShare, DecryptionKeyShare)],
+    ct: &CipherText,
+    commitment: &sha256::Hash,
+) -> bool {
+    verify_decryption_key_shares_batch(&[(shares, ct, commitment)])
+}
+
+/// Decryption key shares of a ciphertext together with the public key shares
+/// of their peers, the ciphertext and its commitment
+pub type SharesOfCiphertext<'a> = (
+    &'a [(PublicKeyShare, DecryptionKeyShare)],
+    &'a CipherText,
+    &'a sha256::Hash,
+);
+
+/// Verifies the decryption key shares of multiple ciphertexts at once. The
+/// shares of each ciphertext are combined as in
+/// [`verify_decryption_key_shares`] and the resulting pairings of all
+/// ciphertexts share a single final exponentiation. The batch is rejected if
+/// it or the shares of any ciphertext are empty.
+pub fn verify_decryption_key_shares_batch(batch: &[SharesOfCiphertext]) -> bool {
+    if batch.is_empty() || batch.iter().any(|(shares, ..)| shares.is_empty()) {
+        return false;
+    }
+
+    let mut g1_points = vec![];
+    let mut g2_points = vec![];
+
+    for (shares, ct, commitment) in batch {
+        let (pks_sum, dks_sum) = shares.iter().fold(
+            (G1Projective::identity(), G1Projective::identity()),
+            |(pks_sum, dks_sum), (pks, dks)| {
+                let coefficient = Scalar::random(&mut OsRng);
+
+                (pks_sum + pks.0 * coefficient, dks_sum + dks.0 * coefficient)
+            },
+        );
+
+        let message = hash_to_message(&ct.encrypted_preimage, &ct.pk.0, commitment);
+
+        // e(dks_sum, message) == e(pks_sum, signature) for every ciphertext
+        // iff the product of e(dks_sum, message) * e(-pks_sum, signature) is one
+        g1_points.push(dks_sum.to_affine());
+        g2_points.push(G2Prepared::from(message));
+        g1_points.push((-pks_sum).to_affine());
+        g2_points.push(G2Prepared::from(ct.signature.0));
+    }
+
+    let terms = g1_points.iter().zip(g2_points.iter()).collect::<Vec<_>>();
+
+    multi_miller_loop(&terms).final_exponentiation() == Gt::identity()
+}
+
 fn xor_with_hash(mut bytes: [u8; 32], agg_dk: &AggregateDecryptionKey) -> [u8; 32] {
     let hash = agg_dk.consensus_hash::<sha256::Hash>();
 
@@ -142,6 +290,112 @@ fn hash_to_message(
     G2Projective::random(&mut ChaChaRng::from_seed(seed)).to_affine()
 }
 
+/// Payload of arbitrary length encrypted to the aggregate public key, e.g. a
+/// backup. The decryption key is derived, shared and verified as for a
+/// [`CipherText`], but it only yields the ChaCha20-Poly1305 key the payload is
+/// encrypted with.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Encodable, Decodable, Serialize, Deserialize)]
+pub struct PayloadCipherText {
+    /// Nonce followed by the encrypted payload and its tag
+    pub encrypted_payload: Vec<u8>,
+    pub pk: EphemeralPublicKey,
+    pub signature: EphemeralSignature,
+}
+
+pub fn encrypt_payload(
+    agg_pk: &AggregatePublicKey,
+    encryption_seed: &[u8; 32],
+    payload: &[u8],
+    commitment: &sha256::Hash,
+) -> PayloadCipherText {
+    let agg_dk = derive_agg_decryption_key(agg_pk, encryption_seed);
+    let encrypted_payload = fedimint_aead::encrypt(payload.to_vec(), &payload_key(&agg_dk))
+        .expect("Encryption with a valid key can't fail");
+
+    let mut ephemeral_sk = derive_ephemeral_sk(encryption_seed);
+    let ephemeral_pk = G1Projective::generator().mul(ephemeral_sk).to_affine();
+    let ephemeral_signature =
+        hash_to_payload_message(&encrypted_payload, &ephemeral_pk, commitment)
+            .mul(ephemeral_sk)
+            .to_affine();
+    ephemeral_sk.zeroize();
+
+    PayloadCipherText {
+        encrypted_payload,
+        pk: EphemeralPublicKey(ephemeral_pk),
+        signature: EphemeralSignature(ephemeral_signature),
+    }
+}
+
+pub fn verify_payload_ciphertext(ct: &PayloadCipherText, commitment: &sha256::Hash) -> bool {
+    let message = hash_to_payload_message(&ct.encrypted_payload, &ct.pk.0, commitment);
+
+    pairing(&G1Affine::generator(), &ct.signature.0) == pairing(&ct.pk.0, &message)
+}
+
+/// Returns `None` if the payload was not encrypted with the key
+pub fn decrypt_payload(ct: &PayloadCipherText, agg_dk: &AggregateDecryptionKey) -> Option<Vec<u8>> {
+    let mut encrypted_payload = ct.encrypted_payload.clone();
+
+    fedimint_aead::decrypt(&mut encrypted_payload, &payload_key(agg_dk))
+        .ok()
+        .map(<[u8]>::to_vec)
+}
+
+pub fn create_payload_decryption_key_share(
+    sks: &SecretKeyShare,
+    ct: &PayloadCipherText,
+) -> DecryptionKeyShare {
+    DecryptionKeyShare(ct.pk.0.mul(sks.0).to_affine())
+}
+
+pub fn verify_payload_decryption_key_share(
+    pks: &PublicKeyShare,
+    dks: &DecryptionKeyShare,
+    ct: &PayloadCipherText,
+    commitment: &sha256::Hash,
+) -> bool {
+    let message = hash_to_payload_message(&ct.encrypted_payload, &ct.pk.0, commitment);
+
+    pairing(&dks.0, &message) == pairing(&pks.0, &ct.signature.0)
+}
+
+pub fn verify_payload_agg_decryption_key(
+    agg_pk: &AggregatePublicKey,
+    agg_dk: &AggregateDecryptionKey,
+    ct: &PayloadCipherText,
+    commitment: &sha256::Hash,
+) -> bool {
+    let message = hash_to_payload_message(&ct.encrypted_payload, &ct.pk.0, commitment);
+
+    pairing(&agg_dk.0, &message) == pairing(&agg_pk.0, &ct.signature.0)
+}
+
+fn payload_key(agg_dk: &AggregateDecryptionKey) -> LessSafeKey {
+    let key = ("FEDIMINT_TPE_BLS12_381_PAYLOAD_KEY", *agg_dk).consensus_hash::<sha256::Hash>();
+
+    fedimint_aead::get_key(&key.to_byte_array())
+}
+
+/// Separated from [`hash_to_message`] by its tag, so a payload ciphertext
+/// can't be passed off as the ciphertext of a preimage
+fn hash_to_payload_message(
+    encrypted_payload: &[u8],
+    ephemeral_pk: &G1Affine,
+    commitment: &sha256::Hash,
+) -> G2Affine {
+    let message = (
+        "FEDIMINT_TPE_BLS12_381_PAYLOAD_MESSAGE",
+        sha256::Hash::hash(encrypted_payload),
+        *ephemeral_pk,
+        *commitment,
+    );
+
+    let seed = message.consensus_hash::<sha256::Hash>().to_byte_array();
+
+    G2Projective::random(&mut ChaChaRng::from_seed(seed)).to_affine()
+}
+
 pub fn aggregate_decryption_shares(
     shares: &BTreeMap<u64, DecryptionKeyShare>,
 ) -> AggregateDecryptionKey {
@@ -164,12 +418,135 @@ fn lagrange_multipliers(scalars: Vec<Scalar>) -> Vec<Scalar> {
                 .iter()
                 .filter(|j| *j != i)
                 .map(|j| j * (j - i).invert().expect("We filtered the case j == i"))
-                .reduce(|a, b| a * b)
-                .expect("We have at least one share")
+                .fold(Scalar::one(), |a, b| a * b)
         })
         .collect()
 }
 
+/// Commitment to the polynomial a peer re-deals its secret key share with. The
+/// first coefficient commits to the share itself, so the aggregate public key
+/// doesn't change when the peer set is replaced.
+#[derive(Clone, Debug, Eq, PartialEq, Encodable, Decodable)]
+pub struct ResharingCommitment(pub Vec<G1Affine>);
+
+/// Re-deals the secret key share of an old peer to the new peers, of which
+/// `threshold` have to cooperate to decrypt. Returns the commitment to
+/// broadcast and the share to send privately to every new peer, keyed by the
+/// index of the new peer as in [`aggregate_decryption_shares`]. A new peer
+/// can't have index zero, since the share for index zero is the secret key
+/// share being re-dealt.
+pub fn deal_resharing(
+    sks: &SecretKeyShare,
+    threshold: usize,
+    new_peers: &[u64],
+) -> Result<(ResharingCommitment, BTreeMap<u64, SecretKeyShare>), TpeError> {
+    if threshold == 0 || new_peers.len() < threshold {
+        return Err(TpeError::InvalidThreshold);
+    }
+
+    if new_peers.contains(&0) {
+        return Err(TpeError::InvalidIndex);
+    }
+
+    let mut poly: Vec<Scalar> = std::iter::once(sks.0)
+        .chain((1..threshold).map(|_| Scalar::random(&mut OsRng)))
+        .collect();
+
+    let commitment = poly
+        .iter()
+        .map(|coefficient| (G1Projective::generator() * coefficient).to_affine())
+        .collect();
+
+    let shares = new_peers
+        .iter()
+        .map(|index| {
+            let share = poly.iter().rev().fold(Scalar::zero(), |acc, coefficient| {
+                acc * Scalar::from(*index) + coefficient
+            });
+
+            (*index, SecretKeyShare(share))
+        })
+        .collect();
+
+    poly.zeroize();
+
+    Ok((ResharingCommitment(commitment), shares))
+}
+
+/// Verifies the share a new peer received from an old peer against the old
+/// peer's public key share and the commitment it broadcast. The commitment
+/// has to be of a polynomial for the agreed `threshold`, otherwise the old peer
+/// could raise the number of new peers required to decrypt.
+pub fn verify_resharing_share(
+    old_pks: &PublicKeyShare,
+    commitment: &ResharingCommitment,
+    threshold: usize,
+    new_index: u64,
+    sks: &SecretKeyShare,
+) -> bool {
+    new_index != 0
+        && commitment.0.len() == threshold
+        && commitment.0.first() == Some(&old_pks.0)
+        && (G1Projective::generator() * sks.0).to_affine()
+            == eval_commitment(commitment, new_index).to_affine()
+}
+
+/// Combines the verified shares a new peer received from a threshold of old
+/// peers, keyed by the index of the old peer, into its new secret key share
+pub fn combine_resharing_shares(
+    shares: &BTreeMap<u64, SecretKeyShare>,
+) -> Result<SecretKeyShare, TpeError> {
+    if shares.contains_key(&0) {
+        return Err(TpeError::InvalidIndex);
+    }
+
+    lagrange_multipliers(shares.keys().cloned().map(Scalar::from).collect())
+        .into_iter()
+        .zip(shares.values())
+        .map(|(lagrange_multiplier, share)| lagrange_multiplier * share.0)
+        .reduce(|a, b| a + b)
+        .map(SecretKeyShare)
+        .ok_or(TpeError::NoShares)
+}
+
+/// Derives the public key share of a new peer from the commitments of the
+/// same threshold of old peers whose shares it combined
+pub fn derive_resharing_public_key_share(
+    commitments: &BTreeMap<u64, ResharingCommitment>,
+    new_index: u64,
+) -> Result<PublicKeyShare, TpeError> {
+    if new_index == 0 || commitments.contains_key(&0) {
+        return Err(TpeError::InvalidIndex);
+    }
+
+    let mut thresholds = commitments.values().map(|commitment| commitment.0.len());
+    if let Some(threshold) = thresholds.next() {
+        if threshold == 0 || thresholds.any(|other| other != threshold) {
+            return Err(TpeError::MismatchedCommitments);
+        }
+    }
+
+    lagrange_multipliers(commitments.keys().cloned().map(Scalar::from).collect())
+        .into_iter()
+        .zip(commitments.values())
+        .map(|(lagrange_multiplier, commitment)| {
+            eval_commitment(commitment, new_index) * lagrange_multiplier
+        })
+        .reduce(|a, b| a + b)
+        .map(|pks| PublicKeyShare(pks.to_affine()))
+        .ok_or(TpeError::NoShares)
+}
+
+fn eval_commitment(commitment: &ResharingCommitment, index: u64) -> G1Projective {
+    commitment
+        .0
+        .iter()
+        .rev()
+        .fold(G1Projective::identity(), |acc, coefficient| {
+            acc * Scalar::from(index) + coefficient
+        })
+}
+
 macro_rules! impl_hash_with_serialized_compressed {
     ($type:ty) => {
         impl std::hash::Hash for $type {
@@ -198,10 +575,15 @@ mod tests {
     use rand::rngs::OsRng;
 
     use crate::{
-        aggregate_decryption_shares, create_decryption_key_share, decrypt_preimage,
-        derive_agg_decryption_key, encrypt_preimage, verify_agg_decryption_key,
-        verify_decryption_key_share, AggregatePublicKey, DecryptionKeyShare, PublicKeyShare,
-        SecretKeyShare,
+        aggregate_decryption_shares, check_agg_decryption_key, check_decryption_key_share,
+        combine_resharing_shares, create_decryption_key_share, create_payload_decryption_key_share,
+        deal_resharing, decrypt_payload, decrypt_preimage, derive_agg_decryption_key,
+        derive_resharing_public_key_share, encrypt_payload, encrypt_preimage,
+        verify_agg_decryption_key, verify_decryption_key_share, verify_decryption_key_shares,
+        verify_decryption_key_shares_batch, verify_payload_agg_decryption_key,
+        verify_payload_ciphertext, verify_payload_decryption_key_share, verify_resharing_share,
+        AggregateDecryptionKey, AggregatePublicKey, DecryptionKeyShare, PublicKeyShare,
+        ResharingCommitment, SecretKeyShare, TpeError,
     };
 
     fn dealer_keygen(
@@ -273,4 +655,370 @@ mod tests {
 
         assert_eq!(preimage, decrypt_preimage(&ciphertext, &agg_dk));
     }
+
+    #[test]
+    fn test_batch_verification() {
+        let (agg_pk, pks, sks) = dealer_keygen(3, 4);
+
+        let commitment = sha256::Hash::hash(&[0_u8; 32]);
+        let ciphertext = encrypt_preimage(&agg_pk, &[7_u8; 32], &[42_u8; 32], &commitment);
+
+        let mut shares: Vec<(PublicKeyShare, DecryptionKeyShare)> = pks
+            .into_iter()
+            .zip(sks.iter())
+            .map(|(pk, sk)| (pk, create_decryption_key_share(sk, &ciphertext)))
+            .collect();
+
+        assert!(verify_decryption_key_shares(
+            &shares,
+            &ciphertext,
+            &commitment
+        ));
+
+        // A share of another peer fails the batch
+        shares[0].1 = shares[1].1;
+
+        assert!(!verify_decryption_key_shares(
+            &shares,
+            &ciphertext,
+            &commitment
+        ));
+    }
+
+    #[test]
+    fn test_batch_verification_of_multiple_ciphertexts() {
+        let (agg_pk, pks, sks) = dealer_keygen(3, 4);
+
+        let commitments = [
+            sha256::Hash::hash(&[0_u8; 32]),
+            sha256::Hash::hash(&[1_u8; 32]),
+        ];
+        let ciphertexts = [
+            encrypt_preimage(&agg_pk, &[7_u8; 32], &[42_u8; 32], &commitments[0]),
+            encrypt_preimage(&agg_pk, &[8_u8; 32], &[43_u8; 32], &commitments[1]),
+        ];
+
+        let mut shares = ciphertexts
+            .iter()
+            .map(|ciphertext| {
+                pks.iter()
+                    .zip(sks.iter())
+                    .map(|(pk, sk)| (*pk, create_decryption_key_share(sk, ciphertext)))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let batch = |shares: &[Vec<(PublicKeyShare, DecryptionKeyShare)>]| {
+            verify_decryption_key_shares_batch(&[
+                (&shares[0], &ciphertexts[0], &commitments[0]),
+                (&shares[1], &ciphertexts[1], &commitments[1]),
+            ])
+        };
+
+        assert!(batch(&shares));
+
+        // A share for the other ciphertext fails the batch
+        shares[1][0].1 = shares[0][0].1;
+
+        assert!(!batch(&shares));
+
+        // As do ciphertexts without shares
+        assert!(!verify_decryption_key_shares_batch(&[]));
+        assert!(!verify_decryption_key_shares_batch(&[
+            (&shares[0], &ciphertexts[0], &commitments[0]),
+            (&[], &ciphertexts[1], &commitments[1]),
+        ]));
+    }
+
+    #[test]
+    fn test_batch_verification_rejects_no_shares() {
+        let (agg_pk, _, _) = dealer_keygen(3, 4);
+
+        let commitment = sha256::Hash::hash(&[0_u8; 32]);
+        let ciphertext = encrypt_preimage(&agg_pk, &[7_u8; 32], &[42_u8; 32], &commitment);
+
+        assert!(!verify_decryption_key_shares(&[], &ciphertext, &commitment));
+    }
+
+    #[test]
+    fn test_secret_key_share_is_redacted() {
+        let (_, _, sks) = dealer_keygen(3, 4);
+
+        assert_eq!(format!("{:?}", sks[0]), "SecretKeyShare(<redacted>)");
+    }
+
+    #[test]
+    fn test_resharing() {
+        let (agg_pk, old_pks, old_sks) = dealer_keygen(3, 4);
+
+        let new_peers: Vec<u64> = (1..=5).collect();
+
+        // The old peers 2, 3 and 4 re-deal their shares to five new peers
+        let deals: BTreeMap<u64, _> = (2_u64..=4)
+            .map(|index| {
+                let sks = &old_sks[index as usize - 1];
+
+                (
+                    index,
+                    deal_resharing(sks, 4, &new_peers).expect("Valid deal"),
+                )
+            })
+            .collect();
+
+        let commitments = deals
+            .iter()
+            .map(|(index, (commitment, _))| (*index, commitment.clone()))
+            .collect();
+
+        let mut new_pks = vec![];
+        let mut new_sks = vec![];
+
+        for new_index in &new_peers {
+            let shares: BTreeMap<u64, SecretKeyShare> = deals
+                .iter()
+                .map(|(index, (commitment, shares))| {
+                    let share = shares[new_index].clone();
+
+                    assert!(verify_resharing_share(
+                        &old_pks[*index as usize - 1],
+                        commitment,
+                        4,
+                        *new_index,
+                        &share
+                    ));
+
+                    (*index, share)
+                })
+                .collect();
+
+            let sks = combine_resharing_shares(&shares).expect("Shares of three old peers");
+            let pks = derive_resharing_public_key_share(&commitments, *new_index)
+                .expect("Commitments of three old peers");
+
+            assert_eq!(pks.0, (G1Projective::generator() * sks.0).to_affine());
+
+            new_pks.push(pks);
+            new_sks.push(sks);
+        }
+
+        // A share that doesn't match the commitment is rejected
+        let (commitment, shares) = &deals[&2];
+        assert!(!verify_resharing_share(
+            &old_pks[1],
+            commitment,
+            4,
+            1,
+            &shares[&2]
+        ));
+
+        // A deal for a higher threshold is rejected even though its shares match
+        let (commitment, shares) = deal_resharing(&old_sks[1], 5, &new_peers).expect("Valid deal");
+        assert!(!verify_resharing_share(
+            &old_pks[1],
+            &commitment,
+            4,
+            2,
+            &shares[&2]
+        ));
+
+        // The share for index zero is the old secret key share itself
+        assert!(!verify_resharing_share(
+            &old_pks[1],
+            &deals[&2].0,
+            4,
+            0,
+            &old_sks[1]
+        ));
+
+        // A commitment that doesn't commit to the old share is rejected
+        let (commitment, shares) = &deals[&3];
+        assert!(!verify_resharing_share(
+            &old_pks[1],
+            commitment,
+            4,
+            1,
+            &shares[&1]
+        ));
+
+        // Four of the new peers can decrypt ciphertexts for the old aggregate
+        // public key
+        let encryption_seed = [7_u8; 32];
+        let commitment = sha256::Hash::hash(&[0_u8; 32]);
+        let ciphertext = encrypt_preimage(&agg_pk, &encryption_seed, &[42_u8; 32], &commitment);
+
+        let shares: BTreeMap<u64, DecryptionKeyShare> = (2_u64..=5)
+            .map(|index| {
+                let share = create_decryption_key_share(&new_sks[index as usize - 1], &ciphertext);
+
+                assert!(verify_decryption_key_share(
+                    &new_pks[index as usize - 1],
+                    &share,
+                    &ciphertext,
+                    &commitment
+                ));
+
+                (index, share)
+            })
+            .collect();
+
+        assert_eq!(
+            aggregate_decryption_shares(&shares),
+            derive_agg_decryption_key(&agg_pk, &encryption_seed)
+        );
+    }
+
+    #[test]
+    fn test_resharing_rejects_invalid_input() {
+        let (_, _, sks) = dealer_keygen(3, 4);
+
+        assert_eq!(
+            deal_resharing(&sks[0], 3, &[0, 1, 2, 3]).err(),
+            Some(TpeError::InvalidIndex)
+        );
+        assert_eq!(
+            deal_resharing(&sks[0], 0, &[1, 2, 3]).err(),
+            Some(TpeError::InvalidThreshold)
+        );
+        assert_eq!(
+            deal_resharing(&sks[0], 4, &[1, 2, 3]).err(),
+            Some(TpeError::InvalidThreshold)
+        );
+
+        // Nothing to combine
+        assert_eq!(
+            combine_resharing_shares(&BTreeMap::new()),
+            Err(TpeError::NoShares)
+        );
+        assert_eq!(
+            derive_resharing_public_key_share(&BTreeMap::new(), 1),
+            Err(TpeError::NoShares)
+        );
+
+        let (commitment_3, shares) = deal_resharing(&sks[0], 3, &[1, 2, 3]).unwrap();
+        let (commitment_2, _) = deal_resharing(&sks[1], 2, &[1, 2, 3]).unwrap();
+
+        // A single old peer re-dealing for a threshold of one keeps its share
+        let (commitment_1, shares_1) = deal_resharing(&sks[2], 1, &[1]).unwrap();
+        assert_eq!(
+            combine_resharing_shares(&BTreeMap::from([(1, shares_1[&1].clone())])),
+            Ok(sks[2].clone())
+        );
+        assert!(derive_resharing_public_key_share(&BTreeMap::from([(1, commitment_1)]), 1).is_ok());
+
+        // Commitments for different thresholds
+        assert_eq!(
+            derive_resharing_public_key_share(
+                &BTreeMap::from([(1, commitment_3.clone()), (2, commitment_2)]),
+                1
+            ),
+            Err(TpeError::MismatchedCommitments)
+        );
+        assert_eq!(
+            derive_resharing_public_key_share(
+                &BTreeMap::from([(1, ResharingCommitment(vec![]))]),
+                1
+            ),
+            Err(TpeError::MismatchedCommitments)
+        );
+
+        // Index zero stands for the secret being re-dealt
+        assert_eq!(
+            derive_resharing_public_key_share(&BTreeMap::from([(1, commitment_3)]), 0),
+            Err(TpeError::InvalidIndex)
+        );
+        assert_eq!(
+            combine_resharing_shares(&BTreeMap::from([(0, shares[&1].clone())])),
+            Err(TpeError::InvalidIndex)
+        );
+    }
+
+    #[test]
+    fn test_checks_reject_invalid_input() {
+        let (agg_pk, pks, sks) = dealer_keygen(3, 4);
+
+        let encryption_seed = [7_u8; 32];
+        let commitment = sha256::Hash::hash(&[0_u8; 32]);
+        let ciphertext = encrypt_preimage(&agg_pk, &encryption_seed, &[42_u8; 32], &commitment);
+        let share = create_decryption_key_share(&sks[0], &ciphertext);
+        let agg_dk = derive_agg_decryption_key(&agg_pk, &encryption_seed);
+
+        assert_eq!(
+            check_decryption_key_share(&pks[0], &share, &ciphertext, &commitment),
+            Ok(())
+        );
+        assert_eq!(
+            check_decryption_key_share(&pks[1], &share, &ciphertext, &commitment),
+            Err(TpeError::InvalidShare)
+        );
+        assert_eq!(
+            check_agg_decryption_key(&agg_pk, &agg_dk, &ciphertext, &commitment),
+            Ok(())
+        );
+        assert_eq!(
+            check_agg_decryption_key(
+                &agg_pk,
+                &AggregateDecryptionKey(share.0),
+                &ciphertext,
+                &commitment
+            ),
+            Err(TpeError::InvalidAggregate)
+        );
+
+        let mut tampered = ciphertext;
+        tampered.encrypted_preimage[0] ^= 1;
+
+        assert_eq!(
+            check_decryption_key_share(&pks[0], &share, &tampered, &commitment),
+            Err(TpeError::InvalidCiphertext)
+        );
+        assert_eq!(
+            check_agg_decryption_key(&agg_pk, &agg_dk, &tampered, &commitment),
+            Err(TpeError::InvalidCiphertext)
+        );
+    }
+
+    #[test]
+    fn test_payload_roundtrip() {
+        let (agg_pk, pks, sks) = dealer_keygen(3, 4);
+
+        let encryption_seed = [7_u8; 32];
+        let payload: Vec<u8> = (0..1_000).map(|i| i as u8).collect();
+        let commitment = sha256::Hash::hash(&[0_u8; 32]);
+        let ciphertext = encrypt_payload(&agg_pk, &encryption_seed, &payload, &commitment);
+
+        assert!(verify_payload_ciphertext(&ciphertext, &commitment));
+
+        let shares: BTreeMap<u64, DecryptionKeyShare> = (1_u64..4)
+            .zip(pks.iter().zip(sks.iter()))
+            .map(|(index, (pk, sk))| {
+                let share = create_payload_decryption_key_share(sk, &ciphertext);
+
+                assert!(verify_payload_decryption_key_share(
+                    pk,
+                    &share,
+                    &ciphertext,
+                    &commitment
+                ));
+
+                (index, share)
+            })
+            .collect();
+
+        let agg_dk = aggregate_decryption_shares(&shares);
+
+        assert!(verify_payload_agg_decryption_key(
+            &agg_pk,
+            &agg_dk,
+            &ciphertext,
+            &commitment
+        ));
+
+        assert_eq!(decrypt_payload(&ciphertext, &agg_dk), Some(payload));
+
+        let mut tampered = ciphertext.clone();
+        tampered.encrypted_payload[20] ^= 1;
+
+        assert!(!verify_payload_ciphertext(&tampered, &commitment));
+        assert_eq!(decrypt_payload(&tampered, &agg_dk), None);
+    }
 }
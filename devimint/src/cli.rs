@@ -18,11 +18,12 @@ use tracing::{debug, error, info, trace, warn};
 
 use crate::devfed::DevJitFed;
 use crate::envs::{
-    FM_FED_SIZE_ENV, FM_INVITE_CODE_ENV, FM_LINK_TEST_DIR_ENV, FM_OFFLINE_NODES_ENV,
-    FM_TEST_DIR_ENV,
+    FM_DEVIMINT_SEED_ENV, FM_FED_SIZE_ENV, FM_INVITE_CODE_ENV, FM_LINK_TEST_DIR_ENV,
+    FM_OFFLINE_NODES_ENV, FM_TEST_DIR_ENV,
 };
 use crate::federation::Fedimintd;
-use crate::util::{poll, ProcessManager};
+use crate::test_all::{run_all, TestAllArgs};
+use crate::util::{init_devimint_seed, poll, ProcessManager};
 use crate::vars::mkdir;
 use crate::{external_daemons, vars, ExternalDaemons};
 
@@ -58,6 +59,12 @@ pub struct CommonArgs {
     /// Run degraded federation with FM_OFFLINE_NODES shutdown
     #[clap(long, env = FM_OFFLINE_NODES_ENV, default_value = "0")]
     pub offline_nodes: usize,
+
+    /// Seed for devimint's random choices (e.g. peer names, preimages).
+    /// Random if not set; the seed used is logged and recorded in the env
+    /// file so a run can be reproduced.
+    #[clap(long, env = FM_DEVIMINT_SEED_ENV)]
+    pub seed: Option<u64>,
 }
 
 impl CommonArgs {
@@ -109,6 +116,8 @@ pub enum Cmd {
     /// for devimint as a cli
     #[clap(flatten)]
     Rpc(RpcCmd),
+    /// Runs the tests of the CI matrix locally and summarizes the results
+    TestAll(TestAllArgs),
 }
 
 #[derive(Subcommand)]
@@ -138,7 +147,10 @@ pub async fn setup(arg: CommonArgs) -> Result<(ProcessManager, TaskGroup)> {
         .with_directive("jsonrpsee-client=off")
         .init()?;
 
-    let globals = vars::Global::new(test_dir, arg.fed_size, arg.offline_nodes).await?;
+    let seed = init_devimint_seed(arg.seed);
+    info!(target: LOG_DEVIMINT, %seed, "Devimint seed (set {FM_DEVIMINT_SEED_ENV} to reproduce)");
+
+    let globals = vars::Global::new(test_dir, arg.fed_size, arg.offline_nodes, seed).await?;
 
     if let Some(link_test_dir) = arg.link_test_dir.as_ref() {
         update_test_dir_link(link_test_dir, &arg.test_dir()).await?;
@@ -323,6 +335,10 @@ pub async fn handle_command(cmd: Cmd, common_args: CommonArgs) -> Result<()> {
             }
         }
         Cmd::Rpc(rpc_cmd) => rpc_command(rpc_cmd, common_args).await?,
+        Cmd::TestAll(args) => {
+            fedimint_logging::TracingSetup::default().init()?;
+            run_all(args, &common_args.mk_test_dir()?).await?;
+        }
         Cmd::RunUi => {
             let (process_mgr, task_group) = setup(common_args).await?;
             let main = async {
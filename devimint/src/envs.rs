@@ -62,6 +62,9 @@ pub const FM_OFFLINE_NODES_ENV: &str = "FM_OFFLINE_NODES";
 // Env variable to set a federation's invite code
 pub const FM_INVITE_CODE_ENV: &str = "FM_INVITE_CODE";
 
+// Env variable to set the seed for devimint's random choices, to reproduce a run
+pub const FM_DEVIMINT_SEED_ENV: &str = "FM_DEVIMINT_SEED";
+
 // util.rs
 
 // Env variable to override gatewayd binary set:
@@ -41,7 +41,7 @@ use super::external::Bitcoind;
 use super::util::{cmd, parse_map, Command, ProcessHandle, ProcessManager};
 use super::vars::utf8;
 use crate::envs::{FM_CLIENT_DIR_ENV, FM_DATA_DIR_ENV};
-use crate::util::{poll, FedimintdCmd};
+use crate::util::{devimint_rng, poll, FedimintdCmd};
 use crate::version_constants::{VERSION_0_3_0, VERSION_0_3_0_ALPHA};
 use crate::{poll_eq, vars};
 
@@ -793,12 +793,13 @@ pub async fn run_cli_dkg(
     )
     .await?;
 
+    let mut rng = devimint_rng("follower-names");
     let followers_names = followers
         .keys()
         .map(|peer_id| {
             (*peer_id, {
                 // This is to be clear that the name will be unrelated to peer id
-                let random_string = rand::thread_rng()
+                let random_string = (&mut rng)
                     .sample_iter(&rand::distributions::Alphanumeric)
                     .take(5)
                     .map(char::from)
@@ -949,12 +950,13 @@ pub async fn run_client_dkg(
         .await?; // sanity check
     let server_gen_params = params[leader_id].consensus.modules.clone();
     set_config_gen_params(leader, auth_for(leader_id), server_gen_params.clone()).await?;
+    let mut rng = devimint_rng("follower-names");
     let followers_names = followers
         .keys()
         .map(|peer_id| {
             (*peer_id, {
                 // This is to be clear that the name will be unrelated to peer id
-                let random_string = rand::thread_rng()
+                let random_string = (&mut rng)
                     .sample_iter(&rand::distributions::Alphanumeric)
                     .take(5)
                     .map(char::from)
@@ -37,6 +37,7 @@ pub mod envs;
 pub mod external;
 pub mod federation;
 pub mod gatewayd;
+pub mod test_all;
 pub mod tests;
 pub mod util;
 pub mod vars;
@@ -0,0 +1,280 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use fedimint_logging::LOG_DEVIMINT;
+use futures::{stream, StreamExt};
+use tokio::fs;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::envs::FM_TEST_DIR_ENV;
+
+/// Script defining the test matrix run in CI, the single source of the tests
+pub const TEST_MATRIX_SCRIPT: &str = "scripts/tests/test-ci-all.sh";
+
+#[derive(Args, Clone, Debug)]
+pub struct TestAllArgs {
+    /// Only run the tests whose name contains one of these strings
+    #[arg(long)]
+    pub filter: Vec<String>,
+
+    /// Number of tests run in parallel
+    #[arg(long, short = 'j', default_value = "1")]
+    pub jobs: usize,
+
+    /// Don't build the workspace before running the tests
+    #[arg(long)]
+    pub no_prebuild: bool,
+
+    /// Print the selected tests without running them
+    #[arg(long)]
+    pub list: bool,
+}
+
+/// A test of the matrix, run by a shell command from the repository root
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCase {
+    pub name: String,
+    pub command: String,
+}
+
+#[derive(Debug, Clone)]
+struct TestOutcome {
+    name: String,
+    success: bool,
+    elapsed: Duration,
+    log_file: PathBuf,
+}
+
+/// Parses the tests out of the functions of the CI matrix script, i.e. every
+/// `function <name>() { ... fm-run-test "${FUNCNAME[0]}" <command> ... }`
+pub fn discover_tests(script: &str) -> Vec<TestCase> {
+    let mut tests = vec![];
+    let mut current_function = None;
+
+    for line in script.lines().map(str::trim) {
+        if let Some(name) = line
+            .strip_prefix("function ")
+            .and_then(|rest| rest.strip_suffix("() {"))
+        {
+            current_function = Some(name.to_string());
+        } else if line == "}" {
+            current_function = None;
+        } else if let Some(command) = line.strip_prefix(r#"fm-run-test "${FUNCNAME[0]}" "#) {
+            if let Some(name) = current_function.take() {
+                tests.push(TestCase {
+                    name,
+                    command: command.trim().to_string(),
+                });
+            }
+        }
+    }
+
+    tests
+}
+
+pub async fn run_all(args: TestAllArgs, test_dir: &Path) -> Result<()> {
+    let script = fs::read_to_string(TEST_MATRIX_SCRIPT)
+        .await
+        .with_context(|| format!("Reading {TEST_MATRIX_SCRIPT}, run from the repository root"))?;
+
+    let tests = discover_tests(&script)
+        .into_iter()
+        .filter(|test| {
+            args.filter.is_empty() || args.filter.iter().any(|f| test.name.contains(f.as_str()))
+        })
+        .collect::<Vec<_>>();
+
+    if tests.is_empty() {
+        bail!("No tests match the filter {:?}", args.filter);
+    }
+
+    if args.list {
+        for test in &tests {
+            println!("{}: {}", test.name, test.command);
+        }
+        return Ok(());
+    }
+
+    let logs_dir = test_dir.join("test-all");
+    fs::create_dir_all(&logs_dir)
+        .await
+        .with_context(|| format!("Creating {}", logs_dir.display()))?;
+
+    // Build once up front, so the tests share the build instead of racing to
+    // compile the workspace
+    if !args.no_prebuild {
+        info!(target: LOG_DEVIMINT, "Pre-building workspace");
+        let status = tokio::process::Command::new("cargo")
+            .args(["build", "--workspace", "--all-targets"])
+            .kill_on_drop(true)
+            .status()
+            .await
+            .context("Running cargo build")?;
+        if !status.success() {
+            bail!("Pre-building the workspace failed");
+        }
+    }
+
+    let start_time = Instant::now();
+    let outcomes = stream::iter(tests)
+        .map(|test| run_test(test, &logs_dir, !args.no_prebuild))
+        .buffer_unordered(args.jobs.max(1))
+        .collect::<Vec<_>>()
+        .await;
+
+    print_summary(&outcomes, start_time.elapsed());
+
+    let failed = outcomes.iter().filter(|outcome| !outcome.success).count();
+    if failed != 0 {
+        bail!("{failed} of {} tests failed", outcomes.len());
+    }
+
+    Ok(())
+}
+
+async fn run_test(test: TestCase, logs_dir: &Path, deny_compilation: bool) -> TestOutcome {
+    let log_file = logs_dir.join(format!("{}.log", test.name));
+    info!(target: LOG_DEVIMINT, name = %test.name, "Starting test");
+
+    let start_time = Instant::now();
+    let success = match run_test_command(&test, &log_file, deny_compilation).await {
+        Ok(success) => success,
+        Err(err) => {
+            warn!(target: LOG_DEVIMINT, name = %test.name, %err, "Failed to run test");
+            false
+        }
+    };
+    let elapsed = start_time.elapsed();
+
+    info!(
+        target: LOG_DEVIMINT,
+        name = %test.name,
+        success,
+        elapsed_ms = %elapsed.as_millis(),
+        "Finished test"
+    );
+
+    TestOutcome {
+        name: test.name,
+        success,
+        elapsed,
+        log_file,
+    }
+}
+
+async fn run_test_command(
+    test: &TestCase,
+    log_file: &Path,
+    deny_compilation: bool,
+) -> Result<bool> {
+    let log = std::fs::File::create(log_file)
+        .with_context(|| format!("Creating {}", log_file.display()))?;
+
+    let mut command = tokio::process::Command::new("bash");
+    command
+        .arg("-c")
+        .arg(&test.command)
+        // every test sets up its own test dir
+        .env_remove(FM_TEST_DIR_ENV)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .kill_on_drop(true);
+
+    // Like in CI, the tests must not rebuild what was just built
+    if deny_compilation {
+        command.env("FM_CARGO_DENY_COMPILATION", "1");
+    }
+
+    let status = command
+        .status()
+        .await
+        .with_context(|| format!("Executing {}", test.command))?;
+
+    Ok(status.success())
+}
+
+fn print_summary(outcomes: &[TestOutcome], elapsed: Duration) {
+    let name_width = outcomes
+        .iter()
+        .map(|outcome| outcome.name.len())
+        .max()
+        .unwrap_or_default();
+
+    println!();
+    for outcome in outcomes {
+        let elapsed = outcome.elapsed.as_secs_f64();
+        if outcome.success {
+            println!("PASS {elapsed:>8.1}s  {}", outcome.name);
+        } else {
+            println!(
+                "FAIL {elapsed:>8.1}s  {:<name_width$}  {}",
+                outcome.name,
+                outcome.log_file.display()
+            );
+        }
+    }
+
+    let passed = outcomes.iter().filter(|outcome| outcome.success).count();
+    println!(
+        "\n{passed} passed, {} failed in {:.1}s",
+        outcomes.len() - passed,
+        elapsed.as_secs_f64()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{discover_tests, TestCase};
+
+    #[test]
+    fn discovers_tests_of_functions() {
+        let script = r#"
+function setup() {
+  export FM_TEST_FAST=1
+}
+
+function unit_tests() {
+    echo "not a test line"
+    fm-run-test "${FUNCNAME[0]}" cargo nextest run --workspace
+}
+export -f unit_tests
+
+function ignored() {
+  echo "no fm-run-test here"
+}
+
+function reboot_test() {
+  fm-run-test "${FUNCNAME[0]}"   ./scripts/tests/reboot-test.sh lnd  
+}
+
+fm-run-test "${FUNCNAME[0]}" ./outside-of-a-function.sh
+"#;
+
+        assert_eq!(
+            discover_tests(script),
+            vec![
+                TestCase {
+                    name: "unit_tests".to_string(),
+                    command: "cargo nextest run --workspace".to_string(),
+                },
+                TestCase {
+                    name: "reboot_test".to_string(),
+                    command: "./scripts/tests/reboot-test.sh lnd".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn discovers_tests_of_ci_script() {
+        let tests = discover_tests(include_str!("../../scripts/tests/test-ci-all.sh"));
+
+        assert!(tests.iter().any(|test| test.name == "rust_unit_tests"));
+        assert!(tests.iter().all(|test| !test.command.is_empty()));
+    }
+}
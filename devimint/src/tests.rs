@@ -19,6 +19,7 @@ use fedimint_ln_client::cli::LnInvoiceResponse;
 use fedimint_logging::LOG_DEVIMINT;
 use hex::ToHex;
 use ln_gateway::rpc::GatewayInfo;
+use rand::Rng as _;
 use serde_json::json;
 use tokio::net::TcpStream;
 use tokio::time::timeout;
@@ -28,7 +29,7 @@ use tracing::{debug, info};
 use crate::cli::{cleanup_on_exit, exec_user_command, setup, write_ready_file, CommonArgs};
 use crate::envs::{FM_DATA_DIR_ENV, FM_DEVIMINT_RUN_DEPRECATED_TESTS_ENV, FM_PASSWORD_ENV};
 use crate::federation::{self, Client, Federation};
-use crate::util::{poll, poll_with_timeout, LoadTestTool, ProcessManager};
+use crate::util::{devimint_rng, poll, poll_with_timeout, LoadTestTool, ProcessManager};
 use crate::version_constants::{VERSION_0_3_0, VERSION_0_3_0_ALPHA, VERSION_0_4_0_ALPHA};
 use crate::{cmd, dev_fed, poll_eq, DevFed, Gatewayd, LightningNode, Lightningd, Lnd};
 
@@ -1202,7 +1203,7 @@ pub async fn start_hold_invoice_payment(
     lnd: &Lnd,
 ) -> anyhow::Result<([u8; 32], cln_rpc::primitives::Sha256, String)> {
     client.use_gateway(gw_cln).await?;
-    let preimage = rand::random::<[u8; 32]>();
+    let preimage = devimint_rng("hold-invoice-preimage").gen::<[u8; 32]>();
     let hash = {
         let mut engine = bitcoin::hashes::sha256::Hash::engine();
         bitcoin::hashes::HashEngine::input(&mut engine, &preimage);
@@ -3,11 +3,12 @@ use std::ffi::OsStr;
 use std::future::Future;
 use std::ops::ControlFlow;
 use std::process::Stdio;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use std::time::Duration;
 use std::{env, unreachable};
 
 use anyhow::{anyhow, bail, format_err, Context, Result};
+use bitcoincore_rpc::bitcoin::hashes::{sha256, Hash as _};
 use fedimint_api_client::api::StatusResponse;
 use fedimint_core::admin_client::{
     ConfigGenParamsRequest, ConfigGenParamsResponse, PeerServerParams,
@@ -19,6 +20,8 @@ use fedimint_core::task::{self, block_in_place, block_on};
 use fedimint_core::time::now;
 use fedimint_core::PeerId;
 use fedimint_logging::LOG_DEVIMINT;
+use rand::rngs::StdRng;
+use rand::SeedableRng as _;
 use semver::Version;
 use serde::de::DeserializeOwned;
 use tokio::fs::OpenOptions;
@@ -39,6 +42,27 @@ use crate::envs::{
     FM_RECOVERYTOOL_BASE_EXECUTABLE_ENV,
 };
 
+/// Seed all of devimint's random choices are derived from, see
+/// [`init_devimint_seed`]
+static DEVIMINT_SEED: OnceLock<u64> = OnceLock::new();
+
+/// Sets the seed for [`devimint_rng`], picking a random one if `seed` is
+/// `None`, and returns the seed in use. The first call wins.
+pub fn init_devimint_seed(seed: Option<u64>) -> u64 {
+    *DEVIMINT_SEED.get_or_init(|| seed.unwrap_or_else(rand::random))
+}
+
+/// Returns a rng that is deterministic given the devimint seed and `purpose`.
+///
+/// Every call site should use its own `purpose`, so that adding randomness in
+/// one place doesn't change the values generated in another.
+pub fn devimint_rng(purpose: &str) -> StdRng {
+    let seed = init_devimint_seed(None);
+    let hash = sha256::Hash::hash(format!("{seed}/{purpose}").as_bytes());
+
+    StdRng::from_seed(hash.to_byte_array())
+}
+
 // If a binary doesn't provide a clap version, default to the first stable
 // release (v0.2.1)
 const DEFAULT_VERSION: Version = Version::new(0, 2, 1);
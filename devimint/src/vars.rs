@@ -62,6 +62,12 @@ impl ToEnvVar for usize {
     }
 }
 
+impl ToEnvVar for u64 {
+    fn to_env_value(&self) -> Option<String> {
+        Some(self.to_string())
+    }
+}
+
 impl ToEnvVar for u16 {
     fn to_env_value(&self) -> Option<String> {
         Some(self.to_string())
@@ -100,12 +106,14 @@ use fedimint_server::net::api::ApiSecrets;
 use fedimintd::envs::FM_FORCE_API_SECRETS_ENV;
 use format as f;
 
+use crate::envs::FM_DEVIMINT_SEED_ENV;
+
 pub fn utf8(path: &Path) -> &str {
     path.as_os_str().to_str().expect("must be valid utf8")
 }
 
 declare_vars! {
-    Global = (test_dir: &Path, fed_size: usize, offline_nodes: usize) =>
+    Global = (test_dir: &Path, fed_size: usize, offline_nodes: usize, seed: u64) =>
     {
         FM_USE_UNKNOWN_MODULE: String = std::env::var(FM_USE_UNKNOWN_MODULE_ENV).unwrap_or_else(|_| "1".into()); env: "FM_USE_UNKNOWN_MODULE";
         FM_ENABLE_MODULE_LNV2: String = std::env::var(FM_ENABLE_MODULE_LNV2_ENV).unwrap_or_else(|_| "1".into()); env: "FM_ENABLE_MODULE_LNV2";
@@ -121,6 +129,7 @@ declare_vars! {
 
         FM_FED_SIZE: usize = fed_size; env: "FM_FED_SIZE";
         FM_OFFLINE_NODES: usize = offline_nodes; env: "FM_OFFLINE_NODES";
+        FM_DEVIMINT_SEED: u64 = seed; env: FM_DEVIMINT_SEED_ENV;
         FM_TMP_DIR: PathBuf = mkdir(test_dir.into()).await?; env: "FM_TMP_DIR";
         FM_TEST_DIR: PathBuf = FM_TMP_DIR.clone(); env: "FM_TEST_DIR";
         FM_TEST_FAST_WEAK_CRYPTO: String = "1"; env: "FM_TEST_FAST_WEAK_CRYPTO";
@@ -205,8 +214,9 @@ impl Global {
         test_dir: &Path,
         fed_size: usize,
         offline_nodes: usize,
+        seed: u64,
     ) -> anyhow::Result<Self> {
-        let this = Self::init(test_dir, fed_size, offline_nodes).await?;
+        let this = Self::init(test_dir, fed_size, offline_nodes, seed).await?;
         Ok(this)
     }
 }
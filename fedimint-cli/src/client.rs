@@ -394,8 +394,10 @@ pub async fn handle_command(
             force_internal,
         } => {
             warn!("Command deprecated. Use `fedimint-cli module ln pay` instead.");
-            let bolt11 =
-                fedimint_ln_client::get_invoice(&payment_info, amount, lnurl_comment).await?;
+            let lnurl_invoice =
+                fedimint_ln_client::get_lnurl_invoice(&payment_info, amount, lnurl_comment)
+                    .await?;
+            let bolt11 = lnurl_invoice.invoice.clone();
             info!("Paying invoice: {bolt11}");
             let lightning_module = client.get_first_module::<LightningClientModule>();
             let ln_gateway = lightning_module
@@ -430,11 +432,12 @@ pub async fn handle_command(
                     }
                 })
             } else {
-                Ok(client
+                let response = client
                     .get_first_module::<LightningClientModule>()
                     .wait_for_ln_payment(payment_type, contract_id, false)
                     .await?
-                    .context("expected a response")?)
+                    .context("expected a response")?;
+                Ok(lnurl_invoice.with_success_message(response))
             }
         }
         ClientCmd::AwaitLnPay { operation_id } => {
@@ -19,7 +19,8 @@ use common::{
     parse_gateway_id, reissue_notes,
 };
 use devimint::cmd;
-use devimint::util::{GatewayClnCli, GatewayLndCli};
+use devimint::envs::FM_DEVIMINT_SEED_ENV;
+use devimint::util::{devimint_rng, init_devimint_seed, GatewayClnCli, GatewayLndCli};
 use fedimint_client::ClientHandleArc;
 use fedimint_core::endpoint_constants::SESSION_COUNT_ENDPOINT;
 use fedimint_core::invite_code::InviteCode;
@@ -32,6 +33,7 @@ use fedimint_ln_common::LightningGateway;
 use fedimint_mint_client::OOBNotes;
 use futures::StreamExt;
 use lightning_invoice::{Bolt11Invoice, Bolt11InvoiceDescription, Description};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use tokio::fs::OpenOptions;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufWriter};
@@ -56,6 +58,13 @@ struct Opts {
     #[arg(long, help = "Output with the metrics results in JSON format")]
     metrics_json_output: Option<PathBuf>,
 
+    #[arg(
+        long,
+        env = FM_DEVIMINT_SEED_ENV,
+        help = "Seed for the random choices of the workload, random if not set"
+    )]
+    seed: Option<u64>,
+
     #[arg(
         long,
         help = "If given, will be used to store and retrieve past metrics for comparison purposes"
@@ -296,6 +305,8 @@ impl std::fmt::Display for EventMetricComparison {
 async fn main() -> anyhow::Result<()> {
     fedimint_logging::TracingSetup::default().init()?;
     let opts = Opts::parse();
+    let seed = init_devimint_seed(opts.seed);
+    info!(%seed, "Load test seed (set {FM_DEVIMINT_SEED_ENV} to reproduce)");
     let (event_sender, event_receiver) = tokio::sync::mpsc::unbounded_channel();
     let summary_handle = spawn("handle metrics summary", {
         let opts = opts.clone();
@@ -816,7 +827,7 @@ async fn do_ln_circular_test_user_task(
         LnCircularStrategy::TwoGateways => {
             // pick the first payment method randomly to avoid overloading one of the
             // gateways
-            let mut invoice_generation = if rand::random::<bool>() {
+            let mut invoice_generation = if devimint_rng(&format!("first-gateway/{prefix}")).gen() {
                 LnInvoiceGeneration::LnCli
             } else {
                 LnInvoiceGeneration::ClnLightningCli
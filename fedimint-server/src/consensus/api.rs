@@ -238,7 +238,7 @@ impl ConsensusApi {
         self.shutdown_sender.send_replace(index);
     }
 
-    async fn get_federation_audit(&self) -> ApiResult<AuditSummary> {
+    pub(crate) async fn get_federation_audit(&self) -> ApiResult<AuditSummary> {
         let mut dbtx = self.db.begin_transaction_nc().await;
         // Writes are related to compacting audit keys, which we can safely ignore
         // within an API request since the compaction will happen when constructing an
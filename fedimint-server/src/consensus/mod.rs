@@ -1,6 +1,7 @@
 #![allow(clippy::let_unit_value)]
 
 pub mod aleph_bft;
+pub mod alerts;
 pub mod api;
 pub mod db;
 pub mod debug;
@@ -33,6 +34,7 @@ use tracing::info;
 use tracing::log::warn;
 
 use crate::config::{ServerConfig, ServerConfigLocal};
+use crate::consensus::alerts::{spawn_alerts, AlertConfig};
 use crate::consensus::api::ConsensusApi;
 use crate::consensus::engine::ConsensusEngine;
 use crate::envs::{FM_DB_CHECKPOINT_RETENTION_DEFAULT, FM_DB_CHECKPOINT_RETENTION_ENV};
@@ -117,6 +119,10 @@ pub async fn run(
         force_api_secret: force_api_secrets.get_active(),
     };
 
+    if let Some(alert_config) = AlertConfig::from_env()? {
+        spawn_alerts(task_group, consensus_api.clone(), alert_config);
+    }
+
     info!(target: LOG_CONSENSUS, "Starting Consensus Api");
 
     let api_handler =
@@ -6,6 +6,16 @@ pub const FM_PEER_ID_SORT_BY_URL_ENV: &str = "FM_PEER_ID_SORT_BY_URL";
 /// checkpoints.
 pub const FM_DB_CHECKPOINT_RETENTION_ENV: &str = "FM_DB_CHECKPOINT_RETENTION";
 
+/// The env var for the url of the ntfy topic alerts are published to. Alerting
+/// is disabled if it is not set.
+pub const FM_ALERT_NTFY_URL_ENV: &str = "FM_ALERT_NTFY_URL";
+
+/// The env var for the number of seconds without a completed session after
+/// which an alert is sent
+pub const FM_ALERT_SESSION_STALL_SECS_ENV: &str = "FM_ALERT_SESSION_STALL_SECS";
+
+pub const FM_ALERT_SESSION_STALL_SECS_DEFAULT: u64 = 10 * 60;
+
 // Default number of checkpoints from the current session should be retained on
 // disk.
 pub const FM_DB_CHECKPOINT_RETENTION_DEFAULT: u64 = 1;
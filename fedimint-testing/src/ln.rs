@@ -21,7 +21,7 @@ use ln_gateway::gateway_lnrpc::{
     InterceptHtlcResponse, PayInvoiceRequest, PayInvoiceResponse,
 };
 use ln_gateway::lightning::cln::{HtlcResult, RouteHtlcStream};
-use ln_gateway::lightning::{ChannelInfo, ILnRpcClient, LightningRpcError};
+use ln_gateway::lightning::{ChannelInfo, ILnRpcClient, LightningRpcError, OpenChannelOptions};
 use rand::rngs::OsRng;
 use tokio::sync::mpsc;
 use tracing::info;
@@ -237,6 +237,7 @@ impl ILnRpcClient for FakeLightningTest {
         _host: String,
         _channel_size_sats: u64,
         _push_amount_sats: u64,
+        _options: OpenChannelOptions,
     ) -> Result<EmptyResponse, LightningRpcError> {
         unimplemented!("FakeLightningTest does not support opening channels")
     }
@@ -3,22 +3,50 @@
 #![allow(clippy::missing_panics_doc)]
 #![allow(clippy::too_many_lines)]
 
-use std::time::Duration;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
 
 use anyhow::bail;
 use bitcoin::address::NetworkUnchecked;
+use bitcoin::hashes::sha256;
 use bitcoin::Address;
 use clap::{CommandFactory, Parser, Subcommand};
 use fedimint_core::config::FederationId;
+use fedimint_core::core::OperationId;
+use fedimint_core::task::sleep;
 use fedimint_core::util::{backon, retry, SafeUrl};
-use fedimint_core::{fedimint_build_code_version_env, BitcoinAmountOrAll};
+use fedimint_core::{fedimint_build_code_version_env, Amount, BitcoinAmountOrAll};
 use fedimint_logging::TracingSetup;
+use ln_gateway::alerts::{AlertEmail, AlertPolicy};
+use ln_gateway::api_token::ApiTokenScope;
+use ln_gateway::drain::DRAIN_POLL_INTERVAL;
+use ln_gateway::dynamic_fees::DynamicFeePolicy;
+use ln_gateway::incoming_limits::IncomingLimits;
+use ln_gateway::lightning::{ChannelPolicy, OpenChannelOptions};
+use ln_gateway::liquidity_cap::LiquidityCap;
+use ln_gateway::payment_log::{PaymentLogFilter, PaymentLogFormat, SummaryPeriod};
+use ln_gateway::payment_retry::PaymentRetryPolicy;
+use ln_gateway::protocols::LightningProtocols;
+use ln_gateway::rebalance::RebalanceBand;
 use ln_gateway::rpc::rpc_client::GatewayRpcClient;
 use ln_gateway::rpc::{
-    BackupPayload, BalancePayload, CloseChannelsWithPeerPayload, ConfigPayload, ConnectFedPayload,
-    DepositAddressPayload, FederationRoutingFees, GetFundingAddressPayload, LeaveFedPayload,
-    OpenChannelPayload, RestorePayload, SetConfigurationPayload, WithdrawPayload, V1_API_ENDPOINT,
+    BackupPayload, BalancePayload, ClaimLightningAddressPayload, CloseChannelsWithPeerPayload,
+    ConfigPayload, ConnectFedPayload, ConnectPeerPayload, CreateApiTokenPayload,
+    CreateInvoiceBatchPayload, CreateReverseSwapPayload, CreateSnapshotPayload, CreateSwapPayload,
+    DepositAddressPayload, DisconnectPeerPayload, ExportPaymentLogPayload, FederationRoutingFees,
+    GatewayPolicy, GetFundingAddressPayload, ImportMissionControlPayload, ImportPolicyPayload,
+    LeaveFedPayload, Lsps1CreateOrderPayload, Lsps1GetInfoPayload, Lsps1OrderPayload,
+    Lsps1PayOrderPayload, OpenChannelPayload, PayKeysendPayload, PaymentAttemptsPayload,
+    PaymentLogPayload, PaymentSummaryPayload, QueryRoutePayload, RefundSwapPayload,
+    RegisterLightningAddressPayload, RemoveLightningAddressPayload, RestorePayload,
+    RestoreSnapshotPayload, RevokeApiTokenPayload, SetAlertPolicyPayload, SetConfigurationPayload,
+    SetDrainingPayload, SetDynamicFeePolicyPayload, SetIncomingLimitsPayload,
+    SetLightningProtocolsPayload, SetLiquidityCapPayload, SetPaymentRetryPolicyPayload,
+    SetRebalanceBandPayload, SetRebalancingPausedPayload, SetSpendingPasswordPayload,
+    SetSweepPolicyPayload, UpdateChannelPolicyPayload, WithdrawPayload, V1_API_ENDPOINT,
 };
+use ln_gateway::sweep::SweepPolicy;
 use serde::Serialize;
 
 const DEFAULT_WAIT_FOR_CHAIN_SYNC_RETRIES: u32 = 60;
@@ -32,9 +60,15 @@ struct Cli {
     address: SafeUrl,
     #[command(subcommand)]
     command: Commands,
+    /// The gateway password or an API token
+    ///
     /// WARNING: Passing in a password from the command line may be less secure!
     #[clap(long)]
     rpcpassword: Option<String>,
+    /// Spending password, required for commands that move funds out of the
+    /// gateway if one is set
+    #[clap(long)]
+    spending_password: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -112,8 +146,243 @@ pub enum Commands {
         #[clap(long)]
         per_federation_routing_fees: Option<Vec<PerFederationRoutingFees>>,
     },
+    /// Set the spending password required for commands that move funds out of
+    /// the gateway. Removes it if no new spending password is given.
+    SetSpendingPassword {
+        #[clap(long)]
+        new_spending_password: Option<String>,
+
+        /// Lightning payments up to this amount don't require the spending
+        /// password
+        #[clap(long)]
+        payment_threshold_msat: Option<u64>,
+    },
+    /// Set the limits on incoming payments of a federation. Limits that are
+    /// not given are removed.
+    SetIncomingLimits {
+        #[clap(long)]
+        federation_id: FederationId,
+
+        /// Maximum amount of a single payment
+        #[clap(long)]
+        max_payment_msat: Option<u64>,
+
+        /// Maximum number of payments accepted during any minute
+        #[clap(long)]
+        max_payments_per_minute: Option<u64>,
+
+        /// Maximum total amount of payments accepted during any 24 hours
+        #[clap(long)]
+        max_daily_volume_msat: Option<u64>,
+    },
+    /// Set the maximum ecash balance the gateway holds in a federation. Once
+    /// it is reached, the gateway stops quoting outgoing payments for the
+    /// federation.
+    SetLiquidityCap {
+        #[clap(long)]
+        federation_id: FederationId,
+
+        /// Maximum ecash balance, the cap is removed if not set
+        #[clap(long)]
+        max_balance_msat: Option<u64>,
+
+        /// Withdraw the ecash above the cap to the lightning node's on-chain
+        /// wallet
+        #[clap(long)]
+        withdraw_excess: bool,
+    },
+    /// Set the protocol versions the gateway serves for a federation. Both
+    /// are served unless disabled.
+    SetLightningProtocols {
+        #[clap(long)]
+        federation_id: FederationId,
+
+        /// Stop registering with the federation and serving LNv1 payments
+        #[clap(long)]
+        disable_lnv1: bool,
+
+        /// Stop serving LNv2 payments
+        #[clap(long)]
+        disable_lnv2: bool,
+    },
+    /// Adjust the routing fees of all federations to the liquidity of the
+    /// lightning node and their outgoing volume, within the given bounds.
+    /// Prints the fee adjustments that are made right away.
+    SetDynamicFees {
+        #[clap(flatten)]
+        policy: DynamicFeePolicy,
+
+        /// Only validate the bounds and print the fee adjustments they would
+        /// make
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Stop adjusting the routing fees automatically
+    DisableDynamicFees,
+    /// Periodically send the on-chain balance of the lightning node above a
+    /// threshold to a cold storage address
+    SetSweepPolicy {
+        #[clap(long)]
+        address: Address<NetworkUnchecked>,
+
+        /// On-chain balance kept in the lightning node's wallet
+        #[clap(long)]
+        threshold_sats: u64,
+
+        #[clap(long, default_value_t = 86_400)]
+        interval_secs: u64,
+
+        /// Fee rate of the sweep transactions, estimated by the lightning node
+        /// if not set
+        #[clap(long)]
+        sat_per_vbyte: Option<u64>,
+    },
+    /// Stop sweeping on-chain funds
+    DisableSweep,
+    /// Print the sweep policy
+    SweepPolicy,
+    /// Post alerts about the gateway's health to a webhook or send them by
+    /// email
+    SetAlertPolicy {
+        #[clap(long)]
+        webhook_url: Option<SafeUrl>,
+
+        /// SMTP relay as `host:port` that accepts the alert emails without
+        /// authentication, e.g. the local mail server
+        #[clap(long)]
+        smtp_relay: Option<String>,
+
+        #[clap(long)]
+        email_from: Option<String>,
+
+        #[clap(long)]
+        email_to: Option<String>,
+
+        /// Alert when the inbound liquidity of all channels drops below this
+        /// amount
+        #[clap(long)]
+        min_inbound_sats: Option<u64>,
+
+        /// Alert when a channel is no longer active
+        #[clap(long)]
+        channel_down: bool,
+
+        /// Alert when a federation doesn't respond
+        #[clap(long)]
+        federation_unreachable: bool,
+
+        /// Alert when at least this share of the payments in percent failed
+        /// within a few minutes
+        #[clap(long)]
+        max_failure_percent: Option<u64>,
+    },
+    /// Stop raising alerts
+    DisableAlerts,
+    /// Print the alert policy
+    AlertPolicy,
+    /// Print the mutating admin requests, newest first
+    AuditLog,
+    /// Print the payments the gateway finished that match the filters, newest
+    /// first
+    PaymentLog {
+        #[clap(flatten)]
+        filter: PaymentLogFilter,
+
+        /// Only print payments that finished at or after this Unix timestamp
+        #[clap(long)]
+        start: Option<u64>,
+
+        /// Only print payments that finished before this Unix timestamp
+        #[clap(long)]
+        end: Option<u64>,
+
+        /// Number of matching payments to skip
+        #[clap(long, default_value_t = 0)]
+        offset: usize,
+
+        /// Maximum number of payments to print
+        #[clap(long)]
+        limit: Option<usize>,
+    },
+    /// Print the payment counts, volumes and fees earned per federation and
+    /// period
+    PaymentSummary {
+        #[clap(long)]
+        period: SummaryPeriod,
+
+        /// Only summarize payments that finished at or after this Unix
+        /// timestamp
+        #[clap(long)]
+        start: Option<u64>,
+
+        /// Only summarize payments that finished before this Unix timestamp
+        #[clap(long)]
+        end: Option<u64>,
+    },
+    /// Print the payments the gateway finished, oldest first, e.g. for
+    /// bookkeeping
+    ExportPaymentLog {
+        #[clap(long, default_value = "csv")]
+        format: PaymentLogFormat,
+
+        /// Only export payments that finished at or after this Unix timestamp
+        #[clap(long)]
+        start: Option<u64>,
+
+        /// Only export payments that finished before this Unix timestamp
+        #[clap(long)]
+        end: Option<u64>,
+    },
+    /// Retry outgoing LNv2 payments that fail with a retryable error
+    SetPaymentRetryPolicy {
+        #[clap(flatten)]
+        policy: PaymentRetryPolicy,
+    },
+    /// Attempt every outgoing LNv2 payment once
+    DisablePaymentRetries,
+    /// Print the payment retry policy
+    PaymentRetryPolicy,
+    /// Print the gateway's fee, routing, liquidity and alerting policies as
+    /// JSON
+    ExportPolicy,
+    /// Import a policy exported from another gateway. Prints the settings that
+    /// are changed.
+    ImportPolicy {
+        /// Path to the JSON file written by `export-policy`
+        file: PathBuf,
+
+        /// Only validate the policy and print the changes it would make
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Generate a Grafana dashboard and Prometheus alert rules for the metrics
+    /// the gateway exports
+    MonitoringBundle {
+        /// Write `grafana-dashboard.json` and `prometheus-rules.yml` to this
+        /// directory instead of printing the bundle
+        #[clap(long)]
+        output_dir: Option<PathBuf>,
+    },
     #[command(subcommand)]
     Lightning(LightningCommands),
+    #[command(subcommand)]
+    Lsps1(Lsps1Commands),
+    #[command(subcommand)]
+    MissionControl(MissionControlCommands),
+    #[command(subcommand)]
+    Rebalance(RebalanceCommands),
+    #[command(subcommand)]
+    InvoiceBatch(InvoiceBatchCommands),
+    #[command(subcommand)]
+    ApiToken(ApiTokenCommands),
+    #[command(subcommand)]
+    LightningAddress(LightningAddressCommands),
+    #[command(subcommand)]
+    Drain(DrainCommands),
+    #[command(subcommand)]
+    Snapshot(SnapshotCommands),
+    #[command(subcommand)]
+    Swap(SwapCommands),
 }
 
 /// This API is intentionally kept very minimal, as its main purpose is to
@@ -139,6 +408,9 @@ pub enum LightningCommands {
         /// The amount to push to the other side of the channel
         #[clap(long)]
         push_amount_sats: Option<u64>,
+
+        #[clap(flatten)]
+        options: OpenChannelOptions,
     },
     /// Close all channels with a peer, claiming the funds to the lightning
     /// node's on-chain wallet
@@ -149,6 +421,77 @@ pub enum LightningCommands {
     },
     /// List active channels
     ListActiveChannels,
+    /// Connect to another lightning node
+    ConnectPeer {
+        /// The public key of the node to connect to
+        #[clap(long)]
+        pubkey: bitcoin::secp256k1::PublicKey,
+
+        #[clap(long)]
+        host: String,
+
+        /// Reconnect to the node whenever the connection is lost, including
+        /// after restarts of the gateway
+        #[clap(long)]
+        persistent: bool,
+    },
+    /// Disconnect from a lightning node, and stop reconnecting to it if it is
+    /// a persistent peer
+    DisconnectPeer {
+        /// The public key of the node to disconnect from
+        #[clap(long)]
+        pubkey: bitcoin::secp256k1::PublicKey,
+    },
+    /// List connected and persistent peers
+    ListPeers,
+    /// Estimate the routing fee and number of hops of a payment
+    QueryRoute {
+        /// The public key of the node to pay
+        #[clap(long)]
+        destination: bitcoin::secp256k1::PublicKey,
+
+        #[clap(long)]
+        amount_msat: u64,
+    },
+    /// Show the results of the periodic probes configured with
+    /// `--probe-target` on gatewayd
+    LiquidityHealth,
+    /// Show the hourly samples of the channel, on-chain and ecash balances and
+    /// of the fees earned, oldest first
+    LiquidityHistory,
+    /// Set the fees and CLTV delta for payments forwarded over a channel
+    UpdateChannelPolicy {
+        /// The short channel id of the channel, as printed by
+        /// `list-active-channels`
+        #[clap(long)]
+        short_channel_id: u64,
+
+        #[clap(flatten)]
+        policy: ChannelPolicy,
+    },
+    /// Send a spontaneous (keysend) payment to another lightning node
+    PayKeysend {
+        /// The public key of the node to pay
+        #[clap(long)]
+        node_id: bitcoin::secp256k1::PublicKey,
+
+        #[clap(long)]
+        amount_msat: u64,
+
+        /// The maximum routing fee to pay
+        #[clap(long)]
+        max_fee_msat: u64,
+
+        /// Custom record to attach to the payment, repeatable. Format:
+        /// <type>=<hex value>
+        #[clap(long = "tlv-record")]
+        tlv_records: Vec<TlvRecord>,
+    },
+    /// Print the attempts to pay an outgoing LNv2 payment
+    PaymentAttempts {
+        #[clap(long)]
+        payment_hash: sha256::Hash,
+    },
     /// Wait for the lightning node to be synced with the blockchain
     WaitForChainSync {
         /// The block height to wait for
@@ -165,6 +508,284 @@ pub enum LightningCommands {
     },
 }
 
+/// Buy channels with inbound liquidity from a Lightning Service Provider. The
+/// gateway's lightning node has to be connected to the LSP.
+#[derive(Subcommand)]
+pub enum Lsps1Commands {
+    /// Show the channel options and limits offered by an LSP
+    GetInfo {
+        #[clap(long)]
+        lsp_node_id: bitcoin::secp256k1::PublicKey,
+    },
+    /// Request a quote for a channel, which has to be paid with `pay-order`
+    CreateOrder {
+        #[clap(long)]
+        lsp_node_id: bitcoin::secp256k1::PublicKey,
+
+        /// The inbound liquidity to buy
+        #[clap(long)]
+        lsp_balance_sats: u64,
+
+        /// Outbound liquidity to buy on top of the inbound liquidity
+        #[clap(long, default_value_t = 0)]
+        client_balance_sats: u64,
+
+        /// The number of blocks the LSP has to keep the channel open
+        #[clap(long)]
+        channel_expiry_blocks: u32,
+
+        #[clap(long)]
+        announce_channel: bool,
+    },
+    /// Pay for an order with the gateway's lightning node
+    PayOrder {
+        #[clap(long)]
+        order_id: String,
+
+        /// The maximum routing fee to pay
+        #[clap(long)]
+        max_fee_msat: u64,
+    },
+    /// Fetch the current state of an order from the LSP
+    GetOrder {
+        #[clap(long)]
+        order_id: String,
+    },
+}
+
+/// Manage the routing history the lightning node uses for pathfinding. Only
+/// supported by LND.
+#[derive(Subcommand)]
+pub enum MissionControlCommands {
+    /// Print the routing history as JSON
+    Export,
+    /// Import a routing history printed by `export`, e.g. after migrating the
+    /// lightning node
+    Import {
+        /// Path to the JSON file written by `export`
+        file: PathBuf,
+
+        /// Override the node's own history even if it is more recent
+        #[clap(long)]
+        force: bool,
+    },
+    /// Clear the routing history
+    Reset,
+    /// Summarize the routing history
+    Stats,
+}
+
+/// Keep the ecash balance of federations within a range by withdrawing the
+/// excess to the lightning node and requesting deposits for shortfalls
+#[derive(Subcommand)]
+pub enum RebalanceCommands {
+    /// Set the range the ecash balance of a federation is kept within
+    SetBand {
+        #[clap(long)]
+        federation_id: FederationId,
+
+        #[clap(long)]
+        min_balance_msat: u64,
+
+        #[clap(long)]
+        max_balance_msat: u64,
+    },
+    /// Stop rebalancing a federation
+    RemoveBand {
+        #[clap(long)]
+        federation_id: FederationId,
+    },
+    /// Stop rebalancing all federations until `resume` is called
+    Pause,
+    /// Resume rebalancing after `pause`
+    Resume,
+    /// Print the bands and the most recent rebalancing actions
+    Status,
+}
+
+/// Create many invoices at once, e.g. for the fixed prices of a point of sale
+#[derive(Subcommand)]
+pub enum InvoiceBatchCommands {
+    /// Create an invoice for every amount and print the batch
+    Create {
+        /// Amount of an invoice, can be repeated
+        #[clap(long = "amount-msat", required = true)]
+        amounts_msat: Vec<u64>,
+
+        #[clap(long)]
+        description: String,
+
+        #[clap(long, default_value_t = 86_400)]
+        expiry_secs: u32,
+
+        /// JSON object of strings passed back in the notifications
+        #[clap(long)]
+        metadata: Option<String>,
+
+        /// Url to post a notification to for every paid invoice
+        #[clap(long)]
+        webhook_url: Option<SafeUrl>,
+    },
+    /// Print all batches and the state of their invoices
+    List,
+}
+
+/// Drain the gateway before maintenance, so that it can be shut down without
+/// interrupting payments
+#[derive(Subcommand)]
+pub enum DrainCommands {
+    /// Stop accepting new payments and federations and complete the payments
+    /// in flight
+    Start {
+        /// Wait until the gateway is drained, printing the progress
+        #[clap(long)]
+        wait: bool,
+    },
+    /// Accept new payments and federations again
+    Stop,
+    /// Print the payments and operations that are still in flight
+    Status,
+}
+
+/// Move the gateway to new hardware without recovering every federation
+#[derive(Subcommand)]
+pub enum SnapshotCommands {
+    /// Print an encrypted snapshot of the gateway's database and the databases
+    /// of its federation clients. The gateway has to be drained first.
+    Create {
+        /// Password the snapshot is encrypted with
+        #[clap(long)]
+        password: String,
+    },
+    /// Restore a snapshot before the gateway joins a federation. The gateway
+    /// has to be restarted afterwards.
+    Restore {
+        /// Path to the JSON file written by `snapshot create`
+        file: PathBuf,
+
+        #[clap(long)]
+        password: String,
+    },
+}
+
+/// Move on-chain funds of the lightning node into its channels via submarine
+/// swaps and back via reverse swaps. Requires the gateway to be started with
+/// `--swap-provider-url`.
+#[derive(Subcommand)]
+pub enum SwapCommands {
+    /// Send on-chain funds to the swap provider, which pays an invoice of the
+    /// lightning node in return
+    Create {
+        /// Amount the lightning node receives
+        #[clap(long)]
+        amount_sats: u64,
+
+        /// Maximum fee the swap provider may charge on top of the amount
+        #[clap(long)]
+        max_fee_sats: u64,
+
+        /// Fee rate of the lockup transaction, estimated by the node if not set
+        #[clap(long)]
+        sat_per_vbyte: Option<u64>,
+    },
+    /// Send the lockup of a failed swap back to the on-chain wallet of the
+    /// lightning node once its timeout is reached. Failed swaps are refunded
+    /// automatically, this refunds them again with a higher fee rate.
+    Refund {
+        /// Id of the swap
+        id: String,
+
+        /// Fee rate of the refund transaction, estimated by the gateway's
+        /// chain source if not set
+        #[clap(long)]
+        sat_per_vbyte: Option<u64>,
+    },
+    /// Print all swaps
+    List,
+    /// Pay the swap provider from the channels of the lightning node, which
+    /// locks up the amount minus its fee on-chain for the node to claim
+    CreateReverse {
+        /// Amount the lightning node pays
+        #[clap(long)]
+        amount_sats: u64,
+
+        /// Maximum of the swap provider's fee and the routing fee together
+        #[clap(long)]
+        max_fee_sats: u64,
+    },
+    /// Print all reverse swaps
+    ListReverse,
+}
+
+/// Manage the lightning addresses served by the gateway. Requires the gateway
+/// to be started with `--lightning-address-domain`.
+#[derive(Subcommand)]
+pub enum LightningAddressCommands {
+    /// Register a lightning address or update its settings. Payments to it are
+    /// credited to the user in the given federation.
+    Register {
+        #[clap(long)]
+        username: String,
+
+        #[clap(long)]
+        federation_id: FederationId,
+
+        /// Shown by the payer's wallet
+        #[clap(long)]
+        description: String,
+
+        #[clap(long, default_value_t = 1_000)]
+        min_sendable_msat: u64,
+
+        #[clap(long, default_value_t = 100_000_000_000)]
+        max_sendable_msat: u64,
+    },
+    /// Remove a lightning address. Its ecash has to be claimed first.
+    Remove {
+        #[clap(long)]
+        username: String,
+    },
+    /// Print the ecash notes issued to the user of a lightning address, to be
+    /// passed on to the user. The notes are printed again until their
+    /// operation ids are acknowledged. Unclaimed notes return to the gateway
+    /// after a year.
+    Claim {
+        #[clap(long)]
+        username: String,
+
+        /// Operation id of notes that were passed on to the user, so the
+        /// gateway forgets them
+        #[clap(long)]
+        acknowledge: Vec<OperationId>,
+    },
+    /// Print the lightning addresses and the amounts credited and issued to
+    /// them
+    List,
+}
+
+/// Manage tokens that can be passed as `--rpcpassword` instead of the gateway
+/// password, with limited permissions
+#[derive(Subcommand)]
+pub enum ApiTokenCommands {
+    /// Create a token and print it. The token can't be shown again.
+    Create {
+        /// Describes what the token is used for
+        #[clap(long)]
+        name: String,
+
+        #[clap(long)]
+        scope: ApiTokenScope,
+    },
+    /// Print the created tokens, without the tokens themselves
+    List,
+    /// Revoke a token
+    Revoke {
+        /// The hash of the token, as printed by `list`
+        #[clap(long)]
+        token_hash: sha256::Hash,
+    },
+}
+
 #[derive(Clone)]
 pub struct PerFederationRoutingFees {
     pub federation_id: FederationId,
@@ -186,6 +807,27 @@ impl std::str::FromStr for PerFederationRoutingFees {
     }
 }
 
+#[derive(Clone)]
+pub struct TlvRecord {
+    pub record_type: u64,
+    pub value: Vec<u8>,
+}
+
+impl std::str::FromStr for TlvRecord {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((record_type, value)) = s.split_once('=') {
+            Ok(TlvRecord {
+                record_type: record_type.parse()?,
+                value: hex::decode(value)?,
+            })
+        } else {
+            bail!("Wrong format, please provide: <type>=<hex value>");
+        }
+    }
+}
+
 impl From<PerFederationRoutingFees> for (FederationId, FederationRoutingFees) {
     fn from(val: PerFederationRoutingFees) -> Self {
         (val.federation_id, val.routing_fees)
@@ -198,7 +840,10 @@ async fn main() -> anyhow::Result<()> {
 
     let cli = Cli::parse();
     let versioned_api = cli.address.join(V1_API_ENDPOINT)?;
-    let client = || GatewayRpcClient::new(versioned_api.clone(), cli.rpcpassword.clone());
+    let client = || {
+        GatewayRpcClient::new(versioned_api.clone(), cli.rpcpassword.clone())
+            .with_spending_password(cli.spending_password.clone())
+    };
 
     match cli.command {
         Commands::VersionHash => {
@@ -296,6 +941,238 @@ async fn main() -> anyhow::Result<()> {
                 })
                 .await?;
         }
+        Commands::SetSpendingPassword {
+            new_spending_password,
+            payment_threshold_msat,
+        } => {
+            client()
+                .set_spending_password(SetSpendingPasswordPayload {
+                    spending_password: new_spending_password,
+                    payment_threshold: payment_threshold_msat.map(Amount::from_msats),
+                })
+                .await?;
+        }
+        Commands::SetIncomingLimits {
+            federation_id,
+            max_payment_msat,
+            max_payments_per_minute,
+            max_daily_volume_msat,
+        } => {
+            client()
+                .set_incoming_limits(SetIncomingLimitsPayload {
+                    federation_id,
+                    limits: IncomingLimits {
+                        max_payment_msat,
+                        max_payments_per_minute,
+                        max_daily_volume_msat,
+                    },
+                })
+                .await?;
+        }
+        Commands::SetLiquidityCap {
+            federation_id,
+            max_balance_msat,
+            withdraw_excess,
+        } => {
+            client()
+                .set_liquidity_cap(SetLiquidityCapPayload {
+                    federation_id,
+                    cap: max_balance_msat.map(|max_balance_msat| LiquidityCap {
+                        max_balance_msat,
+                        withdraw_excess,
+                    }),
+                })
+                .await?;
+        }
+        Commands::SetLightningProtocols {
+            federation_id,
+            disable_lnv1,
+            disable_lnv2,
+        } => {
+            client()
+                .set_lightning_protocols(SetLightningProtocolsPayload {
+                    federation_id,
+                    protocols: LightningProtocols {
+                        lnv1: !disable_lnv1,
+                        lnv2: !disable_lnv2,
+                    },
+                })
+                .await?;
+        }
+        Commands::SetDynamicFees { policy, dry_run } => {
+            let response = client()
+                .set_dynamic_fee_policy(SetDynamicFeePolicyPayload {
+                    policy: Some(policy),
+                    dry_run,
+                })
+                .await?;
+
+            print_response(response);
+        }
+        Commands::SetSweepPolicy {
+            address,
+            threshold_sats,
+            interval_secs,
+            sat_per_vbyte,
+        } => {
+            client()
+                .set_sweep_policy(SetSweepPolicyPayload {
+                    policy: Some(SweepPolicy {
+                        address,
+                        threshold_sats,
+                        interval_secs,
+                        sat_per_vbyte,
+                    }),
+                })
+                .await?;
+        }
+        Commands::DisableSweep => {
+            client()
+                .set_sweep_policy(SetSweepPolicyPayload { policy: None })
+                .await?;
+        }
+        Commands::SweepPolicy => {
+            let response = client().sweep_policy().await?;
+            print_response(response);
+        }
+        Commands::SetAlertPolicy {
+            webhook_url,
+            smtp_relay,
+            email_from,
+            email_to,
+            min_inbound_sats,
+            channel_down,
+            federation_unreachable,
+            max_failure_percent,
+        } => {
+            let email = match (smtp_relay, email_from, email_to) {
+                (Some(smtp_relay), Some(from), Some(to)) => Some(AlertEmail {
+                    smtp_relay,
+                    from,
+                    to,
+                }),
+                (None, None, None) => None,
+                _ => bail!("Alert emails need --smtp-relay, --email-from and --email-to"),
+            };
+
+            client()
+                .set_alert_policy(SetAlertPolicyPayload {
+                    policy: Some(AlertPolicy {
+                        webhook_url,
+                        email,
+                        min_inbound_sats,
+                        channel_down,
+                        federation_unreachable,
+                        max_failure_percent,
+                    }),
+                })
+                .await?;
+        }
+        Commands::DisableAlerts => {
+            client()
+                .set_alert_policy(SetAlertPolicyPayload { policy: None })
+                .await?;
+        }
+        Commands::AlertPolicy => {
+            let response = client().alert_policy().await?;
+            print_response(response);
+        }
+        Commands::AuditLog => {
+            let response = client().audit_log().await?;
+            print_response(response);
+        }
+        Commands::PaymentLog {
+            filter,
+            start,
+            end,
+            offset,
+            limit,
+        } => {
+            let response = client()
+                .payment_log(PaymentLogPayload {
+                    filter,
+                    start: start.map(unix_time),
+                    end: end.map(unix_time),
+                    offset,
+                    limit,
+                })
+                .await?;
+            print_response(response);
+        }
+        Commands::PaymentSummary { period, start, end } => {
+            let response = client()
+                .payment_summary(PaymentSummaryPayload {
+                    period,
+                    start: start.map(unix_time),
+                    end: end.map(unix_time),
+                })
+                .await?;
+            print_response(response);
+        }
+        Commands::ExportPaymentLog { format, start, end } => {
+            let response = client()
+                .export_payment_log(ExportPaymentLogPayload {
+                    format,
+                    start: start.map(unix_time),
+                    end: end.map(unix_time),
+                })
+                .await?;
+            print!("{response}");
+        }
+        Commands::SetPaymentRetryPolicy { policy } => {
+            client()
+                .set_payment_retry_policy(SetPaymentRetryPolicyPayload {
+                    policy: Some(policy),
+                })
+                .await?;
+        }
+        Commands::DisablePaymentRetries => {
+            client()
+                .set_payment_retry_policy(SetPaymentRetryPolicyPayload { policy: None })
+                .await?;
+        }
+        Commands::PaymentRetryPolicy => {
+            let response = client().payment_retry_policy().await?;
+            print_response(response);
+        }
+        Commands::DisableDynamicFees => {
+            client()
+                .set_dynamic_fee_policy(SetDynamicFeePolicyPayload {
+                    policy: None,
+                    dry_run: false,
+                })
+                .await?;
+        }
+        Commands::ExportPolicy => {
+            let response = client().export_policy().await?;
+
+            print_response(response);
+        }
+        Commands::ImportPolicy { file, dry_run } => {
+            let policy: GatewayPolicy = serde_json::from_str(&std::fs::read_to_string(file)?)?;
+            let response = client()
+                .import_policy(ImportPolicyPayload { policy, dry_run })
+                .await?;
+
+            print_response(response);
+        }
+        Commands::MonitoringBundle { output_dir } => {
+            let response = client().monitoring_bundle().await?;
+
+            match output_dir {
+                Some(output_dir) => {
+                    std::fs::write(
+                        output_dir.join("grafana-dashboard.json"),
+                        serde_json::to_string_pretty(&response.grafana_dashboard)?,
+                    )?;
+                    std::fs::write(
+                        output_dir.join("prometheus-rules.yml"),
+                        serde_json::to_string_pretty(&response.prometheus_rules)?,
+                    )?;
+                }
+                None => print_response(response),
+            }
+        }
 
         Commands::Lightning(lightning_command) => match lightning_command {
             LightningCommands::GetFundingAddress => {
@@ -310,6 +1187,7 @@ async fn main() -> anyhow::Result<()> {
                 host,
                 channel_size_sats,
                 push_amount_sats,
+                options,
             } => {
                 client()
                     .open_channel(OpenChannelPayload {
@@ -317,6 +1195,7 @@ async fn main() -> anyhow::Result<()> {
                         host,
                         channel_size_sats,
                         push_amount_sats: push_amount_sats.unwrap_or(0),
+                        options,
                     })
                     .await?;
             }
@@ -330,6 +1209,84 @@ async fn main() -> anyhow::Result<()> {
                 let response = client().list_active_channels().await?;
                 print_response(response);
             }
+            LightningCommands::ConnectPeer {
+                pubkey,
+                host,
+                persistent,
+            } => {
+                client()
+                    .connect_peer(ConnectPeerPayload {
+                        pubkey,
+                        host,
+                        persistent,
+                    })
+                    .await?;
+            }
+            LightningCommands::DisconnectPeer { pubkey } => {
+                client()
+                    .disconnect_peer(DisconnectPeerPayload { pubkey })
+                    .await?;
+            }
+            LightningCommands::ListPeers => {
+                let response = client().list_peers().await?;
+                print_response(response);
+            }
+            LightningCommands::QueryRoute {
+                destination,
+                amount_msat,
+            } => {
+                let response = client()
+                    .query_route(QueryRoutePayload {
+                        destination,
+                        amount: Amount::from_msats(amount_msat),
+                    })
+                    .await?;
+                print_response(response);
+            }
+            LightningCommands::LiquidityHealth => {
+                let response = client().liquidity_health().await?;
+                print_response(response);
+            }
+            LightningCommands::LiquidityHistory => {
+                let response = client().liquidity_history().await?;
+                print_response(response);
+            }
+            LightningCommands::UpdateChannelPolicy {
+                short_channel_id,
+                policy,
+            } => {
+                client()
+                    .update_channel_policy(UpdateChannelPolicyPayload {
+                        short_channel_id,
+                        policy,
+                    })
+                    .await?;
+            }
+            LightningCommands::PayKeysend {
+                node_id,
+                amount_msat,
+                max_fee_msat,
+                tlv_records,
+            } => {
+                let response = client()
+                    .pay_keysend(PayKeysendPayload {
+                        node_id,
+                        amount: Amount::from_msats(amount_msat),
+                        max_fee: Amount::from_msats(max_fee_msat),
+                        tlv_records: tlv_records
+                            .into_iter()
+                            .map(|record| (record.record_type, record.value))
+                            .collect(),
+                    })
+                    .await?;
+                print_response(response);
+            }
+            LightningCommands::PaymentAttempts { payment_hash } => {
+                let response = client()
+                    .payment_attempts(PaymentAttemptsPayload { payment_hash })
+                    .await?;
+                print_response(response);
+            }
             LightningCommands::WaitForChainSync {
                 block_height,
                 max_retries,
@@ -358,6 +1315,281 @@ async fn main() -> anyhow::Result<()> {
                 .map_err(|_| anyhow::anyhow!("Timed out waiting for chain sync"))?;
             }
         },
+        Commands::Lsps1(lsps1_command) => match lsps1_command {
+            Lsps1Commands::GetInfo { lsp_node_id } => {
+                let response = client()
+                    .lsps1_get_info(Lsps1GetInfoPayload { lsp_node_id })
+                    .await?;
+                print_response(response);
+            }
+            Lsps1Commands::CreateOrder {
+                lsp_node_id,
+                lsp_balance_sats,
+                client_balance_sats,
+                channel_expiry_blocks,
+                announce_channel,
+            } => {
+                let response = client()
+                    .lsps1_create_order(Lsps1CreateOrderPayload {
+                        lsp_node_id,
+                        lsp_balance_sat: lsp_balance_sats,
+                        client_balance_sat: client_balance_sats,
+                        channel_expiry_blocks,
+                        announce_channel,
+                    })
+                    .await?;
+                print_response(response);
+            }
+            Lsps1Commands::PayOrder {
+                order_id,
+                max_fee_msat,
+            } => {
+                let response = client()
+                    .lsps1_pay_order(Lsps1PayOrderPayload {
+                        order_id,
+                        max_fee: Amount::from_msats(max_fee_msat),
+                    })
+                    .await?;
+                print_response(response);
+            }
+            Lsps1Commands::GetOrder { order_id } => {
+                let response = client()
+                    .lsps1_get_order(Lsps1OrderPayload { order_id })
+                    .await?;
+                print_response(response);
+            }
+        },
+        Commands::MissionControl(mission_control_command) => match mission_control_command {
+            MissionControlCommands::Export => {
+                let response = client().export_mission_control().await?;
+                print_response(response);
+            }
+            MissionControlCommands::Import { file, force } => {
+                let pairs = serde_json::from_str(&std::fs::read_to_string(file)?)?;
+                client()
+                    .import_mission_control(ImportMissionControlPayload { pairs, force })
+                    .await?;
+            }
+            MissionControlCommands::Reset => {
+                client().reset_mission_control().await?;
+            }
+            MissionControlCommands::Stats => {
+                let response = client().mission_control_stats().await?;
+                print_response(response);
+            }
+        },
+        Commands::InvoiceBatch(invoice_batch_command) => match invoice_batch_command {
+            InvoiceBatchCommands::Create {
+                amounts_msat,
+                description,
+                expiry_secs,
+                metadata,
+                webhook_url,
+            } => {
+                let metadata = match metadata {
+                    Some(metadata) => serde_json::from_str(&metadata)?,
+                    None => BTreeMap::new(),
+                };
+
+                let response = client()
+                    .create_invoice_batch(CreateInvoiceBatchPayload {
+                        amounts: amounts_msat.into_iter().map(Amount::from_msats).collect(),
+                        description,
+                        expiry_secs,
+                        metadata,
+                        webhook_url,
+                    })
+                    .await?;
+
+                print_response(response);
+            }
+            InvoiceBatchCommands::List => {
+                let response = client().list_invoice_batches().await?;
+                print_response(response);
+            }
+        },
+        Commands::Rebalance(rebalance_command) => match rebalance_command {
+            RebalanceCommands::SetBand {
+                federation_id,
+                min_balance_msat,
+                max_balance_msat,
+            } => {
+                client()
+                    .set_rebalance_band(SetRebalanceBandPayload {
+                        federation_id,
+                        band: Some(RebalanceBand {
+                            min_balance_msat,
+                            max_balance_msat,
+                        }),
+                    })
+                    .await?;
+            }
+            RebalanceCommands::RemoveBand { federation_id } => {
+                client()
+                    .set_rebalance_band(SetRebalanceBandPayload {
+                        federation_id,
+                        band: None,
+                    })
+                    .await?;
+            }
+            RebalanceCommands::Pause => {
+                client()
+                    .set_rebalancing_paused(SetRebalancingPausedPayload { paused: true })
+                    .await?;
+            }
+            RebalanceCommands::Resume => {
+                client()
+                    .set_rebalancing_paused(SetRebalancingPausedPayload { paused: false })
+                    .await?;
+            }
+            RebalanceCommands::Status => {
+                let response = client().rebalance_status().await?;
+                print_response(response);
+            }
+        },
+        Commands::ApiToken(api_token_command) => match api_token_command {
+            ApiTokenCommands::Create { name, scope } => {
+                let response = client()
+                    .create_api_token(CreateApiTokenPayload { name, scope })
+                    .await?;
+                print_response(response);
+            }
+            ApiTokenCommands::List => {
+                let response = client().list_api_tokens().await?;
+                print_response(response);
+            }
+            ApiTokenCommands::Revoke { token_hash } => {
+                client()
+                    .revoke_api_token(RevokeApiTokenPayload { token_hash })
+                    .await?;
+            }
+        },
+        Commands::Drain(drain_command) => match drain_command {
+            DrainCommands::Start { wait } => {
+                let mut status = client()
+                    .set_draining(SetDrainingPayload { draining: true })
+                    .await?;
+
+                while wait && !status.drained {
+                    eprintln!(
+                        "Waiting for {} HTLCs and {} operations to complete...",
+                        status.in_flight_htlcs,
+                        status.active_operations.values().sum::<usize>()
+                    );
+                    sleep(DRAIN_POLL_INTERVAL).await;
+                    status = client().drain_status().await?;
+                }
+
+                print_response(status);
+            }
+            DrainCommands::Stop => {
+                let response = client()
+                    .set_draining(SetDrainingPayload { draining: false })
+                    .await?;
+                print_response(response);
+            }
+            DrainCommands::Status => {
+                let response = client().drain_status().await?;
+                print_response(response);
+            }
+        },
+        Commands::LightningAddress(lightning_address_command) => match lightning_address_command {
+            LightningAddressCommands::Register {
+                username,
+                federation_id,
+                description,
+                min_sendable_msat,
+                max_sendable_msat,
+            } => {
+                let response = client()
+                    .register_lightning_address(RegisterLightningAddressPayload {
+                        username,
+                        federation_id,
+                        description,
+                        min_sendable_msat,
+                        max_sendable_msat,
+                    })
+                    .await?;
+                print_response(response);
+            }
+            LightningAddressCommands::Remove { username } => {
+                client()
+                    .remove_lightning_address(RemoveLightningAddressPayload { username })
+                    .await?;
+            }
+            LightningAddressCommands::Claim {
+                username,
+                acknowledge,
+            } => {
+                let response = client()
+                    .claim_lightning_address(ClaimLightningAddressPayload {
+                        username,
+                        acknowledge,
+                    })
+                    .await?;
+                print_response(response);
+            }
+            LightningAddressCommands::List => {
+                let response = client().list_lightning_addresses().await?;
+                print_response(response);
+            }
+        },
+        Commands::Snapshot(snapshot_command) => match snapshot_command {
+            SnapshotCommands::Create { password } => {
+                let response = client()
+                    .create_snapshot(CreateSnapshotPayload { password })
+                    .await?;
+                print_response(response);
+            }
+            SnapshotCommands::Restore { file, password } => {
+                let snapshot = serde_json::from_str(&std::fs::read_to_string(file)?)?;
+                client()
+                    .restore_snapshot(RestoreSnapshotPayload { snapshot, password })
+                    .await?;
+            }
+        },
+        Commands::Swap(swap_command) => match swap_command {
+            SwapCommands::Create {
+                amount_sats,
+                max_fee_sats,
+                sat_per_vbyte,
+            } => {
+                let response = client()
+                    .create_swap(CreateSwapPayload {
+                        amount_sats,
+                        max_fee_sats,
+                        sat_per_vbyte,
+                    })
+                    .await?;
+                print_response(response);
+            }
+            SwapCommands::Refund { id, sat_per_vbyte } => {
+                let response = client()
+                    .refund_swap(RefundSwapPayload { id, sat_per_vbyte })
+                    .await?;
+                print_response(response);
+            }
+            SwapCommands::List => {
+                let response = client().list_swaps().await?;
+                print_response(response);
+            }
+            SwapCommands::CreateReverse {
+                amount_sats,
+                max_fee_sats,
+            } => {
+                let response = client()
+                    .create_reverse_swap(CreateReverseSwapPayload {
+                        amount_sats,
+                        max_fee_sats,
+                    })
+                    .await?;
+                print_response(response);
+            }
+            SwapCommands::ListReverse => {
+                let response = client().list_reverse_swaps().await?;
+                print_response(response);
+            }
+        },
     }
 
     Ok(())
@@ -369,3 +1601,9 @@ pub fn print_response<T: Serialize>(val: T) {
         serde_json::to_string_pretty(&val).expect("Cannot serialize")
     );
 }
+
+/// Converts seconds since the Unix epoch, as taken by the commands filtering
+/// by time
+fn unix_time(secs: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+}
@@ -0,0 +1,88 @@
+use std::time::SystemTime;
+
+use bitcoin_hashes::{sha256, Hash};
+use clap::ValueEnum;
+use fedimint_core::encoding::{Decodable, Encodable};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Permissions of an API token. Every scope includes the permissions of the
+/// scopes before it.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    ValueEnum,
+    Encodable,
+    Decodable,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiTokenScope {
+    /// Read the gateway's info, balances and status, e.g. for monitoring
+    ReadOnly,
+    /// Additionally receive payments and send keysend payments
+    Payments,
+    /// Full access, like the gateway password
+    Admin,
+}
+
+impl ApiTokenScope {
+    pub fn allows(self, required: ApiTokenScope) -> bool {
+        self >= required
+    }
+}
+
+/// A token that can be supplied as Bearer token instead of the gateway
+/// password. Only the hash of the token is stored, so the token itself is
+/// only shown once it is created.
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct ApiToken {
+    /// Identifies the token, e.g. to revoke it
+    pub token_hash: sha256::Hash,
+    pub name: String,
+    pub scope: ApiTokenScope,
+    pub created_at: SystemTime,
+}
+
+/// Returned once when an API token is created
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreatedApiToken {
+    pub token: String,
+    pub token_hash: sha256::Hash,
+}
+
+/// Generates a random token and its hash
+pub fn generate_api_token() -> (String, sha256::Hash) {
+    let token = hex::encode(rand::thread_rng().gen::<[u8; 32]>());
+    let token_hash = hash_api_token(&token);
+    (token, token_hash)
+}
+
+/// Tokens are random, so unlike passwords they don't need a salt
+pub fn hash_api_token(token: &str) -> sha256::Hash {
+    sha256::Hash::hash(token.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_api_token, hash_api_token, ApiTokenScope};
+
+    #[test]
+    fn scopes_include_lower_scopes() {
+        assert!(ApiTokenScope::Admin.allows(ApiTokenScope::Payments));
+        assert!(ApiTokenScope::Payments.allows(ApiTokenScope::ReadOnly));
+        assert!(ApiTokenScope::ReadOnly.allows(ApiTokenScope::ReadOnly));
+        assert!(!ApiTokenScope::ReadOnly.allows(ApiTokenScope::Payments));
+        assert!(!ApiTokenScope::Payments.allows(ApiTokenScope::Admin));
+
+        let (token, token_hash) = generate_api_token();
+        assert_eq!(hash_api_token(&token), token_hash);
+        assert_ne!(generate_api_token().0, token);
+    }
+}
@@ -0,0 +1,114 @@
+use std::time::SystemTime;
+
+use bitcoin_hashes::sha256;
+use fedimint_core::encoding::{Decodable, Encodable};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Number of entries kept in the audit log, older entries are dropped
+pub const AUDIT_LOG_SIZE: usize = 1_000;
+
+/// Attempts to drop the oldest entries of the audit log before leaving it to
+/// the next recorded entry, e.g. if concurrent requests keep conflicting
+pub const AUDIT_LOG_PRUNE_ATTEMPTS: usize = 5;
+
+/// Fields of request bodies that are not recorded, because they hold secrets
+/// or, like encrypted snapshots, are too large to keep
+const REDACTED_FIELDS: &[&str] = &["password", "spending_password", "snapshot"];
+
+/// Who authenticated the request of an audited action
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditActor {
+    /// The gateway password
+    Password,
+    ApiToken {
+        token_hash: sha256::Hash,
+        name: String,
+    },
+    /// Nobody, the gateway was not configured with a password yet
+    Unauthenticated,
+}
+
+/// Mutating admin request, recorded whether it succeeded or not
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub recorded_at: SystemTime,
+    pub actor: AuditActor,
+    /// Endpoint of the request, e.g. `/withdraw`
+    pub action: String,
+    /// JSON body of the request without the redacted fields
+    pub parameters: String,
+    /// Whether the gateway carried out the action
+    pub success: bool,
+}
+
+/// Returns the request body to record, with the values of the redacted fields
+/// replaced. Bodies that are not JSON are not recorded at all, since they
+/// can't be redacted.
+pub fn redact_parameters(body: &[u8]) -> String {
+    if body.is_empty() {
+        return String::new();
+    }
+
+    match serde_json::from_slice::<Value>(body) {
+        Ok(mut parameters) => {
+            redact_value(&mut parameters);
+            parameters.to_string()
+        }
+        Err(_) => "<invalid JSON>".to_string(),
+    }
+}
+
+fn redact_value(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                if REDACTED_FIELDS.contains(&name.as_str()) {
+                    if !field.is_null() {
+                        *field = Value::String("<redacted>".to_string());
+                    }
+                } else {
+                    redact_value(field);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_value),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::redact_parameters;
+
+    #[test]
+    fn redacts_secrets_from_parameters() {
+        let body = json!({
+            "password": "hunter2",
+            "num_route_hints": 2,
+            "routing_fees": {"base_msat": 0, "proportional_millionths": 100},
+        });
+        assert_eq!(
+            redact_parameters(body.to_string().as_bytes()),
+            json!({
+                "password": "<redacted>",
+                "num_route_hints": 2,
+                "routing_fees": {"base_msat": 0, "proportional_millionths": 100},
+            })
+            .to_string()
+        );
+
+        // Removing the spending password is recorded as such
+        let body = json!({"spending_password": null});
+        assert_eq!(
+            redact_parameters(body.to_string().as_bytes()),
+            body.to_string()
+        );
+
+        assert_eq!(redact_parameters(b""), "");
+        assert_eq!(redact_parameters(b"password=hunter2"), "<invalid JSON>");
+    }
+}
@@ -29,11 +29,11 @@ use ln_gateway::gateway_lnrpc::get_route_hints_response::{RouteHint, RouteHintHo
 use ln_gateway::gateway_lnrpc::intercept_htlc_response::{Action, Cancel, Forward, Settle};
 use ln_gateway::gateway_lnrpc::list_active_channels_response::ChannelInfo;
 use ln_gateway::gateway_lnrpc::{
-    CloseChannelsWithPeerRequest, CloseChannelsWithPeerResponse, CreateInvoiceRequest,
-    CreateInvoiceResponse, EmptyRequest, EmptyResponse, GetFundingAddressResponse,
-    GetNodeInfoResponse, GetRouteHintsRequest, GetRouteHintsResponse, InterceptHtlcRequest,
-    InterceptHtlcResponse, ListActiveChannelsResponse, OpenChannelRequest, PayInvoiceRequest,
-    PayInvoiceResponse, PayPrunedInvoiceRequest, PrunedInvoice,
+    CloseChannelsWithPeerRequest, CloseChannelsWithPeerResponse, CommitmentType,
+    CreateInvoiceRequest, CreateInvoiceResponse, EmptyRequest, EmptyResponse,
+    GetFundingAddressResponse, GetNodeInfoResponse, GetRouteHintsRequest, GetRouteHintsResponse,
+    InterceptHtlcRequest, InterceptHtlcResponse, ListActiveChannelsResponse, OpenChannelRequest,
+    PayInvoiceRequest, PayInvoiceResponse, PayPrunedInvoiceRequest, PrunedInvoice,
 };
 use rand::rngs::OsRng;
 use rand::Rng;
@@ -48,6 +48,11 @@ use tracing::{debug, error, info, warn};
 
 const MAX_HTLC_PROCESSING_DURATION: Duration = Duration::MAX;
 
+// BOLT 9 feature bits used to request a channel type from `fundchannel`
+const CHANNEL_TYPE_STATIC_REMOTE_KEY: u32 = 12;
+const CHANNEL_TYPE_ANCHORS_ZERO_FEE_HTLC_TX: u32 = 22;
+const CHANNEL_TYPE_ZERO_CONF: u32 = 50;
+
 #[derive(Parser)]
 #[command(version)]
 struct ClnExtensionOpts {
@@ -835,6 +840,24 @@ impl GatewayLightning for ClnRpcService {
     ) -> Result<tonic::Response<EmptyResponse>, Status> {
         let request_inner = request.into_inner();
 
+        // CLN only negotiates a zero-conf channel if it is part of the requested
+        // channel type, which then also has to name the commitment format
+        let mut channel_type = match request_inner.commitment_type() {
+            CommitmentType::Default if !request_inner.zero_conf => None,
+            CommitmentType::Default | CommitmentType::StaticRemoteKey => {
+                Some(vec![CHANNEL_TYPE_STATIC_REMOTE_KEY])
+            }
+            CommitmentType::Anchors => Some(vec![
+                CHANNEL_TYPE_STATIC_REMOTE_KEY,
+                CHANNEL_TYPE_ANCHORS_ZERO_FEE_HTLC_TX,
+            ]),
+        };
+        if request_inner.zero_conf {
+            if let Some(channel_type) = channel_type.as_mut() {
+                channel_type.push(CHANNEL_TYPE_ZERO_CONF);
+            }
+        }
+
         self.rpc_client()
             .await
             .map_err(|e| Status::internal(e.to_string()))?
@@ -864,7 +887,7 @@ impl GatewayLightning for ClnRpcService {
                         cln_rpc::primitives::Amount::from_sat(request_inner.channel_size_sats),
                     ),
                     feerate: None,
-                    announce: None,
+                    announce: Some(!request_inner.private),
                     minconf: None,
                     push_msat: Some(cln_rpc::primitives::Amount::from_sat(
                         request_inner.push_amount_sats,
@@ -873,9 +896,9 @@ impl GatewayLightning for ClnRpcService {
                     request_amt: None,
                     compact_lease: None,
                     utxos: None,
-                    mindepth: None,
+                    mindepth: request_inner.zero_conf.then_some(0),
                     reserve: None,
-                    channel_type: None,
+                    channel_type,
                 },
             ))
             .await
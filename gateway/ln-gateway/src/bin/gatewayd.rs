@@ -21,7 +21,17 @@ async fn main() -> Result<(), anyhow::Error> {
     TracingSetup::default().init()?;
     let tg = TaskGroup::new();
     tg.install_kill_handler();
-    let gatewayd = Gateway::new_with_default_modules().await?;
+    // A standby gateway waits for the failover lock before it is created, so it
+    // has to be interruptible
+    let Ok(gatewayd) = tg
+        .make_handle()
+        .cancel_on_shutdown(Gateway::new_with_default_modules())
+        .await
+    else {
+        info!("Gatewayd exiting before it became active...");
+        return Ok(());
+    };
+    let gatewayd = gatewayd?;
     let shutdown_receiver = gatewayd.clone().run(&tg).await?;
     shutdown_receiver.await;
     gatewayd.leave_all_federations().await;
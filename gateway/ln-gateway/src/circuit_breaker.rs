@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use fedimint_core::config::FederationId;
+use tracing::warn;
+
+use crate::sliding_window::SlidingWindow;
+use crate::state_machine::pay::{OutgoingPaymentError, OutgoingPaymentErrorType};
+
+/// Thresholds at which the gateway suspends servicing a federation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreakerConfig {
+    /// Share of failed payments in percent at which the federation is
+    /// suspended
+    pub failure_percent: u64,
+    /// Payments required within the window before the failure rate is
+    /// considered
+    pub min_payments: usize,
+    /// Period over which the failure rate is calculated
+    pub window: Duration,
+    /// How long a federation stays suspended before the gateway tries again
+    pub cooldown: Duration,
+}
+
+/// Suspends servicing a federation whose payments fail repeatedly, e.g.
+/// because its guardians are offline or misbehaving, so the gateway doesn't
+/// keep locking up liquidity in payments that will not complete. Only failures
+/// caused by the federation are recorded, failures on the lightning side are
+/// not held against it.
+///
+/// The state is only kept in memory, a restart resumes all federations.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: Option<CircuitBreakerConfig>,
+    /// Success of the payments within the window
+    outcomes: SlidingWindow<bool>,
+    /// Map of federation -> time until which it is suspended
+    suspensions: Mutex<BTreeMap<FederationId, Instant>>,
+}
+
+impl CircuitBreaker {
+    /// Creates a circuit breaker that never trips if `config` is not set
+    pub fn new(config: Option<CircuitBreakerConfig>) -> Self {
+        CircuitBreaker {
+            config,
+            outcomes: SlidingWindow::new(config.map_or(Duration::ZERO, |config| config.window)),
+            suspensions: Mutex::default(),
+        }
+    }
+
+    /// Records the outcome of a payment through `federation_id`
+    pub fn record(&self, federation_id: FederationId, success: bool) {
+        self.record_at(federation_id, success, Instant::now());
+    }
+
+    fn record_at(&self, federation_id: FederationId, success: bool, now: Instant) {
+        let Some(config) = self.config else {
+            return;
+        };
+
+        let mut suspensions = self.suspensions.lock().expect("poisoned");
+
+        // Payments that were in flight when the federation was suspended
+        // don't extend the suspension
+        if suspensions
+            .get(&federation_id)
+            .is_some_and(|until| now < *until)
+        {
+            return;
+        }
+
+        let tripped = self.outcomes.with_entries(federation_id, now, |outcomes| {
+            outcomes.push_back((now, success));
+
+            let payments = outcomes.len();
+            let failures = outcomes.iter().filter(|(_, success)| !success).count();
+            let tripped = payments >= config.min_payments
+                && failures as u64 * 100 >= config.failure_percent * payments as u64;
+            if tripped {
+                warn!(
+                    %federation_id,
+                    failures,
+                    payments,
+                    cooldown_secs = config.cooldown.as_secs(),
+                    "Too many failed payments, suspending federation"
+                );
+                outcomes.clear();
+            }
+            tripped
+        });
+
+        if tripped {
+            suspensions.insert(federation_id, now + config.cooldown);
+        }
+    }
+
+    /// Returns the remaining time `federation_id` is suspended for, if it is
+    /// suspended
+    pub fn suspended_for(&self, federation_id: FederationId) -> Option<Duration> {
+        self.suspended_for_at(federation_id, Instant::now())
+    }
+
+    fn suspended_for_at(&self, federation_id: FederationId, now: Instant) -> Option<Duration> {
+        self.suspensions
+            .lock()
+            .expect("poisoned")
+            .get(&federation_id)
+            .and_then(|until| until.checked_duration_since(now))
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Forgets the payments and the suspension of a federation the gateway
+    /// left
+    pub fn remove(&self, federation_id: FederationId) {
+        self.outcomes.remove(federation_id);
+        self.suspensions
+            .lock()
+            .expect("poisoned")
+            .remove(&federation_id);
+    }
+}
+
+/// Returns whether a failed LNv1 payment is caused by the federation rather
+/// than by the lightning network or the paying client
+pub fn is_federation_failure(error: &OutgoingPaymentError) -> bool {
+    match error.error_type {
+        OutgoingPaymentErrorType::OutgoingContractDoesNotExist { .. }
+        | OutgoingPaymentErrorType::SwapFailed { .. }
+        | OutgoingPaymentErrorType::InvalidFederationConfiguration => true,
+        OutgoingPaymentErrorType::LightningPayError { .. }
+        | OutgoingPaymentErrorType::InvalidOutgoingContract { .. }
+        | OutgoingPaymentErrorType::InvoiceAlreadyPaid => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use bitcoin_hashes::{sha256, Hash};
+    use fedimint_core::config::FederationId;
+
+    use super::{CircuitBreaker, CircuitBreakerConfig};
+
+    #[test]
+    fn suspends_federation_after_repeated_failures() {
+        let breaker = CircuitBreaker::new(Some(CircuitBreakerConfig {
+            failure_percent: 50,
+            min_payments: 4,
+            window: Duration::from_secs(60),
+            cooldown: Duration::from_secs(300),
+        }));
+        let federation_id = FederationId::dummy();
+        let other_federation_id = FederationId(sha256::Hash::from_byte_array([1; 32]));
+        let start = Instant::now();
+
+        // Failures that dropped out of the window are not counted
+        breaker.record_at(federation_id, false, start);
+        breaker.record_at(federation_id, false, start);
+        let later = start + Duration::from_secs(60);
+        breaker.record_at(federation_id, true, later);
+        breaker.record_at(federation_id, true, later);
+        breaker.record_at(federation_id, false, later);
+        assert_eq!(breaker.suspended_for_at(federation_id, later), None);
+
+        breaker.record_at(federation_id, false, later);
+        assert_eq!(
+            breaker.suspended_for_at(federation_id, later),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(breaker.suspended_for_at(other_federation_id, later), None);
+
+        let resumed = later + Duration::from_secs(300);
+        assert_eq!(breaker.suspended_for_at(federation_id, resumed), None);
+
+        // A disabled circuit breaker never trips
+        let disabled = CircuitBreaker::new(None);
+        for _ in 0..10 {
+            disabled.record_at(federation_id, false, start);
+        }
+        assert_eq!(disabled.suspended_for_at(federation_id, start), None);
+    }
+}
@@ -6,6 +6,7 @@ use std::sync::Arc;
 use fedimint_client::module::init::ClientModuleInitRegistry;
 use fedimint_client::secret::{PlainRootSecretStrategy, RootSecretStrategy};
 use fedimint_client::Client;
+use fedimint_core::config::FederationId;
 use fedimint_core::core::ModuleInstanceId;
 use fedimint_core::db::{
     Committable, Database, DatabaseTransaction, IDatabaseTransactionOpsCoreTyped,
@@ -64,12 +65,7 @@ impl GatewayClientBuilder {
         });
         registry.attach(GatewayClientInitV2 { gateway });
 
-        let db_path = self.work_dir.join(format!("{federation_id}.db"));
-
-        let rocksdb = fedimint_rocksdb::RocksDb::open(db_path.clone()).map_err(|e| {
-            GatewayError::DatabaseError(anyhow::anyhow!("Error opening rocksdb: {e:?}"))
-        })?;
-        let db = Database::new(rocksdb, ModuleDecoderRegistry::default());
+        let db = self.open_database(federation_id)?;
 
         let mut client_builder = Client::builder(db);
         client_builder.with_module_inits(registry);
@@ -106,6 +102,16 @@ impl GatewayClientBuilder {
         .map_err(GatewayError::ClientStateMachineError)
     }
 
+    /// Opens the database of the client of the federation `federation_id`
+    pub fn open_database(&self, federation_id: FederationId) -> Result<Database> {
+        let db_path = self.work_dir.join(format!("{federation_id}.db"));
+
+        let rocksdb = fedimint_rocksdb::RocksDb::open(db_path).map_err(|e| {
+            GatewayError::DatabaseError(anyhow::anyhow!("Error opening rocksdb: {e:?}"))
+        })?;
+        Ok(Database::new(rocksdb, ModuleDecoderRegistry::default()))
+    }
+
     pub async fn save_config(
         &self,
         config: FederationConfig,
@@ -1,23 +1,44 @@
 use std::collections::BTreeMap;
+use std::time::SystemTime;
 
 use bitcoin::Network;
 use bitcoin_hashes::sha256;
 use fedimint_core::config::FederationId;
+use fedimint_core::core::OperationId;
 use fedimint_core::db::{
     DatabaseTransaction, DatabaseVersion, IDatabaseTransactionOpsCoreTyped, ServerMigrationFn,
 };
 use fedimint_core::encoding::{Decodable, Encodable};
 use fedimint_core::invite_code::InviteCode;
-use fedimint_core::{impl_db_lookup, impl_db_record, secp256k1};
+use fedimint_core::{impl_db_lookup, impl_db_record, secp256k1, Amount};
 use fedimint_ln_common::serde_routing_fees;
 use fedimint_lnv2_common::contracts::IncomingContract;
+use fedimint_mint_client::OOBNotes;
 use futures::FutureExt;
 use lightning_invoice::RoutingFees;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use strum_macros::EnumIter;
 
+use crate::alerts::AlertPolicy;
+use crate::api_token::ApiToken;
+use crate::audit::AuditEntry;
+use crate::dynamic_fees::DynamicFeePolicy;
+use crate::history::LiquiditySample;
+use crate::incoming_limits::IncomingLimits;
+use crate::invoice_batch::InvoiceBatch;
+use crate::liquidity_cap::LiquidityCap;
+use crate::lnurl::{LightningAddressInvoice, LightningAddressIssuance, LightningAddressUser};
+use crate::lsps1::Lsps1Order;
+use crate::metrics::PaymentOutcomes;
+use crate::payment_log::PaymentLogEntry;
+use crate::payment_retry::{PaymentAttempt, PaymentRetryPolicy};
+use crate::probing::ProbeRecord;
+use crate::protocols::LightningProtocols;
+use crate::rebalance::{RebalanceBand, RebalanceRecord};
 use crate::rpc::rpc_server::hash_password;
+use crate::swap::{ReverseSwapRecord, SwapRecord};
+use crate::sweep::SweepPolicy;
 
 pub const GATEWAYD_DATABASE_VERSION: DatabaseVersion = DatabaseVersion(1);
 
@@ -29,6 +50,36 @@ pub enum DbKeyPrefix {
     GatewayConfiguration = 0x07,
     PreimageAuthentication = 0x08,
     RegisteredIncomingContract = 0x09,
+    SpendingPassword = 0x0a,
+    Lsps1Order = 0x0b,
+    ProbeRecord = 0x0c,
+    PersistentPeer = 0x0d,
+    IncomingLimits = 0x0e,
+    DynamicFeePolicy = 0x0f,
+    LiquidityCap = 0x10,
+    RebalanceBand = 0x11,
+    RebalanceLog = 0x12,
+    RebalancingPaused = 0x13,
+    InvoiceBatch = 0x14,
+    SweepPolicy = 0x15,
+    ApiToken = 0x16,
+    PaymentRetryPolicy = 0x17,
+    PaymentAttempts = 0x18,
+    LightningProtocols = 0x19,
+    LightningAddressUser = 0x1a,
+    LightningAddressInvoice = 0x1b,
+    Swap = 0x1c,
+    LiquiditySample = 0x1d,
+    AlertPolicy = 0x1e,
+    AuditLog = 0x1f,
+    LightningAddressNotes = 0x20,
+    FeesEarned = 0x21,
+    PaymentOutcomes = 0x22,
+    LightningAddressPayment = 0x23,
+    ReverseSwap = 0x24,
+    PaymentNode = 0x25,
+    LightningAddressIssuance = 0x26,
+    PaymentLog = 0x27,
 }
 
 impl std::fmt::Display for DbKeyPrefix {
@@ -109,6 +160,28 @@ impl_db_record!(
     notify_on_modify = true,
 );
 
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct SpendingPasswordKey;
+
+/// Optional secondary password that has to be supplied, in addition to the
+/// gateway password, for requests that move funds out of the gateway. It is
+/// stored as an Argon2 hash, which is affordable since unlike the gateway
+/// password it is only checked when funds leave the gateway.
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct SpendingPassword {
+    pub hashed_password: [u8; 32],
+    pub password_salt: [u8; 16],
+    /// Lightning payments up to this amount don't require the spending
+    /// password, larger ones and all other requests that move funds do
+    pub payment_threshold: Option<Amount>,
+}
+
+impl_db_record!(
+    key = SpendingPasswordKey,
+    value = SpendingPassword,
+    db_prefix = DbKeyPrefix::SpendingPassword,
+);
+
 #[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
 pub struct PreimageAuthentication {
     pub payment_hash: sha256::Hash,
@@ -128,6 +201,473 @@ impl_db_lookup!(
     query_prefix = PreimageAuthenticationPrefix
 );
 
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct Lsps1OrderKey {
+    pub order_id: String,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct Lsps1OrderKeyPrefix;
+
+impl_db_record!(
+    key = Lsps1OrderKey,
+    value = Lsps1Order,
+    db_prefix = DbKeyPrefix::Lsps1Order,
+);
+
+impl_db_lookup!(key = Lsps1OrderKey, query_prefix = Lsps1OrderKeyPrefix);
+
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct ProbeRecordKey {
+    pub destination: secp256k1::PublicKey,
+    pub amount_msat: u64,
+    /// Unix timestamp in seconds
+    pub probed_at: u64,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct ProbeRecordKeyPrefix;
+
+impl_db_record!(
+    key = ProbeRecordKey,
+    value = ProbeRecord,
+    db_prefix = DbKeyPrefix::ProbeRecord,
+);
+
+impl_db_lookup!(key = ProbeRecordKey, query_prefix = ProbeRecordKeyPrefix);
+
+/// Peer the gateway keeps its lightning node connected to. The value is the
+/// host the peer is reachable at.
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct PersistentPeerKey {
+    pub pubkey: secp256k1::PublicKey,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct PersistentPeerKeyPrefix;
+
+impl_db_record!(
+    key = PersistentPeerKey,
+    value = String,
+    db_prefix = DbKeyPrefix::PersistentPeer,
+);
+
+impl_db_lookup!(
+    key = PersistentPeerKey,
+    query_prefix = PersistentPeerKeyPrefix
+);
+
+/// Limits on the incoming payments the gateway accepts for a federation
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct IncomingLimitsKey {
+    pub federation_id: FederationId,
+}
+
+impl_db_record!(
+    key = IncomingLimitsKey,
+    value = IncomingLimits,
+    db_prefix = DbKeyPrefix::IncomingLimits,
+);
+
+/// Bounds within which the gateway adjusts the routing fees of all federations
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct DynamicFeePolicyKey;
+
+impl_db_record!(
+    key = DynamicFeePolicyKey,
+    value = DynamicFeePolicy,
+    db_prefix = DbKeyPrefix::DynamicFeePolicy,
+);
+
+/// Maximum ecash balance the gateway holds in a federation
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct LiquidityCapKey {
+    pub federation_id: FederationId,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct LiquidityCapKeyPrefix;
+
+impl_db_record!(
+    key = LiquidityCapKey,
+    value = LiquidityCap,
+    db_prefix = DbKeyPrefix::LiquidityCap,
+);
+
+impl_db_lookup!(key = LiquidityCapKey, query_prefix = LiquidityCapKeyPrefix);
+
+/// Range the gateway keeps its ecash balance in a federation within
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct RebalanceBandKey {
+    pub federation_id: FederationId,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct RebalanceBandKeyPrefix;
+
+impl_db_record!(
+    key = RebalanceBandKey,
+    value = RebalanceBand,
+    db_prefix = DbKeyPrefix::RebalanceBand,
+);
+
+impl_db_lookup!(
+    key = RebalanceBandKey,
+    query_prefix = RebalanceBandKeyPrefix
+);
+
+/// Action taken to keep the ecash balance of a federation within its band
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct RebalanceLogKey {
+    pub timestamp: SystemTime,
+    pub federation_id: FederationId,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct RebalanceLogKeyPrefix;
+
+impl_db_record!(
+    key = RebalanceLogKey,
+    value = RebalanceRecord,
+    db_prefix = DbKeyPrefix::RebalanceLog,
+);
+
+impl_db_lookup!(key = RebalanceLogKey, query_prefix = RebalanceLogKeyPrefix);
+
+/// Present while the operator paused rebalancing
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct RebalancingPausedKey;
+
+impl_db_record!(
+    key = RebalancingPausedKey,
+    value = (),
+    db_prefix = DbKeyPrefix::RebalancingPaused,
+);
+
+/// Invoices the lightning node created at once
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct InvoiceBatchKey {
+    pub batch_id: u64,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct InvoiceBatchKeyPrefix;
+
+impl_db_record!(
+    key = InvoiceBatchKey,
+    value = InvoiceBatch,
+    db_prefix = DbKeyPrefix::InvoiceBatch,
+);
+
+impl_db_lookup!(key = InvoiceBatchKey, query_prefix = InvoiceBatchKeyPrefix);
+
+/// Policy for sweeping the on-chain funds of the lightning node to cold storage
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct SweepPolicyKey;
+
+impl_db_record!(
+    key = SweepPolicyKey,
+    value = SweepPolicy,
+    db_prefix = DbKeyPrefix::SweepPolicy,
+);
+
+/// API token issued by the operator, keyed by the hash of the token
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct ApiTokenKey {
+    pub token_hash: sha256::Hash,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct ApiTokenKeyPrefix;
+
+impl_db_record!(
+    key = ApiTokenKey,
+    value = ApiToken,
+    db_prefix = DbKeyPrefix::ApiToken,
+);
+
+impl_db_lookup!(key = ApiTokenKey, query_prefix = ApiTokenKeyPrefix);
+
+/// Policy for retrying outgoing LNv2 payments
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct PaymentRetryPolicyKey;
+
+impl_db_record!(
+    key = PaymentRetryPolicyKey,
+    value = PaymentRetryPolicy,
+    db_prefix = DbKeyPrefix::PaymentRetryPolicy,
+);
+
+/// Attempts to pay an outgoing LNv2 payment, oldest first
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct PaymentAttemptsKey {
+    pub payment_hash: sha256::Hash,
+}
+
+impl_db_record!(
+    key = PaymentAttemptsKey,
+    value = Vec<PaymentAttempt>,
+    db_prefix = DbKeyPrefix::PaymentAttempts,
+);
+
+/// Protocol versions the gateway serves for a federation, if they differ from
+/// the default
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct LightningProtocolsKey {
+    pub federation_id: FederationId,
+}
+
+impl_db_record!(
+    key = LightningProtocolsKey,
+    value = LightningProtocols,
+    db_prefix = DbKeyPrefix::LightningProtocols,
+);
+
+/// User of a lightning address served by the gateway
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct LightningAddressUserKey {
+    pub username: String,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct LightningAddressUserKeyPrefix;
+
+impl_db_record!(
+    key = LightningAddressUserKey,
+    value = LightningAddressUser,
+    db_prefix = DbKeyPrefix::LightningAddressUser,
+);
+
+impl_db_lookup!(
+    key = LightningAddressUserKey,
+    query_prefix = LightningAddressUserKeyPrefix
+);
+
+/// Unpaid invoice of a lightning address
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct LightningAddressInvoiceKey {
+    pub payment_hash: sha256::Hash,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct LightningAddressInvoiceKeyPrefix;
+
+impl_db_record!(
+    key = LightningAddressInvoiceKey,
+    value = LightningAddressInvoice,
+    db_prefix = DbKeyPrefix::LightningAddressInvoice,
+);
+
+impl_db_lookup!(
+    key = LightningAddressInvoiceKey,
+    query_prefix = LightningAddressInvoiceKeyPrefix
+);
+
+/// Paid invoice of a lightning address, kept so that payers can verify the
+/// payment
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct LightningAddressPaymentKey {
+    pub payment_hash: sha256::Hash,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct LightningAddressPaymentKeyPrefix;
+
+impl_db_record!(
+    key = LightningAddressPaymentKey,
+    value = LightningAddressInvoice,
+    db_prefix = DbKeyPrefix::LightningAddressPayment,
+);
+
+impl_db_lookup!(
+    key = LightningAddressPaymentKey,
+    query_prefix = LightningAddressPaymentKeyPrefix
+);
+
+/// Ecash issued to the user of a lightning address that was not claimed yet,
+/// keyed by the operation that spent it from the gateway's balance
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct LightningAddressNotesKey {
+    pub username: String,
+    pub operation_id: OperationId,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct LightningAddressNotesKeyPrefix;
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct LightningAddressNotesUserPrefix {
+    pub username: String,
+}
+
+impl_db_record!(
+    key = LightningAddressNotesKey,
+    value = OOBNotes,
+    db_prefix = DbKeyPrefix::LightningAddressNotes,
+);
+
+impl_db_lookup!(
+    key = LightningAddressNotesKey,
+    query_prefix = LightningAddressNotesKeyPrefix,
+    query_prefix = LightningAddressNotesUserPrefix
+);
+
+/// Ecash that is being issued to the user of a lightning address, recorded
+/// before it is spent from the gateway's balance
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct LightningAddressIssuanceKey {
+    pub username: String,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct LightningAddressIssuanceKeyPrefix;
+
+impl_db_record!(
+    key = LightningAddressIssuanceKey,
+    value = LightningAddressIssuance,
+    db_prefix = DbKeyPrefix::LightningAddressIssuance,
+);
+
+impl_db_lookup!(
+    key = LightningAddressIssuanceKey,
+    query_prefix = LightningAddressIssuanceKeyPrefix
+);
+
+/// Submarine swap, keyed by the swap provider's id
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct SwapKey {
+    pub id: String,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct SwapKeyPrefix;
+
+impl_db_record!(
+    key = SwapKey,
+    value = SwapRecord,
+    db_prefix = DbKeyPrefix::Swap,
+);
+
+impl_db_lookup!(key = SwapKey, query_prefix = SwapKeyPrefix);
+
+/// Reverse swap, keyed by the swap provider's id
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct ReverseSwapKey {
+    pub id: String,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct ReverseSwapKeyPrefix;
+
+impl_db_record!(
+    key = ReverseSwapKey,
+    value = ReverseSwapRecord,
+    db_prefix = DbKeyPrefix::ReverseSwap,
+);
+
+impl_db_lookup!(key = ReverseSwapKey, query_prefix = ReverseSwapKeyPrefix);
+
+/// Public key of the lightning node an outgoing payment was last sent from,
+/// keyed by its payment hash
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct PaymentNodeKey {
+    pub payment_hash: sha256::Hash,
+}
+
+impl_db_record!(
+    key = PaymentNodeKey,
+    value = secp256k1::PublicKey,
+    db_prefix = DbKeyPrefix::PaymentNode,
+);
+
+/// Periodic sample of the gateway's liquidity
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct LiquiditySampleKey {
+    pub sampled_at: SystemTime,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct LiquiditySampleKeyPrefix;
+
+impl_db_record!(
+    key = LiquiditySampleKey,
+    value = LiquiditySample,
+    db_prefix = DbKeyPrefix::LiquiditySample,
+);
+
+impl_db_lookup!(
+    key = LiquiditySampleKey,
+    query_prefix = LiquiditySampleKeyPrefix
+);
+
+/// Conditions the gateway raises alerts for
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct AlertPolicyKey;
+
+impl_db_record!(
+    key = AlertPolicyKey,
+    value = AlertPolicy,
+    db_prefix = DbKeyPrefix::AlertPolicy,
+);
+
+/// Mutating admin request. The random nonce keeps requests recorded at the
+/// same time apart.
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct AuditLogKey {
+    pub recorded_at: SystemTime,
+    pub nonce: u64,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct AuditLogKeyPrefix;
+
+impl_db_record!(
+    key = AuditLogKey,
+    value = AuditEntry,
+    db_prefix = DbKeyPrefix::AuditLog,
+);
+
+impl_db_lookup!(key = AuditLogKey, query_prefix = AuditLogKeyPrefix);
+
+/// Fees the gateway earned across all federations
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct FeesEarnedKey;
+
+impl_db_record!(
+    key = FeesEarnedKey,
+    value = Amount,
+    db_prefix = DbKeyPrefix::FeesEarned,
+);
+
+/// Outcomes of the payments the gateway finished across all federations
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct PaymentOutcomesKey;
+
+impl_db_record!(
+    key = PaymentOutcomesKey,
+    value = PaymentOutcomes,
+    db_prefix = DbKeyPrefix::PaymentOutcomes,
+);
+
+/// Finished payment. The random nonce keeps payments finished at the same
+/// time apart.
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable)]
+pub struct PaymentLogKey {
+    pub finished_at: SystemTime,
+    pub nonce: u64,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct PaymentLogKeyPrefix;
+
+impl_db_record!(
+    key = PaymentLogKey,
+    value = PaymentLogEntry,
+    db_prefix = DbKeyPrefix::PaymentLog,
+);
+
+impl_db_lookup!(key = PaymentLogKey, query_prefix = PaymentLogKeyPrefix);
+
 pub fn get_gatewayd_database_migrations() -> BTreeMap<DatabaseVersion, ServerMigrationFn> {
     let mut migrations: BTreeMap<DatabaseVersion, ServerMigrationFn> = BTreeMap::new();
     migrations.insert(DatabaseVersion(0), |dbtx| migrate_to_v1(dbtx).boxed());
@@ -297,7 +837,37 @@ mod fedimint_migration_tests {
                             ensure!(gateway_configuration.is_some(), "validate_migrations was not able to read GatewayConfiguration");
                             info!("Validated GatewayConfiguration");
                         }
-                        DbKeyPrefix::RegisteredIncomingContract => {}
+                        DbKeyPrefix::RegisteredIncomingContract
+                        | DbKeyPrefix::SpendingPassword
+                        | DbKeyPrefix::Lsps1Order
+                        | DbKeyPrefix::ProbeRecord
+                        | DbKeyPrefix::PersistentPeer
+                        | DbKeyPrefix::IncomingLimits
+                        | DbKeyPrefix::DynamicFeePolicy
+                        | DbKeyPrefix::LiquidityCap
+                        | DbKeyPrefix::RebalanceBand
+                        | DbKeyPrefix::RebalanceLog
+                        | DbKeyPrefix::RebalancingPaused
+                        | DbKeyPrefix::InvoiceBatch
+                        | DbKeyPrefix::SweepPolicy
+                        | DbKeyPrefix::ApiToken
+                        | DbKeyPrefix::PaymentRetryPolicy
+                        | DbKeyPrefix::PaymentAttempts
+                        | DbKeyPrefix::LightningProtocols
+                        | DbKeyPrefix::LightningAddressUser
+                        | DbKeyPrefix::LightningAddressInvoice
+                        | DbKeyPrefix::Swap
+                        | DbKeyPrefix::LiquiditySample
+                        | DbKeyPrefix::AlertPolicy
+                        | DbKeyPrefix::AuditLog
+                        | DbKeyPrefix::LightningAddressNotes
+                        | DbKeyPrefix::FeesEarned
+                        | DbKeyPrefix::PaymentOutcomes
+                        | DbKeyPrefix::LightningAddressPayment
+                        | DbKeyPrefix::ReverseSwap
+                        | DbKeyPrefix::PaymentNode
+                        | DbKeyPrefix::LightningAddressIssuance
+                        | DbKeyPrefix::PaymentLog => {}
                     }
                 }
                 Ok(())
@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use fedimint_core::config::FederationId;
+use serde::{Deserialize, Serialize};
+
+/// Time between two checks of the drain progress by `gateway-cli drain --wait`
+pub const DRAIN_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Progress of draining the gateway, e.g. before maintenance. While draining,
+/// the gateway doesn't accept new payments or federations, but completes the
+/// payments that are in flight.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DrainStatus {
+    pub draining: bool,
+    /// Intercepted HTLCs that have not been completed yet
+    pub in_flight_htlcs: usize,
+    /// Operations with active state machines per federation
+    pub active_operations: BTreeMap<FederationId, usize>,
+    /// Set once the gateway is draining and nothing is in flight anymore, so
+    /// it can be shut down
+    pub drained: bool,
+}
+
+impl DrainStatus {
+    pub fn new(
+        draining: bool,
+        in_flight_htlcs: usize,
+        active_operations: BTreeMap<FederationId, usize>,
+    ) -> Self {
+        let drained =
+            draining && in_flight_htlcs == 0 && active_operations.values().all(|count| *count == 0);
+
+        DrainStatus {
+            draining,
+            in_flight_htlcs,
+            active_operations,
+            drained,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use fedimint_core::config::FederationId;
+
+    use super::DrainStatus;
+
+    #[test]
+    fn drained_once_nothing_is_in_flight() {
+        let idle = BTreeMap::from([(FederationId::dummy(), 0)]);
+        let busy = BTreeMap::from([(FederationId::dummy(), 2)]);
+
+        assert!(DrainStatus::new(true, 0, idle.clone()).drained);
+        assert!(!DrainStatus::new(false, 0, idle.clone()).drained);
+        assert!(!DrainStatus::new(true, 1, idle).drained);
+        assert!(!DrainStatus::new(true, 0, busy).drained);
+    }
+}
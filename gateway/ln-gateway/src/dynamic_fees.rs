@@ -0,0 +1,205 @@
+use std::time::{Duration, Instant};
+
+use clap::Args;
+use fedimint_core::config::FederationId;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::Amount;
+use serde::{Deserialize, Serialize};
+
+use crate::lightning::ChannelInfo;
+use crate::rpc::FederationRoutingFees;
+use crate::sliding_window::SlidingWindow;
+
+/// Time between two adjustments of the routing fees
+pub const FEE_ADJUSTMENT_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Window over which the outgoing volume of a federation is measured
+const VOLUME_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Bounds for adjusting the routing fees of every federation automatically.
+/// The fees rise from the minimum towards the maximum as the outbound
+/// liquidity of the lightning node runs low or as the outgoing volume of the
+/// federation approaches `high_volume_msat`, whichever is closer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Args, Encodable, Decodable, Serialize, Deserialize)]
+pub struct DynamicFeePolicy {
+    #[clap(long)]
+    pub min_base_msat: u32,
+
+    #[clap(long)]
+    pub max_base_msat: u32,
+
+    #[clap(long)]
+    pub min_proportional_millionths: u32,
+
+    #[clap(long)]
+    pub max_proportional_millionths: u32,
+
+    /// Outgoing volume of a federation during the last hour at which it is
+    /// charged the maximum fees. The volume is not considered if not set.
+    #[clap(long)]
+    pub high_volume_msat: Option<u64>,
+}
+
+impl DynamicFeePolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_base_msat < self.min_base_msat {
+            return Err("The maximum base fee is below the minimum".to_string());
+        }
+
+        if self.max_proportional_millionths < self.min_proportional_millionths {
+            return Err("The maximum proportional fee is below the minimum".to_string());
+        }
+
+        if self.max_proportional_millionths > 1_000_000 {
+            return Err(format!(
+                "Proportional routing fee of {} millionths exceeds 100%",
+                self.max_proportional_millionths
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the fees for a federation with an outgoing volume of
+    /// `volume_msat` during the last hour, given the lightning node's
+    /// `channels`
+    pub fn fees(&self, channels: &[ChannelInfo], volume_msat: u64) -> FederationRoutingFees {
+        let outbound_sats = channels
+            .iter()
+            .map(|channel| channel.outbound_liquidity_sats)
+            .sum::<u64>();
+        let inbound_sats = channels
+            .iter()
+            .map(|channel| channel.inbound_liquidity_sats)
+            .sum::<u64>();
+
+        // Share of the channel capacity that is not available for outgoing payments
+        let liquidity_pressure = if outbound_sats + inbound_sats == 0 {
+            1.0
+        } else {
+            inbound_sats as f64 / (outbound_sats + inbound_sats) as f64
+        };
+
+        let volume_pressure = self
+            .high_volume_msat
+            .map_or(0.0, |high_volume_msat| {
+                volume_msat as f64 / high_volume_msat.max(1) as f64
+            })
+            .min(1.0);
+
+        let pressure = liquidity_pressure.max(volume_pressure);
+
+        FederationRoutingFees {
+            base_msat: interpolate(self.min_base_msat, self.max_base_msat, pressure),
+            proportional_millionths: interpolate(
+                self.min_proportional_millionths,
+                self.max_proportional_millionths,
+                pressure,
+            ),
+        }
+    }
+}
+
+fn interpolate(min: u32, max: u32, pressure: f64) -> u32 {
+    min + (f64::from(max - min) * pressure).round() as u32
+}
+
+/// A change of the routing fees of a federation made by a `DynamicFeePolicy`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeAdjustment {
+    pub federation_id: FederationId,
+    pub current: FederationRoutingFees,
+    pub adjusted: FederationRoutingFees,
+}
+
+/// Tracks the outgoing payments per federation during the last hour, which
+/// `DynamicFeePolicy` takes into account.
+#[derive(Debug)]
+pub struct OutgoingVolume {
+    // Amounts in msat of the sent payments
+    payments: SlidingWindow<u64>,
+}
+
+impl Default for OutgoingVolume {
+    fn default() -> Self {
+        OutgoingVolume {
+            payments: SlidingWindow::new(VOLUME_WINDOW),
+        }
+    }
+}
+
+impl OutgoingVolume {
+    pub fn record(&self, federation_id: FederationId, amount: Amount) {
+        self.payments
+            .record(federation_id, amount.msats, Instant::now());
+    }
+
+    /// Returns the amount in msat sent for `federation_id` during the last
+    /// hour
+    pub fn volume_msat(&self, federation_id: FederationId) -> u64 {
+        self.payments
+            .with_entries(federation_id, Instant::now(), |sent| {
+                sent.iter().map(|(_, amount_msat)| amount_msat).sum()
+            })
+    }
+
+    /// Forgets the payments of a federation the gateway left
+    pub fn remove(&self, federation_id: FederationId) {
+        self.payments.remove(federation_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DynamicFeePolicy;
+    use crate::lightning::ChannelInfo;
+    use crate::rpc::FederationRoutingFees;
+
+    fn channel(outbound_liquidity_sats: u64, inbound_liquidity_sats: u64) -> ChannelInfo {
+        ChannelInfo {
+            remote_pubkey: String::new(),
+            channel_size_sats: outbound_liquidity_sats + inbound_liquidity_sats,
+            outbound_liquidity_sats,
+            inbound_liquidity_sats,
+            short_channel_id: 0,
+        }
+    }
+
+    #[test]
+    fn fees_follow_liquidity_and_volume() {
+        let policy = DynamicFeePolicy {
+            min_base_msat: 0,
+            max_base_msat: 1_000,
+            min_proportional_millionths: 100,
+            max_proportional_millionths: 5_100,
+            high_volume_msat: Some(1_000_000),
+        };
+        assert_eq!(policy.validate(), Ok(()));
+
+        let fees = |base_msat, proportional_millionths| FederationRoutingFees {
+            base_msat,
+            proportional_millionths,
+        };
+
+        // All liquidity is outbound and nothing was sent
+        assert_eq!(policy.fees(&[channel(1_000, 0)], 0), fees(0, 100));
+
+        // Balanced channels
+        let channels = [channel(300, 200), channel(200, 300)];
+        assert_eq!(policy.fees(&channels, 0), fees(500, 2_600));
+
+        // A high volume raises the fees further
+        assert_eq!(policy.fees(&channels, 750_000), fees(750, 3_850));
+        assert_eq!(policy.fees(&channels, 5_000_000), fees(1_000, 5_100));
+
+        // Without channels there is no outbound liquidity at all
+        assert_eq!(policy.fees(&[], 0), fees(1_000, 5_100));
+
+        let inverted = DynamicFeePolicy {
+            min_base_msat: 10,
+            max_base_msat: 5,
+            ..policy
+        };
+        assert!(inverted.validate().is_err());
+    }
+}
@@ -22,6 +22,30 @@ pub const FM_GATEWAY_FEES_ENV: &str = "FM_GATEWAY_FEES";
 // Env variable to TODO
 pub const FM_NUMBER_OF_ROUTE_HINTS_ENV: &str = "FM_NUMBER_OF_ROUTE_HINTS";
 
+// Env variable to cap the number of in-flight HTLCs per channel
+pub const FM_GATEWAY_MAX_HTLCS_PER_CHANNEL_ENV: &str = "FM_GATEWAY_MAX_HTLCS_PER_CHANNEL";
+
+// Env variable to cap the total msat of in-flight HTLCs per channel
+pub const FM_GATEWAY_MAX_HTLC_MSAT_PER_CHANNEL_ENV: &str = "FM_GATEWAY_MAX_HTLC_MSAT_PER_CHANNEL";
+
+// Env variable to set the destinations the gateway probes, separated by `;`
+pub const FM_GATEWAY_PROBE_TARGETS_ENV: &str = "FM_GATEWAY_PROBE_TARGETS";
+
+// Env variable to set the seconds between two rounds of probes
+pub const FM_GATEWAY_PROBE_INTERVAL_SECS_ENV: &str = "FM_GATEWAY_PROBE_INTERVAL_SECS";
+
+// Env variable to set the on-chain balance below which outgoing payments pause
+pub const FM_GATEWAY_MIN_ONCHAIN_RESERVE_SATS_ENV: &str = "FM_GATEWAY_MIN_ONCHAIN_RESERVE_SATS";
+
+// Env variable to set the lightning balance below which outgoing payments pause
+pub const FM_GATEWAY_MIN_LIGHTNING_RESERVE_SATS_ENV: &str = "FM_GATEWAY_MIN_LIGHTNING_RESERVE_SATS";
+
+// Env variable to run the gateway against a fake lightning node
+pub const FM_GATEWAY_SANDBOX_ENV: &str = "FM_GATEWAY_SANDBOX";
+
+// Env variable to set additional lightning nodes outgoing payments are sent from
+pub const FM_GATEWAY_PAYMENT_NODES_ENV: &str = "FM_GATEWAY_PAYMENT_NODES";
+
 // Env variable to TODO
 pub const FM_LND_RPC_ADDR_ENV: &str = "FM_LND_RPC_ADDR";
 
@@ -31,5 +55,65 @@ pub const FM_LND_TLS_CERT_ENV: &str = "FM_LND_TLS_CERT";
 // Env variable to TODO
 pub const FM_LND_MACAROON_ENV: &str = "FM_LND_MACAROON";
 
+// Env variable to set the number of connections the gateway keeps open to LND
+pub const FM_LND_CONNECTION_POOL_SIZE_ENV: &str = "FM_LND_CONNECTION_POOL_SIZE";
+
+// Env variable to limit the concurrent requests per gRPC method sent to LND
+pub const FM_LND_MAX_CONCURRENT_REQUESTS_ENV: &str = "FM_LND_MAX_CONCURRENT_REQUESTS";
+
+// Env variable to set the address of the gateway's gRPC admin API
+pub const FM_GATEWAY_GRPC_LISTEN_ADDR_ENV: &str = "FM_GATEWAY_GRPC_LISTEN_ADDR";
+
+// Env variable to set the path of the PEM encoded TLS certificate of the
+// gateway's gRPC admin API
+pub const FM_GATEWAY_GRPC_TLS_CERT_ENV: &str = "FM_GATEWAY_GRPC_TLS_CERT";
+
+// Env variable to set the path of the PEM encoded TLS key of the gateway's gRPC
+// admin API
+pub const FM_GATEWAY_GRPC_TLS_KEY_ENV: &str = "FM_GATEWAY_GRPC_TLS_KEY";
+
+// Env variable to set the address of the gateway's prometheus metrics API
+pub const FM_GATEWAY_BIND_METRICS_API_ENV: &str = "FM_GATEWAY_BIND_METRICS_API";
+
+// Env variable to set the domain lightning addresses are served for
+pub const FM_GATEWAY_LIGHTNING_ADDRESS_DOMAIN_ENV: &str = "FM_GATEWAY_LIGHTNING_ADDRESS_DOMAIN";
+
+// Env variable to set the lock file that elects the active gateway for failover
+pub const FM_GATEWAY_FAILOVER_LOCK_ENV: &str = "FM_GATEWAY_FAILOVER_LOCK";
+
+// Env variable to set the fiat currencies balances are shown in
+pub const FM_GATEWAY_FIAT_CURRENCIES_ENV: &str = "FM_GATEWAY_FIAT_CURRENCIES";
+
+// Env variable to set the URL the fiat prices are fetched from
+pub const FM_GATEWAY_PRICE_ORACLE_URL_ENV: &str = "FM_GATEWAY_PRICE_ORACLE_URL";
+
+// Env variable to set the share of failed payments at which a federation is
+// suspended
+pub const FM_GATEWAY_CIRCUIT_BREAKER_FAILURE_PERCENT_ENV: &str =
+    "FM_GATEWAY_CIRCUIT_BREAKER_FAILURE_PERCENT";
+
+// Env variable to set the payments required before the failure rate is
+// considered
+pub const FM_GATEWAY_CIRCUIT_BREAKER_MIN_PAYMENTS_ENV: &str =
+    "FM_GATEWAY_CIRCUIT_BREAKER_MIN_PAYMENTS";
+
+// Env variable to set the window the failure rate is calculated over
+pub const FM_GATEWAY_CIRCUIT_BREAKER_WINDOW_SECS_ENV: &str =
+    "FM_GATEWAY_CIRCUIT_BREAKER_WINDOW_SECS";
+
+// Env variable to set how long a federation stays suspended
+pub const FM_GATEWAY_CIRCUIT_BREAKER_COOLDOWN_SECS_ENV: &str =
+    "FM_GATEWAY_CIRCUIT_BREAKER_COOLDOWN_SECS";
+
+// Env variable to set the API of the swap provider
+pub const FM_GATEWAY_SWAP_PROVIDER_URL_ENV: &str = "FM_GATEWAY_SWAP_PROVIDER_URL";
+
+// Env variable to set the kind of the gateway's own chain source, e.g.
+// `esplora`
+pub const FM_GATEWAY_BITCOIN_RPC_KIND_ENV: &str = "FM_GATEWAY_BITCOIN_RPC_KIND";
+
+// Env variable to set the URL of the gateway's own chain source
+pub const FM_GATEWAY_BITCOIN_RPC_URL_ENV: &str = "FM_GATEWAY_BITCOIN_RPC_URL";
+
 // Env variable to TODO
 pub const FM_GATEWAY_LIGHTNING_ADDR_ENV: &str = "FM_GATEWAY_LIGHTNING_ADDR";
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use fedimint_core::config::FederationId;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::Amount;
+use serde::{Deserialize, Serialize};
+
+use crate::lightning::{ChannelInfo, LightningBalances};
+
+/// Time between two checks whether the balances or channels changed, while
+/// anyone is subscribed to the events
+pub const EVENT_UPDATE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Events buffered for a subscriber that doesn't keep up, older events are
+/// dropped
+pub const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Encodable, Decodable, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentDirection {
+    Incoming,
+    Outgoing,
+}
+
+impl PaymentDirection {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            PaymentDirection::Incoming => "incoming",
+            PaymentDirection::Outgoing => "outgoing",
+        }
+    }
+}
+
+/// Event pushed to the subscribers of the gateway's event stream, e.g. to
+/// update a dashboard without polling
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GatewayEvent {
+    /// Sent when a subscriber connects and whenever the balances change
+    Balances {
+        lightning: LightningBalances,
+        ecash: BTreeMap<FederationId, Amount>,
+    },
+    /// Sent when a subscriber connects and whenever the channels change
+    Channels { channels: Vec<ChannelInfo> },
+    /// Sent when a payment through the gateway succeeded or failed
+    Payment {
+        federation_id: FederationId,
+        direction: PaymentDirection,
+        success: bool,
+        /// Fee earned by the gateway, zero for failed payments
+        fee: Amount,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use fedimint_core::config::FederationId;
+    use fedimint_core::Amount;
+    use serde_json::json;
+
+    use super::{GatewayEvent, PaymentDirection};
+
+    #[test]
+    fn events_are_tagged_with_their_type() {
+        let event = GatewayEvent::Payment {
+            federation_id: FederationId::dummy(),
+            direction: PaymentDirection::Incoming,
+            success: true,
+            fee: Amount::from_msats(1_000),
+        };
+
+        let value = serde_json::to_value(&event).expect("Can serialize");
+        assert_eq!(value["type"], json!("payment"));
+        assert_eq!(value["direction"], json!("incoming"));
+        assert_eq!(
+            serde_json::from_value::<GatewayEvent>(value).expect("Can deserialize"),
+            event
+        );
+    }
+}
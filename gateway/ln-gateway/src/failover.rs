@@ -0,0 +1,99 @@
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Context;
+use fedimint_core::task::sleep;
+use fedimint_core::util::SafeUrl;
+use fs2::FileExt;
+use tracing::info;
+
+/// Time between two attempts of a standby gateway to become the active one
+pub const FAILOVER_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Exclusive lock that elects the active gateway among gateways sharing their
+/// data directory. Only the gateway holding the lock opens the database,
+/// registers with the federations and intercepts HTLCs. The others stand by
+/// until the lock is released, which the OS does once the active gateway's
+/// process exits.
+#[derive(Debug)]
+pub struct FailoverLock {
+    // Holds the lock until it is dropped
+    _file: File,
+}
+
+impl FailoverLock {
+    /// Waits until the lock at `path` is acquired and records `api_addr` in
+    /// it, so the standby gateways can tell which gateway is active
+    pub async fn acquire(path: &Path, api_addr: &SafeUrl) -> anyhow::Result<Self> {
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Failed to open failover lock {}", path.display()))?;
+
+        let mut logged_standby = false;
+        while file.try_lock_exclusive().is_err() {
+            if !logged_standby {
+                let mut active = String::new();
+                file.read_to_string(&mut active)?;
+                file.rewind()?;
+                info!(
+                    active = %active.trim(),
+                    "Another gateway is active, standing by..."
+                );
+                logged_standby = true;
+            }
+
+            sleep(FAILOVER_POLL_INTERVAL).await;
+        }
+
+        file.set_len(0)?;
+        file.rewind()?;
+        writeln!(file, "{api_addr}")?;
+        file.sync_all()?;
+
+        info!(path = %path.display(), "Acquired failover lock, gateway is active");
+
+        Ok(FailoverLock { _file: file })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use fedimint_core::util::SafeUrl;
+
+    use super::FailoverLock;
+
+    #[tokio::test]
+    async fn only_one_gateway_is_active() {
+        let path =
+            std::env::temp_dir().join(format!("gatewayd-failover-{}.lock", rand::random::<u64>()));
+        let api_addr = SafeUrl::parse("http://127.0.0.1:8175").expect("Valid url");
+
+        let active = FailoverLock::acquire(&path, &api_addr)
+            .await
+            .expect("Lock is free");
+        assert_eq!(
+            std::fs::read_to_string(&path).expect("Lock file exists"),
+            format!("{api_addr}\n")
+        );
+
+        let standby = FailoverLock::acquire(&path, &api_addr);
+        assert!(tokio::time::timeout(Duration::from_millis(100), standby)
+            .await
+            .is_err());
+
+        drop(active);
+        FailoverLock::acquire(&path, &api_addr)
+            .await
+            .expect("Lock was released");
+
+        std::fs::remove_file(path).expect("Lock file exists");
+    }
+}
@@ -151,7 +151,6 @@ impl CompleteStateMachine {
             match context.gateway.get_lightning_context().await {
                 Ok(lightning_context) => {
                     match lightning_context
-                        .lnrpc
                         .complete_htlc(intercept_htlc_response.clone())
                         .await
                     {
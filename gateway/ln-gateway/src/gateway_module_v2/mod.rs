@@ -312,11 +312,11 @@ impl GatewayClientModuleV2 {
         incoming_chan_id: u64,
         htlc_id: u64,
         contract: IncomingContract,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<OperationId> {
         let operation_id = OperationId::from_encodable(&contract);
 
         if self.client_ctx.operation_exists(operation_id).await {
-            return Ok(());
+            return Ok(operation_id);
         }
 
         let refund_keypair = self.keypair;
@@ -360,7 +360,7 @@ impl GatewayClientModuleV2 {
             )
             .await?;
 
-        Ok(())
+        Ok(operation_id)
     }
 
     pub async fn relay_direct_swap(&self, contract: IncomingContract) -> anyhow::Result<[u8; 32]> {
@@ -408,7 +408,7 @@ impl GatewayClientModuleV2 {
             .ok_or(anyhow!("The internal send failed"))
     }
 
-    async fn subscribe_receive(&self, operation_id: OperationId) -> Option<[u8; 32]> {
+    pub async fn subscribe_receive(&self, operation_id: OperationId) -> Option<[u8; 32]> {
         let mut stream = self.notifier.subscribe(operation_id).await;
 
         loop {
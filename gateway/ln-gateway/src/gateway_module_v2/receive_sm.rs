@@ -14,13 +14,13 @@ use fedimint_core::encoding::{Decodable, Encodable};
 use fedimint_core::endpoint_constants::AWAIT_OUTPUT_OUTCOME_ENDPOINT;
 use fedimint_core::module::ApiRequestErased;
 use fedimint_core::secp256k1::KeyPair;
-use fedimint_core::task::sleep;
+use fedimint_core::task::{sleep, MaybeSend, MaybeSync};
 use fedimint_core::{NumPeersExt, OutPoint, PeerId, TransactionId};
 use fedimint_lnv2_client::LightningClientStateMachines;
 use fedimint_lnv2_common::contracts::IncomingContract;
 use fedimint_lnv2_common::{LightningInput, LightningInputV0, LightningOutputOutcome};
 use tpe::{aggregate_decryption_shares, AggregatePublicKey, DecryptionKeyShare, PublicKeyShare};
-use tracing::{error, trace};
+use tracing::{error, trace, warn};
 
 use crate::gateway_module_v2::GatewayClientContextV2;
 
@@ -154,32 +154,70 @@ impl ReceiveStateMachine {
         out_point: OutPoint,
         decryption_contract: IncomingContract,
     ) -> BTreeMap<PeerId, DecryptionKeyShare> {
-        let verify_decryption_share = move |peer, outcome: SerdeOutputOutcome| {
-            let outcome = deserialize_outcome::<LightningOutputOutcome>(&outcome, &module_decoder)?;
-
-            match outcome {
-                LightningOutputOutcome::Incoming(share) => {
-                    if !decryption_contract.verify_decryption_share(
-                        tpe_pks.get(&peer).ok_or(anyhow!("Unknown peer pk"))?,
-                        &share,
-                    ) {
-                        bail!("Invalid decryption share");
-                    }
-
-                    Ok(share)
-                }
-                LightningOutputOutcome::Outgoing => {
-                    bail!("Unexpected outcome variant");
-                }
+        // Verifying the shares at once is faster as long as every peer sends a
+        // valid share, otherwise we verify each of them
+        let decoder = module_decoder.clone();
+        let shares =
+            Self::request_decryption_shares(&global_context, out_point, move |_, outcome| {
+                decryption_share(&outcome, &decoder)
+            })
+            .await;
+
+        let batch = shares
+            .iter()
+            .map(|(peer, share)| Some((*tpe_pks.get(peer)?, *share)))
+            .collect::<Option<Vec<_>>>();
+        if batch.is_some_and(|batch| decryption_contract.verify_decryption_shares(&batch)) {
+            return shares;
+        }
+
+        warn!("Received an invalid decryption share, verifying them individually");
+
+        // The shares are only requested again if too few of the received ones
+        // are valid, which saves a round trip and leaves a malicious peer no
+        // second chance to send a different share
+        let valid_shares = shares
+            .into_iter()
+            .filter(|(peer, share)| {
+                tpe_pks
+                    .get(peer)
+                    .is_some_and(|pk| decryption_contract.verify_decryption_share(pk, share))
+            })
+            .collect::<BTreeMap<_, _>>();
+        if global_context.api().all_peers().to_num_peers().threshold() <= valid_shares.len() {
+            return valid_shares;
+        }
+
+        Self::request_decryption_shares(&global_context, out_point, move |peer, outcome| {
+            let share = decryption_share(&outcome, &module_decoder)?;
+
+            if !decryption_contract.verify_decryption_share(
+                tpe_pks.get(&peer).ok_or(anyhow!("Unknown peer pk"))?,
+                &share,
+            ) {
+                bail!("Invalid decryption share");
             }
-        };
 
+            Ok(share)
+        })
+        .await
+    }
+
+    async fn request_decryption_shares(
+        global_context: &DynGlobalClientContext,
+        out_point: OutPoint,
+        filter_map: impl Fn(PeerId, SerdeOutputOutcome) -> anyhow::Result<DecryptionKeyShare>
+            + Clone
+            + MaybeSend
+            + MaybeSync
+            + 'static,
+    ) -> BTreeMap<PeerId, DecryptionKeyShare> {
         loop {
             match global_context
                 .api()
                 .request_with_strategy(
                     FilterMapThreshold::new(
-                        verify_decryption_share.clone(),
+                        filter_map.clone(),
                         global_context.api().all_peers().to_num_peers(),
                     ),
                     AWAIT_OUTPUT_OUTCOME_ENDPOINT.to_owned(),
@@ -248,3 +286,15 @@ impl ReceiveStateMachine {
         old_state.update(ReceiveSMState::Refunding(outpoints))
     }
 }
+
+fn decryption_share(
+    outcome: &SerdeOutputOutcome,
+    module_decoder: &Decoder,
+) -> anyhow::Result<DecryptionKeyShare> {
+    match deserialize_outcome::<LightningOutputOutcome>(outcome, module_decoder)? {
+        LightningOutputOutcome::Incoming(share) => Ok(share),
+        LightningOutputOutcome::Outgoing => {
+            bail!("Unexpected outcome variant");
+        }
+    }
+}
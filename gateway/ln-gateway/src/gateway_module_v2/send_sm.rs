@@ -13,8 +13,11 @@ use fedimint_lnv2_client::{LightningClientStateMachines, LightningInvoice};
 use fedimint_lnv2_common::contracts::OutgoingContract;
 use fedimint_lnv2_common::{LightningInput, LightningInputV0, OutgoingWitness};
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 
 use crate::gateway_module_v2::{GatewayClientContextV2, GatewayClientModuleV2};
+use crate::payment_retry::{PaymentAttempt, PaymentRetryPolicy};
+use crate::LightningContext;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Decodable, Encodable)]
 pub struct SendStateMachine {
@@ -164,22 +167,98 @@ impl SendStateMachine {
                 .map_err(|e| Cancelled::DirectSwapError(e.to_string()));
         }
 
+        let policy = context
+            .gateway
+            .handle_payment_retry_policy_msg()
+            .await
+            .unwrap_or_default();
+        let fee_budget = contract.amount - min_contract_amount;
+        let payment_hash = *invoice.payment_hash();
+        let pruned_invoice = PrunedInvoice::new(&invoice, amount);
+
+        // The contract's timeout keeps approaching while we retry, so we derive the
+        // remaining delay from the blocks mined since the first attempt. This is
+        // best-effort: if the block height can't be read, we keep the number of
+        // blocks we counted last instead of cancelling the payment.
+        let start_height = Self::block_height(&lightning_context).await;
+
+        let mut attempt = 0;
+        let mut elapsed = 0;
+        loop {
+            if let (Some(start_height), Some(block_height)) =
+                (start_height, Self::block_height(&lightning_context).await)
+            {
+                elapsed = block_height.saturating_sub(start_height);
+            }
+            let max_delay = max_delay.saturating_sub(elapsed);
+
+            if max_delay == 0 {
+                return Err(Cancelled::TimeoutTooClose);
+            }
+
+            let attempt_fee_budget = policy.attempt_fee_budget(attempt, fee_budget);
+            let randomize_path = attempt > 0 && policy.randomize_paths;
+            let started_at = fedimint_core::time::now();
+
+            let result = if attempt == 0 {
+                lightning_context
+                    .lnrpc
+                    .pay_private(pruned_invoice.clone(), max_delay, attempt_fee_budget)
+                    .await
+            } else {
+                lightning_context
+                    .lnrpc
+                    .retry_pay_private(
+                        pruned_invoice.clone(),
+                        max_delay,
+                        attempt_fee_budget,
+                        randomize_path,
+                    )
+                    .await
+            };
+
+            context
+                .gateway
+                .record_payment_attempt(
+                    payment_hash,
+                    PaymentAttempt {
+                        started_at,
+                        fee_budget: attempt_fee_budget,
+                        randomized_path: randomize_path,
+                        error: result.as_ref().err().map(ToString::to_string),
+                    },
+                )
+                .await;
+
+            match result {
+                Ok(response) => {
+                    return Ok(response
+                        .preimage
+                        .as_slice()
+                        .try_into()
+                        .expect("Preimage is 32 bytes"));
+                }
+                Err(error)
+                    if attempt + 1 < policy.max_attempts
+                        && PaymentRetryPolicy::is_retryable(&error)
+                        && !invoice.is_expired() =>
+                {
+                    warn!(%payment_hash, attempt, %error, "Payment attempt failed, retrying");
+                    attempt += 1;
+                }
+                Err(error) => return Err(Cancelled::LightningRpcError(error.to_string())),
+            }
+        }
+    }
+
+    async fn block_height(lightning_context: &LightningContext) -> Option<u64> {
         lightning_context
             .lnrpc
-            .pay_private(
-                PrunedInvoice::new(&invoice, amount),
-                max_delay,
-                contract.amount - min_contract_amount,
-            )
+            .info()
             .await
-            .map(|response| {
-                response
-                    .preimage
-                    .as_slice()
-                    .try_into()
-                    .expect("Preimage is 32 bytes")
-            })
-            .map_err(|e| Cancelled::LightningRpcError(e.to_string()))
+            .map(|info| u64::from(info.block_height))
+            .inspect_err(|error| warn!(%error, "Failed to get the block height"))
+            .ok()
     }
 
     async fn transition_send_payment(
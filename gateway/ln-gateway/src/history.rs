@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+use fedimint_core::config::FederationId;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::Amount;
+use serde::{Deserialize, Serialize};
+
+use crate::lightning::ChannelInfo;
+
+/// Time between two samples of the gateway's liquidity
+pub const LIQUIDITY_SAMPLE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Number of samples kept, 90 days of hourly samples
+pub const LIQUIDITY_HISTORY_SIZE: usize = 90 * 24;
+
+/// Liquidity of the gateway at one point in time, sampled periodically to
+/// chart how it develops
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct LiquiditySample {
+    pub sampled_at: SystemTime,
+    /// Sum of the local balances of the channels
+    pub outbound_sats: u64,
+    /// Sum of the remote balances of the channels
+    pub inbound_sats: u64,
+    /// Not set if the lightning node can't report its on-chain balance
+    pub onchain_sats: Option<u64>,
+    pub ecash: BTreeMap<FederationId, Amount>,
+    /// Fees earned since the previous sample, summing them up yields the
+    /// cumulative earnings
+    pub fees_earned: Amount,
+}
+
+impl LiquiditySample {
+    pub fn new(
+        sampled_at: SystemTime,
+        channels: &[ChannelInfo],
+        onchain_sats: Option<u64>,
+        ecash: BTreeMap<FederationId, Amount>,
+        fees_earned: Amount,
+    ) -> Self {
+        LiquiditySample {
+            sampled_at,
+            outbound_sats: channels
+                .iter()
+                .map(|channel| channel.outbound_liquidity_sats)
+                .sum(),
+            inbound_sats: channels
+                .iter()
+                .map(|channel| channel.inbound_liquidity_sats)
+                .sum(),
+            onchain_sats,
+            ecash,
+            fees_earned,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::time::SystemTime;
+
+    use fedimint_core::Amount;
+
+    use super::LiquiditySample;
+    use crate::lightning::ChannelInfo;
+
+    #[test]
+    fn sums_up_channel_liquidity() {
+        let channel = |outbound_liquidity_sats, inbound_liquidity_sats| ChannelInfo {
+            remote_pubkey: String::new(),
+            channel_size_sats: outbound_liquidity_sats + inbound_liquidity_sats,
+            outbound_liquidity_sats,
+            inbound_liquidity_sats,
+            short_channel_id: 0,
+        };
+
+        let sample = LiquiditySample::new(
+            SystemTime::UNIX_EPOCH,
+            &[channel(1_000, 9_000), channel(5_000, 0)],
+            Some(20_000),
+            BTreeMap::new(),
+            Amount::from_msats(1_500),
+        );
+        assert_eq!(sample.outbound_sats, 6_000);
+        assert_eq!(sample.inbound_sats, 9_000);
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use tracing::debug;
+
+/// Caps on the HTLCs the gateway holds per incoming channel while resolving
+/// them with a federation. `None` means the dimension is not limited.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HtlcLimits {
+    /// Maximum number of HTLCs held at the same time per channel
+    pub max_htlcs_per_channel: Option<u64>,
+    /// Maximum total amount of HTLCs held at the same time per channel
+    pub max_htlc_msat_per_channel: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct ChannelExposure {
+    // Map of `htlc_id` -> incoming amount in msat
+    htlcs: BTreeMap<u64, u64>,
+    total_msat: u64,
+}
+
+/// Tracks the HTLCs the gateway has intercepted for federation payments but
+/// not yet completed, keyed by incoming channel, and enforces `HtlcLimits` on
+/// them. This keeps a set of slow payments from exhausting a channel's HTLC
+/// slots.
+#[derive(Debug, Default)]
+pub struct InFlightHtlcs {
+    limits: HtlcLimits,
+    channels: Mutex<BTreeMap<u64, ChannelExposure>>,
+}
+
+impl InFlightHtlcs {
+    pub fn new(limits: HtlcLimits) -> Self {
+        Self {
+            limits,
+            channels: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Records an intercepted HTLC as in flight. Returns false if holding it
+    /// would exceed the limits of its incoming channel, in which case nothing
+    /// is recorded. Reserving an HTLC that is already in flight, like one
+    /// replayed by the lightning node after a reconnect, always succeeds.
+    pub fn try_reserve(&self, incoming_chan_id: u64, htlc_id: u64, amount_msat: u64) -> bool {
+        let mut channels = self.channels.lock().expect("poisoned");
+        let exposure = channels.entry(incoming_chan_id).or_default();
+
+        if exposure.htlcs.contains_key(&htlc_id) {
+            return true;
+        }
+
+        let exceeds_count = self
+            .limits
+            .max_htlcs_per_channel
+            .is_some_and(|max_htlcs| exposure.htlcs.len() as u64 >= max_htlcs);
+        let exceeds_amount = self
+            .limits
+            .max_htlc_msat_per_channel
+            .is_some_and(|max_msat| exposure.total_msat.saturating_add(amount_msat) > max_msat);
+
+        if exceeds_count || exceeds_amount {
+            debug!("Channel {incoming_chan_id} reached its in-flight HTLC limits");
+
+            if exposure.htlcs.is_empty() {
+                channels.remove(&incoming_chan_id);
+            }
+
+            return false;
+        }
+
+        exposure.htlcs.insert(htlc_id, amount_msat);
+        exposure.total_msat += amount_msat;
+
+        true
+    }
+
+    pub fn contains(&self, incoming_chan_id: u64, htlc_id: u64) -> bool {
+        self.channels
+            .lock()
+            .expect("poisoned")
+            .get(&incoming_chan_id)
+            .is_some_and(|exposure| exposure.htlcs.contains_key(&htlc_id))
+    }
+
+    /// Returns the number of HTLCs in flight over all channels
+    pub fn count(&self) -> usize {
+        self.channels
+            .lock()
+            .expect("poisoned")
+            .values()
+            .map(|exposure| exposure.htlcs.len())
+            .sum()
+    }
+
+    /// Removes an HTLC once it has been completed. Releasing an HTLC that was
+    /// never reserved is a no-op.
+    pub fn release(&self, incoming_chan_id: u64, htlc_id: u64) {
+        let mut channels = self.channels.lock().expect("poisoned");
+
+        if let Some(exposure) = channels.get_mut(&incoming_chan_id) {
+            if let Some(amount_msat) = exposure.htlcs.remove(&htlc_id) {
+                exposure.total_msat -= amount_msat;
+            }
+
+            if exposure.htlcs.is_empty() {
+                channels.remove(&incoming_chan_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HtlcLimits, InFlightHtlcs};
+
+    #[test]
+    fn enforces_per_channel_limits() {
+        let in_flight = InFlightHtlcs::new(HtlcLimits {
+            max_htlcs_per_channel: Some(2),
+            max_htlc_msat_per_channel: Some(1_000),
+        });
+
+        assert!(in_flight.try_reserve(1, 0, 400));
+        assert!(in_flight.try_reserve(1, 1, 400));
+        // Count limit reached
+        assert!(!in_flight.try_reserve(1, 2, 100));
+        // Other channels are not affected
+        assert!(in_flight.try_reserve(2, 0, 1_000));
+        // Replayed HTLCs are accepted without being counted twice
+        assert!(in_flight.try_reserve(1, 1, 400));
+
+        in_flight.release(1, 0);
+        // Amount limit reached
+        assert!(!in_flight.try_reserve(1, 2, 700));
+        assert!(in_flight.try_reserve(1, 2, 600));
+
+        in_flight.release(1, 1);
+        in_flight.release(1, 2);
+        // Releasing unknown HTLCs is a no-op
+        in_flight.release(1, 3);
+        assert!(in_flight.try_reserve(1, 3, 1_000));
+    }
+}
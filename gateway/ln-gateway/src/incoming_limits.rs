@@ -0,0 +1,221 @@
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+use fedimint_core::config::FederationId;
+use fedimint_core::encoding::{Decodable, Encodable};
+use serde::{Deserialize, Serialize};
+
+use crate::sliding_window::SlidingWindow;
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Caps on the incoming payments the gateway accepts for a federation, which
+/// keep a single federation from draining the inbound liquidity of the
+/// lightning node. `None` means the dimension is not limited.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize,
+)]
+pub struct IncomingLimits {
+    /// Maximum amount of a single payment
+    pub max_payment_msat: Option<u64>,
+    /// Maximum number of payments accepted during any minute
+    pub max_payments_per_minute: Option<u64>,
+    /// Maximum total amount of payments accepted during any 24 hours
+    pub max_daily_volume_msat: Option<u64>,
+}
+
+impl IncomingLimits {
+    pub fn is_enabled(&self) -> bool {
+        self.max_payment_msat.is_some()
+            || self.max_payments_per_minute.is_some()
+            || self.max_daily_volume_msat.is_some()
+    }
+}
+
+/// The limit an incoming payment was rejected for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncomingLimitViolation {
+    PaymentTooLarge,
+    TooManyPayments,
+    DailyVolumeExceeded,
+}
+
+impl IncomingLimitViolation {
+    /// Label of the violation in the metrics
+    pub fn label(self) -> &'static str {
+        match self {
+            IncomingLimitViolation::PaymentTooLarge => "max_payment",
+            IncomingLimitViolation::TooManyPayments => "max_payments_per_minute",
+            IncomingLimitViolation::DailyVolumeExceeded => "max_daily_volume",
+        }
+    }
+}
+
+impl Display for IncomingLimitViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IncomingLimitViolation::PaymentTooLarge => {
+                write!(f, "Payment exceeds the maximum amount")
+            }
+            IncomingLimitViolation::TooManyPayments => {
+                write!(f, "Too many payments during the last minute")
+            }
+            IncomingLimitViolation::DailyVolumeExceeded => {
+                write!(f, "Payment exceeds the daily volume")
+            }
+        }
+    }
+}
+
+/// Tracks the incoming payments the gateway accepted per federation during the
+/// last 24 hours and enforces `IncomingLimits` on new ones.
+#[derive(Debug)]
+pub struct IncomingRateLimiter {
+    // Amounts in msat of the accepted payments
+    payments: SlidingWindow<u64>,
+}
+
+impl Default for IncomingRateLimiter {
+    fn default() -> Self {
+        IncomingRateLimiter {
+            payments: SlidingWindow::new(DAY),
+        }
+    }
+}
+
+impl IncomingRateLimiter {
+    /// Records an incoming payment of `amount_msat` for `federation_id`.
+    /// Returns the violated limit if accepting it would exceed the `limits`,
+    /// in which case nothing is recorded.
+    pub fn try_record(
+        &self,
+        federation_id: FederationId,
+        limits: &IncomingLimits,
+        amount_msat: u64,
+    ) -> Result<(), IncomingLimitViolation> {
+        self.try_record_at(federation_id, limits, amount_msat, Instant::now())
+    }
+
+    fn try_record_at(
+        &self,
+        federation_id: FederationId,
+        limits: &IncomingLimits,
+        amount_msat: u64,
+        now: Instant,
+    ) -> Result<(), IncomingLimitViolation> {
+        if limits
+            .max_payment_msat
+            .is_some_and(|max_msat| amount_msat > max_msat)
+        {
+            return Err(IncomingLimitViolation::PaymentTooLarge);
+        }
+
+        self.payments.with_entries(federation_id, now, |accepted| {
+            let exceeds_rate = limits.max_payments_per_minute.is_some_and(|max_payments| {
+                let last_minute = accepted
+                    .iter()
+                    .rev()
+                    .take_while(|(accepted_at, _)| now.duration_since(*accepted_at) < MINUTE)
+                    .count();
+                last_minute as u64 >= max_payments
+            });
+            if exceeds_rate {
+                return Err(IncomingLimitViolation::TooManyPayments);
+            }
+
+            let exceeds_volume = limits.max_daily_volume_msat.is_some_and(|max_msat| {
+                let volume = accepted
+                    .iter()
+                    .map(|(_, amount_msat)| amount_msat)
+                    .sum::<u64>();
+                volume.saturating_add(amount_msat) > max_msat
+            });
+            if exceeds_volume {
+                return Err(IncomingLimitViolation::DailyVolumeExceeded);
+            }
+
+            accepted.push_back((now, amount_msat));
+
+            Ok(())
+        })
+    }
+
+    /// Forgets the payments of a federation the gateway left
+    pub fn remove(&self, federation_id: FederationId) {
+        self.payments.remove(federation_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use bitcoin_hashes::{sha256, Hash};
+    use fedimint_core::config::FederationId;
+
+    use super::{IncomingLimitViolation, IncomingLimits, IncomingRateLimiter};
+
+    #[test]
+    fn enforces_per_federation_limits() {
+        let limiter = IncomingRateLimiter::default();
+        let limits = IncomingLimits {
+            max_payment_msat: Some(1_000),
+            max_payments_per_minute: Some(2),
+            max_daily_volume_msat: Some(2_500),
+        };
+        let federation_id = FederationId::dummy();
+        let start = Instant::now();
+
+        assert_eq!(
+            limiter.try_record_at(federation_id, &limits, 1_001, start),
+            Err(IncomingLimitViolation::PaymentTooLarge)
+        );
+        assert_eq!(
+            limiter.try_record_at(federation_id, &limits, 1_000, start),
+            Ok(())
+        );
+        assert_eq!(
+            limiter.try_record_at(federation_id, &limits, 1_000, start),
+            Ok(())
+        );
+        assert_eq!(
+            limiter.try_record_at(federation_id, &limits, 100, start),
+            Err(IncomingLimitViolation::TooManyPayments)
+        );
+
+        // Other federations are not affected
+        let other_federation_id = FederationId(sha256::Hash::from_byte_array([1; 32]));
+        assert_eq!(
+            limiter.try_record_at(other_federation_id, &limits, 1_000, start),
+            Ok(())
+        );
+
+        let later = start + Duration::from_secs(60);
+        assert_eq!(
+            limiter.try_record_at(federation_id, &limits, 600, later),
+            Err(IncomingLimitViolation::DailyVolumeExceeded)
+        );
+        assert_eq!(
+            limiter.try_record_at(federation_id, &limits, 500, later),
+            Ok(())
+        );
+
+        // Payments older than a day no longer count towards the volume
+        let next_day = start + Duration::from_secs(24 * 60 * 60);
+        assert_eq!(
+            limiter.try_record_at(federation_id, &limits, 1_000, next_day),
+            Ok(())
+        );
+
+        let unlimited = IncomingLimits::default();
+        assert!(!unlimited.is_enabled());
+        for _ in 0..10 {
+            assert_eq!(
+                limiter.try_record_at(federation_id, &unlimited, 1_000_000, next_day),
+                Ok(())
+            );
+        }
+    }
+}
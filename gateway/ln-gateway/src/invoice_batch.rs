@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+use bitcoin_hashes::sha256;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::util::SafeUrl;
+use fedimint_core::Amount;
+use lightning_invoice::Bolt11Invoice;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Time between two checks whether pending invoices of a batch were paid
+pub const INVOICE_BATCH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Maximum number of invoices created at once
+pub const MAX_INVOICE_BATCH_SIZE: usize = 1_000;
+
+/// Invoices the gateway's lightning node created at once, e.g. for the fixed
+/// prices of a point of sale. Payments to them are settled to the lightning
+/// node.
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct InvoiceBatch {
+    pub batch_id: u64,
+    pub description: String,
+    /// Data of the integration that created the batch, which is passed back
+    /// in its notifications
+    pub metadata: BTreeMap<String, String>,
+    pub created_at: SystemTime,
+    /// Url a `InvoicePaidNotification` is posted to for every paid invoice
+    pub webhook_url: Option<SafeUrl>,
+    pub invoices: Vec<BatchInvoice>,
+}
+
+impl InvoiceBatch {
+    pub fn is_pending(&self) -> bool {
+        self.invoices
+            .iter()
+            .any(|invoice| invoice.state == BatchInvoiceState::Pending)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct BatchInvoice {
+    pub invoice: Bolt11Invoice,
+    /// Preimage the payment to the invoice is settled with
+    #[serde(skip)]
+    pub preimage: [u8; 32],
+    pub state: BatchInvoiceState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub enum BatchInvoiceState {
+    Pending,
+    Paid { paid_at: SystemTime },
+    Expired,
+}
+
+/// Posted to the webhook of a batch once one of its invoices is paid
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvoicePaidNotification {
+    pub batch_id: u64,
+    /// Position of the invoice within the batch
+    pub index: usize,
+    pub payment_hash: sha256::Hash,
+    pub amount: Option<Amount>,
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// Posts the notification to the webhook of a batch. Failures are only logged,
+/// since the payment is settled already.
+pub async fn notify_webhook(
+    client: &reqwest::Client,
+    webhook_url: &SafeUrl,
+    notification: &InvoicePaidNotification,
+) {
+    let result = client
+        .post(webhook_url.clone().to_unsafe())
+        .json(notification)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status);
+
+    if let Err(e) = result {
+        warn!(
+            "Failed to notify {webhook_url} about the payment of invoice {} of batch {}: {e}",
+            notification.index, notification.batch_id
+        );
+    }
+}
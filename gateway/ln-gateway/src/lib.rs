@@ -15,56 +15,110 @@
 #![allow(clippy::too_many_lines)]
 #![allow(clippy::wildcard_imports)]
 
+pub mod alerts;
+pub mod api_token;
+pub mod audit;
+pub mod circuit_breaker;
 pub mod client;
 mod db;
+pub mod drain;
+pub mod dynamic_fees;
 pub mod envs;
+pub mod events;
+pub mod failover;
 pub mod gateway_module_v2;
+pub mod history;
+mod htlc_limits;
+pub mod incoming_limits;
+pub mod invoice_batch;
 pub mod lightning;
+pub mod liquidity_cap;
+pub mod lnurl;
+pub mod lsps1;
+mod metrics;
+pub mod monitoring;
+pub mod payment_log;
+pub mod payment_retry;
+pub mod price;
+pub mod probing;
+pub mod protocols;
+pub mod rebalance;
+mod reserves;
 pub mod rpc;
+mod sliding_window;
+pub mod snapshot;
 pub mod state_machine;
+pub mod swap;
+pub mod sweep;
 mod types;
 
 pub mod gateway_lnrpc {
     tonic::include_proto!("gateway_lnrpc");
 }
 
+pub mod gateway_admin {
+    tonic::include_proto!("gateway_admin");
+}
+
 use std::borrow::Cow;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
 use std::fmt::Display;
+use std::future::Future;
 use std::net::SocketAddr;
 use std::ops::ControlFlow;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 
-use anyhow::{anyhow, bail};
+use alerts::{
+    send_alert, AlertNotification, AlertObservation, AlertPolicy, AlertState, ALERT_CHECK_INTERVAL,
+    FEDERATION_PING_TIMEOUT,
+};
+use anyhow::{anyhow, bail, Context};
+use api_token::{generate_api_token, hash_api_token, ApiToken, CreatedApiToken};
+use audit::{AuditEntry, AUDIT_LOG_PRUNE_ATTEMPTS, AUDIT_LOG_SIZE};
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use bitcoin::{Address, Network, Txid};
 use bitcoin_hashes::sha256;
+use circuit_breaker::{is_federation_failure, CircuitBreaker, CircuitBreakerConfig};
 use clap::Parser;
 use client::GatewayClientBuilder;
 use db::{
-    DbKeyPrefix, FederationIdKey, GatewayConfiguration, GatewayConfigurationKey, GatewayPublicKey,
-    GATEWAYD_DATABASE_VERSION,
+    AlertPolicyKey, ApiTokenKey, ApiTokenKeyPrefix, AuditLogKey, AuditLogKeyPrefix, DbKeyPrefix,
+    DynamicFeePolicyKey, FederationIdKey, GatewayConfiguration, GatewayConfigurationKey,
+    GatewayPublicKey, IncomingLimitsKey, InvoiceBatchKey, InvoiceBatchKeyPrefix,
+    LightningProtocolsKey, LiquidityCapKey, LiquidityCapKeyPrefix, LiquiditySampleKey,
+    LiquiditySampleKeyPrefix, Lsps1OrderKey, PaymentAttemptsKey, PaymentLogKeyPrefix,
+    PaymentRetryPolicyKey, PersistentPeerKey, PersistentPeerKeyPrefix, RebalanceBandKey,
+    RebalanceBandKeyPrefix, RebalanceLogKey, RebalanceLogKeyPrefix, RebalancingPausedKey,
+    SpendingPassword, SpendingPasswordKey, SweepPolicyKey, GATEWAYD_DATABASE_VERSION,
 };
+use drain::DrainStatus;
+use dynamic_fees::{DynamicFeePolicy, FeeAdjustment, OutgoingVolume, FEE_ADJUSTMENT_INTERVAL};
+use events::{GatewayEvent, PaymentDirection, EVENT_CHANNEL_CAPACITY, EVENT_UPDATE_INTERVAL};
+use failover::FailoverLock;
 use fedimint_api_client::api::FederationError;
+use fedimint_bitcoind::create_bitcoind;
 use fedimint_client::module::init::ClientModuleInitRegistry;
 use fedimint_client::ClientHandleArc;
 use fedimint_core::config::FederationId;
 use fedimint_core::core::{
-    ModuleInstanceId, ModuleKind, LEGACY_HARDCODED_INSTANCE_ID_MINT,
+    ModuleInstanceId, ModuleKind, OperationId, LEGACY_HARDCODED_INSTANCE_ID_MINT,
     LEGACY_HARDCODED_INSTANCE_ID_WALLET,
 };
 use fedimint_core::db::{
     apply_migrations_server, Database, DatabaseTransaction, IDatabaseTransactionOpsCoreTyped,
 };
 use fedimint_core::endpoint_constants::REGISTER_GATEWAY_ENDPOINT;
+use fedimint_core::envs::BitcoinRpcConfig;
 use fedimint_core::fmt_utils::OptStacktrace;
 use fedimint_core::invite_code::InviteCode;
 use fedimint_core::module::CommonModuleInit;
+use fedimint_core::runtime::{spawn, timeout};
 use fedimint_core::secp256k1::schnorr::Signature;
 use fedimint_core::secp256k1::{KeyPair, PublicKey, Secp256k1};
 use fedimint_core::task::{sleep, TaskGroup, TaskHandle, TaskShutdownToken};
@@ -74,39 +128,84 @@ use fedimint_core::{
     fedimint_build_code_version_env, push_db_pair_items, Amount, BitcoinAmountOrAll, BitcoinHash,
 };
 use fedimint_ln_client::pay::PayInvoicePayload;
-use fedimint_ln_common::config::{GatewayFee, LightningClientConfig};
+use fedimint_ln_common::config::{FeeToAmount, GatewayFee, LightningClientConfig};
 use fedimint_ln_common::contracts::Preimage;
 use fedimint_ln_common::route_hints::RouteHint;
 use fedimint_ln_common::LightningCommonInit;
 use fedimint_lnv2_client::{
-    Bolt11InvoiceDescription, CreateBolt11InvoicePayload, PaymentFee, RoutingInfo,
-    SendPaymentPayload,
+    Bolt11InvoiceDescription, CreateBolt11InvoicePayload, LightningInvoice, PaymentFee,
+    RoutingInfo, SendPaymentPayload,
 };
 use fedimint_lnv2_common::contracts::IncomingContract;
 use fedimint_mint_client::{MintClientInit, MintCommonInit};
 use fedimint_wallet_client::{
     WalletClientInit, WalletClientModule, WalletCommonInit, WithdrawState,
 };
+use futures::future::join_all;
 use futures::stream::StreamExt;
-use gateway_lnrpc::intercept_htlc_response::Action;
+use gateway_lnrpc::intercept_htlc_response::{Action, Cancel};
 use gateway_lnrpc::{
-    CloseChannelsWithPeerResponse, GetNodeInfoResponse, GetRouteHintsResponse,
-    InterceptHtlcResponse,
+    CloseChannelsWithPeerResponse, EmptyResponse, GetNodeInfoResponse, GetRouteHintsResponse,
+    InterceptHtlcRequest, InterceptHtlcResponse,
 };
 use hex::ToHex;
-use lightning::{ILnRpcClient, LightningBuilder, LightningMode, LightningRpcError};
+use history::{LiquiditySample, LIQUIDITY_HISTORY_SIZE, LIQUIDITY_SAMPLE_INTERVAL};
+use htlc_limits::{HtlcLimits, InFlightHtlcs};
+use incoming_limits::{IncomingLimits, IncomingRateLimiter};
+use invoice_batch::{
+    notify_webhook, BatchInvoice, BatchInvoiceState, InvoiceBatch, InvoicePaidNotification,
+    INVOICE_BATCH_INTERVAL, MAX_INVOICE_BATCH_SIZE,
+};
+use lightning::multi::{MultiNodeLnRpcClient, PaymentNode};
+use lightning::{ILnRpcClient, InvoiceState, LightningBuilder, LightningMode, LightningRpcError};
 use lightning_invoice::{Bolt11Invoice, RoutingFees};
+use liquidity_cap::LIQUIDITY_CAP_INTERVAL;
+use lnurl::{
+    LightningAddressNotes, LightningAddressUser, PayRequest, PayRequestInvoice, VerifyResponse,
+    LIGHTNING_ADDRESS_INTERVAL,
+};
+use metrics::{
+    total_fees_earned, total_payment_outcomes, InFlightPayment, FEDERATION_ECASH_BALANCE_MSATS,
+    FEDERATION_INCOMING_LIMIT_VIOLATIONS,
+};
+use payment_log::{export, summarize, PaymentLogEntry, PaymentSummaryBucket};
+use payment_retry::{PaymentAttempt, PaymentRetryPolicy, MAX_RECORDED_ATTEMPTS};
+use price::{
+    FiatPrices, HttpPriceOracle, PriceOracle, DEFAULT_PRICE_ORACLE_URL, PRICE_UPDATE_INTERVAL,
+};
+use probing::{LiquidityHealth, ProbeTarget, DEFAULT_PROBE_INTERVAL_SECS};
+use protocols::LightningProtocols;
 use rand::rngs::OsRng;
 use rand::Rng;
+use rebalance::{
+    RebalanceAction, RebalanceOutcome, RebalanceRecord, RebalanceStatus, REBALANCE_INTERVAL,
+    REBALANCE_LOG_SIZE,
+};
+use reserves::{BalanceReserves, SendPause};
 use rpc::{
-    CloseChannelsWithPeerPayload, FederationInfo, GatewayFedConfig, GatewayInfo, LeaveFedPayload,
-    OpenChannelPayload, SetConfigurationPayload, V1_API_ENDPOINT,
+    ClaimLightningAddressPayload, CloseChannelsWithPeerPayload, ConnectPeerPayload,
+    CreateApiTokenPayload, CreateInvoiceBatchPayload, CreateReverseSwapPayload,
+    CreateSnapshotPayload, CreateSwapPayload, DisconnectPeerPayload, ExportPaymentLogPayload,
+    FederationInfo, GatewayFedConfig, GatewayInfo, GatewayPolicy, ImportMissionControlPayload,
+    ImportPolicyPayload, LeaveFedPayload, Lsps1CreateOrderPayload, Lsps1GetInfoPayload,
+    Lsps1OrderPayload, Lsps1PayOrderPayload, MissionControlStats, OpenChannelPayload,
+    PayKeysendPayload, PaymentAttemptsPayload, PaymentLogPayload, PaymentSummaryPayload,
+    PeerStatus, PolicyChange, QueryRoutePayload, RefundSwapPayload,
+    RegisterLightningAddressPayload, RemoveLightningAddressPayload, RestoreSnapshotPayload,
+    RevokeApiTokenPayload, SetAlertPolicyPayload, SetConfigurationPayload, SetDrainingPayload,
+    SetDynamicFeePolicyPayload, SetIncomingLimitsPayload, SetLightningProtocolsPayload,
+    SetLiquidityCapPayload, SetPaymentRetryPolicyPayload, SetRebalanceBandPayload,
+    SetRebalancingPausedPayload, SetSpendingPasswordPayload, SetSweepPolicyPayload,
+    UpdateChannelPolicyPayload, V1_API_ENDPOINT,
 };
+use snapshot::{EncryptedSnapshot, GatewaySnapshot};
 use state_machine::pay::OutgoingPaymentError;
 use state_machine::GatewayClientModule;
 use strum::IntoEnumIterator;
+use swap::{ReverseSwapInfo, SwapClient, SwapInfo, SWAP_UPDATE_INTERVAL};
+use sweep::{SweepPolicy, SWEEP_CHECK_INTERVAL};
 use thiserror::Error;
-use tokio::sync::{Mutex, MutexGuard, RwLock};
+use tokio::sync::{broadcast, Mutex, MutexGuard, RwLock};
 use tracing::{debug, error, info, info_span, warn, Instrument};
 
 use crate::db::{
@@ -118,13 +217,16 @@ use crate::gateway_lnrpc::intercept_htlc_response::Forward;
 use crate::gateway_lnrpc::CreateInvoiceRequest;
 use crate::gateway_module_v2::GatewayClientModuleV2;
 use crate::lightning::cln::RouteHtlcStream;
-use crate::lightning::GatewayLightningBuilder;
+use crate::lightning::sandbox::SandboxLightningBuilder;
+use crate::lightning::{GatewayLightningBuilder, MissionControlPair, RouteEstimate};
+use crate::lsps1::{Lsps1Order, Lsps1OrderState};
+use crate::rpc::grpc_server::{run_grpc_server, validate_grpc_listen, GrpcTls};
 use crate::rpc::rpc_server::{hash_password, run_webserver};
 use crate::rpc::{
     BackupPayload, BalancePayload, ConnectFedPayload, DepositAddressPayload, RestorePayload,
     WithdrawPayload,
 };
-use crate::state_machine::GatewayExtPayStates;
+use crate::state_machine::{GatewayExtPayStates, GatewayExtReceiveStates};
 
 /// The first SCID that the gateway will assign to a federation.
 /// Note: This starts at 1 because an SCID of 0 is considered invalid by LND's
@@ -154,6 +256,17 @@ pub const DEFAULT_FEES: RoutingFees = RoutingFees {
 /// LNv2 CLTV Delta in blocks
 const EXPIRATION_DELTA_MINIMUM_V2: u64 = 144;
 
+/// Time between two checks whether the lightning node is still connected to
+/// the persistent peers
+const PEER_RECONNECT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Time between two checks of the lightning node's balances against the
+/// reserves
+const RESERVE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time between two updates of the federation balances exported as metrics
+const BALANCE_METRICS_INTERVAL: Duration = Duration::from_secs(60);
+
 pub type Result<T> = std::result::Result<T, GatewayError>;
 
 /// Name of the gateway's database that is used for metadata and configuration
@@ -166,13 +279,18 @@ const DEFAULT_MODULE_KINDS: [(ModuleInstanceId, &ModuleKind); 2] = [
     (LEGACY_HARDCODED_INSTANCE_ID_WALLET, &WalletCommonInit::KIND),
 ];
 
-/// Command line parameters for starting the gateway. `mode`, `data_dir`,
-/// `listen`, and `api_addr` are all required.
+/// Command line parameters for starting the gateway. `data_dir`, `listen`,
+/// `api_addr` and either `mode` or `sandbox` are required.
 #[derive(Parser)]
 #[command(version)]
 struct GatewayOpts {
     #[clap(subcommand)]
-    mode: LightningMode,
+    mode: Option<LightningMode>,
+
+    /// Run against a fake lightning node that settles all payments instantly,
+    /// for developing against the gateway API. Never use with real funds.
+    #[arg(long = "sandbox", env = envs::FM_GATEWAY_SANDBOX_ENV)]
+    sandbox: bool,
 
     /// Path to folder containing gateway config and data files
     #[arg(long = "data-dir", env = envs::FM_GATEWAY_DATA_DIR_ENV)]
@@ -206,18 +324,206 @@ struct GatewayOpts {
         default_value_t = DEFAULT_NUM_ROUTE_HINTS
     )]
     num_route_hints: u32,
+
+    /// Maximum number of HTLCs per incoming channel the gateway holds at the
+    /// same time for federation payments. Unlimited if not set.
+    #[arg(long = "max-htlcs-per-channel", env = envs::FM_GATEWAY_MAX_HTLCS_PER_CHANNEL_ENV)]
+    max_htlcs_per_channel: Option<u64>,
+
+    /// Maximum total msat of HTLCs per incoming channel the gateway holds at
+    /// the same time for federation payments. Unlimited if not set.
+    #[arg(
+        long = "max-htlc-msat-per-channel",
+        env = envs::FM_GATEWAY_MAX_HTLC_MSAT_PER_CHANNEL_ENV
+    )]
+    max_htlc_msat_per_channel: Option<u64>,
+
+    /// On-chain balance of the lightning node below which the gateway stops
+    /// quoting outgoing payments. Not checked if not set.
+    #[arg(
+        long = "min-onchain-reserve-sats",
+        env = envs::FM_GATEWAY_MIN_ONCHAIN_RESERVE_SATS_ENV
+    )]
+    min_onchain_reserve_sats: Option<u64>,
+
+    /// Sum of the local channel balances of the lightning node below which
+    /// the gateway stops quoting outgoing payments. Not checked if not set.
+    #[arg(
+        long = "min-lightning-reserve-sats",
+        env = envs::FM_GATEWAY_MIN_LIGHTNING_RESERVE_SATS_ENV
+    )]
+    min_lightning_reserve_sats: Option<u64>,
+
+    /// Destination to periodically probe with a fixed amount to monitor the
+    /// liquidity of its routes, repeatable. Format: <node id>,<amount msat>
+    #[arg(
+        long = "probe-target",
+        env = envs::FM_GATEWAY_PROBE_TARGETS_ENV,
+        value_delimiter = ';'
+    )]
+    probe_targets: Vec<ProbeTarget>,
+
+    /// Seconds between two rounds of probes
+    #[arg(
+        long = "probe-interval-secs",
+        env = envs::FM_GATEWAY_PROBE_INTERVAL_SECS_ENV,
+        default_value_t = DEFAULT_PROBE_INTERVAL_SECS
+    )]
+    probe_interval_secs: u64,
+
+    /// Additional lightning node to send outgoing payments from, repeatable.
+    /// Payments are sent from the node with the most outbound liquidity and
+    /// retried from the next one if they fail.
+    /// Format: lnd,<rpc addr>,<tls cert>,<macaroon> or cln,<extension addr>
+    #[arg(
+        long = "payment-node",
+        env = envs::FM_GATEWAY_PAYMENT_NODES_ENV,
+        value_delimiter = ';'
+    )]
+    payment_nodes: Vec<PaymentNode>,
+
+    /// Address to serve the gRPC admin API on. Disabled if not set. Without a
+    /// TLS certificate only loopback addresses are allowed.
+    #[arg(long = "grpc-listen", env = envs::FM_GATEWAY_GRPC_LISTEN_ADDR_ENV)]
+    grpc_listen: Option<SocketAddr>,
+
+    /// PEM encoded TLS certificate the gRPC admin API is served with
+    #[arg(
+        long = "grpc-tls-cert",
+        env = envs::FM_GATEWAY_GRPC_TLS_CERT_ENV,
+        requires = "grpc_tls_key"
+    )]
+    grpc_tls_cert: Option<PathBuf>,
+
+    /// PEM encoded key of the gRPC admin API's TLS certificate
+    #[arg(
+        long = "grpc-tls-key",
+        env = envs::FM_GATEWAY_GRPC_TLS_KEY_ENV,
+        requires = "grpc_tls_cert"
+    )]
+    grpc_tls_key: Option<PathBuf>,
+
+    /// Address to serve prometheus metrics on. Disabled if not set.
+    #[arg(long = "bind-metrics-api", env = envs::FM_GATEWAY_BIND_METRICS_API_ENV)]
+    bind_metrics_api: Option<SocketAddr>,
+
+    /// Domain to serve lightning addresses for, e.g. `example.com` for
+    /// `alice@example.com`. Requests to `https://<domain>/.well-known/lnurlp/`
+    /// have to be proxied to the gateway's webserver. Disabled if not set.
+    #[arg(
+        long = "lightning-address-domain",
+        env = envs::FM_GATEWAY_LIGHTNING_ADDRESS_DOMAIN_ENV
+    )]
+    lightning_address_domain: Option<String>,
+
+    /// Lock file for active/passive failover between gateways sharing the
+    /// data directory, e.g. on a network volume. A gateway stands by until it
+    /// holds the lock. Disabled if not set.
+    #[arg(long = "failover-lock", env = envs::FM_GATEWAY_FAILOVER_LOCK_ENV)]
+    failover_lock: Option<PathBuf>,
+
+    /// Fiat currency to show the value of balances in, repeatable, e.g. `USD`.
+    /// The prices are fetched from the price oracle. Disabled if not set.
+    #[arg(
+        long = "fiat-currency",
+        env = envs::FM_GATEWAY_FIAT_CURRENCIES_ENV,
+        value_delimiter = ','
+    )]
+    fiat_currencies: Vec<String>,
+
+    /// URL of the price oracle, which has to return a JSON object with the
+    /// price of one bitcoin per currency code
+    #[arg(
+        long = "price-oracle-url",
+        env = envs::FM_GATEWAY_PRICE_ORACLE_URL_ENV,
+        default_value = DEFAULT_PRICE_ORACLE_URL
+    )]
+    price_oracle_url: SafeUrl,
+
+    /// Share of failed payments in percent at which the gateway suspends
+    /// servicing a federation, e.g. because its guardians are offline.
+    /// Disabled if not set.
+    #[arg(
+        long = "circuit-breaker-failure-percent",
+        env = envs::FM_GATEWAY_CIRCUIT_BREAKER_FAILURE_PERCENT_ENV
+    )]
+    circuit_breaker_failure_percent: Option<u64>,
+
+    /// Payments of a federation required within the window before its failure
+    /// rate is considered
+    #[arg(
+        long = "circuit-breaker-min-payments",
+        env = envs::FM_GATEWAY_CIRCUIT_BREAKER_MIN_PAYMENTS_ENV,
+        default_value_t = 10
+    )]
+    circuit_breaker_min_payments: usize,
+
+    /// Period in seconds over which the failure rate is calculated
+    #[arg(
+        long = "circuit-breaker-window-secs",
+        env = envs::FM_GATEWAY_CIRCUIT_BREAKER_WINDOW_SECS_ENV,
+        default_value_t = 600
+    )]
+    circuit_breaker_window_secs: u64,
+
+    /// Seconds a federation stays suspended before the gateway services it
+    /// again
+    #[arg(
+        long = "circuit-breaker-cooldown-secs",
+        env = envs::FM_GATEWAY_CIRCUIT_BREAKER_COOLDOWN_SECS_ENV,
+        default_value_t = 300
+    )]
+    circuit_breaker_cooldown_secs: u64,
+
+    /// API of a Boltz-style swap provider, e.g. `https://api.boltz.exchange/v2`,
+    /// to move the on-chain funds of the lightning node into its channels and
+    /// back. Disabled if not set.
+    #[arg(long = "swap-provider-url", env = envs::FM_GATEWAY_SWAP_PROVIDER_URL_ENV)]
+    swap_provider_url: Option<SafeUrl>,
+
+    /// Kind of the gateway's own chain source, e.g. `esplora`. Reverse swaps
+    /// are only claimed once their lockup is confirmed on it, since the swap
+    /// provider can't be trusted to report its lockups. Disabled if not set.
+    #[arg(
+        long = "bitcoin-rpc-kind",
+        env = envs::FM_GATEWAY_BITCOIN_RPC_KIND_ENV,
+        requires = "bitcoin_rpc_url"
+    )]
+    bitcoin_rpc_kind: Option<String>,
+
+    /// URL of the gateway's own chain source
+    #[arg(
+        long = "bitcoin-rpc-url",
+        env = envs::FM_GATEWAY_BITCOIN_RPC_URL_ENV,
+        requires = "bitcoin_rpc_kind"
+    )]
+    bitcoin_rpc_url: Option<SafeUrl>,
 }
 
 impl GatewayOpts {
     /// Converts the command line parameters into a helper struct the Gateway
     /// uses to store runtime parameters.
-    fn to_gateway_parameters(&self) -> anyhow::Result<GatewayParameters> {
+    fn to_gateway_parameters(
+        &self,
+        failover_lock: Option<FailoverLock>,
+    ) -> anyhow::Result<GatewayParameters> {
         let versioned_api = self.api_addr.join(V1_API_ENDPOINT).map_err(|e| {
             anyhow::anyhow!(
                 "Failed to version gateway API address: {api_addr:?}, error: {e:?}",
                 api_addr = self.api_addr,
             )
         })?;
+        let grpc_tls = self
+            .grpc_tls_cert
+            .clone()
+            .zip(self.grpc_tls_key.clone())
+            .map(|(cert_path, key_path)| GrpcTls {
+                cert_path,
+                key_path,
+            });
+        if let Some(grpc_listen) = self.grpc_listen {
+            validate_grpc_listen(grpc_listen, grpc_tls.as_ref())?;
+        }
         Ok(GatewayParameters {
             listen: self.listen,
             versioned_api,
@@ -225,6 +531,42 @@ impl GatewayOpts {
             network: self.network,
             num_route_hints: self.num_route_hints,
             fees: self.fees.clone(),
+            htlc_limits: HtlcLimits {
+                max_htlcs_per_channel: self.max_htlcs_per_channel,
+                max_htlc_msat_per_channel: self.max_htlc_msat_per_channel,
+            },
+            reserves: BalanceReserves {
+                min_onchain_balance_sats: self.min_onchain_reserve_sats,
+                min_lightning_balance_sats: self.min_lightning_reserve_sats,
+            },
+            probe_targets: self.probe_targets.clone(),
+            probe_interval: Duration::from_secs(self.probe_interval_secs),
+            payment_nodes: self.payment_nodes.clone(),
+            grpc_listen: self.grpc_listen,
+            grpc_tls,
+            bind_metrics_api: self.bind_metrics_api,
+            lightning_address_domain: self.lightning_address_domain.clone(),
+            failover_lock: failover_lock.map(Arc::new),
+            fiat_currencies: self
+                .fiat_currencies
+                .iter()
+                .map(|currency| currency.to_uppercase())
+                .collect(),
+            price_oracle: Arc::new(HttpPriceOracle::new(self.price_oracle_url.clone())),
+            circuit_breaker: self.circuit_breaker_failure_percent.map(|failure_percent| {
+                CircuitBreakerConfig {
+                    failure_percent,
+                    min_payments: self.circuit_breaker_min_payments,
+                    window: Duration::from_secs(self.circuit_breaker_window_secs),
+                    cooldown: Duration::from_secs(self.circuit_breaker_cooldown_secs),
+                }
+            }),
+            swap_client: self.swap_provider_url.clone().map(SwapClient::new),
+            bitcoin_rpc: self
+                .bitcoin_rpc_kind
+                .clone()
+                .zip(self.bitcoin_rpc_url.clone())
+                .map(|(kind, url)| BitcoinRpcConfig { kind, url }),
         })
     }
 }
@@ -243,6 +585,21 @@ pub struct GatewayParameters {
     network: Option<Network>,
     num_route_hints: u32,
     fees: Option<GatewayFee>,
+    htlc_limits: HtlcLimits,
+    reserves: BalanceReserves,
+    probe_targets: Vec<ProbeTarget>,
+    probe_interval: Duration,
+    payment_nodes: Vec<PaymentNode>,
+    grpc_listen: Option<SocketAddr>,
+    grpc_tls: Option<GrpcTls>,
+    bind_metrics_api: Option<SocketAddr>,
+    lightning_address_domain: Option<String>,
+    failover_lock: Option<Arc<FailoverLock>>,
+    fiat_currencies: Vec<String>,
+    price_oracle: Arc<dyn PriceOracle>,
+    circuit_breaker: Option<CircuitBreakerConfig>,
+    swap_client: Option<SwapClient>,
+    bitcoin_rpc: Option<BitcoinRpcConfig>,
 }
 
 #[cfg_attr(doc, aquamarine::aquamarine)]
@@ -292,6 +649,23 @@ pub struct LightningContext {
     pub lightning_public_key: PublicKey,
     pub lightning_alias: String,
     pub lightning_network: Network,
+    in_flight_htlcs: Arc<InFlightHtlcs>,
+}
+
+impl LightningContext {
+    /// Completes an intercepted HTLC and releases its in-flight reservation,
+    /// even if the lightning node fails to complete it. HTLCs intercepted for
+    /// federation payments should always be completed through this method
+    /// instead of `lnrpc` directly.
+    pub async fn complete_htlc(
+        &self,
+        htlc: InterceptHtlcResponse,
+    ) -> std::result::Result<EmptyResponse, LightningRpcError> {
+        let (incoming_chan_id, htlc_id) = (htlc.incoming_chan_id, htlc.htlc_id);
+        let result = self.lnrpc.complete_htlc(htlc).await;
+        self.in_flight_htlcs.release(incoming_chan_id, htlc_id);
+        result
+    }
 }
 
 // A marker struct, to distinguish lock over `Gateway::clients`.
@@ -341,6 +715,69 @@ pub struct Gateway {
 
     // The socket the gateway listens on.
     listen: SocketAddr,
+
+    // HTLCs held for federation payments that have not been completed yet, used to enforce
+    // per-channel in-flight limits.
+    in_flight_htlcs: Arc<InFlightHtlcs>,
+
+    // Incoming payments accepted per federation, used to enforce the federations' incoming limits.
+    incoming_limiter: Arc<IncomingRateLimiter>,
+
+    // Outgoing payments sent per federation during the last hour, used to adjust the routing fees.
+    outgoing_volume: Arc<OutgoingVolume>,
+
+    // Pauses quoting outgoing payments while the Lightning node's balances are below the
+    // configured reserves.
+    send_pause: Arc<SendPause>,
+
+    // Destinations that are probed periodically to monitor their liquidity.
+    probe_targets: Vec<ProbeTarget>,
+
+    // Time between two rounds of probes.
+    probe_interval: Duration,
+
+    // Additional Lightning nodes outgoing payments can be sent from.
+    payment_nodes: Vec<PaymentNode>,
+
+    // The socket the gRPC admin API is served on, if any.
+    grpc_listen: Option<SocketAddr>,
+
+    // The TLS certificate the gRPC admin API is served with, if any.
+    grpc_tls: Option<GrpcTls>,
+
+    // The socket prometheus metrics are served on, if any.
+    bind_metrics_api: Option<SocketAddr>,
+
+    // The domain lightning addresses are served for, if any.
+    lightning_address_domain: Option<String>,
+
+    // Set while the gateway is draining, so that it only completes the payments in flight.
+    draining: Arc<AtomicBool>,
+
+    // Held while the gateway is the active one of gateways sharing the data directory.
+    failover_lock: Option<Arc<FailoverLock>>,
+
+    // Fiat currencies the value of balances is shown in.
+    fiat_currencies: Vec<String>,
+
+    // Source of the fiat prices.
+    price_oracle: Arc<dyn PriceOracle>,
+
+    // Most recently fetched fiat prices, if any.
+    fiat_prices: Arc<RwLock<Option<FiatPrices>>>,
+
+    // Outcomes of the payments per federation, used to suspend federations whose payments keep
+    // failing.
+    circuit_breaker: Arc<CircuitBreaker>,
+
+    // Client of the swap provider, if one is configured.
+    swap_client: Option<SwapClient>,
+
+    // Chain source the lockups of reverse swaps are verified with, if one is configured.
+    bitcoin_rpc: Option<BitcoinRpcConfig>,
+
+    // Balance, channel and payment events pushed to the subscribers of the event stream.
+    events: broadcast::Sender<GatewayEvent>,
 }
 
 impl std::fmt::Debug for Gateway {
@@ -354,6 +791,7 @@ impl std::fmt::Debug for Gateway {
             .field("scid_to_federation", &self.scid_to_federation)
             .field("gateway_id", &self.gateway_id)
             .field("next_scid", &self.next_scid)
+            .field("failover_lock", &self.failover_lock)
             .finish_non_exhaustive()
     }
 }
@@ -385,6 +823,23 @@ impl Gateway {
                 num_route_hints,
                 fees: Some(GatewayFee(fees)),
                 network,
+                htlc_limits: HtlcLimits::default(),
+                reserves: BalanceReserves::default(),
+                probe_targets: vec![],
+                probe_interval: Duration::from_secs(DEFAULT_PROBE_INTERVAL_SECS),
+                payment_nodes: vec![],
+                grpc_listen: None,
+                grpc_tls: None,
+                bind_metrics_api: None,
+                lightning_address_domain: None,
+                failover_lock: None,
+                fiat_currencies: vec![],
+                price_oracle: Arc::new(HttpPriceOracle::new(
+                    SafeUrl::parse(DEFAULT_PRICE_ORACLE_URL).expect("Valid url"),
+                )),
+                circuit_breaker: None,
+                swap_client: None,
+                bitcoin_rpc: None,
             },
             gateway_db,
             client_builder,
@@ -405,6 +860,13 @@ impl Gateway {
 
         let decoders = registry.available_decoders(DEFAULT_MODULE_KINDS.iter().copied())?;
 
+        // A standby gateway waits here until the active gateway releases the lock, so
+        // only one of them opens the shared database at a time
+        let failover_lock = match &opts.failover_lock {
+            Some(path) => Some(FailoverLock::acquire(path, &opts.api_addr).await?),
+            None => None,
+        };
+
         let gateway_db = Database::new(
             fedimint_rocksdb::RocksDb::open(opts.data_dir.join(DB_FILE))?,
             decoders,
@@ -421,12 +883,38 @@ impl Gateway {
             fedimint_build_code_version_env!()
         );
 
-        let gateway_parameters = opts.to_gateway_parameters()?;
+        let gateway_parameters = opts.to_gateway_parameters(failover_lock)?;
+        for payment_node in &gateway_parameters.payment_nodes {
+            payment_node.0.validate().context("Invalid payment node")?;
+        }
+
+        let lightning_builder: Arc<dyn LightningBuilder + Send + Sync> =
+            match (opts.mode, opts.sandbox) {
+                (Some(lightning_mode), false) => {
+                    lightning_mode.validate()?;
+                    Arc::new(GatewayLightningBuilder { lightning_mode })
+                }
+                (None, true) => {
+                    if opts
+                        .network
+                        .is_some_and(|network| network != Network::Regtest)
+                    {
+                        bail!("Sandbox mode is only supported on regtest");
+                    }
+
+                    warn!("****************************************************************");
+                    warn!("* SANDBOX MODE: the gateway is not connected to a lightning    *");
+                    warn!("* node. Payments are fake and settle instantly. Do not connect *");
+                    warn!("* federations holding real funds.                              *");
+                    warn!("****************************************************************");
+                    Arc::new(SandboxLightningBuilder)
+                }
+                (Some(_), true) => bail!("--sandbox can't be used with a lightning node"),
+                (None, false) => bail!("A lightning node is required unless --sandbox is set"),
+            };
 
         Gateway::new(
-            Arc::new(GatewayLightningBuilder {
-                lightning_mode: opts.mode,
-            }),
+            lightning_builder,
             gateway_parameters,
             gateway_db,
             client_builder,
@@ -470,6 +958,26 @@ impl Gateway {
             client_joining_lock: Arc::new(Mutex::new(ClientsJoinLock)),
             versioned_api: gateway_parameters.versioned_api,
             listen: gateway_parameters.listen,
+            in_flight_htlcs: Arc::new(InFlightHtlcs::new(gateway_parameters.htlc_limits)),
+            incoming_limiter: Arc::new(IncomingRateLimiter::default()),
+            outgoing_volume: Arc::new(OutgoingVolume::default()),
+            send_pause: Arc::new(SendPause::new(gateway_parameters.reserves)),
+            probe_targets: gateway_parameters.probe_targets,
+            probe_interval: gateway_parameters.probe_interval,
+            payment_nodes: gateway_parameters.payment_nodes,
+            grpc_listen: gateway_parameters.grpc_listen,
+            grpc_tls: gateway_parameters.grpc_tls,
+            bind_metrics_api: gateway_parameters.bind_metrics_api,
+            lightning_address_domain: gateway_parameters.lightning_address_domain,
+            draining: Arc::new(AtomicBool::new(false)),
+            failover_lock: gateway_parameters.failover_lock,
+            fiat_currencies: gateway_parameters.fiat_currencies,
+            price_oracle: gateway_parameters.price_oracle,
+            fiat_prices: Arc::new(RwLock::new(None)),
+            circuit_breaker: Arc::new(CircuitBreaker::new(gateway_parameters.circuit_breaker)),
+            swap_client: gateway_parameters.swap_client,
+            bitcoin_rpc: gateway_parameters.bitcoin_rpc,
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
         })
     }
 
@@ -554,16 +1062,62 @@ impl Gateway {
     /// begins listening for intercepted HTLCs, and starts the webserver to
     /// service requests.
     pub async fn run(self, tg: &TaskGroup) -> anyhow::Result<TaskShutdownToken> {
+        if let Some(socket_addr) = self.bind_metrics_api {
+            tg.spawn_cancellable("metrics-server", {
+                let tg = tg.clone();
+                async move { fedimint_metrics::run_api_server(socket_addr, tg).await }
+            });
+        }
+
         self.register_clients_timer(tg);
         Box::pin(self.load_clients()).await;
         self.start_gateway(tg);
-        // start webserver last to avoid handling requests before fully initialized
+        self.start_probing(tg);
+        self.start_peer_reconnection(tg);
+        self.start_reserve_monitoring(tg);
+        self.start_balance_metrics(tg);
+        self.start_dynamic_fees(tg);
+        self.start_liquidity_caps(tg);
+        self.start_rebalancing(tg);
+        self.start_invoice_batch_settlement(tg);
+        self.start_sweeping(tg);
+        self.start_lightning_address_settlement(tg);
+        self.start_price_updates(tg);
+        self.start_swap_updates(tg);
+        self.start_event_updates(tg);
+        self.start_liquidity_sampling(tg);
+        self.start_alerting(tg);
+        // start webservers last to avoid handling requests before fully initialized
+        if let Some(grpc_listen) = self.grpc_listen {
+            run_grpc_server(
+                Arc::new(self.clone()),
+                grpc_listen,
+                self.grpc_tls.as_ref(),
+                tg,
+            )?;
+        }
         run_webserver(Arc::new(self), tg).await?;
         let handle = tg.make_handle();
         let shutdown_receiver = handle.make_shutdown_rx();
         Ok(shutdown_receiver)
     }
 
+    /// Lets outgoing payments be sent from the configured payment nodes in
+    /// addition to the primary Lightning node `lnrpc`.
+    async fn with_payment_nodes(&self, lnrpc: Arc<dyn ILnRpcClient>) -> Arc<dyn ILnRpcClient> {
+        if self.payment_nodes.is_empty() {
+            return lnrpc;
+        }
+
+        let payment_nodes = join_all(self.payment_nodes.iter().map(PaymentNode::build)).await;
+
+        Arc::new(MultiNodeLnRpcClient::new(
+            lnrpc,
+            payment_nodes,
+            self.gateway_db.clone(),
+        ))
+    }
+
     /// Begins the task for listening for intercepted HTLCs from the Lightning
     /// node.
     fn start_gateway(&self, task_group: &TaskGroup) {
@@ -619,10 +1173,11 @@ impl Gateway {
 
                                 info!("Successfully loaded Gateway clients.");
                                 let lightning_context = LightningContext {
-                                    lnrpc: ln_client,
+                                    lnrpc: self_copy.with_payment_nodes(ln_client).await,
                                     lightning_public_key,
                                     lightning_alias,
                                     lightning_network,
+                                    in_flight_htlcs: self_copy.in_flight_htlcs.clone(),
                                 };
                                 self_copy.set_gateway_state(GatewayState::Running {
                                     lightning_context
@@ -699,7 +1254,50 @@ impl Gateway {
                         )
                         .await
                     {
-                        if let Err(error) = client
+                        if !self
+                            .load_lightning_protocols(client.federation_id())
+                            .await
+                            .lnv2
+                        {
+                            Self::cancel_htlc(
+                                &lightning_context,
+                                &htlc_request,
+                                "LNv2 is disabled for the federation",
+                            )
+                            .await;
+                            continue;
+                        }
+
+                        if !self
+                            .check_federation_suspended(
+                                &lightning_context,
+                                client.federation_id(),
+                                &htlc_request,
+                            )
+                            .await
+                        {
+                            continue;
+                        }
+
+                        if !self.reserve_htlc(&lightning_context, &htlc_request).await {
+                            continue;
+                        }
+
+                        if !self
+                            .check_incoming_limits(
+                                &lightning_context,
+                                client.federation_id(),
+                                &htlc_request,
+                            )
+                            .await
+                        {
+                            continue;
+                        }
+
+                        let fee = Amount::from_msats(htlc_request.incoming_amount_msat)
+                            .saturating_sub(contract.commitment.amount);
+
+                        match client
                             .get_first_module::<GatewayClientModuleV2>()
                             .relay_incoming_htlc(
                                 payment_hash,
@@ -709,7 +1307,26 @@ impl Gateway {
                             )
                             .await
                         {
-                            error!("Error relaying incoming HTLC: {error:?}");
+                            Ok(operation_id) => {
+                                self.track_incoming_payment(
+                                    client.federation_id(),
+                                    payment_hash,
+                                    Amount::from_msats(htlc_request.incoming_amount_msat),
+                                    fee,
+                                    async move {
+                                        client
+                                            .get_first_module::<GatewayClientModuleV2>()
+                                            .subscribe_receive(operation_id)
+                                            .await
+                                            .is_some()
+                                    },
+                                );
+                            }
+                            Err(error) => {
+                                error!("Error relaying incoming HTLC: {error:?}");
+                                self.in_flight_htlcs
+                                    .release(htlc_request.incoming_chan_id, htlc_request.htlc_id);
+                            }
                         }
 
                         continue;
@@ -724,11 +1341,47 @@ impl Gateway {
                         // Just forward the HTLC if we do not have a federation that
                         // corresponds to the short channel id
                         if let Some(federation_id) = federation_id {
+                            if !self.load_lightning_protocols(*federation_id).await.lnv1 {
+                                Self::cancel_htlc(
+                                    &lightning_context,
+                                    &htlc_request,
+                                    "LNv1 is disabled for the federation",
+                                )
+                                .await;
+                                continue;
+                            }
+
+                            if !self
+                                .check_federation_suspended(
+                                    &lightning_context,
+                                    *federation_id,
+                                    &htlc_request,
+                                )
+                                .await
+                            {
+                                continue;
+                            }
+
                             let clients = self.clients.read().await;
                             let client = clients.get(federation_id);
                             // Just forward the HTLC if we do not have a client that
                             // corresponds to the federation id
                             if let Some(client) = client {
+                                if !self.reserve_htlc(&lightning_context, &htlc_request).await {
+                                    continue;
+                                }
+
+                                if !self
+                                    .check_incoming_limits(
+                                        &lightning_context,
+                                        *federation_id,
+                                        &htlc_request,
+                                    )
+                                    .await
+                                {
+                                    continue;
+                                }
+
                                 let cf = client
                                     .borrow()
                                     .with(|client| async {
@@ -739,7 +1392,13 @@ impl Gateway {
                                                 .gateway_handle_intercepted_htlc(htlc)
                                                 .await
                                             {
-                                                Ok(_) => {
+                                                Ok(operation_id) => {
+                                                    self.track_incoming_payment_v1(
+                                                        client.clone(),
+                                                        *federation_id,
+                                                        operation_id,
+                                                        &htlc_request,
+                                                    );
                                                     return Some(ControlFlow::<(), ()>::Continue(()))
                                                 }
                                                 Err(e) => {
@@ -755,6 +1414,10 @@ impl Gateway {
                                 if let Some(ControlFlow::Continue(())) = cf {
                                     continue;
                                 }
+
+                                // The HTLC is forwarded below, so it is no longer held by us
+                                self.in_flight_htlcs
+                                    .release(htlc_request.incoming_chan_id, htlc_request.htlc_id);
                             } else {
                                 info!("Got no client result");
                             }
@@ -780,6 +1443,219 @@ impl Gateway {
         }
     }
 
+    /// Reserves an in-flight slot for an HTLC intercepted for a federation
+    /// payment. If the HTLC's incoming channel is at its in-flight limits or
+    /// the gateway is draining, the HTLC is cancelled and false is returned.
+    async fn reserve_htlc(
+        &self,
+        lightning_context: &LightningContext,
+        htlc_request: &InterceptHtlcRequest,
+    ) -> bool {
+        // HTLCs replayed by the lightning node are still completed
+        if self.is_draining()
+            && !self
+                .in_flight_htlcs
+                .contains(htlc_request.incoming_chan_id, htlc_request.htlc_id)
+        {
+            info!(
+                "Cancelling HTLC {}, the gateway is draining",
+                PrettyInterceptHtlcRequest(htlc_request)
+            );
+            Self::cancel_htlc(lightning_context, htlc_request, "Gateway is draining").await;
+            return false;
+        }
+
+        if self.in_flight_htlcs.try_reserve(
+            htlc_request.incoming_chan_id,
+            htlc_request.htlc_id,
+            htlc_request.incoming_amount_msat,
+        ) {
+            return true;
+        }
+
+        warn!(
+            "Cancelling HTLC {}, incoming channel is at its in-flight limits",
+            PrettyInterceptHtlcRequest(htlc_request)
+        );
+
+        Self::cancel_htlc(
+            lightning_context,
+            htlc_request,
+            "Channel in-flight HTLC limit reached",
+        )
+        .await;
+
+        false
+    }
+
+    /// Records an HTLC intercepted for a payment to `federation_id` against the
+    /// federation's incoming limits. If the HTLC violates them, it is cancelled
+    /// and false is returned.
+    async fn check_incoming_limits(
+        &self,
+        lightning_context: &LightningContext,
+        federation_id: FederationId,
+        htlc_request: &InterceptHtlcRequest,
+    ) -> bool {
+        let limits = self.load_incoming_limits(federation_id).await;
+        let Err(violation) = self.incoming_limiter.try_record(
+            federation_id,
+            &limits,
+            htlc_request.incoming_amount_msat,
+        ) else {
+            return true;
+        };
+
+        warn!(
+            "Cancelling HTLC {} of federation {federation_id}: {violation}",
+            PrettyInterceptHtlcRequest(htlc_request)
+        );
+        FEDERATION_INCOMING_LIMIT_VIOLATIONS
+            .with_label_values(&[&federation_id.to_string(), violation.label()])
+            .inc();
+
+        Self::cancel_htlc(
+            lightning_context,
+            htlc_request,
+            "Incoming payment limit reached",
+        )
+        .await;
+
+        false
+    }
+
+    /// Cancels an HTLC intercepted for a payment to `federation_id` while the
+    /// federation is suspended by the circuit breaker, in which case false is
+    /// returned.
+    async fn check_federation_suspended(
+        &self,
+        lightning_context: &LightningContext,
+        federation_id: FederationId,
+        htlc_request: &InterceptHtlcRequest,
+    ) -> bool {
+        if self.circuit_breaker.suspended_for(federation_id).is_none() {
+            return true;
+        }
+
+        warn!(
+            "Cancelling HTLC {}, federation {federation_id} is suspended",
+            PrettyInterceptHtlcRequest(htlc_request)
+        );
+        Self::cancel_htlc(lightning_context, htlc_request, "Federation is suspended").await;
+
+        false
+    }
+
+    async fn cancel_htlc(
+        lightning_context: &LightningContext,
+        htlc_request: &InterceptHtlcRequest,
+        reason: &str,
+    ) {
+        let outcome = InterceptHtlcResponse {
+            action: Some(Action::Cancel(Cancel {
+                reason: reason.to_string(),
+            })),
+            payment_hash: htlc_request.payment_hash.clone(),
+            incoming_chan_id: htlc_request.incoming_chan_id,
+            htlc_id: htlc_request.htlc_id,
+        };
+
+        if let Err(error) = lightning_context.complete_htlc(outcome).await {
+            error!("Error sending HTLC response to lightning node: {error:?}");
+        }
+    }
+
+    async fn load_lightning_protocols(&self, federation_id: FederationId) -> LightningProtocols {
+        self.gateway_db
+            .begin_transaction_nc()
+            .await
+            .get_value(&LightningProtocolsKey { federation_id })
+            .await
+            .unwrap_or_default()
+    }
+
+    async fn load_incoming_limits(&self, federation_id: FederationId) -> IncomingLimits {
+        self.gateway_db
+            .begin_transaction_nc()
+            .await
+            .get_value(&IncomingLimitsKey { federation_id })
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Tracks an incoming LNv1 payment in the metrics until the gateway either
+    /// received the preimage or refunded the incoming contract. The gateway's
+    /// fee is the difference between the HTLC's incoming and outgoing amount.
+    fn track_incoming_payment_v1(
+        &self,
+        client: ClientHandleArc,
+        federation_id: FederationId,
+        operation_id: OperationId,
+        htlc_request: &InterceptHtlcRequest,
+    ) {
+        let fee = Amount::from_msats(
+            htlc_request
+                .incoming_amount_msat
+                .saturating_sub(htlc_request.outgoing_amount_msat),
+        );
+
+        let payment_hash = sha256::Hash::from_slice(&htlc_request.payment_hash).expect("32 bytes");
+        let amount = Amount::from_msats(htlc_request.incoming_amount_msat);
+        self.track_incoming_payment(federation_id, payment_hash, amount, fee, async move {
+            let gateway_module = client.get_first_module::<GatewayClientModule>();
+            let Ok(updates) = gateway_module
+                .gateway_subscribe_ln_receive(operation_id)
+                .await
+            else {
+                return false;
+            };
+
+            let mut updates = updates.into_stream();
+            while let Some(update) = updates.next().await {
+                match update {
+                    GatewayExtReceiveStates::Funding => {}
+                    GatewayExtReceiveStates::Preimage(_) => return true,
+                    GatewayExtReceiveStates::RefundSuccess { .. }
+                    | GatewayExtReceiveStates::RefundError { .. }
+                    | GatewayExtReceiveStates::FundingFailed { .. } => return false,
+                }
+            }
+
+            false
+        });
+    }
+
+    /// Counts an incoming payment as in flight in the metrics until `received`
+    /// resolves to whether the gateway obtained the preimage, which is then
+    /// recorded by the circuit breaker.
+    fn track_incoming_payment(
+        &self,
+        federation_id: FederationId,
+        payment_hash: sha256::Hash,
+        amount: Amount,
+        fee: Amount,
+        received: impl Future<Output = bool> + Send + 'static,
+    ) {
+        let payment = InFlightPayment::start(
+            self.gateway_db.clone(),
+            federation_id,
+            PaymentDirection::Incoming,
+            payment_hash,
+            amount,
+            self.events.clone(),
+        );
+        let circuit_breaker = self.circuit_breaker.clone();
+        spawn("track incoming payment", async move {
+            let received = received.await;
+            if received {
+                payment.succeeded(fee).await;
+            } else {
+                payment.failed().await;
+            }
+            circuit_breaker.record(federation_id, received);
+        });
+    }
+
     /// Helper function for atomically changing the Gateway's internal state.
     async fn set_gateway_state(&self, state: GatewayState) {
         let mut lock = self.state.write().await;
@@ -826,6 +1702,7 @@ impl Gateway {
                 network: Some(gateway_config.network),
                 block_height: Some(node_info.3),
                 synced_to_chain: node_info.4,
+                fiat_prices: self.fiat_prices.read().await.clone(),
             });
         }
 
@@ -842,6 +1719,7 @@ impl Gateway {
             network: None,
             block_height: None,
             synced_to_chain: false,
+            fiat_prices: self.fiat_prices.read().await.clone(),
         })
     }
 
@@ -969,10 +1847,50 @@ impl Gateway {
     async fn handle_pay_invoice_msg(&self, payload: PayInvoicePayload) -> Result<Preimage> {
         if let GatewayState::Running { .. } = self.get_state().await {
             debug!("Handling pay invoice message: {payload:?}");
+            if self.is_draining() {
+                return Err(GatewayError::Draining);
+            }
+
             let client = self.select_client(payload.federation_id).await?;
+            if !self
+                .load_lightning_protocols(payload.federation_id)
+                .await
+                .lnv1
+            {
+                return Err(GatewayError::ProtocolDisabled("LNv1"));
+            }
+
+            if self
+                .circuit_breaker
+                .suspended_for(payload.federation_id)
+                .is_some()
+            {
+                return Err(GatewayError::FederationSuspended);
+            }
+
+            if self.send_pause.is_paused()
+                || self.liquidity_cap_reached(&payload.federation_id).await
+            {
+                return Err(GatewayError::TemporarilyUnavailable);
+            }
+
             let contract_id = payload.contract_id;
+            let federation_id = payload.federation_id;
+            let amount = payload.payment_data.amount();
+            let fee = self.outgoing_fee_v1(federation_id, amount).await;
+            let payment = InFlightPayment::start(
+                self.gateway_db.clone(),
+                federation_id,
+                PaymentDirection::Outgoing,
+                payload.payment_data.payment_hash(),
+                amount.unwrap_or(Amount::ZERO),
+                self.events.clone(),
+            );
             let gateway_module = &client.value().get_first_module::<GatewayClientModule>();
-            let operation_id = gateway_module.gateway_pay_bolt11_invoice(payload).await?;
+            let operation_id = gateway_module
+                .gateway_pay_bolt11_invoice(payload)
+                .await
+                .inspect_err(|_| self.circuit_breaker.record(federation_id, false))?;
             let mut updates = gateway_module
                 .gateway_subscribe_ln_pay(operation_id)
                 .await?
@@ -981,6 +1899,10 @@ impl Gateway {
                 match update {
                     GatewayExtPayStates::Success { preimage, .. } => {
                         debug!("Successfully paid invoice: {contract_id}");
+                        payment.succeeded(fee).await;
+                        self.circuit_breaker.record(federation_id, true);
+                        self.outgoing_volume
+                            .record(federation_id, amount.unwrap_or(Amount::ZERO));
                         return Ok(preimage);
                     }
                     GatewayExtPayStates::Fail {
@@ -988,10 +1910,18 @@ impl Gateway {
                         error_message,
                     } => {
                         error!("{error_message} while paying invoice: {contract_id}");
+                        payment.failed().await;
+                        if is_federation_failure(&error) {
+                            self.circuit_breaker.record(federation_id, false);
+                        }
                         return Err(GatewayError::OutgoingPaymentError(Box::new(error)));
                     }
                     GatewayExtPayStates::Canceled { error } => {
                         error!("Cancelled with {error} while paying invoice: {contract_id}");
+                        payment.failed().await;
+                        if is_federation_failure(&error) {
+                            self.circuit_breaker.record(federation_id, false);
+                        }
                         return Err(GatewayError::OutgoingPaymentError(Box::new(error)));
                     }
                     GatewayExtPayStates::Created => {
@@ -1012,6 +1942,21 @@ impl Gateway {
         Err(GatewayError::Disconnected)
     }
 
+    /// Returns the fee the gateway charges a federation for paying an invoice
+    /// of `amount` via LNv1.
+    async fn outgoing_fee_v1(&self, federation_id: FederationId, amount: Option<Amount>) -> Amount {
+        let Some(amount) = amount else {
+            return Amount::ZERO;
+        };
+
+        self.gateway_db
+            .begin_transaction_nc()
+            .await
+            .get_value(&FederationIdKey { id: federation_id })
+            .await
+            .map_or(Amount::ZERO, |config| config.fees.to_amount(&amount))
+    }
+
     /// Handles a connection request to join a new federation. The gateway will
     /// download the federation's client configuration, construct a new
     /// client, registers, the gateway with the federation, and persists the
@@ -1020,6 +1965,10 @@ impl Gateway {
         &self,
         payload: ConnectFedPayload,
     ) -> Result<FederationInfo> {
+        if self.is_draining() {
+            return Err(GatewayError::Draining);
+        }
+
         if let GatewayState::Running { lightning_context } = self.get_state().await {
             let invite_code = InviteCode::from_str(&payload.invite_code).map_err(|e| {
                 GatewayError::InvalidMetadata(format!("Invalid federation member string {e:?}"))
@@ -1065,12 +2014,18 @@ impl Gateway {
 
             // Instead of using `make_federation_info`, we manually create federation info
             // here because short channel id is not yet persisted
+            let balance_msat = client.get_balance().await;
             let federation_info = FederationInfo {
                 federation_id,
-                balance_msat: client.get_balance().await,
+                balance_msat,
                 config: client.get_config().clone(),
                 channel_id: Some(mint_channel_id),
                 routing_fees: Some(gateway_config.routing_fees.into()),
+                incoming_limits: IncomingLimits::default(),
+                liquidity_cap: None,
+                protocols: LightningProtocols::default(),
+                balance_fiat: self.fiat_value(balance_msat).await,
+                suspended_until: None,
             };
 
             Self::check_federation_network(&federation_info, gateway_config.network)?;
@@ -1146,9 +2101,28 @@ impl Gateway {
             id: payload.federation_id,
         })
         .await;
+        dbtx.remove_entry(&IncomingLimitsKey {
+            federation_id: payload.federation_id,
+        })
+        .await;
+        dbtx.remove_entry(&LiquidityCapKey {
+            federation_id: payload.federation_id,
+        })
+        .await;
+        dbtx.remove_entry(&LightningProtocolsKey {
+            federation_id: payload.federation_id,
+        })
+        .await;
+        dbtx.remove_entry(&RebalanceBandKey {
+            federation_id: payload.federation_id,
+        })
+        .await;
         dbtx.commit_tx_result()
             .await
             .map_err(GatewayError::DatabaseError)?;
+        self.incoming_limiter.remove(payload.federation_id);
+        self.circuit_breaker.remove(payload.federation_id);
+        self.outgoing_volume.remove(payload.federation_id);
         Ok(federation_info)
     }
 
@@ -1290,225 +2264,2662 @@ impl Gateway {
         Ok(())
     }
 
-    pub async fn handle_get_funding_address_msg(&self) -> Result<Address> {
-        let context = self.get_lightning_context().await?;
-        let response = context.lnrpc.get_funding_address().await?;
-        Address::from_str(&response.address)
-            .map(Address::assume_checked)
-            .map_err(|e| GatewayError::LightningResponseParseError(e.into()))
+    /// Replaces the limits on incoming payments of a connected federation.
+    /// Limits that don't cap anything are removed.
+    pub async fn handle_set_incoming_limits_msg(
+        &self,
+        SetIncomingLimitsPayload {
+            federation_id,
+            limits,
+        }: SetIncomingLimitsPayload,
+    ) -> Result<()> {
+        self.select_client(federation_id).await?;
+
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        let key = IncomingLimitsKey { federation_id };
+        if limits.is_enabled() {
+            dbtx.insert_entry(&key, &limits).await;
+        } else {
+            dbtx.remove_entry(&key).await;
+        }
+        dbtx.commit_tx().await;
+
+        info!("Set incoming limits of federation {federation_id} to {limits:?}");
+
+        Ok(())
     }
 
-    /// Instructs the Gateway's Lightning node to open a channel to a peer
-    /// specified by `pubkey`.
-    pub async fn handle_open_channel_msg(
+    /// Sets the protocol versions the gateway serves for a connected
+    /// federation. Disabling LNv1 removes the gateway's registration from the
+    /// federation, enabling it registers the gateway right away. Invoices
+    /// created via LNv2 before it was disabled are still settled.
+    pub async fn handle_set_lightning_protocols_msg(
         &self,
-        OpenChannelPayload {
-            pubkey,
-            host,
-            channel_size_sats,
-            push_amount_sats,
-        }: OpenChannelPayload,
+        SetLightningProtocolsPayload {
+            federation_id,
+            protocols,
+        }: SetLightningProtocolsPayload,
     ) -> Result<()> {
-        let context = self.get_lightning_context().await?;
-        context
-            .lnrpc
-            .open_channel(pubkey, host, channel_size_sats, push_amount_sats)
-            .await?;
+        protocols
+            .validate()
+            .map_err(GatewayError::InvalidMetadata)?;
+        let client = self.select_client(federation_id).await?;
+        let previous = self.load_lightning_protocols(federation_id).await;
+
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        let key = LightningProtocolsKey { federation_id };
+        if protocols.is_default() {
+            dbtx.remove_entry(&key).await;
+        } else {
+            dbtx.insert_entry(&key, &protocols).await;
+        }
+        let federation_config = dbtx.get_value(&FederationIdKey { id: federation_id }).await;
+        let keypair = dbtx
+            .get_value(&GatewayPublicKey)
+            .await
+            .expect("Gateway keypair does not exist");
+        dbtx.commit_tx().await;
+
+        info!("Set protocols of federation {federation_id} to {protocols:?}");
+
+        if previous.lnv1 && !protocols.lnv1 {
+            client
+                .value()
+                .get_first_module::<GatewayClientModule>()
+                .remove_from_federation(keypair)
+                .await;
+        }
+
+        if !previous.lnv1 && protocols.lnv1 {
+            if let (Some(gateway_config), Some(federation_config)) =
+                (self.clone_gateway_config().await, federation_config)
+            {
+                self.register_federations(&gateway_config, &[(federation_id, federation_config)])
+                    .await?;
+            }
+        }
+
         Ok(())
     }
 
-    /// Instructs the Gateway's Lightning node to close all channels with a peer
-    /// specified by `pubkey`.
-    pub async fn handle_close_channels_with_peer_msg(
+    /// Sets or, if `cap` is `None`, removes the maximum ecash balance of a
+    /// connected federation.
+    pub async fn handle_set_liquidity_cap_msg(
         &self,
-        CloseChannelsWithPeerPayload { pubkey }: CloseChannelsWithPeerPayload,
-    ) -> Result<CloseChannelsWithPeerResponse> {
-        let context = self.get_lightning_context().await?;
-        let response = context.lnrpc.close_channels_with_peer(pubkey).await?;
-        Ok(response)
+        SetLiquidityCapPayload { federation_id, cap }: SetLiquidityCapPayload,
+    ) -> Result<()> {
+        self.select_client(federation_id).await?;
+
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        let key = LiquidityCapKey { federation_id };
+        if let Some(cap) = cap {
+            dbtx.insert_entry(&key, &cap).await;
+        } else {
+            dbtx.remove_entry(&key).await;
+        }
+        dbtx.commit_tx().await;
+
+        info!("Set liquidity cap of federation {federation_id} to {cap:?}");
+
+        Ok(())
+    }
+
+    /// Returns true if the gateway's ecash balance in the federation reached
+    /// its liquidity cap, in which case it doesn't pay invoices for the
+    /// federation
+    async fn liquidity_cap_reached(&self, federation_id: &FederationId) -> bool {
+        let Some(cap) = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .get_value(&LiquidityCapKey {
+                federation_id: *federation_id,
+            })
+            .await
+        else {
+            return false;
+        };
+
+        let Some(client) = self.clients.read().await.get(federation_id).cloned() else {
+            return false;
+        };
+
+        cap.is_reached(client.value().get_balance().await)
+    }
+
+    /// Sets or, if `band` is `None`, removes the range the ecash balance of a
+    /// connected federation is kept within.
+    pub async fn handle_set_rebalance_band_msg(
+        &self,
+        SetRebalanceBandPayload {
+            federation_id,
+            band,
+        }: SetRebalanceBandPayload,
+    ) -> Result<()> {
+        self.select_client(federation_id).await?;
+
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        let key = RebalanceBandKey { federation_id };
+        if let Some(band) = band {
+            band.validate()
+                .map_err(GatewayError::GatewayConfigurationError)?;
+            dbtx.insert_entry(&key, &band).await;
+        } else {
+            dbtx.remove_entry(&key).await;
+        }
+        dbtx.commit_tx().await;
+
+        info!("Set rebalance band of federation {federation_id} to {band:?}");
+
+        Ok(())
+    }
+
+    fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    /// Starts or stops draining the gateway. While draining, the gateway
+    /// cancels new HTLCs, rejects new outgoing payments and federations and
+    /// removes its LNv1 registrations, so that clients choose other gateways.
+    /// Payments in flight are completed, and once the returned status reports
+    /// the gateway as drained, it can be shut down.
+    pub async fn handle_set_draining_msg(
+        &self,
+        SetDrainingPayload { draining }: SetDrainingPayload,
+    ) -> DrainStatus {
+        let was_draining = self.draining.swap(draining, Ordering::Relaxed);
+
+        if draining && !was_draining {
+            info!("Draining the gateway");
+
+            let keypair = self
+                .gateway_db
+                .begin_transaction_nc()
+                .await
+                .get_value(&GatewayPublicKey)
+                .await
+                .expect("Gateway keypair does not exist");
+            for client in self.clients.read().await.values() {
+                client
+                    .value()
+                    .get_first_module::<GatewayClientModule>()
+                    .remove_from_federation(keypair)
+                    .await;
+            }
+        }
+
+        if !draining && was_draining {
+            info!("Stopped draining the gateway");
+
+            // Registers the gateway with the federations again
+            if let Some(gateway_config) = self.clone_gateway_config().await {
+                let federations = self
+                    .gateway_db
+                    .begin_transaction_nc()
+                    .await
+                    .find_by_prefix(&FederationIdKeyPrefix)
+                    .await
+                    .map(|(key, config)| (key.id, config))
+                    .collect::<Vec<_>>()
+                    .await;
+                if let Err(e) = self
+                    .register_federations(&gateway_config, &federations)
+                    .await
+                {
+                    warn!("Failed to register with the federations after draining: {e}");
+                }
+            }
+        }
+
+        self.handle_drain_status_msg().await
+    }
+
+    pub async fn handle_drain_status_msg(&self) -> DrainStatus {
+        let mut active_operations = BTreeMap::new();
+        for (federation_id, client) in self.clients.read().await.iter() {
+            let operations = client.value().get_active_operations().await;
+            active_operations.insert(*federation_id, operations.len());
+        }
+
+        DrainStatus::new(
+            self.is_draining(),
+            self.in_flight_htlcs.count(),
+            active_operations,
+        )
+    }
+
+    /// Creates an encrypted snapshot of the gateway's database and the
+    /// databases of the federation clients. Since the databases can't be read
+    /// in one transaction, the gateway has to be drained first so that no
+    /// payment moves between them while they are read.
+    pub async fn handle_create_snapshot_msg(
+        &self,
+        CreateSnapshotPayload { password }: CreateSnapshotPayload,
+    ) -> Result<EncryptedSnapshot> {
+        let _join_federation = self.client_joining_lock.lock().await;
+
+        if !self.handle_drain_status_msg().await.drained {
+            return Err(GatewayError::UnexpectedState(
+                "The gateway has to be drained before creating a snapshot".to_string(),
+            ));
+        }
+
+        let gateway = snapshot::read_entries(&self.gateway_db)
+            .await
+            .map_err(GatewayError::DatabaseError)?;
+
+        let mut federations = BTreeMap::new();
+        for (federation_id, client) in self.clients.read().await.iter() {
+            let entries = snapshot::read_entries(client.value().db())
+                .await
+                .map_err(GatewayError::DatabaseError)?;
+            federations.insert(*federation_id, entries);
+        }
+
+        let snapshot = GatewaySnapshot {
+            created_at: fedimint_core::time::now(),
+            database_version: GATEWAYD_DATABASE_VERSION,
+            gateway,
+            federations,
+        };
+
+        info!(
+            federations = snapshot.federations.len(),
+            "Created database snapshot"
+        );
+
+        snapshot
+            .encrypt(&password)
+            .map_err(GatewayError::ClientStateMachineError)
+    }
+
+    /// Restores a snapshot on a fresh gateway that has not joined any
+    /// federation yet. The restored configuration replaces the current one,
+    /// including the password. The gateway has to be restarted afterwards to
+    /// connect to the restored federations with the restored identity.
+    pub async fn handle_restore_snapshot_msg(
+        &self,
+        RestoreSnapshotPayload { snapshot, password }: RestoreSnapshotPayload,
+    ) -> Result<()> {
+        let _join_federation = self.client_joining_lock.lock().await;
+
+        let has_federations = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .find_by_prefix(&FederationIdKeyPrefix)
+            .await
+            .next()
+            .await
+            .is_some();
+        if has_federations || !self.clients.read().await.is_empty() {
+            return Err(GatewayError::GatewayConfigurationError(
+                "A snapshot can only be restored before the gateway joins a federation".to_string(),
+            ));
+        }
+
+        let snapshot = GatewaySnapshot::decrypt(&snapshot, &password)
+            .map_err(|e| GatewayError::InvalidMetadata(e.to_string()))?;
+
+        if GATEWAYD_DATABASE_VERSION < snapshot.database_version {
+            return Err(GatewayError::InvalidMetadata(format!(
+                "The snapshot was created by a newer gateway with database version {}",
+                snapshot.database_version
+            )));
+        }
+
+        for (federation_id, entries) in &snapshot.federations {
+            let db = self.client_builder.open_database(*federation_id)?;
+            snapshot::write_entries(&db, entries)
+                .await
+                .map_err(GatewayError::DatabaseError)?;
+        }
+
+        // The gateway's database is written last, since its federation configs mark
+        // the restore as done. Older database versions are migrated on the restart.
+        snapshot::write_entries(&self.gateway_db, &snapshot.gateway)
+            .await
+            .map_err(GatewayError::DatabaseError)?;
+
+        let gateway_config = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .get_value(&GatewayConfigurationKey)
+            .await;
+        *self.gateway_config.write().await = gateway_config;
+
+        warn!(
+            federations = snapshot.federations.len(),
+            "Restored database snapshot, restart the gateway to load it"
+        );
+
+        Ok(())
+    }
+
+    /// Pauses or resumes rebalancing of all federations. The bands are kept
+    /// while rebalancing is paused.
+    pub async fn handle_set_rebalancing_paused_msg(
+        &self,
+        SetRebalancingPausedPayload { paused }: SetRebalancingPausedPayload,
+    ) {
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        if paused {
+            dbtx.insert_entry(&RebalancingPausedKey, &()).await;
+        } else {
+            dbtx.remove_entry(&RebalancingPausedKey).await;
+        }
+        dbtx.commit_tx().await;
+
+        info!("Set rebalancing paused to {paused}");
+    }
+
+    pub async fn handle_rebalance_status_msg(&self) -> RebalanceStatus {
+        let mut dbtx = self.gateway_db.begin_transaction_nc().await;
+        let paused = dbtx.get_value(&RebalancingPausedKey).await.is_some();
+        let bands = dbtx
+            .find_by_prefix(&RebalanceBandKeyPrefix)
+            .await
+            .map(|(key, band)| (key.federation_id, band))
+            .collect()
+            .await;
+        let mut log = dbtx
+            .find_by_prefix(&RebalanceLogKeyPrefix)
+            .await
+            .map(|(_, record)| record)
+            .collect::<Vec<_>>()
+            .await;
+        log.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+
+        RebalanceStatus { paused, bands, log }
+    }
+
+    /// Adds a rebalancing action to the log, dropping the oldest actions once
+    /// the log is full
+    async fn record_rebalance(&self, record: RebalanceRecord) {
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        dbtx.insert_entry(
+            &RebalanceLogKey {
+                timestamp: record.timestamp,
+                federation_id: record.federation_id,
+            },
+            &record,
+        )
+        .await;
+
+        let mut keys = dbtx
+            .find_by_prefix(&RebalanceLogKeyPrefix)
+            .await
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>()
+            .await;
+        if keys.len() > REBALANCE_LOG_SIZE {
+            keys.sort_by_key(|key| key.timestamp);
+            for key in &keys[..keys.len() - REBALANCE_LOG_SIZE] {
+                dbtx.remove_entry(key).await;
+            }
+        }
+
+        dbtx.commit_tx().await;
+    }
+
+    /// Creates an invoice on the lightning node for every amount of the batch.
+    /// Payments to them are settled by the task started in
+    /// `start_invoice_batch_settlement`.
+    pub async fn handle_create_invoice_batch_msg(
+        &self,
+        CreateInvoiceBatchPayload {
+            amounts,
+            description,
+            expiry_secs,
+            metadata,
+            webhook_url,
+        }: CreateInvoiceBatchPayload,
+    ) -> Result<InvoiceBatch> {
+        if amounts.is_empty() || MAX_INVOICE_BATCH_SIZE < amounts.len() {
+            return Err(GatewayError::InvalidMetadata(format!(
+                "A batch has to contain between 1 and {MAX_INVOICE_BATCH_SIZE} invoices"
+            )));
+        }
+
+        let lnrpc = self.get_lightning_context().await?.lnrpc;
+        if !lnrpc.supports_invoice_lookups() {
+            return Err(GatewayError::LightningRpcError(
+                LightningRpcError::FailedToGetInvoice {
+                    failure_reason: "The lightning node can't settle invoice batches".to_string(),
+                },
+            ));
+        }
+
+        let mut invoices = Vec::with_capacity(amounts.len());
+        for amount in amounts {
+            let preimage: [u8; 32] = OsRng.gen();
+            let response = lnrpc
+                .create_invoice(CreateInvoiceRequest {
+                    payment_hash: sha256::Hash::hash(&preimage).to_byte_array().to_vec(),
+                    amount_msat: amount.msats,
+                    expiry_secs,
+                    description: Some(Description::Direct(description.clone())),
+                })
+                .await?;
+            let invoice = Bolt11Invoice::from_str(&response.invoice)
+                .map_err(|e| GatewayError::LightningResponseParseError(anyhow!(e)))?;
+
+            invoices.push(BatchInvoice {
+                invoice,
+                preimage,
+                state: BatchInvoiceState::Pending,
+            });
+        }
+
+        let batch = InvoiceBatch {
+            batch_id: OsRng.gen(),
+            description,
+            metadata,
+            created_at: fedimint_core::time::now(),
+            webhook_url,
+            invoices,
+        };
+
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        dbtx.insert_new_entry(
+            &InvoiceBatchKey {
+                batch_id: batch.batch_id,
+            },
+            &batch,
+        )
+        .await;
+        dbtx.commit_tx().await;
+
+        info!(
+            "Created batch {} of {} invoices",
+            batch.batch_id,
+            batch.invoices.len()
+        );
+
+        Ok(batch)
+    }
+
+    /// Registers a lightning address for a connected federation, see
+    /// `lnurl::register_lightning_address`
+    pub async fn handle_register_lightning_address_msg(
+        &self,
+        payload: RegisterLightningAddressPayload,
+    ) -> Result<LightningAddressUser> {
+        self.select_client(payload.federation_id).await?;
+        lnurl::register_lightning_address(&self.gateway_db, payload).await
+    }
+
+    /// Removes a lightning address together with its unpaid invoices, see
+    /// `lnurl::remove_lightning_address`
+    pub async fn handle_remove_lightning_address_msg(
+        &self,
+        payload: RemoveLightningAddressPayload,
+    ) -> Result<()> {
+        lnurl::remove_lightning_address(&self.gateway_db, payload).await
+    }
+
+    pub async fn handle_list_lightning_addresses_msg(&self) -> Vec<LightningAddressUser> {
+        lnurl::list_lightning_addresses(&self.gateway_db).await
+    }
+
+    /// Hands out the ecash issued to the user of a lightning address until the
+    /// operator acknowledges it, see `lnurl::claim_lightning_address`
+    pub async fn handle_claim_lightning_address_msg(
+        &self,
+        payload: ClaimLightningAddressPayload,
+    ) -> Result<Vec<LightningAddressNotes>> {
+        lnurl::claim_lightning_address(&self.gateway_db, payload).await
     }
 
-    /// Returns a list of Lightning network channels from the Gateway's
-    /// Lightning node.
-    pub async fn handle_list_active_channels_msg(&self) -> Result<Vec<lightning::ChannelInfo>> {
-        let context = self.get_lightning_context().await?;
-        let channels = context.lnrpc.list_active_channels().await?;
-        Ok(channels)
+    /// Returns the LNURL-pay request of a lightning address
+    pub async fn handle_lnurl_pay_request(&self, username: &str) -> Result<PayRequest> {
+        let (user, domain) = lnurl::load_lightning_address_user(
+            &self.gateway_db,
+            self.lightning_address_domain.as_deref(),
+            username,
+        )
+        .await?;
+        Ok(user.pay_request(domain))
+    }
+
+    /// Creates an invoice on the lightning node for a payment of `amount_msat`
+    /// to a lightning address, see `lnurl::create_invoice`
+    pub async fn handle_lnurl_pay_callback(
+        &self,
+        username: &str,
+        amount_msat: u64,
+    ) -> Result<PayRequestInvoice> {
+        let (user, domain) = lnurl::load_lightning_address_user(
+            &self.gateway_db,
+            self.lightning_address_domain.as_deref(),
+            username,
+        )
+        .await?;
+        if !self.clients.read().await.contains_key(&user.federation_id) {
+            return Err(GatewayError::InvalidMetadata(
+                "The lightning address is not available".to_string(),
+            ));
+        }
+
+        let lightning_context = self.get_lightning_context().await?;
+        lnurl::create_invoice(
+            &lightning_context,
+            &self.gateway_db,
+            domain,
+            user,
+            amount_msat,
+        )
+        .await
+    }
+
+    /// Returns whether the invoice of a lightning address with `payment_hash`
+    /// was paid, together with its preimage once it was
+    pub async fn handle_lnurl_verify(
+        &self,
+        username: &str,
+        payment_hash: sha256::Hash,
+    ) -> Result<VerifyResponse> {
+        lnurl::verify_invoice(&self.gateway_db, username, payment_hash).await
+    }
+
+    /// Creates a submarine swap that turns `amount_sats` of the on-chain funds
+    /// of the lightning node into outbound liquidity, see `swap::create_swap`
+    pub async fn handle_create_swap_msg(&self, payload: CreateSwapPayload) -> Result<SwapInfo> {
+        let Some(swap_client) = &self.swap_client else {
+            return Err(GatewayError::GatewayConfigurationError(
+                "No swap provider is configured".to_string(),
+            ));
+        };
+        if self.bitcoin_rpc.is_none() {
+            return Err(GatewayError::GatewayConfigurationError(
+                "No chain source to refund the lockups of swaps is configured".to_string(),
+            ));
+        }
+
+        let lightning_context = self.get_lightning_context().await?;
+        swap::create_swap(swap_client, &lightning_context, &self.gateway_db, payload).await
+    }
+
+    /// Creates a reverse swap that turns `amount_sats` of the channel balance
+    /// of the lightning node into on-chain funds, see
+    /// `swap::create_reverse_swap`
+    pub async fn handle_create_reverse_swap_msg(
+        &self,
+        payload: CreateReverseSwapPayload,
+    ) -> Result<ReverseSwapInfo> {
+        let Some(swap_client) = &self.swap_client else {
+            return Err(GatewayError::GatewayConfigurationError(
+                "No swap provider is configured".to_string(),
+            ));
+        };
+        if self.bitcoin_rpc.is_none() {
+            return Err(GatewayError::GatewayConfigurationError(
+                "No chain source to verify the lockups of reverse swaps is configured".to_string(),
+            ));
+        }
+
+        let lightning_context = self.get_lightning_context().await?;
+        swap::create_reverse_swap(swap_client, &lightning_context, &self.gateway_db, payload).await
+    }
+
+    /// Returns all reverse swaps, newest first
+    pub async fn handle_list_reverse_swaps_msg(&self) -> Vec<ReverseSwapInfo> {
+        swap::list_reverse_swaps(&self.gateway_db).await
+    }
+
+    /// Refunds the lockup of a swap, see `swap::refund_swap`
+    pub async fn handle_refund_swap_msg(&self, payload: RefundSwapPayload) -> Result<SwapInfo> {
+        let Some(bitcoin_rpc) = &self.bitcoin_rpc else {
+            return Err(GatewayError::GatewayConfigurationError(
+                "No chain source to refund the lockups of swaps is configured".to_string(),
+            ));
+        };
+        let bitcoin_rpc = create_bitcoind(bitcoin_rpc, TaskGroup::new().make_handle())
+            .map_err(|e| GatewayError::GatewayConfigurationError(e.to_string()))?;
+
+        let lightning_context = self.get_lightning_context().await?;
+        swap::refund_swap(&bitcoin_rpc, &lightning_context, &self.gateway_db, payload).await
+    }
+
+    /// Returns all swaps, newest first
+    pub async fn handle_list_swaps_msg(&self) -> Vec<SwapInfo> {
+        swap::list_swaps(&self.gateway_db).await
+    }
+
+    pub async fn handle_list_invoice_batches_msg(&self) -> Vec<InvoiceBatch> {
+        let mut batches = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .find_by_prefix(&InvoiceBatchKeyPrefix)
+            .await
+            .map(|(_, batch)| batch)
+            .collect::<Vec<_>>()
+            .await;
+        batches.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        batches
+    }
+
+    /// Sets or, if the policy is `None`, removes the policy for sweeping the
+    /// on-chain funds of the lightning node to cold storage.
+    pub async fn handle_set_sweep_policy_msg(
+        &self,
+        SetSweepPolicyPayload { policy }: SetSweepPolicyPayload,
+    ) -> Result<()> {
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        if let Some(policy) = &policy {
+            let network = self.get_lightning_context().await?.lightning_network;
+            if !policy.address.is_valid_for_network(network) {
+                return Err(GatewayError::InvalidMetadata(format!(
+                    "The sweep address is not valid for {network}"
+                )));
+            }
+
+            if policy.interval_secs == 0 {
+                return Err(GatewayError::InvalidMetadata(
+                    "The sweep interval has to be positive".to_string(),
+                ));
+            }
+
+            dbtx.insert_entry(&SweepPolicyKey, policy).await;
+        } else {
+            dbtx.remove_entry(&SweepPolicyKey).await;
+        }
+        dbtx.commit_tx().await;
+
+        info!("Set sweep policy to {policy:?}");
+
+        Ok(())
+    }
+
+    pub async fn handle_sweep_policy_msg(&self) -> Option<SweepPolicy> {
+        self.gateway_db
+            .begin_transaction_nc()
+            .await
+            .get_value(&SweepPolicyKey)
+            .await
+    }
+
+    /// Sets or, if the policy is `None`, removes the conditions the gateway
+    /// raises alerts for.
+    pub async fn handle_set_alert_policy_msg(
+        &self,
+        SetAlertPolicyPayload { policy }: SetAlertPolicyPayload,
+    ) -> Result<()> {
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        if let Some(policy) = &policy {
+            policy.validate().map_err(GatewayError::InvalidMetadata)?;
+
+            dbtx.insert_entry(&AlertPolicyKey, policy).await;
+        } else {
+            dbtx.remove_entry(&AlertPolicyKey).await;
+        }
+        dbtx.commit_tx().await;
+
+        info!("Set alert policy to {policy:?}");
+
+        Ok(())
+    }
+
+    pub async fn handle_alert_policy_msg(&self) -> Option<AlertPolicy> {
+        self.gateway_db
+            .begin_transaction_nc()
+            .await
+            .get_value(&AlertPolicyKey)
+            .await
+    }
+
+    /// Sets or, if the policy is `None`, removes the policy for retrying
+    /// outgoing LNv2 payments. Without a policy every payment is attempted
+    /// once.
+    pub async fn handle_set_payment_retry_policy_msg(
+        &self,
+        SetPaymentRetryPolicyPayload { policy }: SetPaymentRetryPolicyPayload,
+    ) -> Result<()> {
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        if let Some(policy) = &policy {
+            policy.validate().map_err(GatewayError::InvalidMetadata)?;
+            dbtx.insert_entry(&PaymentRetryPolicyKey, policy).await;
+            info!(?policy, "Set payment retry policy");
+        } else {
+            dbtx.remove_entry(&PaymentRetryPolicyKey).await;
+            info!("Removed payment retry policy");
+        }
+        dbtx.commit_tx().await;
+
+        Ok(())
+    }
+
+    pub async fn handle_payment_retry_policy_msg(&self) -> Option<PaymentRetryPolicy> {
+        self.gateway_db
+            .begin_transaction_nc()
+            .await
+            .get_value(&PaymentRetryPolicyKey)
+            .await
+    }
+
+    /// Records an attempt to pay the invoice with `payment_hash`
+    pub async fn record_payment_attempt(
+        &self,
+        payment_hash: sha256::Hash,
+        attempt: PaymentAttempt,
+    ) {
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        let key = PaymentAttemptsKey { payment_hash };
+        let mut attempts = dbtx.get_value(&key).await.unwrap_or_default();
+        attempts.push(attempt);
+        if attempts.len() > MAX_RECORDED_ATTEMPTS {
+            attempts.remove(0);
+        }
+        dbtx.insert_entry(&key, &attempts).await;
+        dbtx.commit_tx().await;
+    }
+
+    /// Returns the attempts to pay the invoice with `payment_hash`, oldest
+    /// first
+    pub async fn handle_payment_attempts_msg(
+        &self,
+        PaymentAttemptsPayload { payment_hash }: PaymentAttemptsPayload,
+    ) -> Vec<PaymentAttempt> {
+        self.gateway_db
+            .begin_transaction_nc()
+            .await
+            .get_value(&PaymentAttemptsKey { payment_hash })
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Replaces or, if the policy is `None`, removes the bounds within which
+    /// the routing fees of all federations are adjusted. Returns the fee
+    /// adjustments the policy makes right away. If `dry_run` is set, the
+    /// policy is only validated and nothing is changed.
+    pub async fn handle_set_dynamic_fee_policy_msg(
+        &self,
+        SetDynamicFeePolicyPayload { policy, dry_run }: SetDynamicFeePolicyPayload,
+    ) -> Result<Vec<FeeAdjustment>> {
+        let Some(policy) = policy else {
+            if !dry_run {
+                let mut dbtx = self.gateway_db.begin_transaction().await;
+                dbtx.remove_entry(&DynamicFeePolicyKey).await;
+                dbtx.commit_tx().await;
+                info!("Removed dynamic fee policy");
+            }
+            return Ok(vec![]);
+        };
+
+        policy
+            .validate()
+            .map_err(GatewayError::GatewayConfigurationError)?;
+
+        let adjustments = self.fee_adjustments(&policy).await?;
+
+        if dry_run {
+            return Ok(adjustments);
+        }
+
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        dbtx.insert_entry(&DynamicFeePolicyKey, &policy).await;
+        dbtx.commit_tx().await;
+        info!("Set dynamic fee policy to {policy:?}");
+
+        self.apply_fee_adjustments(&adjustments).await?;
+
+        Ok(adjustments)
+    }
+
+    /// Computes the routing fees `policy` sets for every connected federation
+    /// and returns those that differ from the current fees
+    async fn fee_adjustments(&self, policy: &DynamicFeePolicy) -> Result<Vec<FeeAdjustment>> {
+        let context = self.get_lightning_context().await?;
+        let channels = context.lnrpc.list_active_channels().await?;
+
+        let federation_configs = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .find_by_prefix(&FederationIdKeyPrefix)
+            .await
+            .map(|(key, config)| (key.id, config))
+            .collect::<Vec<_>>()
+            .await;
+
+        Ok(federation_configs
+            .into_iter()
+            .map(|(federation_id, config)| FeeAdjustment {
+                federation_id,
+                current: config.fees.into(),
+                adjusted: policy.fees(&channels, self.outgoing_volume.volume_msat(federation_id)),
+            })
+            .filter(|adjustment| adjustment.current != adjustment.adjusted)
+            .collect())
+    }
+
+    async fn apply_fee_adjustments(&self, adjustments: &[FeeAdjustment]) -> Result<()> {
+        if adjustments.is_empty() {
+            return Ok(());
+        }
+
+        self.handle_set_configuration_msg(SetConfigurationPayload {
+            password: None,
+            network: None,
+            num_route_hints: None,
+            routing_fees: None,
+            per_federation_routing_fees: Some(
+                adjustments
+                    .iter()
+                    .map(|adjustment| (adjustment.federation_id, adjustment.adjusted.clone()))
+                    .collect(),
+            ),
+        })
+        .await?;
+
+        info!(
+            "Adjusted the routing fees of {} federations",
+            adjustments.len()
+        );
+
+        Ok(())
+    }
+
+    /// Sets or, if `spending_password` is `None`, removes the spending
+    /// password required for requests that move funds out of the gateway.
+    pub async fn handle_set_spending_password_msg(
+        &self,
+        SetSpendingPasswordPayload {
+            spending_password,
+            payment_threshold,
+        }: SetSpendingPasswordPayload,
+    ) -> Result<()> {
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+
+        if let Some(spending_password) = spending_password {
+            let password_salt: [u8; 16] = rand::thread_rng().gen();
+            let hashed_password = fedimint_aead::hash_password(&spending_password, &password_salt)?;
+            dbtx.insert_entry(
+                &SpendingPasswordKey,
+                &SpendingPassword {
+                    hashed_password,
+                    password_salt,
+                    payment_threshold,
+                },
+            )
+            .await;
+            info!("Set spending password with payment threshold {payment_threshold:?}");
+        } else {
+            dbtx.remove_entry(&SpendingPasswordKey).await;
+            info!("Removed spending password");
+        }
+
+        dbtx.commit_tx().await;
+
+        Ok(())
+    }
+
+    /// Returns true if no spending password is set, if `spending_password`
+    /// matches it or if the request is a lightning payment of
+    /// `payment_amount` that doesn't exceed the payment threshold.
+    pub async fn verify_spending_password(
+        &self,
+        spending_password: Option<&str>,
+        payment_amount: Option<Amount>,
+    ) -> bool {
+        let Some(SpendingPassword {
+            hashed_password,
+            password_salt,
+            payment_threshold,
+        }) = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .get_value(&SpendingPasswordKey)
+            .await
+        else {
+            return true;
+        };
+
+        if let (Some(amount), Some(threshold)) = (payment_amount, payment_threshold) {
+            if amount <= threshold {
+                return true;
+            }
+        }
+
+        spending_password.is_some_and(|password| {
+            fedimint_aead::hash_password(password, &password_salt)
+                .is_ok_and(|hash| hash == hashed_password)
+        })
+    }
+
+    /// Issues a new API token. The token itself is only returned here, the
+    /// gateway only stores its hash.
+    pub async fn handle_create_api_token_msg(
+        &self,
+        CreateApiTokenPayload { name, scope }: CreateApiTokenPayload,
+    ) -> Result<CreatedApiToken> {
+        if name.is_empty() {
+            return Err(GatewayError::InvalidMetadata(
+                "The name of an API token must not be empty".to_string(),
+            ));
+        }
+
+        let (token, token_hash) = generate_api_token();
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        dbtx.insert_new_entry(
+            &ApiTokenKey { token_hash },
+            &ApiToken {
+                token_hash,
+                name: name.clone(),
+                scope,
+                created_at: fedimint_core::time::now(),
+            },
+        )
+        .await;
+        dbtx.commit_tx().await;
+
+        info!(?scope, %token_hash, "Created API token {name}");
+
+        Ok(CreatedApiToken { token, token_hash })
+    }
+
+    /// Lists the issued API tokens, oldest first
+    pub async fn handle_list_api_tokens_msg(&self) -> Vec<ApiToken> {
+        let mut tokens = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .find_by_prefix(&ApiTokenKeyPrefix)
+            .await
+            .map(|(_, token)| token)
+            .collect::<Vec<_>>()
+            .await;
+        tokens.sort_by_key(|token| token.created_at);
+        tokens
+    }
+
+    /// Revokes an API token, requests authenticated with it are rejected
+    /// right away.
+    pub async fn handle_revoke_api_token_msg(
+        &self,
+        RevokeApiTokenPayload { token_hash }: RevokeApiTokenPayload,
+    ) -> Result<()> {
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        dbtx.remove_entry(&ApiTokenKey { token_hash }).await.ok_or(
+            GatewayError::InvalidMetadata(format!("No API token with hash {token_hash}")),
+        )?;
+        dbtx.commit_tx().await;
+
+        info!(%token_hash, "Revoked API token");
+
+        Ok(())
+    }
+
+    /// Returns the API token `token`, if it was issued and not revoked.
+    pub async fn api_token(&self, token: &str) -> Option<ApiToken> {
+        self.gateway_db
+            .begin_transaction_nc()
+            .await
+            .get_value(&ApiTokenKey {
+                token_hash: hash_api_token(token),
+            })
+            .await
+    }
+
+    /// Records a mutating admin request, dropping the oldest entries once
+    /// `AUDIT_LOG_SIZE` is reached. The entry is committed on its own, so
+    /// that concurrent requests pruning the same entries can't lose it.
+    pub async fn record_audit_entry(&self, entry: AuditEntry) {
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        dbtx.insert_new_entry(
+            &AuditLogKey {
+                recorded_at: entry.recorded_at,
+                nonce: OsRng.gen(),
+            },
+            &entry,
+        )
+        .await;
+        dbtx.commit_tx().await;
+
+        let pruned = self
+            .gateway_db
+            .autocommit(
+                |dbtx, _| {
+                    Box::pin(async move {
+                        let mut keys = dbtx
+                            .find_by_prefix(&AuditLogKeyPrefix)
+                            .await
+                            .map(|(key, _)| key)
+                            .collect::<Vec<_>>()
+                            .await;
+                        if keys.len() > AUDIT_LOG_SIZE {
+                            keys.sort_by_key(|key| key.recorded_at);
+                            for key in &keys[..keys.len() - AUDIT_LOG_SIZE] {
+                                dbtx.remove_entry(key).await;
+                            }
+                        }
+                        Ok::<(), ()>(())
+                    })
+                },
+                Some(AUDIT_LOG_PRUNE_ATTEMPTS),
+            )
+            .await;
+        if let Err(e) = pruned {
+            debug!("Failed to prune the audit log, leaving it to the next entry: {e:?}");
+        }
+    }
+
+    /// Returns the audit log, newest entries first
+    pub async fn handle_audit_log_msg(&self) -> Vec<AuditEntry> {
+        let mut entries = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .find_by_prefix(&AuditLogKeyPrefix)
+            .await
+            .map(|(_, entry)| entry)
+            .collect::<Vec<_>>()
+            .await;
+        entries.sort_by(|a, b| b.recorded_at.cmp(&a.recorded_at));
+        entries
+    }
+
+    /// Returns the payments that finished at or after `start` and before
+    /// `end`, oldest first
+    async fn load_payment_log(
+        &self,
+        start: Option<SystemTime>,
+        end: Option<SystemTime>,
+    ) -> Vec<PaymentLogEntry> {
+        let mut entries = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .find_by_prefix(&PaymentLogKeyPrefix)
+            .await
+            .map(|(_, entry)| entry)
+            .filter(|entry| {
+                std::future::ready(
+                    start.is_none_or(|start| start <= entry.finished_at)
+                        && end.is_none_or(|end| entry.finished_at < end),
+                )
+            })
+            .collect::<Vec<_>>()
+            .await;
+        entries.sort_by_key(|entry| entry.finished_at);
+        entries
+    }
+
+    /// Returns the payments that finished within the time range of the payload
+    /// and match its filter, newest first, paged by its offset and limit
+    pub async fn handle_payment_log_msg(
+        &self,
+        PaymentLogPayload {
+            filter,
+            start,
+            end,
+            offset,
+            limit,
+        }: PaymentLogPayload,
+    ) -> Vec<PaymentLogEntry> {
+        self.load_payment_log(start, end)
+            .await
+            .into_iter()
+            .rev()
+            .filter(|entry| filter.matches(entry))
+            .skip(offset)
+            .take(limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+
+    /// Groups the payments that finished within the time range of the payload
+    /// by federation and period
+    pub async fn handle_payment_summary_msg(
+        &self,
+        PaymentSummaryPayload { period, start, end }: PaymentSummaryPayload,
+    ) -> Vec<PaymentSummaryBucket> {
+        summarize(&self.load_payment_log(start, end).await, period)
+    }
+
+    /// Returns the payments that finished within the time range of the
+    /// payload in the requested format, oldest first
+    pub async fn handle_export_payment_log_msg(
+        &self,
+        ExportPaymentLogPayload { format, start, end }: ExportPaymentLogPayload,
+    ) -> String {
+        export(&self.load_payment_log(start, end).await, format)
+    }
+
+    /// Returns the gateway's fee, routing, liquidity and alerting policies, so
+    /// that they can be imported on another gateway.
+    pub async fn handle_export_policy_msg(&self) -> Result<GatewayPolicy> {
+        let gateway_config =
+            self.clone_gateway_config()
+                .await
+                .ok_or(GatewayError::GatewayConfigurationError(
+                    "The gateway is not configured".to_string(),
+                ))?;
+
+        let mut dbtx = self.gateway_db.begin_transaction_nc().await;
+        let per_federation_routing_fees = dbtx
+            .find_by_prefix(&FederationIdKeyPrefix)
+            .await
+            .map(|(key, config)| (key.id, config.fees.into()))
+            .collect::<BTreeMap<_, _>>()
+            .await;
+
+        let mut per_federation_incoming_limits = BTreeMap::new();
+        let mut per_federation_protocols = BTreeMap::new();
+        for federation_id in per_federation_routing_fees.keys().copied() {
+            if let Some(limits) = dbtx.get_value(&IncomingLimitsKey { federation_id }).await {
+                per_federation_incoming_limits.insert(federation_id, limits);
+            }
+
+            if let Some(protocols) = dbtx
+                .get_value(&LightningProtocolsKey { federation_id })
+                .await
+            {
+                per_federation_protocols.insert(federation_id, protocols);
+            }
+        }
+
+        let per_federation_liquidity_caps = dbtx
+            .find_by_prefix(&LiquidityCapKeyPrefix)
+            .await
+            .map(|(key, cap)| (key.federation_id, cap))
+            .collect::<BTreeMap<_, _>>()
+            .await;
+
+        let per_federation_rebalance_bands = dbtx
+            .find_by_prefix(&RebalanceBandKeyPrefix)
+            .await
+            .map(|(key, band)| (key.federation_id, band))
+            .collect::<BTreeMap<_, _>>()
+            .await;
+
+        Ok(GatewayPolicy {
+            num_route_hints: gateway_config.num_route_hints,
+            routing_fees: gateway_config.routing_fees.into(),
+            per_federation_routing_fees,
+            per_federation_incoming_limits,
+            per_federation_liquidity_caps,
+            per_federation_rebalance_bands,
+            per_federation_protocols,
+            dynamic_fee_policy: dbtx.get_value(&DynamicFeePolicyKey).await,
+            payment_retry_policy: dbtx.get_value(&PaymentRetryPolicyKey).await,
+            alert_policy: dbtx.get_value(&AlertPolicyKey).await,
+            sweep_policy: dbtx.get_value(&SweepPolicyKey).await,
+        })
+    }
+
+    /// Validates a `GatewayPolicy` exported from another gateway and returns
+    /// the settings it changes. Unless `dry_run` is set, the policy is applied
+    /// afterwards. Per-federation settings are only replaced for the
+    /// federations the policy lists, while the gateway-wide policies are
+    /// replaced or, if missing from the policy, removed.
+    pub async fn handle_import_policy_msg(
+        &self,
+        ImportPolicyPayload { policy, dry_run }: ImportPolicyPayload,
+    ) -> Result<Vec<PolicyChange>> {
+        let current = self.handle_export_policy_msg().await?;
+
+        let all_fees = std::iter::once(&policy.routing_fees)
+            .chain(policy.per_federation_routing_fees.values());
+        for fees in all_fees {
+            if fees.proportional_millionths > 1_000_000 {
+                return Err(GatewayError::GatewayConfigurationError(format!(
+                    "Proportional routing fee of {} millionths exceeds 100%",
+                    fees.proportional_millionths
+                )));
+            }
+        }
+
+        let policy_federations = policy
+            .per_federation_routing_fees
+            .keys()
+            .chain(policy.per_federation_incoming_limits.keys())
+            .chain(policy.per_federation_liquidity_caps.keys())
+            .chain(policy.per_federation_rebalance_bands.keys())
+            .chain(policy.per_federation_protocols.keys());
+        for federation_id in policy_federations {
+            if !current
+                .per_federation_routing_fees
+                .contains_key(federation_id)
+            {
+                return Err(GatewayError::GatewayConfigurationError(format!(
+                    "Policy contains settings for federation {federation_id} that is not connected"
+                )));
+            }
+        }
+
+        for protocols in policy.per_federation_protocols.values() {
+            protocols
+                .validate()
+                .map_err(GatewayError::InvalidMetadata)?;
+        }
+
+        for band in policy.per_federation_rebalance_bands.values() {
+            band.validate()
+                .map_err(GatewayError::GatewayConfigurationError)?;
+        }
+
+        if let Some(retry_policy) = &policy.payment_retry_policy {
+            retry_policy
+                .validate()
+                .map_err(GatewayError::InvalidMetadata)?;
+        }
+
+        if let Some(dynamic_fee_policy) = &policy.dynamic_fee_policy {
+            dynamic_fee_policy
+                .validate()
+                .map_err(GatewayError::GatewayConfigurationError)?;
+        }
+
+        let changes = current.diff(&policy);
+
+        if dry_run || changes.is_empty() {
+            return Ok(changes);
+        }
+
+        // The handlers of the sweep and alert policies validate them before
+        // changing anything, so they are applied first to not leave a policy
+        // that fails validation half imported.
+        if current.sweep_policy != policy.sweep_policy {
+            self.handle_set_sweep_policy_msg(SetSweepPolicyPayload {
+                policy: policy.sweep_policy,
+            })
+            .await?;
+        }
+
+        if current.alert_policy != policy.alert_policy {
+            self.handle_set_alert_policy_msg(SetAlertPolicyPayload {
+                policy: policy.alert_policy,
+            })
+            .await?;
+        }
+
+        if current.payment_retry_policy != policy.payment_retry_policy {
+            self.handle_set_payment_retry_policy_msg(SetPaymentRetryPolicyPayload {
+                policy: policy.payment_retry_policy,
+            })
+            .await?;
+        }
+
+        self.handle_set_configuration_msg(SetConfigurationPayload {
+            password: None,
+            network: None,
+            num_route_hints: Some(policy.num_route_hints),
+            routing_fees: Some(policy.routing_fees),
+            per_federation_routing_fees: Some(
+                policy.per_federation_routing_fees.into_iter().collect(),
+            ),
+        })
+        .await?;
+
+        for (federation_id, limits) in policy.per_federation_incoming_limits {
+            self.handle_set_incoming_limits_msg(SetIncomingLimitsPayload {
+                federation_id,
+                limits,
+            })
+            .await?;
+        }
+
+        for (federation_id, cap) in policy.per_federation_liquidity_caps {
+            self.handle_set_liquidity_cap_msg(SetLiquidityCapPayload {
+                federation_id,
+                cap: Some(cap),
+            })
+            .await?;
+        }
+
+        for (federation_id, band) in policy.per_federation_rebalance_bands {
+            self.handle_set_rebalance_band_msg(SetRebalanceBandPayload {
+                federation_id,
+                band: Some(band),
+            })
+            .await?;
+        }
+
+        for (federation_id, protocols) in policy.per_federation_protocols {
+            if current.per_federation_protocols.get(&federation_id) != Some(&protocols) {
+                self.handle_set_lightning_protocols_msg(SetLightningProtocolsPayload {
+                    federation_id,
+                    protocols,
+                })
+                .await?;
+            }
+        }
+
+        // The dynamic fee policy overrides the routing fees of all federations,
+        // hence it is applied last.
+        if current.dynamic_fee_policy != policy.dynamic_fee_policy {
+            self.handle_set_dynamic_fee_policy_msg(SetDynamicFeePolicyPayload {
+                policy: policy.dynamic_fee_policy,
+                dry_run: false,
+            })
+            .await?;
+        }
+
+        info!("Imported gateway policy with {} changes", changes.len());
+
+        Ok(changes)
+    }
+
+    pub async fn handle_get_funding_address_msg(&self) -> Result<Address> {
+        let context = self.get_lightning_context().await?;
+        let response = context.lnrpc.get_funding_address().await?;
+        Address::from_str(&response.address)
+            .map(Address::assume_checked)
+            .map_err(|e| GatewayError::LightningResponseParseError(e.into()))
+    }
+
+    /// Instructs the Gateway's Lightning node to open a channel to a peer
+    /// specified by `pubkey`.
+    pub async fn handle_open_channel_msg(
+        &self,
+        OpenChannelPayload {
+            pubkey,
+            host,
+            channel_size_sats,
+            push_amount_sats,
+            options,
+        }: OpenChannelPayload,
+    ) -> Result<()> {
+        let context = self.get_lightning_context().await?;
+        context
+            .lnrpc
+            .open_channel(pubkey, host, channel_size_sats, push_amount_sats, options)
+            .await?;
+        Ok(())
+    }
+
+    /// Instructs the Gateway's Lightning node to send a spontaneous (keysend)
+    /// payment and returns its preimage.
+    pub async fn handle_pay_keysend_msg(
+        &self,
+        PayKeysendPayload {
+            node_id,
+            amount,
+            max_fee,
+            tlv_records,
+        }: PayKeysendPayload,
+    ) -> Result<Preimage> {
+        let context = self.get_lightning_context().await?;
+        let response = context
+            .lnrpc
+            .pay_keysend(node_id, amount, max_fee, tlv_records)
+            .await?;
+        let preimage = response.preimage.try_into().map_err(|_| {
+            GatewayError::LightningResponseParseError(anyhow!("Preimage is not 32 bytes"))
+        })?;
+        Ok(Preimage(preimage))
+    }
+
+    /// Instructs the Gateway's Lightning node to close all channels with a peer
+    /// specified by `pubkey`.
+    pub async fn handle_close_channels_with_peer_msg(
+        &self,
+        CloseChannelsWithPeerPayload { pubkey }: CloseChannelsWithPeerPayload,
+    ) -> Result<CloseChannelsWithPeerResponse> {
+        let context = self.get_lightning_context().await?;
+        let response = context.lnrpc.close_channels_with_peer(pubkey).await?;
+        Ok(response)
+    }
+
+    /// Returns a list of Lightning network channels from the Gateway's
+    /// Lightning node.
+    pub async fn handle_list_active_channels_msg(&self) -> Result<Vec<lightning::ChannelInfo>> {
+        let context = self.get_lightning_context().await?;
+        let channels = context.lnrpc.list_active_channels().await?;
+        Ok(channels)
+    }
+
+    /// Connects the Gateway's Lightning node to a peer. Persistent peers are
+    /// stored in the database and reconnected whenever the connection is lost.
+    pub async fn handle_connect_peer_msg(
+        &self,
+        ConnectPeerPayload {
+            pubkey,
+            host,
+            persistent,
+        }: ConnectPeerPayload,
+    ) -> Result<()> {
+        let context = self.get_lightning_context().await?;
+        context.lnrpc.connect_peer(pubkey, host.clone()).await?;
+
+        if persistent {
+            let mut dbtx = self.gateway_db.begin_transaction().await;
+            dbtx.insert_entry(&PersistentPeerKey { pubkey }, &host)
+                .await;
+            dbtx.commit_tx().await;
+            info!("Added persistent peer {pubkey}@{host}");
+        }
+
+        Ok(())
+    }
+
+    /// Disconnects the Gateway's Lightning node from a peer and stops
+    /// reconnecting to it if it is a persistent peer.
+    pub async fn handle_disconnect_peer_msg(
+        &self,
+        DisconnectPeerPayload { pubkey }: DisconnectPeerPayload,
+    ) -> Result<()> {
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        if dbtx
+            .remove_entry(&PersistentPeerKey { pubkey })
+            .await
+            .is_some()
+        {
+            info!("Removed persistent peer {pubkey}");
+        }
+        dbtx.commit_tx().await;
+
+        let context = self.get_lightning_context().await?;
+        context.lnrpc.disconnect_peer(pubkey).await?;
+        Ok(())
+    }
+
+    /// Returns the peers the Gateway's Lightning node is connected to and the
+    /// persistent peers, including disconnected ones.
+    pub async fn handle_list_peers_msg(&self) -> Result<Vec<PeerStatus>> {
+        let context = self.get_lightning_context().await?;
+        let mut peers = context
+            .lnrpc
+            .list_peers()
+            .await?
+            .into_iter()
+            .map(|peer| {
+                (
+                    peer.pubkey,
+                    PeerStatus {
+                        pubkey: peer.pubkey,
+                        address: Some(peer.address),
+                        connected: true,
+                        persistent: false,
+                    },
+                )
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        for (pubkey, host) in self.load_persistent_peers().await {
+            peers
+                .entry(pubkey)
+                .or_insert(PeerStatus {
+                    pubkey,
+                    address: Some(host),
+                    connected: false,
+                    persistent: true,
+                })
+                .persistent = true;
+        }
+
+        Ok(peers.into_values().collect())
+    }
+
+    async fn load_persistent_peers(&self) -> Vec<(PublicKey, String)> {
+        self.gateway_db
+            .begin_transaction_nc()
+            .await
+            .find_by_prefix(&PersistentPeerKeyPrefix)
+            .await
+            .map(|(key, host)| (key.pubkey, host))
+            .collect()
+            .await
+    }
+
+    /// Spawns a task that reconnects the Gateway's Lightning node to the
+    /// persistent peers it lost the connection to, including after restarts.
+    fn start_peer_reconnection(&self, task_group: &TaskGroup) {
+        let gateway = self.clone();
+        task_group.spawn_cancellable("reconnect persistent peers", async move {
+            loop {
+                sleep(PEER_RECONNECT_INTERVAL).await;
+
+                let persistent_peers = gateway.load_persistent_peers().await;
+                if persistent_peers.is_empty() {
+                    continue;
+                }
+
+                let Ok(context) = gateway.get_lightning_context().await else {
+                    debug!("Not reconnecting peers, the gateway is not connected");
+                    continue;
+                };
+
+                let connected_peers = match context.lnrpc.list_peers().await {
+                    Ok(peers) => peers
+                        .into_iter()
+                        .map(|peer| peer.pubkey)
+                        .collect::<BTreeSet<_>>(),
+                    Err(e) => {
+                        warn!("Failed to list peers: {e}");
+                        continue;
+                    }
+                };
+
+                for (pubkey, host) in persistent_peers {
+                    if connected_peers.contains(&pubkey) {
+                        continue;
+                    }
+
+                    match context.lnrpc.connect_peer(pubkey, host.clone()).await {
+                        Ok(()) => info!("Reconnected to persistent peer {pubkey}@{host}"),
+                        Err(e) => warn!("Failed to reconnect to {pubkey}@{host}: {e}"),
+                    }
+                }
+            }
+        });
+    }
+
+    /// Estimates the routing fee and hop count of a payment from the Gateway's
+    /// Lightning node, so that the fee can be shown before sending.
+    pub async fn handle_query_route_msg(
+        &self,
+        QueryRoutePayload {
+            destination,
+            amount,
+        }: QueryRoutePayload,
+    ) -> Result<RouteEstimate> {
+        let context = self.get_lightning_context().await?;
+        Ok(context.lnrpc.query_route(destination, amount).await?)
+    }
+
+    /// Updates the forwarding fees and CLTV delta of one of the channels of
+    /// the Gateway's Lightning node.
+    pub async fn handle_update_channel_policy_msg(
+        &self,
+        UpdateChannelPolicyPayload {
+            short_channel_id,
+            policy,
+        }: UpdateChannelPolicyPayload,
+    ) -> Result<()> {
+        let context = self.get_lightning_context().await?;
+        context
+            .lnrpc
+            .update_channel_policy(short_channel_id, policy)
+            .await?;
+        info!("Updated policy of channel {short_channel_id} to {policy:?}");
+        Ok(())
+    }
+
+    /// Returns the routing history of the Gateway's Lightning node, so that it
+    /// can be imported after migrating the node.
+    pub async fn handle_export_mission_control_msg(&self) -> Result<Vec<MissionControlPair>> {
+        let context = self.get_lightning_context().await?;
+        Ok(context.lnrpc.query_mission_control().await?)
+    }
+
+    /// Imports routing history into the Gateway's Lightning node.
+    pub async fn handle_import_mission_control_msg(
+        &self,
+        ImportMissionControlPayload { pairs, force }: ImportMissionControlPayload,
+    ) -> Result<()> {
+        let context = self.get_lightning_context().await?;
+        let num_pairs = pairs.len();
+        context.lnrpc.import_mission_control(pairs, force).await?;
+        info!("Imported mission control history for {num_pairs} node pairs");
+        Ok(())
+    }
+
+    /// Clears the routing history of the Gateway's Lightning node, e.g. after
+    /// network topology changes made it stale.
+    pub async fn handle_reset_mission_control_msg(&self) -> Result<()> {
+        let context = self.get_lightning_context().await?;
+        context.lnrpc.reset_mission_control().await?;
+        info!("Reset mission control");
+        Ok(())
+    }
+
+    /// Summarizes the routing history of the Gateway's Lightning node.
+    pub async fn handle_mission_control_stats_msg(&self) -> Result<MissionControlStats> {
+        let context = self.get_lightning_context().await?;
+        let pairs = context.lnrpc.query_mission_control().await?;
+        Ok(MissionControlStats::from_pairs(&pairs))
+    }
+
+    /// Requests the channel options and limits offered by an LSP.
+    pub async fn handle_lsps1_get_info_msg(
+        &self,
+        Lsps1GetInfoPayload { lsp_node_id }: Lsps1GetInfoPayload,
+    ) -> Result<serde_json::Value> {
+        let context = self.get_lightning_context().await?;
+        lsps1::get_info(context.lnrpc.as_ref(), lsp_node_id).await
+    }
+
+    /// Requests a quote for a channel with inbound liquidity from an LSP and
+    /// stores the resulting order, which can then be paid with
+    /// `handle_lsps1_pay_order_msg`.
+    pub async fn handle_lsps1_create_order_msg(
+        &self,
+        Lsps1CreateOrderPayload {
+            lsp_node_id,
+            lsp_balance_sat,
+            client_balance_sat,
+            channel_expiry_blocks,
+            announce_channel,
+        }: Lsps1CreateOrderPayload,
+    ) -> Result<Lsps1Order> {
+        let context = self.get_lightning_context().await?;
+        let order = lsps1::create_order(
+            context.lnrpc.as_ref(),
+            lsp_node_id,
+            lsp_balance_sat,
+            client_balance_sat,
+            channel_expiry_blocks,
+            announce_channel,
+        )
+        .await?;
+
+        info!(
+            "Created LSPS1 order {} with {lsp_node_id} for {} sat",
+            order.order_id, order.order_total_sat
+        );
+        self.save_lsps1_order(&order).await;
+
+        Ok(order)
+    }
+
+    /// Pays for a previously created LSPS1 order with the gateway's lightning
+    /// node.
+    pub async fn handle_lsps1_pay_order_msg(
+        &self,
+        Lsps1PayOrderPayload { order_id, max_fee }: Lsps1PayOrderPayload,
+    ) -> Result<Lsps1Order> {
+        let order = self.get_lsps1_order(&order_id).await?;
+        if order.state != Lsps1OrderState::Created {
+            return Err(GatewayError::UnexpectedState(format!(
+                "LSPS1 order {order_id} is {:?}",
+                order.state
+            )));
+        }
+
+        let context = self.get_lightning_context().await?;
+        lsps1::pay_order(context.lnrpc.as_ref(), &order, max_fee).await?;
+
+        info!("Paid LSPS1 order {order_id}");
+        let order = Lsps1Order {
+            state: Lsps1OrderState::Paid,
+            ..order
+        };
+        self.save_lsps1_order(&order).await;
+
+        Ok(order)
+    }
+
+    /// Refreshes the state of an LSPS1 order from the LSP.
+    pub async fn handle_lsps1_get_order_msg(
+        &self,
+        Lsps1OrderPayload { order_id }: Lsps1OrderPayload,
+    ) -> Result<Lsps1Order> {
+        let order = self.get_lsps1_order(&order_id).await?;
+        let context = self.get_lightning_context().await?;
+        let order = lsps1::refresh_order(context.lnrpc.as_ref(), order).await?;
+        self.save_lsps1_order(&order).await;
+
+        Ok(order)
+    }
+
+    async fn get_lsps1_order(&self, order_id: &str) -> Result<Lsps1Order> {
+        self.gateway_db
+            .begin_transaction_nc()
+            .await
+            .get_value(&Lsps1OrderKey {
+                order_id: order_id.to_string(),
+            })
+            .await
+            .ok_or(GatewayError::UnexpectedState(format!(
+                "Unknown LSPS1 order {order_id}"
+            )))
+    }
+
+    async fn save_lsps1_order(&self, order: &Lsps1Order) {
+        let mut dbtx = self.gateway_db.begin_transaction().await;
+        dbtx.insert_entry(
+            &Lsps1OrderKey {
+                order_id: order.order_id.clone(),
+            },
+            order,
+        )
+        .await;
+        dbtx.commit_tx().await;
+    }
+
+    /// Registers the gateway with each specified federation.
+    async fn register_federations(
+        &self,
+        gateway_config: &GatewayConfiguration,
+        federations: &[(FederationId, FederationConfig)],
+    ) -> Result<()> {
+        if let Ok(lightning_context) = self.get_lightning_context().await {
+            let route_hints = Self::fetch_lightning_route_hints(
+                lightning_context.lnrpc.clone(),
+                gateway_config.num_route_hints,
+            )
+            .await;
+            if route_hints.is_empty() {
+                warn!("Gateway did not retrieve any route hints, may reduce receive success rate.");
+            }
+
+            for (federation_id, federation_config) in federations {
+                if self.is_draining() || !self.load_lightning_protocols(*federation_id).await.lnv1 {
+                    continue;
+                }
+
+                if let Some(client) = self.clients.read().await.get(federation_id) {
+                    if let Err(e) = async {
+                        client
+                            .value()
+                            .get_first_module::<GatewayClientModule>()
+                            .register_with_federation(
+                                route_hints.clone(),
+                                GW_ANNOUNCEMENT_TTL,
+                                federation_config.fees,
+                                lightning_context.clone(),
+                            )
+                            .await
+                    }
+                    .instrument(client.span())
+                    .await
+                    {
+                        Err(GatewayError::FederationError(FederationError::general(
+                            REGISTER_GATEWAY_ENDPOINT,
+                            serde_json::Value::Null,
+                            anyhow::anyhow!("Error registering federation {federation_id}: {e:?}"),
+                        )))?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// This function will return a `GatewayConfiguration` one of two
+    /// ways. To avoid conflicting configs, the below order is the
+    /// order in which the gateway will respect configurations:
+    /// - `GatewayConfiguration` is read from the database.
+    /// - All cli or environment variables are set such that we can create a
+    ///   `GatewayConfiguration`
+    async fn get_gateway_configuration(
+        gateway_db: Database,
+        gateway_parameters: &GatewayParameters,
+    ) -> Option<GatewayConfiguration> {
+        let mut dbtx = gateway_db.begin_transaction().await;
+
+        // Always use the gateway configuration from the database if it exists.
+        if let Some(gateway_config) = dbtx.get_value(&GatewayConfigurationKey).await {
+            return Some(gateway_config);
+        }
+
+        // If the password is not provided, return None
+        let password = gateway_parameters.password.as_ref()?;
+
+        // If the DB does not have the gateway configuration, we can construct one from
+        // the provided password (required) and the defaults.
+        // Use gateway parameters provided by the environment or CLI
+        let num_route_hints = gateway_parameters.num_route_hints;
+        let routing_fees = gateway_parameters
+            .fees
+            .clone()
+            .unwrap_or(GatewayFee(DEFAULT_FEES));
+        let network = gateway_parameters.network.unwrap_or(DEFAULT_NETWORK);
+        let password_salt: [u8; 16] = rand::thread_rng().gen();
+        let hashed_password = hash_password(password, password_salt);
+        let gateway_config = GatewayConfiguration {
+            hashed_password,
+            network,
+            num_route_hints,
+            routing_fees: routing_fees.0,
+            password_salt,
+        };
+
+        Some(gateway_config)
+    }
+
+    /// Removes a federation client from the Gateway's in memory structures that
+    /// keep track of available clients. Does not remove the persisted
+    /// client configuration in the database.
+    async fn remove_client(
+        &self,
+        federation_id: FederationId,
+        // Note: MUST be protected by a lock, to keep
+        // `clients` and opened databases in sync
+        _lock: &MutexGuard<'_, ClientsJoinLock>,
+    ) -> Result<()> {
+        let client = self
+            .clients
+            .write()
+            .await
+            .remove(&federation_id)
+            .ok_or(GatewayError::InvalidMetadata(format!(
+                "No federation with id {federation_id}"
+            )))?
+            .into_value();
+
+        if let Some(client) = Arc::into_inner(client) {
+            client.shutdown().await;
+        } else {
+            error!("client is not unique, failed to remove client");
+        }
+
+        // Remove previously assigned scid from `scid_to_federation` map
+        self.scid_to_federation
+            .write()
+            .await
+            .retain(|_, fid| *fid != federation_id);
+        Ok(())
+    }
+
+    /// Retrieves a `ClientHandleArc` from the Gateway's in memory structures
+    /// that keep track of available clients, given a `federation_id`.
+    pub async fn select_client(
+        &self,
+        federation_id: FederationId,
+    ) -> Result<Spanned<fedimint_client::ClientHandleArc>> {
+        self.clients
+            .read()
+            .await
+            .get(&federation_id)
+            .cloned()
+            .ok_or(GatewayError::InvalidMetadata(format!(
+                "No federation with id {federation_id}"
+            )))
+    }
+
+    /// Reads the connected federation client configs from the Gateway's
+    /// database and reconstructs the clients necessary for interacting with
+    /// connection federations.
+    async fn load_clients(&self) {
+        let dbtx = self.gateway_db.begin_transaction().await;
+        let configs = self.client_builder.load_configs(dbtx.into_nc()).await;
+
+        let _join_federation = self.client_joining_lock.lock().await;
+
+        for config in configs.clone() {
+            let federation_id = config.invite_code.federation_id();
+            let scid = config.mint_channel_id;
+
+            if let Ok(client) = Box::pin(Spanned::try_new(
+                info_span!("client", federation_id  = %federation_id.clone()),
+                self.client_builder.build(config, Arc::new(self.clone())),
+            ))
+            .await
+            {
+                // Registering each client happens in the background, since we're loading
+                // the clients for the first time, just add them to
+                // the in-memory maps
+                self.clients.write().await.insert(federation_id, client);
+                self.scid_to_federation
+                    .write()
+                    .await
+                    .insert(scid, federation_id);
+            } else {
+                warn!("Failed to load client for federation: {federation_id}");
+            }
+        }
+
+        if let Some(max_mint_channel_id) = configs.iter().map(|cfg| cfg.mint_channel_id).max() {
+            let mut next_scid = self.next_scid.lock().await;
+            *next_scid = max_mint_channel_id + 1;
+        }
+    }
+
+    /// Spawns a task that regularly compares the Lightning node's balances to
+    /// the configured reserves and pauses or resumes quoting outgoing payments
+    /// accordingly.
+    fn start_reserve_monitoring(&self, task_group: &TaskGroup) {
+        if !self.send_pause.reserves().is_enabled() {
+            return;
+        }
+
+        let gateway = self.clone();
+        task_group.spawn_cancellable("monitor balance reserves", async move {
+            loop {
+                if let Ok(context) = gateway.get_lightning_context().await {
+                    match context.lnrpc.get_balances().await {
+                        Ok(balances) => gateway.send_pause.update(&balances),
+                        Err(e) => warn!("Failed to check balance reserves: {e}"),
+                    }
+                }
+
+                sleep(RESERVE_CHECK_INTERVAL).await;
+            }
+        });
+    }
+
+    /// Spawns a task that exports the ecash balance of every connected
+    /// federation as metrics, if the metrics API is enabled.
+    fn start_balance_metrics(&self, task_group: &TaskGroup) {
+        if self.bind_metrics_api.is_none() {
+            return;
+        }
+
+        let gateway = self.clone();
+        task_group.spawn_cancellable("update federation balance metrics", async move {
+            loop {
+                let clients = gateway.clients.read().await.clone();
+                let mut balances = Vec::new();
+                for (federation_id, client) in clients {
+                    balances.push((federation_id, client.value().get_balance().await));
+                }
+
+                // Federations the gateway left are no longer exported
+                FEDERATION_ECASH_BALANCE_MSATS.reset();
+                for (federation_id, balance) in balances {
+                    FEDERATION_ECASH_BALANCE_MSATS
+                        .with_label_values(&[&federation_id.to_string()])
+                        .set(balance.msats as i64);
+                }
+
+                sleep(BALANCE_METRICS_INTERVAL).await;
+            }
+        });
+    }
+
+    /// Spawns a task that withdraws the ecash above the liquidity cap of every
+    /// federation whose cap is configured to do so to the Lightning node's
+    /// on-chain wallet.
+    fn start_liquidity_caps(&self, task_group: &TaskGroup) {
+        let gateway = self.clone();
+        task_group.spawn_cancellable("withdraw ecash above liquidity caps", async move {
+            loop {
+                sleep(LIQUIDITY_CAP_INTERVAL).await;
+
+                let caps = gateway
+                    .gateway_db
+                    .begin_transaction_nc()
+                    .await
+                    .find_by_prefix(&LiquidityCapKeyPrefix)
+                    .await
+                    .map(|(key, cap)| (key.federation_id, cap))
+                    .collect::<Vec<_>>()
+                    .await;
+
+                for (federation_id, cap) in caps {
+                    let Some(client) = gateway.clients.read().await.get(&federation_id).cloned()
+                    else {
+                        continue;
+                    };
+
+                    let Some(amount) = cap.excess_withdrawal(client.value().get_balance().await)
+                    else {
+                        continue;
+                    };
+
+                    match gateway
+                        .withdraw_to_lightning_node(federation_id, amount)
+                        .await
+                    {
+                        Ok(txid) => info!(
+                            "Withdrew {amount} above the liquidity cap of federation \
+                             {federation_id} in {txid}"
+                        ),
+                        Err(e) => warn!(
+                            "Failed to withdraw ecash above the liquidity cap of federation \
+                             {federation_id}: {e}"
+                        ),
+                    }
+                }
+            }
+        });
+    }
+
+    /// Spawns a task that checks every `REBALANCE_INTERVAL` whether the ecash
+    /// balances are within their bands, withdrawing the excess of federations
+    /// above their band and requesting deposits for federations below it.
+    fn start_rebalancing(&self, task_group: &TaskGroup) {
+        let gateway = self.clone();
+        task_group.spawn_cancellable("rebalance ecash balances", async move {
+            // Federations a deposit was requested for, so that no further deposit
+            // addresses are allocated until their balance is within the band again
+            let mut requested_deposits = BTreeSet::new();
+
+            loop {
+                sleep(REBALANCE_INTERVAL).await;
+
+                let mut dbtx = gateway.gateway_db.begin_transaction_nc().await;
+                if dbtx.get_value(&RebalancingPausedKey).await.is_some() {
+                    continue;
+                }
+
+                let bands = dbtx
+                    .find_by_prefix(&RebalanceBandKeyPrefix)
+                    .await
+                    .map(|(key, band)| (key.federation_id, band))
+                    .collect::<Vec<_>>()
+                    .await;
+                drop(dbtx);
+
+                for (federation_id, band) in bands {
+                    let Some(client) = gateway.clients.read().await.get(&federation_id).cloned()
+                    else {
+                        continue;
+                    };
+
+                    let balance = client.value().get_balance().await;
+                    let Some(action) = band.action(balance) else {
+                        requested_deposits.remove(&federation_id);
+                        continue;
+                    };
+
+                    let outcome = match action {
+                        RebalanceAction::Withdraw { amount } => {
+                            match gateway
+                                .withdraw_to_lightning_node(federation_id, amount)
+                                .await
+                            {
+                                Ok(txid) => RebalanceOutcome::Withdrawn { txid },
+                                Err(e) => RebalanceOutcome::Failed {
+                                    error: e.to_string(),
+                                },
+                            }
+                        }
+                        RebalanceAction::TopUp { .. } => {
+                            if !requested_deposits.insert(federation_id) {
+                                continue;
+                            }
+
+                            match gateway
+                                .handle_address_msg(DepositAddressPayload { federation_id })
+                                .await
+                            {
+                                Ok(address) => RebalanceOutcome::DepositRequested {
+                                    address: address.to_string(),
+                                },
+                                Err(e) => {
+                                    requested_deposits.remove(&federation_id);
+                                    RebalanceOutcome::Failed {
+                                        error: e.to_string(),
+                                    }
+                                }
+                            }
+                        }
+                    };
+
+                    info!(
+                        "Rebalanced federation {federation_id} with a balance of {balance}: \
+                         {action:?} {outcome:?}"
+                    );
+
+                    gateway
+                        .record_rebalance(RebalanceRecord {
+                            federation_id,
+                            timestamp: fedimint_core::time::now(),
+                            balance,
+                            action,
+                            outcome,
+                        })
+                        .await;
+                }
+            }
+        });
+    }
+
+    /// Spawns a task that checks the pending invoices of all batches every
+    /// `INVOICE_BATCH_INTERVAL`, settles accepted payments and notifies the
+    /// webhooks of the batches about paid invoices.
+    fn start_invoice_batch_settlement(&self, task_group: &TaskGroup) {
+        let gateway = self.clone();
+        task_group.spawn_cancellable("settle invoice batches", async move {
+            let client = reqwest::Client::new();
+
+            loop {
+                sleep(INVOICE_BATCH_INTERVAL).await;
+
+                let Ok(lightning_context) = gateway.get_lightning_context().await else {
+                    continue;
+                };
+
+                let batches = gateway
+                    .gateway_db
+                    .begin_transaction_nc()
+                    .await
+                    .find_by_prefix(&InvoiceBatchKeyPrefix)
+                    .await
+                    .map(|(_, batch)| batch)
+                    .filter(|batch| std::future::ready(batch.is_pending()))
+                    .collect::<Vec<_>>()
+                    .await;
+
+                for mut batch in batches {
+                    let mut paid = vec![];
+
+                    for (index, batch_invoice) in batch.invoices.iter_mut().enumerate() {
+                        if batch_invoice.state != BatchInvoiceState::Pending {
+                            continue;
+                        }
+
+                        let payment_hash = *batch_invoice.invoice.payment_hash();
+                        let state = match lightning_context.lnrpc.lookup_invoice(payment_hash).await
+                        {
+                            Ok(state) => state,
+                            Err(e) => {
+                                warn!("Failed to look up invoice {payment_hash}: {e}");
+                                continue;
+                            }
+                        };
+
+                        match state {
+                            InvoiceState::Open => {
+                                // The lightning node cancels expired invoices itself
+                                if batch_invoice.invoice.is_expired() {
+                                    batch_invoice.state = BatchInvoiceState::Expired;
+                                }
+                            }
+                            InvoiceState::Accepted => {
+                                if let Err(e) = lightning_context
+                                    .lnrpc
+                                    .settle_hold_invoice(batch_invoice.preimage)
+                                    .await
+                                {
+                                    warn!("Failed to settle invoice {payment_hash}: {e}");
+                                    continue;
+                                }
+
+                                batch_invoice.state = BatchInvoiceState::Paid {
+                                    paid_at: fedimint_core::time::now(),
+                                };
+                                paid.push(index);
+                            }
+                            InvoiceState::Settled => {
+                                batch_invoice.state = BatchInvoiceState::Paid {
+                                    paid_at: fedimint_core::time::now(),
+                                };
+                                paid.push(index);
+                            }
+                            InvoiceState::Canceled => {
+                                batch_invoice.state = BatchInvoiceState::Expired;
+                            }
+                        }
+                    }
+
+                    let mut dbtx = gateway.gateway_db.begin_transaction().await;
+                    dbtx.insert_entry(
+                        &InvoiceBatchKey {
+                            batch_id: batch.batch_id,
+                        },
+                        &batch,
+                    )
+                    .await;
+                    dbtx.commit_tx().await;
+
+                    for index in paid {
+                        let invoice = &batch.invoices[index].invoice;
+                        info!("Invoice {index} of batch {} was paid", batch.batch_id);
+
+                        if let Some(webhook_url) = &batch.webhook_url {
+                            let notification = InvoicePaidNotification {
+                                batch_id: batch.batch_id,
+                                index,
+                                payment_hash: *invoice.payment_hash(),
+                                amount: invoice.amount_milli_satoshis().map(Amount::from_msats),
+                                metadata: batch.metadata.clone(),
+                            };
+                            notify_webhook(&client, webhook_url, &notification).await;
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    /// Spawns a task that fetches the fiat prices from the price oracle every
+    /// `PRICE_UPDATE_INTERVAL`. Failed updates keep the previous prices.
+    fn start_price_updates(&self, task_group: &TaskGroup) {
+        if self.fiat_currencies.is_empty() {
+            return;
+        }
+
+        let gateway = self.clone();
+        task_group.spawn_cancellable("update fiat prices", async move {
+            loop {
+                match gateway
+                    .price_oracle
+                    .fetch_prices(&gateway.fiat_currencies)
+                    .await
+                {
+                    Ok(prices) => {
+                        *gateway.fiat_prices.write().await = Some(FiatPrices {
+                            prices,
+                            updated_at: fedimint_core::time::now(),
+                        });
+                    }
+                    Err(e) => warn!("Failed to fetch fiat prices: {e:?}"),
+                }
+
+                sleep(PRICE_UPDATE_INTERVAL).await;
+            }
+        });
+    }
+
+    /// Spawns a task that sends the balances and channels to the subscribers
+    /// of the event stream whenever they change. The lightning node is only
+    /// queried while anyone is subscribed.
+    fn start_event_updates(&self, task_group: &TaskGroup) {
+        let gateway = self.clone();
+        task_group.spawn_cancellable("push gateway events", async move {
+            let mut last_events = vec![];
+            loop {
+                sleep(EVENT_UPDATE_INTERVAL).await;
+
+                if gateway.events.receiver_count() == 0 {
+                    last_events.clear();
+                    continue;
+                }
+
+                let events = gateway.state_events().await;
+                for event in &events {
+                    if !last_events.contains(event) {
+                        // Fails if the last subscriber left in the meantime
+                        let _ = gateway.events.send(event.clone());
+                    }
+                }
+                last_events = events;
+            }
+        });
+    }
+
+    /// Spawns a task that records a sample of the gateway's liquidity every
+    /// `LIQUIDITY_SAMPLE_INTERVAL`, dropping the oldest samples once
+    /// `LIQUIDITY_HISTORY_SIZE` is reached.
+    fn start_liquidity_sampling(&self, task_group: &TaskGroup) {
+        let gateway = self.clone();
+        task_group.spawn_cancellable("sample liquidity", async move {
+            let mut last_fees = total_fees_earned(&gateway.gateway_db).await;
+            loop {
+                sleep(LIQUIDITY_SAMPLE_INTERVAL).await;
+
+                let Ok(lightning_context) = gateway.get_lightning_context().await else {
+                    continue;
+                };
+
+                let channels = match lightning_context.lnrpc.list_active_channels().await {
+                    Ok(channels) => channels,
+                    Err(e) => {
+                        warn!("Failed to list channels to sample liquidity: {e}");
+                        continue;
+                    }
+                };
+                let onchain_sats = lightning_context
+                    .lnrpc
+                    .get_balances()
+                    .await
+                    .ok()
+                    .map(|balances| balances.onchain_balance_sats);
+
+                let mut ecash = BTreeMap::new();
+                for (federation_id, client) in gateway.clients.read().await.clone() {
+                    ecash.insert(federation_id, client.value().get_balance().await);
+                }
+
+                let fees = total_fees_earned(&gateway.gateway_db).await;
+                let sample = LiquiditySample::new(
+                    fedimint_core::time::now(),
+                    &channels,
+                    onchain_sats,
+                    ecash,
+                    fees.saturating_sub(last_fees),
+                );
+                last_fees = fees;
+
+                let mut dbtx = gateway.gateway_db.begin_transaction().await;
+                dbtx.insert_entry(
+                    &LiquiditySampleKey {
+                        sampled_at: sample.sampled_at,
+                    },
+                    &sample,
+                )
+                .await;
+
+                let mut keys = dbtx
+                    .find_by_prefix(&LiquiditySampleKeyPrefix)
+                    .await
+                    .map(|(key, _)| key)
+                    .collect::<Vec<_>>()
+                    .await;
+                if keys.len() > LIQUIDITY_HISTORY_SIZE {
+                    keys.sort_by_key(|key| key.sampled_at);
+                    for key in &keys[..keys.len() - LIQUIDITY_HISTORY_SIZE] {
+                        dbtx.remove_entry(key).await;
+                    }
+                }
+
+                dbtx.commit_tx().await;
+            }
+        });
+    }
+
+    /// Returns the liquidity samples, oldest first
+    pub async fn handle_liquidity_history_msg(&self) -> Vec<LiquiditySample> {
+        let mut samples = self
+            .gateway_db
+            .begin_transaction_nc()
+            .await
+            .find_by_prefix(&LiquiditySampleKeyPrefix)
+            .await
+            .map(|(_, sample)| sample)
+            .collect::<Vec<_>>()
+            .await;
+        samples.sort_by_key(|sample| sample.sampled_at);
+        samples
+    }
+
+    /// Spawns a task that evaluates the alert policy every
+    /// `ALERT_CHECK_INTERVAL` and delivers the alerts whose conditions started
+    /// to the policy's webhook and email address.
+    fn start_alerting(&self, task_group: &TaskGroup) {
+        let gateway = self.clone();
+        task_group.spawn_cancellable("evaluate alerts", async move {
+            let client = reqwest::Client::new();
+            let mut state = AlertState::default();
+            loop {
+                sleep(ALERT_CHECK_INTERVAL).await;
+
+                let Some(policy) = gateway.handle_alert_policy_msg().await else {
+                    state = AlertState::default();
+                    continue;
+                };
+
+                let observation = gateway.observe_alert_conditions(&policy).await;
+                for alert in state.evaluate(&policy, observation) {
+                    warn!(?alert, "Raising alert");
+                    let notification = AlertNotification {
+                        gateway_id: gateway.gateway_id,
+                        raised_at: fedimint_core::time::now(),
+                        alert,
+                    };
+                    send_alert(&client, &policy, &notification).await;
+                }
+            }
+        });
+    }
+
+    async fn observe_alert_conditions(&self, policy: &AlertPolicy) -> AlertObservation {
+        let channels = match self.get_lightning_context().await {
+            Ok(lightning_context) => lightning_context
+                .lnrpc
+                .list_active_channels()
+                .await
+                .inspect_err(|e| warn!("Failed to list channels to evaluate alerts: {e}"))
+                .ok(),
+            Err(_) => None,
+        };
+
+        let mut unreachable_federations = BTreeSet::new();
+        if policy.federation_unreachable {
+            for (federation_id, client) in self.clients.read().await.clone() {
+                let response = timeout(
+                    FEDERATION_PING_TIMEOUT,
+                    client.value().api().session_count(),
+                )
+                .await;
+                if !matches!(response, Ok(Ok(_))) {
+                    unreachable_federations.insert(federation_id);
+                }
+            }
+        }
+
+        let outcomes = total_payment_outcomes(&self.gateway_db).await;
+        AlertObservation {
+            channels,
+            unreachable_federations,
+            payments: outcomes.payments,
+            failed_payments: outcomes.failed_payments,
+        }
+    }
+
+    /// Subscribes to the balance, channel and payment events of the gateway
+    pub fn subscribe_events(&self) -> broadcast::Receiver<GatewayEvent> {
+        self.events.subscribe()
+    }
+
+    /// Returns the current balances and channels as events. Those the
+    /// lightning node can't provide are left out.
+    pub async fn state_events(&self) -> Vec<GatewayEvent> {
+        let Ok(lightning_context) = self.get_lightning_context().await else {
+            return vec![];
+        };
+
+        let mut events = vec![];
+        if let Ok(lightning) = lightning_context.lnrpc.get_balances().await {
+            let mut ecash = BTreeMap::new();
+            for (federation_id, client) in self.clients.read().await.clone() {
+                ecash.insert(federation_id, client.value().get_balance().await);
+            }
+            events.push(GatewayEvent::Balances { lightning, ecash });
+        }
+
+        if let Ok(channels) = lightning_context.lnrpc.list_active_channels().await {
+            events.push(GatewayEvent::Channels { channels });
+        }
+
+        events
+    }
+
+    /// Spawns a task that settles the invoices the swap provider paid, tracks
+    /// the status of the pending swaps and refunds or claims their lockups
+    /// every `SWAP_UPDATE_INTERVAL`.
+    fn start_swap_updates(&self, task_group: &TaskGroup) {
+        let Some(swap_client) = self.swap_client.clone() else {
+            return;
+        };
+
+        let bitcoin_rpc = match self
+            .bitcoin_rpc
+            .as_ref()
+            .map(|config| create_bitcoind(config, task_group.make_handle()))
+            .transpose()
+        {
+            Ok(bitcoin_rpc) => bitcoin_rpc,
+            Err(e) => {
+                warn!(
+                    "Failed to create the chain source, swaps are not refunded and reverse swaps \
+                     are not claimed: {e}"
+                );
+                None
+            }
+        };
+
+        let gateway = self.clone();
+        task_group.spawn_cancellable("update swaps", async move {
+            loop {
+                sleep(SWAP_UPDATE_INTERVAL).await;
+
+                let Ok(lightning_context) = gateway.get_lightning_context().await else {
+                    continue;
+                };
+
+                swap::update_swaps(
+                    &swap_client,
+                    bitcoin_rpc.as_ref(),
+                    &lightning_context,
+                    &gateway.gateway_db,
+                )
+                .await;
+                if let Some(bitcoin_rpc) = &bitcoin_rpc {
+                    swap::update_reverse_swaps(
+                        &swap_client,
+                        bitcoin_rpc,
+                        &lightning_context,
+                        &gateway.gateway_db,
+                    )
+                    .await;
+                }
+            }
+        });
     }
 
-    /// Registers the gateway with each specified federation.
-    async fn register_federations(
-        &self,
-        gateway_config: &GatewayConfiguration,
-        federations: &[(FederationId, FederationConfig)],
-    ) -> Result<()> {
-        if let Ok(lightning_context) = self.get_lightning_context().await {
-            let route_hints = Self::fetch_lightning_route_hints(
-                lightning_context.lnrpc.clone(),
-                gateway_config.num_route_hints,
-            )
-            .await;
-            if route_hints.is_empty() {
-                warn!("Gateway did not retrieve any route hints, may reduce receive success rate.");
-            }
+    /// Spawns a task that checks the unpaid invoices of lightning addresses
+    /// every `LIGHTNING_ADDRESS_INTERVAL`, settles accepted payments, credits
+    /// them to the users of the addresses and issues their balances as ecash.
+    fn start_lightning_address_settlement(&self, task_group: &TaskGroup) {
+        if self.lightning_address_domain.is_none() {
+            return;
+        }
 
-            for (federation_id, federation_config) in federations {
-                if let Some(client) = self.clients.read().await.get(federation_id) {
-                    if let Err(e) = async {
-                        client
-                            .value()
-                            .get_first_module::<GatewayClientModule>()
-                            .register_with_federation(
-                                route_hints.clone(),
-                                GW_ANNOUNCEMENT_TTL,
-                                federation_config.fees,
-                                lightning_context.clone(),
-                            )
+        let gateway = self.clone();
+        task_group.spawn_cancellable("settle lightning address payments", async move {
+            loop {
+                sleep(LIGHTNING_ADDRESS_INTERVAL).await;
+
+                let Ok(lightning_context) = gateway.get_lightning_context().await else {
+                    continue;
+                };
+
+                lnurl::settle_invoices(&lightning_context, &gateway.gateway_db).await;
+
+                for user in gateway.handle_list_lightning_addresses_msg().await {
+                    let Ok(client) = gateway.select_client(user.federation_id).await else {
+                        continue;
+                    };
+
+                    if let Err(e) =
+                        lnurl::issue_ecash(client.value(), &gateway.gateway_db, &user.username)
                             .await
-                    }
-                    .instrument(client.span())
-                    .await
                     {
-                        Err(GatewayError::FederationError(FederationError::general(
-                            REGISTER_GATEWAY_ENDPOINT,
-                            serde_json::Value::Null,
-                            anyhow::anyhow!("Error registering federation {federation_id}: {e:?}"),
-                        )))?;
+                        warn!(
+                            "Failed to issue ecash to lightning address {}: {e}",
+                            user.username
+                        );
                     }
                 }
             }
-        }
-        Ok(())
+        });
     }
 
-    /// This function will return a `GatewayConfiguration` one of two
-    /// ways. To avoid conflicting configs, the below order is the
-    /// order in which the gateway will respect configurations:
-    /// - `GatewayConfiguration` is read from the database.
-    /// - All cli or environment variables are set such that we can create a
-    ///   `GatewayConfiguration`
-    async fn get_gateway_configuration(
-        gateway_db: Database,
-        gateway_parameters: &GatewayParameters,
-    ) -> Option<GatewayConfiguration> {
-        let mut dbtx = gateway_db.begin_transaction().await;
+    /// Spawns a task that sends the on-chain balance of the lightning node
+    /// above the threshold of the sweep policy to its address, once per
+    /// interval of the policy.
+    fn start_sweeping(&self, task_group: &TaskGroup) {
+        let gateway = self.clone();
+        task_group.spawn_cancellable("sweep on-chain funds", async move {
+            let mut last_sweep: Option<Instant> = None;
 
-        // Always use the gateway configuration from the database if it exists.
-        if let Some(gateway_config) = dbtx.get_value(&GatewayConfigurationKey).await {
-            return Some(gateway_config);
-        }
+            loop {
+                sleep(SWEEP_CHECK_INTERVAL).await;
 
-        // If the password is not provided, return None
-        let password = gateway_parameters.password.as_ref()?;
+                let Some(policy) = gateway.handle_sweep_policy_msg().await else {
+                    continue;
+                };
 
-        // If the DB does not have the gateway configuration, we can construct one from
-        // the provided password (required) and the defaults.
-        // Use gateway parameters provided by the environment or CLI
-        let num_route_hints = gateway_parameters.num_route_hints;
-        let routing_fees = gateway_parameters
-            .fees
-            .clone()
-            .unwrap_or(GatewayFee(DEFAULT_FEES));
-        let network = gateway_parameters.network.unwrap_or(DEFAULT_NETWORK);
-        let password_salt: [u8; 16] = rand::thread_rng().gen();
-        let hashed_password = hash_password(password, password_salt);
-        let gateway_config = GatewayConfiguration {
-            hashed_password,
-            network,
-            num_route_hints,
-            routing_fees: routing_fees.0,
-            password_salt,
-        };
+                if last_sweep.is_some_and(|last_sweep| last_sweep.elapsed() < policy.interval()) {
+                    continue;
+                }
 
-        Some(gateway_config)
-    }
+                let Ok(lightning_context) = gateway.get_lightning_context().await else {
+                    continue;
+                };
 
-    /// Removes a federation client from the Gateway's in memory structures that
-    /// keep track of available clients. Does not remove the persisted
-    /// client configuration in the database.
-    async fn remove_client(
-        &self,
-        federation_id: FederationId,
-        // Note: MUST be protected by a lock, to keep
-        // `clients` and opened databases in sync
-        _lock: &MutexGuard<'_, ClientsJoinLock>,
-    ) -> Result<()> {
-        let client = self
-            .clients
-            .write()
-            .await
-            .remove(&federation_id)
-            .ok_or(GatewayError::InvalidMetadata(format!(
-                "No federation with id {federation_id}"
-            )))?
-            .into_value();
+                let address = match policy
+                    .address
+                    .clone()
+                    .require_network(lightning_context.lightning_network)
+                {
+                    Ok(address) => address,
+                    Err(e) => {
+                        warn!("Invalid sweep address: {e}");
+                        continue;
+                    }
+                };
 
-        if let Some(client) = Arc::into_inner(client) {
-            client.shutdown().await;
-        } else {
-            error!("client is not unique, failed to remove client");
-        }
+                let balances = match lightning_context.lnrpc.get_balances().await {
+                    Ok(balances) => balances,
+                    Err(e) => {
+                        warn!("Failed to get the on-chain balance to sweep: {e}");
+                        continue;
+                    }
+                };
 
-        // Remove previously assigned scid from `scid_to_federation` map
-        self.scid_to_federation
-            .write()
-            .await
-            .retain(|_, fid| *fid != federation_id);
-        Ok(())
+                last_sweep = Some(Instant::now());
+
+                let Some(amount) = policy.sweep_amount(balances.onchain_balance_sats) else {
+                    continue;
+                };
+
+                match lightning_context
+                    .lnrpc
+                    .send_onchain(address.clone(), amount, policy.sat_per_vbyte)
+                    .await
+                {
+                    Ok(txid) => info!("Swept {amount} to {address} in {txid}"),
+                    Err(e) => warn!("Failed to sweep {amount} to {address}: {e}"),
+                }
+            }
+        });
     }
 
-    /// Retrieves a `ClientHandleArc` from the Gateway's in memory structures
-    /// that keep track of available clients, given a `federation_id`.
-    pub async fn select_client(
+    /// Withdraws ecash of a federation to the on-chain wallet of the lightning
+    /// node
+    async fn withdraw_to_lightning_node(
         &self,
         federation_id: FederationId,
-    ) -> Result<Spanned<fedimint_client::ClientHandleArc>> {
-        self.clients
-            .read()
-            .await
-            .get(&federation_id)
-            .cloned()
-            .ok_or(GatewayError::InvalidMetadata(format!(
-                "No federation with id {federation_id}"
-            )))
-    }
-
-    /// Reads the connected federation client configs from the Gateway's
-    /// database and reconstructs the clients necessary for interacting with
-    /// connection federations.
-    async fn load_clients(&self) {
-        let dbtx = self.gateway_db.begin_transaction().await;
-        let configs = self.client_builder.load_configs(dbtx.into_nc()).await;
+        amount: bitcoin::Amount,
+    ) -> Result<Txid> {
+        let address = self.handle_get_funding_address_msg().await?;
 
-        let _join_federation = self.client_joining_lock.lock().await;
+        self.handle_withdraw_msg(WithdrawPayload {
+            federation_id,
+            amount: BitcoinAmountOrAll::Amount(amount),
+            address: Address::new(address.network, address.payload),
+        })
+        .await
+    }
 
-        for config in configs.clone() {
-            let federation_id = config.invite_code.federation_id();
-            let scid = config.mint_channel_id;
+    /// Spawns a task that adjusts the routing fees of all federations every
+    /// `FEE_ADJUSTMENT_INTERVAL` while a dynamic fee policy is set.
+    fn start_dynamic_fees(&self, task_group: &TaskGroup) {
+        let gateway = self.clone();
+        task_group.spawn_cancellable("adjust routing fees", async move {
+            loop {
+                sleep(FEE_ADJUSTMENT_INTERVAL).await;
 
-            if let Ok(client) = Box::pin(Spanned::try_new(
-                info_span!("client", federation_id  = %federation_id.clone()),
-                self.client_builder.build(config, Arc::new(self.clone())),
-            ))
-            .await
-            {
-                // Registering each client happens in the background, since we're loading
-                // the clients for the first time, just add them to
-                // the in-memory maps
-                self.clients.write().await.insert(federation_id, client);
-                self.scid_to_federation
-                    .write()
+                let policy = gateway
+                    .gateway_db
+                    .begin_transaction_nc()
                     .await
-                    .insert(scid, federation_id);
-            } else {
-                warn!("Failed to load client for federation: {federation_id}");
+                    .get_value(&DynamicFeePolicyKey)
+                    .await;
+                let Some(policy) = policy else {
+                    continue;
+                };
+
+                let result = match gateway.fee_adjustments(&policy).await {
+                    Ok(adjustments) => gateway.apply_fee_adjustments(&adjustments).await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = result {
+                    warn!("Failed to adjust routing fees: {e}");
+                }
             }
-        }
+        });
+    }
 
-        if let Some(max_mint_channel_id) = configs.iter().map(|cfg| cfg.mint_channel_id).max() {
-            let mut next_scid = self.next_scid.lock().await;
-            *next_scid = max_mint_channel_id + 1;
+    /// Spawns a task that probes the configured destinations every
+    /// `probe_interval` while the Gateway is connected to its Lightning node.
+    fn start_probing(&self, task_group: &TaskGroup) {
+        if self.probe_targets.is_empty() {
+            return;
         }
+
+        let gateway = self.clone();
+        task_group.spawn_cancellable("probe liquidity", async move {
+            loop {
+                sleep(gateway.probe_interval).await;
+
+                let Ok(context) = gateway.get_lightning_context().await else {
+                    debug!("Not probing, the gateway is not connected to its lightning node");
+                    continue;
+                };
+
+                probing::probe_targets(
+                    context.lnrpc.as_ref(),
+                    &gateway.gateway_db,
+                    &gateway.probe_targets,
+                )
+                .await;
+            }
+        });
+    }
+
+    /// Returns the recorded probes of the configured destinations.
+    pub async fn handle_liquidity_health_msg(&self) -> Vec<LiquidityHealth> {
+        probing::liquidity_health(&self.gateway_db, &self.probe_targets).await
     }
 
     /// Legacy mechanism for registering the Gateway with connected federations.
@@ -1604,6 +5015,15 @@ impl Gateway {
             .get_value(&federation_key)
             .await
             .map(|config| config.fees.into());
+        let incoming_limits = dbtx
+            .get_value(&IncomingLimitsKey { federation_id })
+            .await
+            .unwrap_or_default();
+        let liquidity_cap = dbtx.get_value(&LiquidityCapKey { federation_id }).await;
+        let protocols = dbtx
+            .get_value(&LightningProtocolsKey { federation_id })
+            .await
+            .unwrap_or_default();
 
         FederationInfo {
             federation_id,
@@ -1611,9 +5031,28 @@ impl Gateway {
             config,
             channel_id,
             routing_fees,
+            incoming_limits,
+            liquidity_cap,
+            protocols,
+            balance_fiat: self.fiat_value(balance_msat).await,
+            suspended_until: self
+                .circuit_breaker
+                .suspended_for(federation_id)
+                .map(|remaining| fedimint_core::time::now() + remaining),
         }
     }
 
+    /// Returns the value of `amount` in the configured fiat currencies, empty
+    /// until the prices are fetched
+    async fn fiat_value(&self, amount: Amount) -> BTreeMap<String, f64> {
+        self.fiat_prices
+            .read()
+            .await
+            .as_ref()
+            .map(|prices| prices.convert(amount))
+            .unwrap_or_default()
+    }
+
     /// Verifies that the supplied `network` matches the Bitcoin network in the
     /// connected client's configuration.
     fn check_federation_network(info: &FederationInfo, network: Network) -> Result<()> {
@@ -1711,8 +5150,16 @@ impl Gateway {
     }
 
     /// Returns payment information that LNv2 clients can use to instruct this
-    /// Gateway to pay an invoice or receive a payment.
+    /// Gateway to pay an invoice or receive a payment. Returns `None` if LNv2
+    /// is disabled for the federation.
     pub async fn routing_info_v2(&self, federation_id: &FederationId) -> Option<RoutingInfo> {
+        if self.is_draining()
+            || self.circuit_breaker.suspended_for(*federation_id).is_some()
+            || !self.load_lightning_protocols(*federation_id).await.lnv2
+        {
+            return None;
+        }
+
         Some(RoutingInfo {
             public_key: self.public_key_v2(federation_id).await?,
             send_fee_default: PaymentFee::one_percent(),
@@ -1720,6 +5167,8 @@ impl Gateway {
             receive_fee: PaymentFee::half_of_one_percent(),
             expiration_delta_default: 500,
             expiration_delta_minimum: EXPIRATION_DELTA_MINIMUM_V2,
+            send_paused: self.send_pause.is_paused()
+                || self.liquidity_cap_reached(federation_id).await,
         })
     }
 
@@ -1729,6 +5178,30 @@ impl Gateway {
         &self,
         payload: SendPaymentPayload,
     ) -> anyhow::Result<std::result::Result<[u8; 32], Signature>> {
+        if self.is_draining() {
+            bail!("The gateway is draining");
+        }
+
+        if !self
+            .load_lightning_protocols(payload.federation_id)
+            .await
+            .lnv2
+        {
+            bail!("LNv2 is disabled for the federation");
+        }
+
+        if self
+            .circuit_breaker
+            .suspended_for(payload.federation_id)
+            .is_some()
+        {
+            bail!("The federation is suspended after repeated payment failures");
+        }
+
+        if self.send_pause.is_paused() || self.liquidity_cap_reached(&payload.federation_id).await {
+            return Err(GatewayError::TemporarilyUnavailable.into());
+        }
+
         let clients = self.clients.read().await;
 
         let client = clients
@@ -1736,10 +5209,40 @@ impl Gateway {
             .ok_or(anyhow!("Federation client not available"))?
             .value();
 
-        client
+        let federation_id = payload.federation_id;
+        let (payment_hash, amount, fee) = match &payload.invoice {
+            LightningInvoice::Bolt11(invoice, amount) => (
+                *invoice.payment_hash(),
+                *amount,
+                payload.contract.amount.saturating_sub(*amount),
+            ),
+        };
+        let payment = InFlightPayment::start(
+            self.gateway_db.clone(),
+            federation_id,
+            PaymentDirection::Outgoing,
+            payment_hash,
+            amount,
+            self.events.clone(),
+        );
+
+        let result = client
             .get_first_module::<GatewayClientModuleV2>()
             .send_payment(payload)
             .await
+            .inspect_err(|_| self.circuit_breaker.record(federation_id, false))?;
+
+        // A failed lightning payment is not held against the federation
+        match result {
+            Ok(..) => {
+                payment.succeeded(fee).await;
+                self.circuit_breaker.record(federation_id, true);
+                self.outgoing_volume.record(federation_id, amount);
+            }
+            Err(..) => payment.failed().await,
+        }
+
+        Ok(result)
     }
 
     /// For the LNv2 protocol, this will create an invoice by fetching it from
@@ -1754,6 +5257,14 @@ impl Gateway {
             bail!("The contract is invalid")
         }
 
+        if !self
+            .load_lightning_protocols(payload.federation_id)
+            .await
+            .lnv2
+        {
+            bail!("LNv2 is disabled for the federation");
+        }
+
         let payment_info = self
             .routing_info_v2(&payload.federation_id)
             .await
@@ -1771,6 +5282,14 @@ impl Gateway {
             bail!("The contract amount does not pay the correct amount of fees");
         }
 
+        let limits = self.load_incoming_limits(payload.federation_id).await;
+        if limits
+            .max_payment_msat
+            .is_some_and(|max_msat| payload.invoice_amount.msats > max_msat)
+        {
+            bail!("The invoice amount exceeds the gateway's limit for incoming payments");
+        }
+
         if payload.contract.commitment.expiration <= duration_since_epoch().as_secs() {
             bail!("The contract has already expired");
         }
@@ -1913,6 +5432,16 @@ pub enum GatewayError {
     FederationAlreadyConnected,
     #[error("Error parsing response: {}", OptStacktrace(.0))]
     LightningResponseParseError(anyhow::Error),
+    #[error("{0} is disabled for the federation")]
+    ProtocolDisabled(&'static str),
+    #[error("The gateway is draining")]
+    Draining,
+    #[error("The federation is suspended after repeated payment failures")]
+    FederationSuspended,
+    #[error("The gateway temporarily doesn't send payments")]
+    TemporarilyUnavailable,
+    #[error("The spending password is missing or invalid")]
+    InvalidSpendingPassword,
 }
 
 impl IntoResponse for GatewayError {
@@ -1930,6 +5459,14 @@ impl IntoResponse for GatewayError {
                 "The gateway is disconnected from the Lightning Node".to_string(),
                 StatusCode::NOT_FOUND,
             ),
+            GatewayError::TemporarilyUnavailable => (
+                "The gateway temporarily doesn't send payments".to_string(),
+                StatusCode::SERVICE_UNAVAILABLE,
+            ),
+            GatewayError::InvalidSpendingPassword => (
+                "The spending password is missing or invalid".to_string(),
+                StatusCode::UNAUTHORIZED,
+            ),
             _ => (
                 "An internal gateway error occurred".to_string(),
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -13,14 +13,16 @@ use tonic::transport::{Channel, Endpoint};
 use tonic::Request;
 use tracing::info;
 
-use super::{ChannelInfo, ILnRpcClient, LightningRpcError};
+use super::{
+    ChannelCommitmentType, ChannelInfo, ILnRpcClient, LightningRpcError, OpenChannelOptions,
+};
 use crate::gateway_lnrpc::gateway_lightning_client::GatewayLightningClient;
 use crate::gateway_lnrpc::{
-    self, CloseChannelsWithPeerRequest, CloseChannelsWithPeerResponse, CreateInvoiceRequest,
-    CreateInvoiceResponse, EmptyRequest, EmptyResponse, GetFundingAddressResponse,
-    GetNodeInfoResponse, GetRouteHintsRequest, GetRouteHintsResponse, InterceptHtlcRequest,
-    InterceptHtlcResponse, OpenChannelRequest, PayInvoiceRequest, PayInvoiceResponse,
-    PayPrunedInvoiceRequest,
+    self, CloseChannelsWithPeerRequest, CloseChannelsWithPeerResponse, CommitmentType,
+    CreateInvoiceRequest, CreateInvoiceResponse, EmptyRequest, EmptyResponse,
+    GetFundingAddressResponse, GetNodeInfoResponse, GetRouteHintsRequest, GetRouteHintsResponse,
+    InterceptHtlcRequest, InterceptHtlcResponse, OpenChannelRequest, PayInvoiceRequest,
+    PayInvoiceResponse, PayPrunedInvoiceRequest,
 };
 use crate::lightning::MAX_LIGHTNING_RETRIES;
 pub type HtlcResult = std::result::Result<InterceptHtlcRequest, tonic::Status>;
@@ -206,15 +208,25 @@ impl ILnRpcClient for NetworkLnRpcClient {
         host: String,
         channel_size_sats: u64,
         push_amount_sats: u64,
+        options: OpenChannelOptions,
     ) -> Result<EmptyResponse, LightningRpcError> {
         let mut client = self.connect().await?;
 
+        let commitment_type = match options.commitment_type {
+            None => CommitmentType::Default,
+            Some(ChannelCommitmentType::StaticRemoteKey) => CommitmentType::StaticRemoteKey,
+            Some(ChannelCommitmentType::Anchors) => CommitmentType::Anchors,
+        };
+
         let res = client
             .open_channel(OpenChannelRequest {
                 pubkey: pubkey.to_string(),
                 host,
                 channel_size_sats,
                 push_amount_sats,
+                private: options.private,
+                zero_conf: options.zero_conf,
+                commitment_type: commitment_type.into(),
             })
             .await
             .map_err(|status| LightningRpcError::FailedToOpenChannel {
@@ -1,29 +1,38 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::ops::{Deref, DerefMut};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::ensure;
 use async_trait::async_trait;
-use bitcoin_hashes::Hash;
-use fedimint_core::task::{sleep, TaskGroup};
+use bitcoin_hashes::{sha256, Hash};
+use fedimint_core::task::{sleep, timeout, TaskGroup};
 use fedimint_core::{secp256k1, Amount};
 use fedimint_ln_common::PrunedInvoice;
 use hex::ToHex;
+use rand::Rng;
 use secp256k1::PublicKey;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore};
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::Status;
-use tonic_lnd::invoicesrpc::AddHoldInvoiceRequest;
+use tonic_lnd::invoicesrpc::{AddHoldInvoiceRequest, CancelInvoiceMsg, SettleInvoiceMsg};
 use tonic_lnd::lnrpc::failure::FailureCode;
+use tonic_lnd::lnrpc::invoice::InvoiceState as LndInvoiceState;
 use tonic_lnd::lnrpc::payment::PaymentStatus;
 use tonic_lnd::lnrpc::{
-    ChanInfoRequest, ChannelPoint, CloseChannelRequest, ConnectPeerRequest, GetInfoRequest,
-    LightningAddress, ListChannelsRequest, OpenChannelRequest,
+    ChanInfoRequest, ChannelBalanceRequest, ChannelPoint, CloseChannelRequest, CommitmentType,
+    ConnectPeerRequest, DisconnectPeerRequest, GetInfoRequest, LightningAddress,
+    ListChannelsRequest, ListPeersRequest, OpenChannelRequest, PaymentFailureReason, PaymentHash,
+    PolicyUpdateRequest, QueryRoutesRequest, SendCoinsRequest, SendCustomMessageRequest,
+    SubscribeCustomMessagesRequest, WalletBalanceRequest,
 };
 use tonic_lnd::routerrpc::{
-    CircuitKey, ForwardHtlcInterceptResponse, ResolveHoldForwardAction, SendPaymentRequest,
-    TrackPaymentRequest,
+    CircuitKey, ForwardHtlcInterceptResponse, PairData, PairHistory, QueryMissionControlRequest,
+    ResetMissionControlRequest, ResolveHoldForwardAction, SendPaymentRequest, TrackPaymentRequest,
+    XImportMissionControlRequest,
 };
 use tonic_lnd::tonic::Code;
 use tonic_lnd::walletrpc::AddrRequest;
@@ -31,7 +40,11 @@ use tonic_lnd::{connect, Client as LndClient};
 use tracing::{debug, error, info, trace, warn};
 
 use super::cln::RouteHtlcStream;
-use super::{ChannelInfo, ILnRpcClient, LightningRpcError, MAX_LIGHTNING_RETRIES};
+use super::{
+    ChannelCommitmentType, ChannelInfo, ChannelPolicy, ILnRpcClient, InvoiceState,
+    LightningBalances, LightningRpcError, MissionControlPair, OpenChannelOptions, PeerInfo,
+    ProbeResult, RouteEstimate, MAX_LIGHTNING_RETRIES,
+};
 use crate::gateway_lnrpc::create_invoice_request::Description;
 use crate::gateway_lnrpc::get_route_hints_response::{RouteHint, RouteHintHop};
 use crate::gateway_lnrpc::intercept_htlc_response::{Action, Cancel, Forward, Settle};
@@ -40,16 +53,107 @@ use crate::gateway_lnrpc::{
     GetFundingAddressResponse, GetNodeInfoResponse, GetRouteHintsResponse, InterceptHtlcRequest,
     InterceptHtlcResponse, PayInvoiceRequest, PayInvoiceResponse,
 };
+use crate::metrics::LND_QUEUED_REQUESTS;
 
 type HtlcSubscriptionSender = mpsc::Sender<Result<InterceptHtlcRequest, Status>>;
 
 const LND_PAYMENT_TIMEOUT_SECONDS: i32 = 180;
 
+/// Custom record type LND uses to carry the preimage of a keysend payment
+const LND_KEYSEND_RECORD_TYPE: u64 = 5_482_373_484;
+
+/// Custom records with a type below this value are reserved
+const LND_CUSTOM_RECORD_TYPE_MIN: u64 = 65536;
+
+/// Peer message type carrying LSPS JSON-RPC messages (bLIP-50)
+const LSPS_MESSAGE_TYPE: u32 = 37913;
+
+const LSPS_RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default number of connections the gateway keeps open to LND
+pub const DEFAULT_LND_CONNECTION_POOL_SIZE: usize = 4;
+
+/// Default number of requests per gRPC method the gateway sends to LND at the
+/// same time
+pub const DEFAULT_LND_MAX_CONCURRENT_REQUESTS: usize = 16;
+
+/// Connections to LND that are opened on first use and reused afterwards.
+/// Requests are spread over the connections round-robin and every gRPC method
+/// has its own limiter, so a burst of payments can't starve the other methods
+/// or overload LND.
+struct LndConnectionPool {
+    connections: Vec<tokio::sync::Mutex<Option<LndClient>>>,
+    next_connection: AtomicUsize,
+    max_concurrent_requests: usize,
+    limiters: std::sync::Mutex<BTreeMap<&'static str, Arc<Semaphore>>>,
+}
+
+impl LndConnectionPool {
+    fn new(size: usize, max_concurrent_requests: usize) -> Self {
+        LndConnectionPool {
+            connections: (0..size.max(1))
+                .map(|_| tokio::sync::Mutex::new(None))
+                .collect(),
+            next_connection: AtomicUsize::new(0),
+            max_concurrent_requests: max_concurrent_requests.max(1),
+            limiters: std::sync::Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn limiter(&self, method: &'static str) -> Arc<Semaphore> {
+        self.limiters
+            .lock()
+            .expect("poisoned")
+            .entry(method)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.max_concurrent_requests)))
+            .clone()
+    }
+}
+
+/// A pooled connection to LND. Holds a slot of its method's limiter until it
+/// is dropped.
+struct LndConnection {
+    client: LndClient,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Deref for LndConnection {
+    type Target = LndClient;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+
+impl DerefMut for LndConnection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.client
+    }
+}
+
+/// Counts a request as queued in the metrics while it waits for a slot of its
+/// method's limiter, including when the waiting request is cancelled
+struct QueuedLndRequest(&'static str);
+
+impl QueuedLndRequest {
+    fn new(method: &'static str) -> Self {
+        LND_QUEUED_REQUESTS.with_label_values(&[method]).inc();
+        QueuedLndRequest(method)
+    }
+}
+
+impl Drop for QueuedLndRequest {
+    fn drop(&mut self) {
+        LND_QUEUED_REQUESTS.with_label_values(&[self.0]).dec();
+    }
+}
+
 pub struct GatewayLndClient {
     /// LND client
     address: String,
     tls_cert: String,
     macaroon: String,
+    pool: Arc<LndConnectionPool>,
     lnd_sender: Option<mpsc::Sender<ForwardHtlcInterceptResponse>>,
 }
 
@@ -58,7 +162,8 @@ impl GatewayLndClient {
         address: String,
         tls_cert: String,
         macaroon: String,
-        lnd_sender: Option<mpsc::Sender<ForwardHtlcInterceptResponse>>,
+        connection_pool_size: usize,
+        max_concurrent_requests: usize,
     ) -> Self {
         info!(
             "Gateway configured to connect to LND LnRpcClient at \n address: {},\n tls cert path: {},\n macaroon path: {} ",
@@ -68,11 +173,45 @@ impl GatewayLndClient {
             address,
             tls_cert,
             macaroon,
-            lnd_sender,
+            pool: Arc::new(LndConnectionPool::new(
+                connection_pool_size,
+                max_concurrent_requests,
+            )),
+            lnd_sender: None,
         }
     }
 
-    async fn connect(&self) -> Result<LndClient, LightningRpcError> {
+    /// Waits for a free slot of `method`'s limiter and returns the next
+    /// connection of the pool, which is opened if it isn't yet
+    async fn connect(&self, method: &'static str) -> Result<LndConnection, LightningRpcError> {
+        let permit = {
+            let _queued = QueuedLndRequest::new(method);
+            self.pool
+                .limiter(method)
+                .acquire_owned()
+                .await
+                .expect("Limiter is never closed")
+        };
+
+        let index =
+            self.pool.next_connection.fetch_add(1, Ordering::Relaxed) % self.pool.connections.len();
+        let mut connection = self.pool.connections[index].lock().await;
+        let client = match connection.as_ref() {
+            Some(client) => client.clone(),
+            None => {
+                let client = self.open_connection().await?;
+                *connection = Some(client.clone());
+                client
+            }
+        };
+
+        Ok(LndConnection {
+            client,
+            _permit: permit,
+        })
+    }
+
+    async fn open_connection(&self) -> Result<LndClient, LightningRpcError> {
         let mut retries = 0;
         let client = loop {
             if retries >= MAX_LIGHTNING_RETRIES {
@@ -106,7 +245,7 @@ impl GatewayLndClient {
         lnd_rx: mpsc::Receiver<ForwardHtlcInterceptResponse>,
         gateway_sender: HtlcSubscriptionSender,
     ) -> Result<(), LightningRpcError> {
-        let mut client = self.connect().await?;
+        let mut client = self.connect("spawn_interceptor").await?;
 
         // Verify that LND is reachable via RPC before attempting to spawn a new thread
         // that will intercept HTLCs.
@@ -280,6 +419,157 @@ impl GatewayLndClient {
             }
         }
     }
+
+    /// Pays `invoice`, unless LND already paid it. If `retry` is set, a
+    /// previously failed payment of the invoice is attempted again instead of
+    /// returning its failure.
+    async fn send_private_payment(
+        &self,
+        invoice: PrunedInvoice,
+        max_delay: u64,
+        max_fee: Amount,
+        retry: bool,
+        time_pref: f64,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        info!("LND Paying invoice {invoice:?}");
+        let mut client = self.connect("pay_private").await?;
+
+        debug!("LND got client to pay invoice {invoice:?}, will check if payment already exists");
+
+        // If the payment exists, that means we've already tried to pay the invoice
+        let previous_payment = match self
+            .lookup_payment(invoice.payment_hash.to_byte_array().to_vec(), &mut client)
+            .await
+        {
+            Err(LightningRpcError::FailedPayment { .. }) if retry => None,
+            result => result?,
+        };
+
+        let preimage: Vec<u8> = if let Some(preimage) = previous_payment {
+            info!("LND payment already exists for invoice {invoice:?}");
+            hex::FromHex::from_hex(preimage.as_str()).map_err(|error| {
+                LightningRpcError::FailedPayment {
+                    failure_reason: format!("Failed to convert preimage {error:?}"),
+                }
+            })?
+        } else {
+            // LND API allows fee limits in the `i64` range, but we use `u64` for
+            // max_fee_msat. This means we can only set an enforceable fee limit
+            // between 0 and i64::MAX
+            let fee_limit_msat: i64 =
+                max_fee
+                    .msats
+                    .try_into()
+                    .map_err(|error| LightningRpcError::FailedPayment {
+                        failure_reason: format!(
+                            "max_fee_msat exceeds valid LND fee limit ranges {error:?}"
+                        ),
+                    })?;
+
+            let amt_msat = invoice.amount.msats.try_into().map_err(|error| {
+                LightningRpcError::FailedPayment {
+                    failure_reason: format!("amount exceeds valid LND amount ranges {error:?}"),
+                }
+            })?;
+            let final_cltv_delta = invoice.min_final_cltv_delta.try_into().map_err(|error| {
+                LightningRpcError::FailedPayment {
+                    failure_reason: format!("final cltv delta exceeds valid LND range {error:?}"),
+                }
+            })?;
+            let cltv_limit =
+                max_delay
+                    .try_into()
+                    .map_err(|error| LightningRpcError::FailedPayment {
+                        failure_reason: format!("max delay exceeds valid LND range {error:?}"),
+                    })?;
+
+            let dest_features = wire_features_to_lnd_feature_vec(&invoice.destination_features)
+                .map_err(|e| LightningRpcError::FailedPayment {
+                    failure_reason: e.to_string(),
+                })?;
+
+            debug!("LND payment does not exist for invoice {invoice:?}, will attempt to pay");
+            let payments = client
+                .router()
+                .send_payment_v2(SendPaymentRequest {
+                    amt_msat,
+                    dest: invoice.destination.serialize().to_vec(),
+                    dest_features,
+                    payment_hash: invoice.payment_hash.to_byte_array().to_vec(),
+                    payment_addr: invoice.payment_secret.to_vec(),
+                    route_hints: route_hints_to_lnd(&invoice.route_hints),
+                    final_cltv_delta,
+                    cltv_limit,
+                    no_inflight_updates: false,
+                    timeout_seconds: LND_PAYMENT_TIMEOUT_SECONDS,
+                    fee_limit_msat,
+                    time_pref,
+                    ..Default::default()
+                })
+                .await
+                .map_err(|status| {
+                    info!("LND payment request failed for invoice {invoice:?} with {status:?}");
+                    LightningRpcError::FailedPayment {
+                        failure_reason: format!("Failed to make outgoing payment {status:?}"),
+                    }
+                })?;
+
+            debug!(
+                "LND payment request sent for invoice {invoice:?}, waiting for payment status..."
+            );
+            let mut messages = payments.into_inner();
+            loop {
+                match messages
+                    .message()
+                    .await
+                    .map_err(|error| LightningRpcError::FailedPayment {
+                        failure_reason: format!("Failed to get payment status {error:?}"),
+                    }) {
+                    Ok(Some(payment)) if payment.status() == PaymentStatus::Succeeded => {
+                        info!("LND payment succeeded for invoice {invoice:?}");
+                        break hex::FromHex::from_hex(payment.payment_preimage.as_str()).map_err(
+                            |error| LightningRpcError::FailedPayment {
+                                failure_reason: format!("Failed to convert preimage {error:?}"),
+                            },
+                        )?;
+                    }
+                    Ok(Some(payment)) if payment.status() == PaymentStatus::InFlight => {
+                        debug!("LND payment is inflight");
+                        continue;
+                    }
+                    Ok(Some(payment)) => {
+                        info!("LND payment failed for invoice {invoice:?} with {payment:?}");
+                        let failure_reason = payment.failure_reason();
+                        return Err(match failure_reason {
+                            PaymentFailureReason::FailureReasonNoRoute
+                            | PaymentFailureReason::FailureReasonInsufficientBalance => {
+                                LightningRpcError::NoRoute {
+                                    failure_reason: format!("{failure_reason:?}"),
+                                }
+                            }
+                            _ => LightningRpcError::FailedPayment {
+                                failure_reason: format!("{failure_reason:?}"),
+                            },
+                        });
+                    }
+                    Ok(None) => {
+                        info!("LND payment failed for invoice {invoice:?} with no payment status");
+                        return Err(LightningRpcError::FailedPayment {
+                            failure_reason: format!(
+                                "Failed to get payment status for payment hash {:?}",
+                                invoice.payment_hash
+                            ),
+                        });
+                    }
+                    Err(e) => {
+                        info!("LND payment failed for invoice {invoice:?} with {e:?}");
+                        return Err(e);
+                    }
+                }
+            }
+        };
+        Ok(PayInvoiceResponse { preimage })
+    }
 }
 
 impl fmt::Debug for GatewayLndClient {
@@ -291,7 +581,7 @@ impl fmt::Debug for GatewayLndClient {
 #[async_trait]
 impl ILnRpcClient for GatewayLndClient {
     async fn info(&self) -> Result<GetNodeInfoResponse, LightningRpcError> {
-        let mut client = self.connect().await?;
+        let mut client = self.connect("info").await?;
         let info = client
             .lightning()
             .get_info(GetInfoRequest {})
@@ -329,7 +619,7 @@ impl ILnRpcClient for GatewayLndClient {
         &self,
         num_route_hints: usize,
     ) -> Result<GetRouteHintsResponse, LightningRpcError> {
-        let mut client = self.connect().await?;
+        let mut client = self.connect("routehints").await?;
         let mut channels = client
             .lightning()
             .list_channels(ListChannelsRequest {
@@ -411,136 +701,124 @@ impl ILnRpcClient for GatewayLndClient {
         max_delay: u64,
         max_fee: Amount,
     ) -> Result<PayInvoiceResponse, LightningRpcError> {
-        info!("LND Paying invoice {invoice:?}");
-        let mut client = self.connect().await?;
-
-        debug!("LND got client to pay invoice {invoice:?}, will check if payment already exists");
+        self.send_private_payment(invoice, max_delay, max_fee, false, 0.0)
+            .await
+    }
 
-        // If the payment exists, that means we've already tried to pay the invoice
-        let preimage: Vec<u8> = if let Some(preimage) = self
-            .lookup_payment(invoice.payment_hash.to_byte_array().to_vec(), &mut client)
-            .await?
-        {
-            info!("LND payment already exists for invoice {invoice:?}");
-            hex::FromHex::from_hex(preimage.as_str()).map_err(|error| {
-                LightningRpcError::FailedPayment {
-                    failure_reason: format!("Failed to convert preimage {error:?}"),
-                }
-            })?
+    async fn retry_pay_private(
+        &self,
+        invoice: PrunedInvoice,
+        max_delay: u64,
+        max_fee: Amount,
+        randomize_path: bool,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        // A random time preference weighs the fees of routes against their success
+        // probability differently, so that LND likely picks another route
+        let time_pref = if randomize_path {
+            rand::thread_rng().gen_range(-1.0..=1.0)
         } else {
-            // LND API allows fee limits in the `i64` range, but we use `u64` for
-            // max_fee_msat. This means we can only set an enforceable fee limit
-            // between 0 and i64::MAX
-            let fee_limit_msat: i64 =
-                max_fee
-                    .msats
-                    .try_into()
-                    .map_err(|error| LightningRpcError::FailedPayment {
-                        failure_reason: format!(
-                            "max_fee_msat exceeds valid LND fee limit ranges {error:?}"
-                        ),
-                    })?;
+            0.0
+        };
 
-            let amt_msat = invoice.amount.msats.try_into().map_err(|error| {
-                LightningRpcError::FailedPayment {
+        self.send_private_payment(invoice, max_delay, max_fee, true, time_pref)
+            .await
+    }
+
+    /// Returns true if the lightning backend supports payments without full
+    /// invoices
+    fn supports_private_payments(&self) -> bool {
+        true
+    }
+
+    async fn pay_keysend(
+        &self,
+        node_id: PublicKey,
+        amount: Amount,
+        max_fee: Amount,
+        tlv_records: BTreeMap<u64, Vec<u8>>,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        if let Some(record_type) = tlv_records.keys().find(|record_type| {
+            **record_type < LND_CUSTOM_RECORD_TYPE_MIN || **record_type == LND_KEYSEND_RECORD_TYPE
+        }) {
+            return Err(LightningRpcError::FailedPayment {
+                failure_reason: format!("Custom record type {record_type} is not allowed"),
+            });
+        }
+
+        let amt_msat =
+            amount
+                .msats
+                .try_into()
+                .map_err(|error| LightningRpcError::FailedPayment {
                     failure_reason: format!("amount exceeds valid LND amount ranges {error:?}"),
-                }
-            })?;
-            let final_cltv_delta = invoice.min_final_cltv_delta.try_into().map_err(|error| {
-                LightningRpcError::FailedPayment {
-                    failure_reason: format!("final cltv delta exceeds valid LND range {error:?}"),
-                }
-            })?;
-            let cltv_limit =
-                max_delay
-                    .try_into()
-                    .map_err(|error| LightningRpcError::FailedPayment {
-                        failure_reason: format!("max delay exceeds valid LND range {error:?}"),
-                    })?;
+                })?;
+        let fee_limit_msat =
+            max_fee
+                .msats
+                .try_into()
+                .map_err(|error| LightningRpcError::FailedPayment {
+                    failure_reason: format!(
+                        "max_fee_msat exceeds valid LND fee limit ranges {error:?}"
+                    ),
+                })?;
 
-            let dest_features = wire_features_to_lnd_feature_vec(&invoice.destination_features)
-                .map_err(|e| LightningRpcError::FailedPayment {
-                    failure_reason: e.to_string(),
+        let preimage: [u8; 32] = rand::random();
+        let payment_hash = sha256::Hash::hash(&preimage);
+
+        let mut dest_custom_records: HashMap<u64, Vec<u8>> = tlv_records.into_iter().collect();
+        dest_custom_records.insert(LND_KEYSEND_RECORD_TYPE, preimage.to_vec());
+
+        info!("LND sending keysend payment {payment_hash} of {amount} to {node_id}");
+        let mut client = self.connect("pay_keysend").await?;
+        let mut messages = client
+            .router()
+            .send_payment_v2(SendPaymentRequest {
+                amt_msat,
+                dest: node_id.serialize().to_vec(),
+                payment_hash: payment_hash.to_byte_array().to_vec(),
+                dest_custom_records,
+                no_inflight_updates: true,
+                timeout_seconds: LND_PAYMENT_TIMEOUT_SECONDS,
+                fee_limit_msat,
+                ..Default::default()
+            })
+            .await
+            .map_err(|status| LightningRpcError::FailedPayment {
+                failure_reason: format!("Failed to make keysend payment {status:?}"),
+            })?
+            .into_inner();
+
+        loop {
+            let payment = messages
+                .message()
+                .await
+                .map_err(|error| LightningRpcError::FailedPayment {
+                    failure_reason: format!("Failed to get payment status {error:?}"),
+                })?
+                .ok_or(LightningRpcError::FailedPayment {
+                    failure_reason: format!(
+                        "Failed to get payment status for payment hash {payment_hash}"
+                    ),
                 })?;
 
-            debug!("LND payment does not exist for invoice {invoice:?}, will attempt to pay");
-            let payments = client
-                .router()
-                .send_payment_v2(SendPaymentRequest {
-                    amt_msat,
-                    dest: invoice.destination.serialize().to_vec(),
-                    dest_features,
-                    payment_hash: invoice.payment_hash.to_byte_array().to_vec(),
-                    payment_addr: invoice.payment_secret.to_vec(),
-                    route_hints: route_hints_to_lnd(&invoice.route_hints),
-                    final_cltv_delta,
-                    cltv_limit,
-                    no_inflight_updates: false,
-                    timeout_seconds: LND_PAYMENT_TIMEOUT_SECONDS,
-                    fee_limit_msat,
-                    ..Default::default()
-                })
-                .await
-                .map_err(|status| {
-                    info!("LND payment request failed for invoice {invoice:?} with {status:?}");
-                    LightningRpcError::FailedPayment {
-                        failure_reason: format!("Failed to make outgoing payment {status:?}"),
-                    }
-                })?;
-
-            debug!(
-                "LND payment request sent for invoice {invoice:?}, waiting for payment status..."
-            );
-            let mut messages = payments.into_inner();
-            loop {
-                match messages
-                    .message()
-                    .await
-                    .map_err(|error| LightningRpcError::FailedPayment {
-                        failure_reason: format!("Failed to get payment status {error:?}"),
-                    }) {
-                    Ok(Some(payment)) if payment.status() == PaymentStatus::Succeeded => {
-                        info!("LND payment succeeded for invoice {invoice:?}");
-                        break hex::FromHex::from_hex(payment.payment_preimage.as_str()).map_err(
-                            |error| LightningRpcError::FailedPayment {
-                                failure_reason: format!("Failed to convert preimage {error:?}"),
-                            },
-                        )?;
-                    }
-                    Ok(Some(payment)) if payment.status() == PaymentStatus::InFlight => {
-                        debug!("LND payment is inflight");
-                        continue;
-                    }
-                    Ok(Some(payment)) => {
-                        info!("LND payment failed for invoice {invoice:?} with {payment:?}");
-                        let failure_reason = payment.failure_reason();
-                        return Err(LightningRpcError::FailedPayment {
-                            failure_reason: format!("{failure_reason:?}"),
-                        });
-                    }
-                    Ok(None) => {
-                        info!("LND payment failed for invoice {invoice:?} with no payment status");
-                        return Err(LightningRpcError::FailedPayment {
-                            failure_reason: format!(
-                                "Failed to get payment status for payment hash {:?}",
-                                invoice.payment_hash
-                            ),
-                        });
-                    }
-                    Err(e) => {
-                        info!("LND payment failed for invoice {invoice:?} with {e:?}");
-                        return Err(e);
-                    }
+            match payment.status() {
+                PaymentStatus::Succeeded => {
+                    info!("LND keysend payment {payment_hash} succeeded");
+                    return Ok(PayInvoiceResponse {
+                        preimage: preimage.to_vec(),
+                    });
+                }
+                PaymentStatus::InFlight => {
+                    debug!("LND keysend payment {payment_hash} is inflight");
+                }
+                _ => {
+                    info!("LND keysend payment {payment_hash} failed with {payment:?}");
+                    return Err(LightningRpcError::FailedPayment {
+                        failure_reason: format!("{:?}", payment.failure_reason()),
+                    });
                 }
             }
-        };
-        Ok(PayInvoiceResponse { preimage })
-    }
-
-    /// Returns true if the lightning backend supports payments without full
-    /// invoices
-    fn supports_private_payments(&self) -> bool {
-        true
+        }
     }
 
     async fn route_htlcs<'a>(
@@ -562,12 +840,13 @@ impl ILnRpcClient for GatewayLndClient {
             gateway_sender.clone(),
         )
         .await?;
-        let new_client = Arc::new(Self::new(
-            self.address.clone(),
-            self.tls_cert.clone(),
-            self.macaroon.clone(),
-            Some(lnd_sender.clone()),
-        ));
+        let new_client = Arc::new(Self {
+            address: self.address.clone(),
+            tls_cert: self.tls_cert.clone(),
+            macaroon: self.macaroon.clone(),
+            pool: self.pool.clone(),
+            lnd_sender: Some(lnd_sender.clone()),
+        });
         Ok((Box::pin(ReceiverStream::new(gateway_receiver)), new_client))
     }
 
@@ -620,7 +899,7 @@ impl ILnRpcClient for GatewayLndClient {
         &self,
         create_invoice_request: CreateInvoiceRequest,
     ) -> Result<CreateInvoiceResponse, LightningRpcError> {
-        let mut client = self.connect().await?;
+        let mut client = self.connect("create_invoice").await?;
         let description =
             create_invoice_request
                 .description
@@ -657,8 +936,117 @@ impl ILnRpcClient for GatewayLndClient {
         Ok(CreateInvoiceResponse { invoice })
     }
 
+    async fn create_hold_invoice(
+        &self,
+        create_invoice_request: CreateInvoiceRequest,
+    ) -> Result<CreateInvoiceResponse, LightningRpcError> {
+        // Invoices created by the gateway in LND are always hold invoices
+        self.create_invoice(create_invoice_request)
+            .await
+            .map_err(|e| LightningRpcError::FailedToCreateHoldInvoice {
+                failure_reason: e.to_string(),
+            })
+    }
+
+    async fn settle_hold_invoice(
+        &self,
+        preimage: [u8; 32],
+    ) -> Result<EmptyResponse, LightningRpcError> {
+        let mut client = self.connect("settle_hold_invoice").await?;
+
+        client
+            .invoices()
+            .settle_invoice(SettleInvoiceMsg {
+                preimage: preimage.to_vec(),
+            })
+            .await
+            .map_err(|status| LightningRpcError::FailedToResolveHoldInvoice {
+                failure_reason: format!("Failed to settle hold invoice {status:?}"),
+            })?;
+
+        Ok(EmptyResponse {})
+    }
+
+    async fn cancel_hold_invoice(
+        &self,
+        payment_hash: sha256::Hash,
+    ) -> Result<EmptyResponse, LightningRpcError> {
+        let mut client = self.connect("cancel_hold_invoice").await?;
+
+        client
+            .invoices()
+            .cancel_invoice(CancelInvoiceMsg {
+                payment_hash: payment_hash.to_byte_array().to_vec(),
+            })
+            .await
+            .map_err(|status| LightningRpcError::FailedToResolveHoldInvoice {
+                failure_reason: format!("Failed to cancel hold invoice {status:?}"),
+            })?;
+
+        Ok(EmptyResponse {})
+    }
+
+    async fn send_onchain(
+        &self,
+        address: bitcoin::Address,
+        amount: bitcoin::Amount,
+        sat_per_vbyte: Option<u64>,
+    ) -> Result<bitcoin::Txid, LightningRpcError> {
+        let mut client = self.connect("send_onchain").await?;
+
+        let response = client
+            .lightning()
+            .send_coins(SendCoinsRequest {
+                addr: address.to_string(),
+                amount: amount.to_sat() as i64,
+                sat_per_vbyte: sat_per_vbyte.unwrap_or_default(),
+                ..Default::default()
+            })
+            .await
+            .map_err(|status| LightningRpcError::FailedToSendOnchain {
+                failure_reason: format!("Failed to send coins {status:?}"),
+            })?
+            .into_inner();
+
+        bitcoin::Txid::from_str(&response.txid).map_err(|e| {
+            LightningRpcError::FailedToSendOnchain {
+                failure_reason: format!("Invalid txid {}: {e}", response.txid),
+            }
+        })
+    }
+
+    fn supports_invoice_lookups(&self) -> bool {
+        true
+    }
+
+    async fn lookup_invoice(
+        &self,
+        payment_hash: sha256::Hash,
+    ) -> Result<InvoiceState, LightningRpcError> {
+        let mut client = self.connect("lookup_invoice").await?;
+
+        let invoice = client
+            .lightning()
+            .lookup_invoice(PaymentHash {
+                r_hash: payment_hash.to_byte_array().to_vec(),
+                ..Default::default()
+            })
+            .await
+            .map_err(|status| LightningRpcError::FailedToGetInvoice {
+                failure_reason: format!("Failed to look up invoice {status:?}"),
+            })?
+            .into_inner();
+
+        Ok(match invoice.state() {
+            LndInvoiceState::Open => InvoiceState::Open,
+            LndInvoiceState::Accepted => InvoiceState::Accepted,
+            LndInvoiceState::Settled => InvoiceState::Settled,
+            LndInvoiceState::Canceled => InvoiceState::Canceled,
+        })
+    }
+
     async fn get_funding_address(&self) -> Result<GetFundingAddressResponse, LightningRpcError> {
-        let mut client = self.connect().await?;
+        let mut client = self.connect("get_funding_address").await?;
 
         match client
             .wallet()
@@ -684,8 +1072,9 @@ impl ILnRpcClient for GatewayLndClient {
         host: String,
         channel_size_sats: u64,
         push_amount_sats: u64,
+        options: OpenChannelOptions,
     ) -> Result<EmptyResponse, LightningRpcError> {
-        let mut client = self.connect().await?;
+        let mut client = self.connect("open_channel").await?;
 
         // Connect to the peer first
         client
@@ -710,6 +1099,14 @@ impl ILnRpcClient for GatewayLndClient {
                 node_pubkey: pubkey.serialize().to_vec(),
                 local_funding_amount: channel_size_sats.try_into().expect("u64 -> i64"),
                 push_sat: push_amount_sats.try_into().expect("u64 -> i64"),
+                private: options.private,
+                zero_conf: options.zero_conf,
+                commitment_type: match options.commitment_type {
+                    None => CommitmentType::UnknownCommitmentType,
+                    Some(ChannelCommitmentType::StaticRemoteKey) => CommitmentType::StaticRemoteKey,
+                    Some(ChannelCommitmentType::Anchors) => CommitmentType::Anchors,
+                }
+                .into(),
                 ..Default::default()
             })
             .await
@@ -725,7 +1122,7 @@ impl ILnRpcClient for GatewayLndClient {
         &self,
         pubkey: PublicKey,
     ) -> Result<CloseChannelsWithPeerResponse, LightningRpcError> {
-        let mut client = self.connect().await?;
+        let mut client = self.connect("close_channels_with_peer").await?;
 
         let channels_with_peer = client
             .lightning()
@@ -778,7 +1175,7 @@ impl ILnRpcClient for GatewayLndClient {
     }
 
     async fn list_active_channels(&self) -> Result<Vec<ChannelInfo>, LightningRpcError> {
-        let mut client = self.connect().await?;
+        let mut client = self.connect("list_active_channels").await?;
 
         match client
             .lightning()
@@ -846,6 +1243,457 @@ impl ILnRpcClient for GatewayLndClient {
             }),
         }
     }
+
+    async fn lsps_request(
+        &self,
+        lsp_node_id: PublicKey,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, LightningRpcError> {
+        let mut client = self.connect("lsps_request").await?;
+
+        // Subscribe before sending the request, so the response can't be missed
+        let mut messages = client
+            .lightning()
+            .subscribe_custom_messages(SubscribeCustomMessagesRequest {})
+            .await
+            .map_err(|status| LightningRpcError::FailedLspsRequest {
+                failure_reason: format!("Failed to subscribe to custom messages {status:?}"),
+            })?
+            .into_inner();
+
+        let request_id = rand::random::<u64>().to_string();
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request_id,
+            "method": method,
+            "params": params,
+        });
+
+        debug!("Sending LSPS request {method} to {lsp_node_id}");
+        client
+            .lightning()
+            .send_custom_message(SendCustomMessageRequest {
+                peer: lsp_node_id.serialize().to_vec(),
+                r#type: LSPS_MESSAGE_TYPE,
+                data: request.to_string().into_bytes(),
+            })
+            .await
+            .map_err(|status| LightningRpcError::FailedLspsRequest {
+                failure_reason: format!("Failed to send custom message {status:?}"),
+            })?;
+
+        let response = timeout(LSPS_RESPONSE_TIMEOUT, async {
+            loop {
+                let message = match messages.message().await {
+                    Ok(Some(message)) => message,
+                    Ok(None) => {
+                        return Err(LightningRpcError::FailedLspsRequest {
+                            failure_reason: "Custom message stream closed".to_string(),
+                        });
+                    }
+                    Err(status) => {
+                        return Err(LightningRpcError::FailedLspsRequest {
+                            failure_reason: format!("Failed to receive custom message {status:?}"),
+                        });
+                    }
+                };
+
+                if message.peer != lsp_node_id.serialize() || message.r#type != LSPS_MESSAGE_TYPE {
+                    continue;
+                }
+
+                let Ok(response) = serde_json::from_slice::<serde_json::Value>(&message.data)
+                else {
+                    warn!("Received invalid LSPS message from {lsp_node_id}");
+                    continue;
+                };
+
+                if response.get("id").and_then(serde_json::Value::as_str)
+                    == Some(request_id.as_str())
+                {
+                    return Ok(response);
+                }
+            }
+        })
+        .await
+        .map_err(|_| LightningRpcError::FailedLspsRequest {
+            failure_reason: format!("Timed out waiting for response to {method}"),
+        })??;
+
+        if let Some(error) = response.get("error") {
+            return Err(LightningRpcError::FailedLspsRequest {
+                failure_reason: format!("LSP returned error for {method}: {error}"),
+            });
+        }
+
+        response
+            .get("result")
+            .cloned()
+            .ok_or(LightningRpcError::FailedLspsRequest {
+                failure_reason: format!("LSP response to {method} has no result"),
+            })
+    }
+
+    async fn query_mission_control(&self) -> Result<Vec<MissionControlPair>, LightningRpcError> {
+        let mut client = self.connect("query_mission_control").await?;
+        let pairs = client
+            .router()
+            .query_mission_control(QueryMissionControlRequest {})
+            .await
+            .map_err(|status| LightningRpcError::FailedToAccessMissionControl {
+                failure_reason: format!("Failed to query mission control {status:?}"),
+            })?
+            .into_inner()
+            .pairs;
+
+        let parse_node = |node: &[u8]| {
+            PublicKey::from_slice(node).map_err(|e| {
+                LightningRpcError::FailedToAccessMissionControl {
+                    failure_reason: format!("Invalid node public key {e:?}"),
+                }
+            })
+        };
+
+        pairs
+            .into_iter()
+            .map(|pair| {
+                let history = pair.history.unwrap_or_default();
+                Ok(MissionControlPair {
+                    node_from: parse_node(&pair.node_from)?,
+                    node_to: parse_node(&pair.node_to)?,
+                    fail_time: history.fail_time as u64,
+                    fail_amt_msat: history.fail_amt_msat as u64,
+                    success_time: history.success_time as u64,
+                    success_amt_msat: history.success_amt_msat as u64,
+                })
+            })
+            .collect()
+    }
+
+    async fn import_mission_control(
+        &self,
+        pairs: Vec<MissionControlPair>,
+        force: bool,
+    ) -> Result<(), LightningRpcError> {
+        let pairs = pairs
+            .into_iter()
+            .map(|pair| PairHistory {
+                node_from: pair.node_from.serialize().to_vec(),
+                node_to: pair.node_to.serialize().to_vec(),
+                history: Some(PairData {
+                    fail_time: pair.fail_time as i64,
+                    fail_amt_sat: (pair.fail_amt_msat / 1000) as i64,
+                    fail_amt_msat: pair.fail_amt_msat as i64,
+                    success_time: pair.success_time as i64,
+                    success_amt_sat: (pair.success_amt_msat / 1000) as i64,
+                    success_amt_msat: pair.success_amt_msat as i64,
+                }),
+            })
+            .collect();
+
+        let mut client = self.connect("import_mission_control").await?;
+        client
+            .router()
+            .x_import_mission_control(XImportMissionControlRequest { pairs, force })
+            .await
+            .map_err(|status| LightningRpcError::FailedToAccessMissionControl {
+                failure_reason: format!("Failed to import mission control {status:?}"),
+            })?;
+
+        Ok(())
+    }
+
+    async fn reset_mission_control(&self) -> Result<(), LightningRpcError> {
+        let mut client = self.connect("reset_mission_control").await?;
+        client
+            .router()
+            .reset_mission_control(ResetMissionControlRequest {})
+            .await
+            .map_err(|status| LightningRpcError::FailedToAccessMissionControl {
+                failure_reason: format!("Failed to reset mission control {status:?}"),
+            })?;
+
+        Ok(())
+    }
+
+    async fn probe(
+        &self,
+        destination: PublicKey,
+        amount: Amount,
+        max_fee: Amount,
+    ) -> Result<ProbeResult, LightningRpcError> {
+        let amt_msat = amount
+            .msats
+            .try_into()
+            .map_err(|error| LightningRpcError::FailedProbe {
+                failure_reason: format!("amount exceeds valid LND amount ranges {error:?}"),
+            })?;
+        let fee_limit_msat =
+            max_fee
+                .msats
+                .try_into()
+                .map_err(|error| LightningRpcError::FailedProbe {
+                    failure_reason: format!(
+                        "max_fee_msat exceeds valid LND fee limit ranges {error:?}"
+                    ),
+                })?;
+
+        // Nobody knows the preimage of a random payment hash, so the destination
+        // fails the payment once it arrives
+        let payment_hash: [u8; 32] = rand::random();
+
+        let mut client = self.connect("probe").await?;
+        let mut payments = client
+            .router()
+            .send_payment_v2(SendPaymentRequest {
+                amt_msat,
+                dest: destination.serialize().to_vec(),
+                payment_hash: payment_hash.to_vec(),
+                timeout_seconds: LND_PAYMENT_TIMEOUT_SECONDS,
+                fee_limit_msat,
+                ..Default::default()
+            })
+            .await
+            .map_err(|status| LightningRpcError::FailedProbe {
+                failure_reason: format!("Failed to send probe {status:?}"),
+            })?
+            .into_inner();
+
+        while let Some(payment) =
+            payments
+                .message()
+                .await
+                .map_err(|error| LightningRpcError::FailedProbe {
+                    failure_reason: format!("Failed to get probe status {error:?}"),
+                })?
+        {
+            match payment.status() {
+                PaymentStatus::Failed => {
+                    let reachable = payment.failure_reason()
+                        == PaymentFailureReason::FailureReasonIncorrectPaymentDetails;
+                    let fee = payment
+                        .htlcs
+                        .last()
+                        .and_then(|htlc| htlc.route.as_ref())
+                        .map(|route| Amount::from_msats(route.total_fees_msat as u64));
+
+                    return Ok(ProbeResult {
+                        reachable,
+                        fee: fee.filter(|_| reachable),
+                    });
+                }
+                PaymentStatus::Succeeded => {
+                    return Err(LightningRpcError::FailedProbe {
+                        failure_reason: "Probe payment unexpectedly succeeded".to_string(),
+                    });
+                }
+                PaymentStatus::InFlight | PaymentStatus::Unknown => {}
+            }
+        }
+
+        Err(LightningRpcError::FailedProbe {
+            failure_reason: "Probe status stream ended".to_string(),
+        })
+    }
+
+    async fn update_channel_policy(
+        &self,
+        short_channel_id: u64,
+        policy: ChannelPolicy,
+    ) -> Result<(), LightningRpcError> {
+        let mut client = self.connect("update_channel_policy").await?;
+
+        let channel_point = client
+            .lightning()
+            .get_chan_info(ChanInfoRequest {
+                chan_id: short_channel_id,
+            })
+            .await
+            .map_err(|status| LightningRpcError::FailedToUpdateChannelPolicy {
+                failure_reason: format!("Failed to get channel info {status:?}"),
+            })?
+            .into_inner()
+            .chan_point;
+        let channel_point = bitcoin::OutPoint::from_str(&channel_point).map_err(|e| {
+            LightningRpcError::FailedToUpdateChannelPolicy {
+                failure_reason: format!("Failed to parse channel point {e:?}"),
+            }
+        })?;
+
+        let failed_updates = client
+            .lightning()
+            .update_channel_policy(PolicyUpdateRequest {
+                scope: Some(tonic_lnd::lnrpc::policy_update_request::Scope::ChanPoint(
+                    ChannelPoint {
+                        funding_txid: Some(
+                            tonic_lnd::lnrpc::channel_point::FundingTxid::FundingTxidBytes(
+                                <bitcoin::Txid as AsRef<[u8]>>::as_ref(&channel_point.txid)
+                                    .as_ref()
+                                    .to_vec(),
+                            ),
+                        ),
+                        output_index: channel_point.vout,
+                    },
+                )),
+                base_fee_msat: policy.base_fee_msat as i64,
+                fee_rate_ppm: policy.fee_rate_ppm,
+                time_lock_delta: policy.cltv_delta,
+                ..Default::default()
+            })
+            .await
+            .map_err(|status| LightningRpcError::FailedToUpdateChannelPolicy {
+                failure_reason: format!("Failed to update channel policy {status:?}"),
+            })?
+            .into_inner()
+            .failed_updates;
+
+        if let Some(failed_update) = failed_updates.first() {
+            return Err(LightningRpcError::FailedToUpdateChannelPolicy {
+                failure_reason: failed_update.update_error.clone(),
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn connect_peer(&self, pubkey: PublicKey, host: String) -> Result<(), LightningRpcError> {
+        let mut client = self.connect("connect_peer").await?;
+
+        match client
+            .lightning()
+            .connect_peer(ConnectPeerRequest {
+                addr: Some(LightningAddress {
+                    pubkey: pubkey.to_string(),
+                    host,
+                }),
+                perm: false,
+                timeout: 10,
+            })
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(status) if status.message().contains("already connected") => Ok(()),
+            Err(status) => Err(LightningRpcError::FailedToConnectToPeer {
+                failure_reason: format!("Failed to connect to peer {status:?}"),
+            }),
+        }
+    }
+
+    async fn disconnect_peer(&self, pubkey: PublicKey) -> Result<(), LightningRpcError> {
+        let mut client = self.connect("disconnect_peer").await?;
+
+        client
+            .lightning()
+            .disconnect_peer(DisconnectPeerRequest {
+                pub_key: pubkey.to_string(),
+            })
+            .await
+            .map_err(|status| LightningRpcError::FailedToDisconnectPeer {
+                failure_reason: format!("Failed to disconnect from peer {status:?}"),
+            })?;
+
+        Ok(())
+    }
+
+    async fn list_peers(&self) -> Result<Vec<PeerInfo>, LightningRpcError> {
+        let mut client = self.connect("list_peers").await?;
+
+        let peers = client
+            .lightning()
+            .list_peers(ListPeersRequest { latest_error: true })
+            .await
+            .map_err(|status| LightningRpcError::FailedToListPeers {
+                failure_reason: format!("Failed to list peers {status:?}"),
+            })?
+            .into_inner()
+            .peers;
+
+        peers
+            .into_iter()
+            .map(|peer| {
+                Ok(PeerInfo {
+                    pubkey: PublicKey::from_str(&peer.pub_key).map_err(|e| {
+                        LightningRpcError::FailedToListPeers {
+                            failure_reason: format!("LND returned an invalid pubkey {e:?}"),
+                        }
+                    })?,
+                    address: peer.address,
+                    inbound: peer.inbound,
+                })
+            })
+            .collect()
+    }
+
+    async fn query_route(
+        &self,
+        destination: PublicKey,
+        amount: Amount,
+    ) -> Result<RouteEstimate, LightningRpcError> {
+        let amt_msat =
+            amount
+                .msats
+                .try_into()
+                .map_err(|error| LightningRpcError::FailedToQueryRoute {
+                    failure_reason: format!("amount exceeds valid LND amount ranges {error:?}"),
+                })?;
+
+        let mut client = self.connect("query_route").await?;
+
+        let route = client
+            .lightning()
+            .query_routes(QueryRoutesRequest {
+                pub_key: destination.to_string(),
+                amt_msat,
+                use_mission_control: true,
+                ..Default::default()
+            })
+            .await
+            .map_err(|status| LightningRpcError::FailedToQueryRoute {
+                failure_reason: format!("Failed to query routes {status:?}"),
+            })?
+            .into_inner()
+            .routes
+            .into_iter()
+            .next()
+            .ok_or(LightningRpcError::FailedToQueryRoute {
+                failure_reason: format!("No route to {destination}"),
+            })?;
+
+        Ok(RouteEstimate {
+            fee: Amount::from_msats(route.total_fees_msat as u64),
+            num_hops: route.hops.len(),
+        })
+    }
+
+    async fn get_balances(&self) -> Result<LightningBalances, LightningRpcError> {
+        let mut client = self.connect("get_balances").await?;
+
+        let wallet_balance = client
+            .lightning()
+            .wallet_balance(WalletBalanceRequest {})
+            .await
+            .map_err(|status| LightningRpcError::FailedToGetBalances {
+                failure_reason: format!("Failed to get wallet balance {status:?}"),
+            })?
+            .into_inner();
+
+        let channel_balance = client
+            .lightning()
+            .channel_balance(ChannelBalanceRequest {})
+            .await
+            .map_err(|status| LightningRpcError::FailedToGetBalances {
+                failure_reason: format!("Failed to get channel balance {status:?}"),
+            })?
+            .into_inner();
+
+        Ok(LightningBalances {
+            onchain_balance_sats: wallet_balance.confirmed_balance as u64,
+            lightning_balance_msats: channel_balance
+                .local_balance
+                .map_or(0, |balance| balance.msat),
+        })
+    }
 }
 
 fn route_hints_to_lnd(
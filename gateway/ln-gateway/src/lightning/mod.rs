@@ -1,11 +1,16 @@
 pub mod cln;
 pub mod lnd;
+pub mod multi;
+pub mod sandbox;
 
+use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::sync::Arc;
 
+use anyhow::{ensure, Context};
 use async_trait::async_trait;
-use clap::Subcommand;
+use bitcoin_hashes::sha256;
+use clap::{Args, Subcommand, ValueEnum};
 use fedimint_core::encoding::{Decodable, Encodable};
 use fedimint_core::task::TaskGroup;
 use fedimint_core::util::SafeUrl;
@@ -15,9 +20,12 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use self::cln::{NetworkLnRpcClient, RouteHtlcStream};
-use self::lnd::GatewayLndClient;
+use self::lnd::{
+    GatewayLndClient, DEFAULT_LND_CONNECTION_POOL_SIZE, DEFAULT_LND_MAX_CONCURRENT_REQUESTS,
+};
 use crate::envs::{
-    FM_GATEWAY_LIGHTNING_ADDR_ENV, FM_LND_MACAROON_ENV, FM_LND_RPC_ADDR_ENV, FM_LND_TLS_CERT_ENV,
+    FM_GATEWAY_LIGHTNING_ADDR_ENV, FM_LND_CONNECTION_POOL_SIZE_ENV, FM_LND_MACAROON_ENV,
+    FM_LND_MAX_CONCURRENT_REQUESTS_ENV, FM_LND_RPC_ADDR_ENV, FM_LND_TLS_CERT_ENV,
 };
 use crate::gateway_lnrpc::{
     CloseChannelsWithPeerResponse, CreateInvoiceRequest, CreateInvoiceResponse, EmptyResponse,
@@ -57,6 +65,32 @@ pub enum LightningRpcError {
     FailedToListActiveChannels { failure_reason: String },
     #[error("Failed to wait for chain sync: {failure_reason}")]
     FailedToWaitForChainSync { failure_reason: String },
+    #[error("Failed to create hold invoice: {failure_reason}")]
+    FailedToCreateHoldInvoice { failure_reason: String },
+    #[error("Failed to resolve hold invoice: {failure_reason}")]
+    FailedToResolveHoldInvoice { failure_reason: String },
+    #[error("LSPS request failed: {failure_reason}")]
+    FailedLspsRequest { failure_reason: String },
+    #[error("Failed to access mission control: {failure_reason}")]
+    FailedToAccessMissionControl { failure_reason: String },
+    #[error("Failed to update channel policy: {failure_reason}")]
+    FailedToUpdateChannelPolicy { failure_reason: String },
+    #[error("Failed to probe: {failure_reason}")]
+    FailedProbe { failure_reason: String },
+    #[error("Failed to disconnect from peer: {failure_reason}")]
+    FailedToDisconnectPeer { failure_reason: String },
+    #[error("Failed to list peers: {failure_reason}")]
+    FailedToListPeers { failure_reason: String },
+    #[error("Failed to query route: {failure_reason}")]
+    FailedToQueryRoute { failure_reason: String },
+    #[error("Failed to get balances: {failure_reason}")]
+    FailedToGetBalances { failure_reason: String },
+    #[error("Failed to send on-chain: {failure_reason}")]
+    FailedToSendOnchain { failure_reason: String },
+    /// The payment failed without any HTLC left in flight, because the node
+    /// found no route or has too little liquidity
+    #[error("No route to pay: {failure_reason}")]
+    NoRoute { failure_reason: String },
 }
 
 /// A trait that the gateway uses to interact with a lightning node. This allows
@@ -93,6 +127,35 @@ pub trait ILnRpcClient: Debug + Send + Sync {
         })
     }
 
+    /// Attempts a private payment again after [`ILnRpcClient::pay_private`]
+    /// failed for the invoice. If `randomize_path` is set, the lightning node
+    /// is asked to prefer a different route than for the previous attempt.
+    async fn retry_pay_private(
+        &self,
+        invoice: PrunedInvoice,
+        max_delay: u64,
+        max_fee: Amount,
+        _randomize_path: bool,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        self.pay_private(invoice, max_delay, max_fee).await
+    }
+
+    /// Sends a spontaneous (keysend) payment of `amount` to `node_id`, paying
+    /// at most `max_fee` in routing fees. `tlv_records` are attached to the
+    /// payment as custom records and must use types in the custom range
+    /// (>= 65536). Returns the preimage generated for the payment.
+    async fn pay_keysend(
+        &self,
+        _node_id: secp256k1::PublicKey,
+        _amount: Amount,
+        _max_fee: Amount,
+        _tlv_records: BTreeMap<u64, Vec<u8>>,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        Err(LightningRpcError::FailedPayment {
+            failure_reason: "Keysend payments not supported".to_string(),
+        })
+    }
+
     /// Returns true if the lightning backend supports payments without full
     /// invoices. If this returns true, then [`ILnRpcClient::pay_private`] has
     /// to be implemented.
@@ -128,6 +191,43 @@ pub trait ILnRpcClient: Debug + Send + Sync {
         create_invoice_request: CreateInvoiceRequest,
     ) -> Result<CreateInvoiceResponse, LightningRpcError>;
 
+    /// Creates a hold invoice for the request's payment hash. Incoming
+    /// payments to it are accepted by the lightning node but only settled
+    /// once [`ILnRpcClient::settle_hold_invoice`] is called with the
+    /// preimage, so it can be released after the federation transaction is
+    /// accepted.
+    async fn create_hold_invoice(
+        &self,
+        _create_invoice_request: CreateInvoiceRequest,
+    ) -> Result<CreateInvoiceResponse, LightningRpcError> {
+        Err(LightningRpcError::FailedToCreateHoldInvoice {
+            failure_reason: "Hold invoices not supported".to_string(),
+        })
+    }
+
+    /// Settles a hold invoice created with
+    /// [`ILnRpcClient::create_hold_invoice`], claiming its accepted payment.
+    async fn settle_hold_invoice(
+        &self,
+        _preimage: [u8; 32],
+    ) -> Result<EmptyResponse, LightningRpcError> {
+        Err(LightningRpcError::FailedToResolveHoldInvoice {
+            failure_reason: "Hold invoices not supported".to_string(),
+        })
+    }
+
+    /// Cancels a hold invoice created with
+    /// [`ILnRpcClient::create_hold_invoice`], failing back any accepted
+    /// payment.
+    async fn cancel_hold_invoice(
+        &self,
+        _payment_hash: sha256::Hash,
+    ) -> Result<EmptyResponse, LightningRpcError> {
+        Err(LightningRpcError::FailedToResolveHoldInvoice {
+            failure_reason: "Hold invoices not supported".to_string(),
+        })
+    }
+
     /// Get a funding address belonging to the gateway's lightning node
     /// wallet.
     async fn get_funding_address(&self) -> Result<GetFundingAddressResponse, LightningRpcError>;
@@ -140,6 +240,7 @@ pub trait ILnRpcClient: Debug + Send + Sync {
         host: String,
         channel_size_sats: u64,
         push_amount_sats: u64,
+        options: OpenChannelOptions,
     ) -> Result<EmptyResponse, LightningRpcError>;
 
     /// Close all channels with a peer lightning node from the gateway's
@@ -150,9 +251,165 @@ pub trait ILnRpcClient: Debug + Send + Sync {
     ) -> Result<CloseChannelsWithPeerResponse, LightningRpcError>;
 
     async fn list_active_channels(&self) -> Result<Vec<ChannelInfo>, LightningRpcError>;
+
+    /// Sends an LSPS JSON-RPC request to the Lightning Service Provider
+    /// `lsp_node_id` and returns the `result` of its response. The gateway's
+    /// lightning node has to be connected to the LSP.
+    async fn lsps_request(
+        &self,
+        _lsp_node_id: secp256k1::PublicKey,
+        _method: &str,
+        _params: serde_json::Value,
+    ) -> Result<serde_json::Value, LightningRpcError> {
+        Err(LightningRpcError::FailedLspsRequest {
+            failure_reason: "LSPS requests not supported".to_string(),
+        })
+    }
+
+    /// Returns the payment success and failure history per node pair that
+    /// the lightning node uses to pick routes.
+    async fn query_mission_control(&self) -> Result<Vec<MissionControlPair>, LightningRpcError> {
+        Err(LightningRpcError::FailedToAccessMissionControl {
+            failure_reason: "Mission control not supported".to_string(),
+        })
+    }
+
+    /// Imports routing history, e.g. exported from another node with
+    /// `query_mission_control`. Unless `force` is set, the imported history
+    /// is only applied where it is more recent than the node's own.
+    async fn import_mission_control(
+        &self,
+        _pairs: Vec<MissionControlPair>,
+        _force: bool,
+    ) -> Result<(), LightningRpcError> {
+        Err(LightningRpcError::FailedToAccessMissionControl {
+            failure_reason: "Mission control not supported".to_string(),
+        })
+    }
+
+    /// Clears all routing history of the lightning node.
+    async fn reset_mission_control(&self) -> Result<(), LightningRpcError> {
+        Err(LightningRpcError::FailedToAccessMissionControl {
+            failure_reason: "Mission control not supported".to_string(),
+        })
+    }
+
+    /// Sends a payment of `amount` to `destination` that the destination can't
+    /// claim, paying at most `max_fee` in routing fees, to test whether the
+    /// lightning node currently has a route with enough liquidity.
+    async fn probe(
+        &self,
+        _destination: secp256k1::PublicKey,
+        _amount: Amount,
+        _max_fee: Amount,
+    ) -> Result<ProbeResult, LightningRpcError> {
+        Err(LightningRpcError::FailedProbe {
+            failure_reason: "Probing not supported".to_string(),
+        })
+    }
+
+    /// Sets the policy the lightning node applies to payments it forwards
+    /// over the channel `short_channel_id`.
+    async fn update_channel_policy(
+        &self,
+        _short_channel_id: u64,
+        _policy: ChannelPolicy,
+    ) -> Result<(), LightningRpcError> {
+        Err(LightningRpcError::FailedToUpdateChannelPolicy {
+            failure_reason: "Updating channel policies not supported".to_string(),
+        })
+    }
+
+    /// Connects the lightning node to the peer `pubkey` reachable at `host`.
+    /// Succeeds if the node is already connected to the peer.
+    async fn connect_peer(
+        &self,
+        _pubkey: secp256k1::PublicKey,
+        _host: String,
+    ) -> Result<(), LightningRpcError> {
+        Err(LightningRpcError::FailedToConnectToPeer {
+            failure_reason: "Connecting to peers not supported".to_string(),
+        })
+    }
+
+    async fn disconnect_peer(
+        &self,
+        _pubkey: secp256k1::PublicKey,
+    ) -> Result<(), LightningRpcError> {
+        Err(LightningRpcError::FailedToDisconnectPeer {
+            failure_reason: "Disconnecting from peers not supported".to_string(),
+        })
+    }
+
+    /// Returns the peers the lightning node is currently connected to
+    async fn list_peers(&self) -> Result<Vec<PeerInfo>, LightningRpcError> {
+        Err(LightningRpcError::FailedToListPeers {
+            failure_reason: "Listing peers not supported".to_string(),
+        })
+    }
+
+    /// Finds the route the lightning node would currently use to pay
+    /// `amount` to `destination`, without sending a payment
+    async fn query_route(
+        &self,
+        _destination: secp256k1::PublicKey,
+        _amount: Amount,
+    ) -> Result<RouteEstimate, LightningRpcError> {
+        Err(LightningRpcError::FailedToQueryRoute {
+            failure_reason: "Querying routes not supported".to_string(),
+        })
+    }
+
+    async fn get_balances(&self) -> Result<LightningBalances, LightningRpcError> {
+        Err(LightningRpcError::FailedToGetBalances {
+            failure_reason: "Getting balances not supported".to_string(),
+        })
+    }
+
+    /// Sends `amount` from the on-chain wallet of the lightning node to
+    /// `address`, paying `sat_per_vbyte` or, if not set, the fee rate
+    /// estimated by the node
+    async fn send_onchain(
+        &self,
+        _address: bitcoin::Address,
+        _amount: bitcoin::Amount,
+        _sat_per_vbyte: Option<u64>,
+    ) -> Result<bitcoin::Txid, LightningRpcError> {
+        Err(LightningRpcError::FailedToSendOnchain {
+            failure_reason: "Sending on-chain not supported".to_string(),
+        })
+    }
+
+    /// Returns true if the lightning backend can look up the state of
+    /// invoices it created. If this returns true, then
+    /// [`ILnRpcClient::lookup_invoice`] has to be implemented.
+    fn supports_invoice_lookups(&self) -> bool {
+        false
+    }
+
+    /// Returns the state of an invoice created with
+    /// [`ILnRpcClient::create_invoice`]
+    async fn lookup_invoice(
+        &self,
+        _payment_hash: sha256::Hash,
+    ) -> Result<InvoiceState, LightningRpcError> {
+        Err(LightningRpcError::FailedToGetInvoice {
+            failure_reason: "Looking up invoices not supported".to_string(),
+        })
+    }
+}
+
+/// State of an invoice created by the lightning node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InvoiceState {
+    Open,
+    /// A payment was accepted but has to be settled with the preimage
+    Accepted,
+    Settled,
+    Canceled,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ChannelInfo {
     pub remote_pubkey: String,
     pub channel_size_sats: u64,
@@ -161,6 +418,110 @@ pub struct ChannelInfo {
     pub short_channel_id: u64,
 }
 
+/// The routing history of the lightning node for payments forwarded from
+/// `node_from` to `node_to`. Times are unix timestamps in seconds, all fields
+/// are zero if there was no such payment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MissionControlPair {
+    pub node_from: secp256k1::PublicKey,
+    pub node_to: secp256k1::PublicKey,
+    /// Time of the last failure
+    pub fail_time: u64,
+    /// Smallest amount that failed to forward
+    pub fail_amt_msat: u64,
+    /// Time of the last success
+    pub success_time: u64,
+    /// Largest amount that was forwarded successfully
+    pub success_amt_msat: u64,
+}
+
+/// A peer the lightning node is connected to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerInfo {
+    pub pubkey: secp256k1::PublicKey,
+    /// Network address of the connection
+    pub address: String,
+    /// Whether the peer opened the connection
+    pub inbound: bool,
+}
+
+/// Funds of the lightning node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LightningBalances {
+    /// Confirmed balance of the on-chain wallet
+    pub onchain_balance_sats: u64,
+    /// Sum of the local balances of all channels
+    pub lightning_balance_msats: u64,
+}
+
+/// Outcome of [`ILnRpcClient::query_route`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteEstimate {
+    /// Routing fee of the route
+    pub fee: Amount,
+    /// Number of channels of the route
+    pub num_hops: usize,
+}
+
+/// Outcome of [`ILnRpcClient::probe`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeResult {
+    /// Whether the probe reached the destination
+    pub reachable: bool,
+    /// Routing fee of the route that reached the destination
+    pub fee: Option<Amount>,
+}
+
+/// Forwarding policy of a single channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Args, Serialize, Deserialize)]
+pub struct ChannelPolicy {
+    /// Fee charged per forwarded payment
+    #[clap(long)]
+    pub base_fee_msat: u64,
+
+    /// Fee charged proportionally to the forwarded amount, in parts per
+    /// million
+    #[clap(long)]
+    pub fee_rate_ppm: u32,
+
+    /// Number of blocks between the expiries of the incoming and the
+    /// outgoing HTLC of a forwarded payment
+    #[clap(long)]
+    pub cltv_delta: u32,
+}
+
+/// Optional parameters for opening a channel. The default is a public channel
+/// with the lightning node's default commitment type that can be used once its
+/// funding transaction has confirmed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Args, Serialize, Deserialize)]
+pub struct OpenChannelOptions {
+    /// Don't announce the channel to the network
+    #[clap(long)]
+    #[serde(default)]
+    pub private: bool,
+
+    /// Use the channel before its funding transaction confirms. The peer has
+    /// to accept zero-conf channels from the gateway's lightning node.
+    #[clap(long)]
+    #[serde(default)]
+    pub zero_conf: bool,
+
+    /// The commitment format of the channel, the lightning node's default is
+    /// used if unset
+    #[clap(long)]
+    #[serde(default)]
+    pub commitment_type: Option<ChannelCommitmentType>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelCommitmentType {
+    /// The remote party's output always pays to the same key
+    StaticRemoteKey,
+    /// Anchor outputs that allow fee bumping a force close transaction
+    Anchors,
+}
+
 #[derive(Debug, Clone, Subcommand, Serialize, Deserialize)]
 pub enum LightningMode {
     #[clap(name = "lnd")]
@@ -176,6 +537,23 @@ pub enum LightningMode {
         /// LND macaroon file path
         #[arg(long = "lnd-macaroon", env = FM_LND_MACAROON_ENV)]
         lnd_macaroon: String,
+
+        /// Number of connections the gateway keeps open to LND
+        #[arg(
+            long = "lnd-connection-pool-size",
+            env = FM_LND_CONNECTION_POOL_SIZE_ENV,
+            default_value_t = DEFAULT_LND_CONNECTION_POOL_SIZE
+        )]
+        lnd_connection_pool_size: usize,
+
+        /// Maximum number of requests per gRPC method the gateway sends to LND
+        /// at the same time, further requests wait for a free slot
+        #[arg(
+            long = "lnd-max-concurrent-requests",
+            env = FM_LND_MAX_CONCURRENT_REQUESTS_ENV,
+            default_value_t = DEFAULT_LND_MAX_CONCURRENT_REQUESTS
+        )]
+        lnd_max_concurrent_requests: usize,
     },
     #[clap(name = "cln")]
     Cln {
@@ -184,6 +562,70 @@ pub enum LightningMode {
     },
 }
 
+impl LightningMode {
+    /// Checks the configuration of the lightning backend when the gateway
+    /// starts, so that mistakes are reported right away instead of as failing
+    /// payments once the gateway is running.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        match self {
+            LightningMode::Lnd {
+                lnd_rpc_addr,
+                lnd_tls_cert,
+                lnd_macaroon,
+                lnd_connection_pool_size,
+                lnd_max_concurrent_requests,
+            } => {
+                let url = SafeUrl::parse(lnd_rpc_addr).with_context(|| {
+                    format!(
+                        "Invalid LND RPC address {lnd_rpc_addr}, expected a URL like \
+                         https://localhost:10009"
+                    )
+                })?;
+                ensure!(
+                    url.scheme() == "https",
+                    "The LND RPC address {lnd_rpc_addr} has to use https"
+                );
+
+                let tls_cert = read_file(lnd_tls_cert, "LND TLS certificate")?;
+                ensure!(
+                    String::from_utf8_lossy(&tls_cert).contains("BEGIN CERTIFICATE"),
+                    "The LND TLS certificate {lnd_tls_cert} is not a PEM certificate, use the \
+                     tls.cert file from LND's data directory"
+                );
+
+                let macaroon = read_file(lnd_macaroon, "LND macaroon")?;
+                ensure!(
+                    !macaroon.is_empty(),
+                    "The LND macaroon {lnd_macaroon} is empty"
+                );
+
+                ensure!(
+                    *lnd_connection_pool_size > 0,
+                    "The LND connection pool needs at least one connection"
+                );
+                ensure!(
+                    *lnd_max_concurrent_requests > 0,
+                    "At least one concurrent request to LND has to be allowed"
+                );
+            }
+            LightningMode::Cln { cln_extension_addr } => {
+                ensure!(
+                    matches!(cln_extension_addr.scheme(), "http" | "https"),
+                    "The CLN extension address {cln_extension_addr} has to be an http(s) URL"
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn read_file(path: &str, name: &str) -> anyhow::Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| {
+        format!("Can't read the {name} {path}, check that it exists and the gateway may read it")
+    })
+}
+
 #[async_trait]
 pub trait LightningBuilder {
     async fn build(&self) -> Box<dyn ILnRpcClient>;
@@ -205,12 +647,63 @@ impl LightningBuilder for GatewayLightningBuilder {
                 lnd_rpc_addr,
                 lnd_tls_cert,
                 lnd_macaroon,
+                lnd_connection_pool_size,
+                lnd_max_concurrent_requests,
             } => Box::new(GatewayLndClient::new(
                 lnd_rpc_addr,
                 lnd_tls_cert,
                 lnd_macaroon,
-                None,
+                lnd_connection_pool_size,
+                lnd_max_concurrent_requests,
             )),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use fedimint_core::util::SafeUrl;
+
+    use super::LightningMode;
+
+    #[test]
+    fn rejects_invalid_backend_configuration() {
+        let dir = std::env::temp_dir().join(format!("gatewayd-validate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tls_cert = dir.join("tls.cert");
+        let macaroon = dir.join("admin.macaroon");
+        std::fs::write(
+            &tls_cert,
+            "-----BEGIN CERTIFICATE-----\n-----END CERTIFICATE-----\n",
+        )
+        .unwrap();
+        std::fs::write(&macaroon, [1, 2, 3]).unwrap();
+
+        let lnd = |lnd_rpc_addr: &str, lnd_tls_cert: &str| LightningMode::Lnd {
+            lnd_rpc_addr: lnd_rpc_addr.to_string(),
+            lnd_tls_cert: lnd_tls_cert.to_string(),
+            lnd_macaroon: macaroon.to_str().unwrap().to_string(),
+            lnd_connection_pool_size: 1,
+            lnd_max_concurrent_requests: 1,
+        };
+        let tls_cert = tls_cert.to_str().unwrap();
+
+        assert!(lnd("https://localhost:10009", tls_cert).validate().is_ok());
+        assert!(lnd("localhost:10009", tls_cert).validate().is_err());
+        assert!(lnd("https://localhost:10009", "/does/not/exist")
+            .validate()
+            .is_err());
+        // The macaroon is not a certificate
+        assert!(lnd("https://localhost:10009", macaroon.to_str().unwrap())
+            .validate()
+            .is_err());
+
+        let cln = |addr: &str| LightningMode::Cln {
+            cln_extension_addr: SafeUrl::parse(addr).unwrap(),
+        };
+        assert!(cln("http://127.0.0.1:3301").validate().is_ok());
+        assert!(cln("ws://127.0.0.1:3301").validate().is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
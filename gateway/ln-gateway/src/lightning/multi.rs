@@ -0,0 +1,567 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use bitcoin_hashes::{sha256, Hash};
+use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
+use fedimint_core::task::TaskGroup;
+use fedimint_core::{secp256k1, Amount};
+use fedimint_ln_common::PrunedInvoice;
+use futures::future::join_all;
+use lightning_invoice::Bolt11Invoice;
+use tracing::{info, warn};
+
+use super::cln::RouteHtlcStream;
+use super::lnd::{DEFAULT_LND_CONNECTION_POOL_SIZE, DEFAULT_LND_MAX_CONCURRENT_REQUESTS};
+use super::{
+    ChannelInfo, ChannelPolicy, GatewayLightningBuilder, ILnRpcClient, InvoiceState,
+    LightningBalances, LightningBuilder, LightningMode, LightningRpcError, MissionControlPair,
+    OpenChannelOptions, PeerInfo, ProbeResult, RouteEstimate,
+};
+use crate::db::PaymentNodeKey;
+use crate::gateway_lnrpc::{
+    CloseChannelsWithPeerResponse, CreateInvoiceRequest, CreateInvoiceResponse, EmptyResponse,
+    GetFundingAddressResponse, GetNodeInfoResponse, GetRouteHintsResponse, InterceptHtlcResponse,
+    PayInvoiceRequest, PayInvoiceResponse,
+};
+
+/// Additional lightning node the gateway can send outgoing payments from.
+/// Incoming payments are always received by the gateway's primary node, since
+/// federations route them to its public key.
+#[derive(Debug, Clone)]
+pub struct PaymentNode(pub LightningMode);
+
+impl FromStr for PaymentNode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mode = match s.split(',').collect::<Vec<_>>().as_slice() {
+            ["lnd", lnd_rpc_addr, lnd_tls_cert, lnd_macaroon] => LightningMode::Lnd {
+                lnd_rpc_addr: (*lnd_rpc_addr).to_string(),
+                lnd_tls_cert: (*lnd_tls_cert).to_string(),
+                lnd_macaroon: (*lnd_macaroon).to_string(),
+                lnd_connection_pool_size: DEFAULT_LND_CONNECTION_POOL_SIZE,
+                lnd_max_concurrent_requests: DEFAULT_LND_MAX_CONCURRENT_REQUESTS,
+            },
+            ["cln", cln_extension_addr] => LightningMode::Cln {
+                cln_extension_addr: cln_extension_addr
+                    .parse()
+                    .context("Invalid CLN extension address")?,
+            },
+            _ => bail!(
+                "Wrong format, please provide: lnd,<rpc addr>,<tls cert>,<macaroon> or \
+                 cln,<extension addr>"
+            ),
+        };
+
+        Ok(PaymentNode(mode))
+    }
+}
+
+impl PaymentNode {
+    pub async fn build(&self) -> Arc<dyn ILnRpcClient> {
+        let builder = GatewayLightningBuilder {
+            lightning_mode: self.0.clone(),
+        };
+
+        Arc::from(builder.build().await)
+    }
+}
+
+/// Sends outgoing payments from whichever of several lightning nodes is best
+/// able to pay them. All other requests, including the routing of intercepted
+/// HTLCs, are served by the primary node.
+///
+/// A payment only falls back to the next node if it failed before any HTLC
+/// was sent, since the recipient would settle the HTLCs of both nodes. The
+/// node a payment was sent from is persisted before sending, so that retries
+/// of the payment, also after a restart, are always sent from the same node,
+/// which knows whether the previous attempt is still in flight.
+#[derive(Debug)]
+pub struct MultiNodeLnRpcClient {
+    primary: Arc<dyn ILnRpcClient>,
+    payment_nodes: Vec<Arc<dyn ILnRpcClient>>,
+    db: Database,
+}
+
+impl MultiNodeLnRpcClient {
+    pub fn new(
+        primary: Arc<dyn ILnRpcClient>,
+        payment_nodes: Vec<Arc<dyn ILnRpcClient>>,
+        db: Database,
+    ) -> Self {
+        info!(
+            "Sending outgoing payments from {} lightning nodes",
+            payment_nodes.len() + 1
+        );
+
+        MultiNodeLnRpcClient {
+            primary,
+            payment_nodes,
+            db,
+        }
+    }
+
+    fn nodes(&self) -> impl Iterator<Item = &Arc<dyn ILnRpcClient>> {
+        std::iter::once(&self.primary).chain(&self.payment_nodes)
+    }
+
+    /// Returns the nodes to try for a payment of `amount` in the order they
+    /// should be tried in
+    async fn payment_candidates(
+        &self,
+        amount: Amount,
+        private: bool,
+    ) -> Vec<Arc<dyn ILnRpcClient>> {
+        let nodes = self
+            .nodes()
+            .filter(|node| !private || node.supports_private_payments())
+            .collect::<Vec<_>>();
+
+        let balances = join_all(nodes.iter().map(|node| node.get_balances())).await;
+
+        payment_order(&balances, amount)
+            .into_iter()
+            .map(|index| nodes[index].clone())
+            .collect()
+    }
+
+    /// Returns the node with the public key `node_id`, if it is reachable
+    async fn find_node(&self, node_id: secp256k1::PublicKey) -> Option<Arc<dyn ILnRpcClient>> {
+        for node in self.nodes() {
+            if fetch_node_id(node.as_ref()).await.ok() == Some(node_id) {
+                return Some(node.clone());
+            }
+        }
+
+        None
+    }
+
+    async fn pay_with_failover<F, Fut>(
+        &self,
+        payment_hash: sha256::Hash,
+        amount: Amount,
+        private: bool,
+        pay: F,
+    ) -> Result<PayInvoiceResponse, LightningRpcError>
+    where
+        F: Fn(Arc<dyn ILnRpcClient>) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<PayInvoiceResponse, LightningRpcError>> + Send,
+    {
+        let mut candidates = self.payment_candidates(amount, private).await;
+        let mut last_error = LightningRpcError::FailedToConnect;
+
+        // A previous attempt may still be in flight, which only the node it was
+        // sent from knows about
+        let previous_node_id = self
+            .db
+            .begin_transaction_nc()
+            .await
+            .get_value(&PaymentNodeKey { payment_hash })
+            .await;
+        if let Some(node_id) = previous_node_id {
+            let node =
+                self.find_node(node_id)
+                    .await
+                    .ok_or_else(|| LightningRpcError::FailedPayment {
+                        failure_reason: format!(
+                        "Lightning node {node_id} that payment {payment_hash} was sent from is \
+                         unavailable"
+                    ),
+                    })?;
+
+            match pay(node.clone()).await {
+                Err(error @ LightningRpcError::NoRoute { .. }) => {
+                    warn!(
+                        "Payment {payment_hash} failed on lightning node {node_id}, trying the \
+                         next lightning node: {error}"
+                    );
+                    candidates.retain(|candidate| !Arc::ptr_eq(candidate, &node));
+                    last_error = error;
+                }
+                result => return result,
+            }
+        }
+
+        for node in candidates {
+            let node_id = match fetch_node_id(node.as_ref()).await {
+                Ok(node_id) => node_id,
+                Err(error) => {
+                    warn!(
+                        "Skipping unreachable lightning node for payment {payment_hash}: {error}"
+                    );
+                    last_error = error;
+                    continue;
+                }
+            };
+
+            let mut dbtx = self.db.begin_transaction().await;
+            dbtx.insert_entry(&PaymentNodeKey { payment_hash }, &node_id)
+                .await;
+            dbtx.commit_tx().await;
+
+            match pay(node).await {
+                Err(error) if is_pre_send_failure(&error) => {
+                    warn!(
+                        "Payment {payment_hash} failed before it was sent, trying the next \
+                         lightning node: {error}"
+                    );
+                    last_error = error;
+                }
+                result => return result,
+            }
+        }
+
+        Err(last_error)
+    }
+}
+
+/// Returns the public key of `node`
+async fn fetch_node_id(node: &dyn ILnRpcClient) -> Result<secp256k1::PublicKey, LightningRpcError> {
+    let info = node.info().await?;
+
+    secp256k1::PublicKey::from_slice(&info.pub_key).map_err(|e| {
+        LightningRpcError::FailedToGetNodeInfo {
+            failure_reason: format!("Invalid public key: {e}"),
+        }
+    })
+}
+
+/// Returns true if a payment that failed with `error` left no HTLC in flight,
+/// so it can be sent from another node. Other errors, e.g. a dropped
+/// connection while the payment is in flight, are ambiguous.
+fn is_pre_send_failure(error: &LightningRpcError) -> bool {
+    matches!(
+        error,
+        LightningRpcError::FailedToConnect | LightningRpcError::NoRoute { .. }
+    )
+}
+
+/// Orders the nodes with the given `balances` for a payment of `amount`:
+/// nodes with enough outbound liquidity come first, then nodes that don't
+/// report their balances and finally nodes with too little liquidity. Nodes
+/// with more liquidity are preferred, unreachable nodes are skipped.
+fn payment_order(
+    balances: &[Result<LightningBalances, LightningRpcError>],
+    amount: Amount,
+) -> Vec<usize> {
+    let mut order = balances
+        .iter()
+        .enumerate()
+        .filter_map(|(index, balances)| match balances {
+            Ok(balances) if amount.msats <= balances.lightning_balance_msats => {
+                Some(((0, u64::MAX - balances.lightning_balance_msats), index))
+            }
+            Ok(balances) => Some(((2, u64::MAX - balances.lightning_balance_msats), index)),
+            Err(LightningRpcError::FailedToConnect) => None,
+            Err(_) => Some(((1, 0), index)),
+        })
+        .collect::<Vec<_>>();
+
+    // The sort is stable, so the primary node wins ties
+    order.sort_by_key(|(priority, _)| *priority);
+
+    order.into_iter().map(|(_, index)| index).collect()
+}
+
+#[async_trait]
+impl ILnRpcClient for MultiNodeLnRpcClient {
+    async fn info(&self) -> Result<GetNodeInfoResponse, LightningRpcError> {
+        self.primary.info().await
+    }
+
+    async fn routehints(
+        &self,
+        num_route_hints: usize,
+    ) -> Result<GetRouteHintsResponse, LightningRpcError> {
+        self.primary.routehints(num_route_hints).await
+    }
+
+    async fn pay(
+        &self,
+        invoice: PayInvoiceRequest,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        let amount = Bolt11Invoice::from_str(&invoice.invoice)
+            .ok()
+            .and_then(|invoice| invoice.amount_milli_satoshis())
+            .map_or(Amount::ZERO, Amount::from_msats);
+        let payment_hash = sha256::Hash::from_slice(&invoice.payment_hash).map_err(|e| {
+            LightningRpcError::FailedPayment {
+                failure_reason: format!("Invalid payment hash: {e}"),
+            }
+        })?;
+
+        self.pay_with_failover(payment_hash, amount, false, |node| {
+            let invoice = invoice.clone();
+            async move { node.pay(invoice).await }
+        })
+        .await
+    }
+
+    async fn pay_private(
+        &self,
+        invoice: PrunedInvoice,
+        max_delay: u64,
+        max_fee: Amount,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        self.pay_with_failover(invoice.payment_hash, invoice.amount, true, |node| {
+            let invoice = invoice.clone();
+            async move { node.pay_private(invoice, max_delay, max_fee).await }
+        })
+        .await
+    }
+
+    async fn retry_pay_private(
+        &self,
+        invoice: PrunedInvoice,
+        max_delay: u64,
+        max_fee: Amount,
+        randomize_path: bool,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        self.pay_with_failover(invoice.payment_hash, invoice.amount, true, |node| {
+            let invoice = invoice.clone();
+            async move {
+                node.retry_pay_private(invoice, max_delay, max_fee, randomize_path)
+                    .await
+            }
+        })
+        .await
+    }
+
+    async fn pay_keysend(
+        &self,
+        node_id: secp256k1::PublicKey,
+        amount: Amount,
+        max_fee: Amount,
+        tlv_records: BTreeMap<u64, Vec<u8>>,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        self.primary
+            .pay_keysend(node_id, amount, max_fee, tlv_records)
+            .await
+    }
+
+    fn supports_private_payments(&self) -> bool {
+        self.primary.supports_private_payments()
+    }
+
+    fn supports_invoice_lookups(&self) -> bool {
+        self.primary.supports_invoice_lookups()
+    }
+
+    async fn route_htlcs<'a>(
+        self: Box<Self>,
+        _task_group: &mut TaskGroup,
+    ) -> Result<(RouteHtlcStream<'a>, Arc<dyn ILnRpcClient>), LightningRpcError> {
+        Err(LightningRpcError::FailedToRouteHtlcs {
+            failure_reason: "HTLCs are routed by the primary lightning node".to_string(),
+        })
+    }
+
+    async fn complete_htlc(
+        &self,
+        htlc: InterceptHtlcResponse,
+    ) -> Result<EmptyResponse, LightningRpcError> {
+        self.primary.complete_htlc(htlc).await
+    }
+
+    async fn create_invoice(
+        &self,
+        create_invoice_request: CreateInvoiceRequest,
+    ) -> Result<CreateInvoiceResponse, LightningRpcError> {
+        self.primary.create_invoice(create_invoice_request).await
+    }
+
+    async fn create_hold_invoice(
+        &self,
+        create_invoice_request: CreateInvoiceRequest,
+    ) -> Result<CreateInvoiceResponse, LightningRpcError> {
+        self.primary
+            .create_hold_invoice(create_invoice_request)
+            .await
+    }
+
+    async fn settle_hold_invoice(
+        &self,
+        preimage: [u8; 32],
+    ) -> Result<EmptyResponse, LightningRpcError> {
+        self.primary.settle_hold_invoice(preimage).await
+    }
+
+    async fn cancel_hold_invoice(
+        &self,
+        payment_hash: sha256::Hash,
+    ) -> Result<EmptyResponse, LightningRpcError> {
+        self.primary.cancel_hold_invoice(payment_hash).await
+    }
+
+    async fn lookup_invoice(
+        &self,
+        payment_hash: sha256::Hash,
+    ) -> Result<InvoiceState, LightningRpcError> {
+        self.primary.lookup_invoice(payment_hash).await
+    }
+
+    async fn get_funding_address(&self) -> Result<GetFundingAddressResponse, LightningRpcError> {
+        self.primary.get_funding_address().await
+    }
+
+    async fn send_onchain(
+        &self,
+        address: bitcoin::Address,
+        amount: bitcoin::Amount,
+        sat_per_vbyte: Option<u64>,
+    ) -> Result<bitcoin::Txid, LightningRpcError> {
+        self.primary
+            .send_onchain(address, amount, sat_per_vbyte)
+            .await
+    }
+
+    async fn open_channel(
+        &self,
+        pubkey: secp256k1::PublicKey,
+        host: String,
+        channel_size_sats: u64,
+        push_amount_sats: u64,
+        options: OpenChannelOptions,
+    ) -> Result<EmptyResponse, LightningRpcError> {
+        self.primary
+            .open_channel(pubkey, host, channel_size_sats, push_amount_sats, options)
+            .await
+    }
+
+    async fn close_channels_with_peer(
+        &self,
+        pubkey: secp256k1::PublicKey,
+    ) -> Result<CloseChannelsWithPeerResponse, LightningRpcError> {
+        self.primary.close_channels_with_peer(pubkey).await
+    }
+
+    async fn list_active_channels(&self) -> Result<Vec<ChannelInfo>, LightningRpcError> {
+        self.primary.list_active_channels().await
+    }
+
+    async fn lsps_request(
+        &self,
+        lsp_node_id: secp256k1::PublicKey,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, LightningRpcError> {
+        self.primary.lsps_request(lsp_node_id, method, params).await
+    }
+
+    async fn query_mission_control(&self) -> Result<Vec<MissionControlPair>, LightningRpcError> {
+        self.primary.query_mission_control().await
+    }
+
+    async fn import_mission_control(
+        &self,
+        pairs: Vec<MissionControlPair>,
+        force: bool,
+    ) -> Result<(), LightningRpcError> {
+        self.primary.import_mission_control(pairs, force).await
+    }
+
+    async fn reset_mission_control(&self) -> Result<(), LightningRpcError> {
+        self.primary.reset_mission_control().await
+    }
+
+    async fn probe(
+        &self,
+        destination: secp256k1::PublicKey,
+        amount: Amount,
+        max_fee: Amount,
+    ) -> Result<ProbeResult, LightningRpcError> {
+        self.primary.probe(destination, amount, max_fee).await
+    }
+
+    async fn update_channel_policy(
+        &self,
+        short_channel_id: u64,
+        policy: ChannelPolicy,
+    ) -> Result<(), LightningRpcError> {
+        self.primary
+            .update_channel_policy(short_channel_id, policy)
+            .await
+    }
+
+    async fn connect_peer(
+        &self,
+        pubkey: secp256k1::PublicKey,
+        host: String,
+    ) -> Result<(), LightningRpcError> {
+        self.primary.connect_peer(pubkey, host).await
+    }
+
+    async fn disconnect_peer(&self, pubkey: secp256k1::PublicKey) -> Result<(), LightningRpcError> {
+        self.primary.disconnect_peer(pubkey).await
+    }
+
+    async fn list_peers(&self) -> Result<Vec<PeerInfo>, LightningRpcError> {
+        self.primary.list_peers().await
+    }
+
+    async fn query_route(
+        &self,
+        destination: secp256k1::PublicKey,
+        amount: Amount,
+    ) -> Result<RouteEstimate, LightningRpcError> {
+        self.primary.query_route(destination, amount).await
+    }
+
+    async fn get_balances(&self) -> Result<LightningBalances, LightningRpcError> {
+        self.primary.get_balances().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fedimint_core::Amount;
+
+    use super::{is_pre_send_failure, payment_order};
+    use crate::lightning::{LightningBalances, LightningRpcError};
+
+    fn balances(lightning_balance_msats: u64) -> Result<LightningBalances, LightningRpcError> {
+        Ok(LightningBalances {
+            onchain_balance_sats: 0,
+            lightning_balance_msats,
+        })
+    }
+
+    #[test]
+    fn prefers_nodes_with_enough_liquidity() {
+        let nodes = [
+            balances(1_000),
+            Err(LightningRpcError::FailedToConnect),
+            balances(5_000),
+            Err(LightningRpcError::FailedToGetBalances {
+                failure_reason: "Getting balances not supported".to_string(),
+            }),
+            balances(3_000),
+        ];
+
+        assert_eq!(
+            payment_order(&nodes, Amount::from_msats(2_000)),
+            vec![2, 4, 3, 0]
+        );
+
+        // Ties are broken in favor of the primary node
+        let nodes = [balances(5_000), balances(5_000)];
+        assert_eq!(payment_order(&nodes, Amount::from_msats(2_000)), vec![0, 1]);
+    }
+
+    #[test]
+    fn fails_over_only_if_no_htlc_was_sent() {
+        assert!(is_pre_send_failure(&LightningRpcError::FailedToConnect));
+        assert!(is_pre_send_failure(&LightningRpcError::NoRoute {
+            failure_reason: "FailureReasonNoRoute".to_string(),
+        }));
+
+        // The stream of an in-flight payment may have dropped
+        assert!(!is_pre_send_failure(&LightningRpcError::FailedPayment {
+            failure_reason: "Failed to get payment status".to_string(),
+        }));
+    }
+}
@@ -0,0 +1,297 @@
+//! A fake lightning backend for developing against the gateway API without a
+//! lightning node or regtest infrastructure. Outgoing payments succeed
+//! instantly and invoices created by the gateway are paid right away.
+//!
+//! Payments reveal [`SANDBOX_PREIMAGE`], so a federation only accepts the
+//! outgoing payments of invoices with its hash as payment hash. Never run a
+//! gateway in sandbox mode with real funds.
+
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bitcoin_hashes::{sha256, Hash};
+use fedimint_core::runtime::{sleep, spawn};
+use fedimint_core::task::TaskGroup;
+use fedimint_core::{secp256k1, Amount};
+use fedimint_ln_common::PrunedInvoice;
+use lightning_invoice::{Bolt11Invoice, Currency, InvoiceBuilder, PaymentSecret};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::info;
+
+use super::cln::{HtlcResult, RouteHtlcStream};
+use super::{ChannelInfo, ILnRpcClient, LightningBuilder, LightningRpcError, OpenChannelOptions};
+use crate::gateway_lnrpc::create_invoice_request::Description;
+use crate::gateway_lnrpc::{
+    CloseChannelsWithPeerResponse, CreateInvoiceRequest, CreateInvoiceResponse, EmptyResponse,
+    GetFundingAddressResponse, GetNodeInfoResponse, GetRouteHintsResponse, InterceptHtlcRequest,
+    InterceptHtlcResponse, PayInvoiceRequest, PayInvoiceResponse,
+};
+
+/// Preimage revealed by every payment the sandbox makes
+pub const SANDBOX_PREIMAGE: [u8; 32] = [0x5a; 32];
+
+/// Alias of the sandbox node, shown in the gateway's info
+const SANDBOX_ALIAS: &str = "SANDBOX - NO REAL PAYMENTS";
+
+const SANDBOX_BLOCK_HEIGHT: u32 = 1_000;
+
+/// Channel id of all simulated incoming payments
+const SANDBOX_CHANNEL_ID: u64 = 1;
+
+/// Delay before an invoice is paid, which gives the gateway time to register
+/// the contract it created the invoice for
+const SANDBOX_PAYMENT_DELAY: Duration = Duration::from_secs(1);
+
+/// Builds a new [`SandboxLightning`] whenever the gateway (re)connects to its
+/// lightning node
+#[derive(Debug, Clone, Copy)]
+pub struct SandboxLightningBuilder;
+
+#[async_trait]
+impl LightningBuilder for SandboxLightningBuilder {
+    async fn build(&self) -> Box<dyn ILnRpcClient> {
+        Box::new(SandboxLightning::new())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SandboxLightning {
+    node_secret_key: SecretKey,
+    htlc_sender: mpsc::Sender<HtlcResult>,
+    htlc_receiver: Arc<Mutex<Option<mpsc::Receiver<HtlcResult>>>>,
+    next_htlc_id: Arc<AtomicU64>,
+}
+
+impl SandboxLightning {
+    pub fn new() -> Self {
+        // The node key is fixed, so invoices stay valid across restarts
+        let node_secret_key =
+            SecretKey::from_slice(&sha256::Hash::hash(b"fedimint-gateway-sandbox").to_byte_array())
+                .expect("Hash is a valid secret key");
+        let (htlc_sender, htlc_receiver) = mpsc::channel(100);
+
+        SandboxLightning {
+            node_secret_key,
+            htlc_sender,
+            htlc_receiver: Arc::new(Mutex::new(Some(htlc_receiver))),
+            next_htlc_id: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn node_public_key(&self) -> PublicKey {
+        self.node_secret_key.public_key(&Secp256k1::signing_only())
+    }
+
+    fn payment_response(&self, amount_msat: u64) -> PayInvoiceResponse {
+        info!("Sandbox paid {amount_msat} msat");
+        PayInvoiceResponse {
+            preimage: SANDBOX_PREIMAGE.to_vec(),
+        }
+    }
+
+    /// Simulates an incoming payment of `amount_msat` to `payment_hash`, which
+    /// the gateway receives like an intercepted HTLC
+    fn pay_invoice_to_gateway(&self, payment_hash: Vec<u8>, amount_msat: u64) {
+        let htlc = InterceptHtlcRequest {
+            payment_hash,
+            incoming_amount_msat: amount_msat,
+            outgoing_amount_msat: amount_msat,
+            incoming_expiry: SANDBOX_BLOCK_HEIGHT + 144,
+            short_channel_id: None,
+            incoming_chan_id: SANDBOX_CHANNEL_ID,
+            htlc_id: self.next_htlc_id.fetch_add(1, Ordering::Relaxed),
+        };
+        let htlc_sender = self.htlc_sender.clone();
+
+        spawn("sandbox incoming payment", async move {
+            sleep(SANDBOX_PAYMENT_DELAY).await;
+            // The stream is gone if the gateway disconnected in the meantime
+            let _ = htlc_sender.send(Ok(htlc)).await;
+        });
+    }
+}
+
+impl Default for SandboxLightning {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ILnRpcClient for SandboxLightning {
+    async fn info(&self) -> Result<GetNodeInfoResponse, LightningRpcError> {
+        Ok(GetNodeInfoResponse {
+            pub_key: self.node_public_key().serialize().to_vec(),
+            alias: SANDBOX_ALIAS.to_string(),
+            network: "regtest".to_string(),
+            block_height: SANDBOX_BLOCK_HEIGHT,
+            synced_to_chain: true,
+        })
+    }
+
+    async fn routehints(
+        &self,
+        _num_route_hints: usize,
+    ) -> Result<GetRouteHintsResponse, LightningRpcError> {
+        Ok(GetRouteHintsResponse {
+            route_hints: vec![],
+        })
+    }
+
+    async fn pay(
+        &self,
+        invoice: PayInvoiceRequest,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        let invoice = Bolt11Invoice::from_str(&invoice.invoice).map_err(|e| {
+            LightningRpcError::FailedPayment {
+                failure_reason: format!("Invalid invoice {e:?}"),
+            }
+        })?;
+
+        Ok(self.payment_response(invoice.amount_milli_satoshis().unwrap_or(0)))
+    }
+
+    fn supports_private_payments(&self) -> bool {
+        true
+    }
+
+    async fn pay_private(
+        &self,
+        invoice: PrunedInvoice,
+        _max_delay: u64,
+        _max_fee: Amount,
+    ) -> Result<PayInvoiceResponse, LightningRpcError> {
+        Ok(self.payment_response(invoice.amount.msats))
+    }
+
+    async fn route_htlcs<'a>(
+        self: Box<Self>,
+        _task_group: &mut TaskGroup,
+    ) -> Result<(RouteHtlcStream<'a>, Arc<dyn ILnRpcClient>), LightningRpcError> {
+        let htlc_receiver = self.htlc_receiver.lock().expect("poisoned").take().ok_or(
+            LightningRpcError::FailedToRouteHtlcs {
+                failure_reason: "HTLCs are already routed".to_string(),
+            },
+        )?;
+
+        Ok((
+            Box::pin(ReceiverStream::new(htlc_receiver)),
+            Arc::new(*self),
+        ))
+    }
+
+    async fn complete_htlc(
+        &self,
+        _htlc: InterceptHtlcResponse,
+    ) -> Result<EmptyResponse, LightningRpcError> {
+        Ok(EmptyResponse {})
+    }
+
+    async fn create_invoice(
+        &self,
+        create_invoice_request: CreateInvoiceRequest,
+    ) -> Result<CreateInvoiceResponse, LightningRpcError> {
+        let payment_hash =
+            sha256::Hash::from_slice(&create_invoice_request.payment_hash).map_err(|e| {
+                LightningRpcError::FailedToGetInvoice {
+                    failure_reason: format!("Invalid payment hash {e:?}"),
+                }
+            })?;
+
+        let builder = InvoiceBuilder::new(Currency::Regtest)
+            .payment_hash(payment_hash)
+            .current_timestamp()
+            .min_final_cltv_expiry_delta(18)
+            .payment_secret(PaymentSecret([0; 32]))
+            .amount_milli_satoshis(create_invoice_request.amount_msat)
+            .expiry_time(Duration::from_secs(u64::from(
+                create_invoice_request.expiry_secs,
+            )));
+
+        let ctx = Secp256k1::signing_only();
+        let sign = |m: &secp256k1::Message| ctx.sign_ecdsa_recoverable(m, &self.node_secret_key);
+        let invoice = match create_invoice_request.description {
+            Some(Description::Hash(hash)) => builder
+                .description_hash(sha256::Hash::from_slice(&hash).map_err(|e| {
+                    LightningRpcError::FailedToGetInvoice {
+                        failure_reason: format!("Invalid description hash {e:?}"),
+                    }
+                })?)
+                .build_signed(sign),
+            Some(Description::Direct(description)) => {
+                builder.description(description).build_signed(sign)
+            }
+            None => builder.description(String::new()).build_signed(sign),
+        }
+        .map_err(|e| LightningRpcError::FailedToGetInvoice {
+            failure_reason: format!("Failed to create invoice {e:?}"),
+        })?;
+
+        self.pay_invoice_to_gateway(
+            create_invoice_request.payment_hash,
+            create_invoice_request.amount_msat,
+        );
+
+        Ok(CreateInvoiceResponse {
+            invoice: invoice.to_string(),
+        })
+    }
+
+    async fn create_hold_invoice(
+        &self,
+        create_invoice_request: CreateInvoiceRequest,
+    ) -> Result<CreateInvoiceResponse, LightningRpcError> {
+        self.create_invoice(create_invoice_request).await
+    }
+
+    async fn settle_hold_invoice(
+        &self,
+        _preimage: [u8; 32],
+    ) -> Result<EmptyResponse, LightningRpcError> {
+        Ok(EmptyResponse {})
+    }
+
+    async fn cancel_hold_invoice(
+        &self,
+        _payment_hash: sha256::Hash,
+    ) -> Result<EmptyResponse, LightningRpcError> {
+        Ok(EmptyResponse {})
+    }
+
+    async fn get_funding_address(&self) -> Result<GetFundingAddressResponse, LightningRpcError> {
+        Err(LightningRpcError::FailedToGetFundingAddress {
+            failure_reason: "The sandbox has no on-chain wallet".to_string(),
+        })
+    }
+
+    async fn open_channel(
+        &self,
+        _pubkey: secp256k1::PublicKey,
+        _host: String,
+        _channel_size_sats: u64,
+        _push_amount_sats: u64,
+        _options: OpenChannelOptions,
+    ) -> Result<EmptyResponse, LightningRpcError> {
+        Err(LightningRpcError::FailedToOpenChannel {
+            failure_reason: "The sandbox has no channels".to_string(),
+        })
+    }
+
+    async fn close_channels_with_peer(
+        &self,
+        _pubkey: secp256k1::PublicKey,
+    ) -> Result<CloseChannelsWithPeerResponse, LightningRpcError> {
+        Ok(CloseChannelsWithPeerResponse {
+            num_channels_closed: 0,
+        })
+    }
+
+    async fn list_active_channels(&self) -> Result<Vec<ChannelInfo>, LightningRpcError> {
+        Ok(vec![])
+    }
+}
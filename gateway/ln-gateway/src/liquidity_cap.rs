@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::Amount;
+use serde::{Deserialize, Serialize};
+
+/// Time between two checks whether ecash above a cap has to be withdrawn
+pub const LIQUIDITY_CAP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Excess ecash is only withdrawn once it reaches this amount, so the on-chain
+/// fees stay small compared to the withdrawn amount
+const MIN_WITHDRAWAL_SATS: u64 = 10_000;
+
+/// Maximum ecash balance the gateway holds in a federation, which limits its
+/// exposure to a single federation. Once the balance reaches the cap, the
+/// gateway stops quoting outgoing payments for the federation, since these
+/// swap more of the federation's ecash into the gateway's lightning
+/// liquidity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct LiquidityCap {
+    pub max_balance_msat: u64,
+    /// Withdraw the ecash above the cap to the lightning node's on-chain
+    /// wallet
+    pub withdraw_excess: bool,
+}
+
+impl LiquidityCap {
+    pub fn is_reached(&self, balance: Amount) -> bool {
+        balance.msats >= self.max_balance_msat
+    }
+
+    /// Returns the amount to withdraw to bring `balance` back to the cap, if
+    /// excess ecash should be withdrawn
+    pub fn excess_withdrawal(&self, balance: Amount) -> Option<bitcoin::Amount> {
+        if !self.withdraw_excess {
+            return None;
+        }
+
+        let excess_sats = balance.msats.saturating_sub(self.max_balance_msat) / 1000;
+        (excess_sats >= MIN_WITHDRAWAL_SATS).then(|| bitcoin::Amount::from_sat(excess_sats))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fedimint_core::Amount;
+
+    use super::LiquidityCap;
+
+    #[test]
+    fn withdraws_excess_above_cap() {
+        let cap = LiquidityCap {
+            max_balance_msat: 1_000_000_000,
+            withdraw_excess: true,
+        };
+
+        assert!(!cap.is_reached(Amount::from_msats(999_999_999)));
+        assert!(cap.is_reached(Amount::from_msats(1_000_000_000)));
+
+        // Too little to be worth the on-chain fees
+        assert_eq!(
+            cap.excess_withdrawal(Amount::from_msats(1_009_999_999)),
+            None
+        );
+        assert_eq!(
+            cap.excess_withdrawal(Amount::from_msats(1_050_000_000)),
+            Some(bitcoin::Amount::from_sat(50_000))
+        );
+
+        let keep_excess = LiquidityCap {
+            withdraw_excess: false,
+            ..cap
+        };
+        assert_eq!(
+            keep_excess.excess_withdrawal(Amount::from_msats(2_000_000_000)),
+            None
+        );
+    }
+}
@@ -0,0 +1,1101 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use axum::extract::{Path, Query};
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use bitcoin_hashes::{sha256, Hash};
+use fedimint_client::ClientHandleArc;
+use fedimint_core::config::FederationId;
+use fedimint_core::core::OperationId;
+use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::time::duration_since_epoch;
+use fedimint_core::Amount;
+use fedimint_mint_client::{
+    MintClientModule, MintCommonInit, MintOperationMeta, MintOperationMetaVariant, OOBNotes,
+};
+use futures::StreamExt;
+use lightning_invoice::Bolt11Invoice;
+use rand::rngs::OsRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tower_http::cors::CorsLayer;
+use tracing::{error, info, instrument, warn};
+
+use crate::db::{
+    LightningAddressInvoiceKey, LightningAddressInvoiceKeyPrefix, LightningAddressIssuanceKey,
+    LightningAddressNotesKey, LightningAddressNotesUserPrefix, LightningAddressPaymentKey,
+    LightningAddressUserKey, LightningAddressUserKeyPrefix,
+};
+use crate::gateway_lnrpc::create_invoice_request::Description;
+use crate::gateway_lnrpc::CreateInvoiceRequest;
+use crate::lightning::{InvoiceState, LightningRpcError};
+use crate::rpc::{
+    ClaimLightningAddressPayload, RegisterLightningAddressPayload, RemoveLightningAddressPayload,
+};
+use crate::{Gateway, GatewayError, LightningContext};
+
+/// Route of the LNURL-pay request of a lightning address (LUD-16)
+pub const LNURLP_ENDPOINT: &str = "/.well-known/lnurlp/:username";
+
+/// Route of the LNURL-pay request that LNURLs of a user encode (LUD-06)
+pub const LNURLP_PAY_ENDPOINT: &str = "/lnurlp/:username";
+
+/// Route the wallet of the payer requests the invoice from (LUD-06)
+pub const LNURLP_CALLBACK_ENDPOINT: &str = "/lnurlp/:username/callback";
+
+/// Route the wallet of the payer polls until the invoice is paid (LUD-21)
+pub const LNURLP_VERIFY_ENDPOINT: &str = "/lnurlp/:username/verify/:payment_hash";
+
+/// Time between two checks whether invoices of lightning addresses were paid
+pub const LIGHTNING_ADDRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Time until invoices of lightning addresses expire
+pub const LIGHTNING_ADDRESS_INVOICE_EXPIRY_SECS: u32 = 10 * 60;
+
+/// Time after their expiry that unpaid invoices are forgotten, even if the
+/// lightning node can't be asked about them anymore
+pub const LIGHTNING_ADDRESS_INVOICE_RETENTION: Duration = Duration::from_secs(60 * 60);
+
+/// Maximum number of unpaid invoices per lightning address. Since the callback
+/// is public, this limits how fast anyone can create invoices for an address.
+pub const LIGHTNING_ADDRESS_MAX_OPEN_INVOICES: usize = 20;
+
+/// Time after which the gateway takes back ecash issued to a lightning address
+/// that was neither claimed nor reissued
+pub const LIGHTNING_ADDRESS_NOTES_EXPIRY: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// User of a lightning address served by the gateway. Payments to the address
+/// are received by the lightning node, credited to the user and then issued to
+/// them as ecash of the federation, which the operator claims for the user.
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct LightningAddressUser {
+    pub username: String,
+    pub federation_id: FederationId,
+    /// Shown by the payer's wallet
+    pub description: String,
+    pub min_sendable_msat: u64,
+    pub max_sendable_msat: u64,
+    /// Total amount of the payments received for the user
+    pub credited: Amount,
+    /// Total amount of the ecash issued to the user. May exceed `credited` by
+    /// the change of the notes, which is deducted from the next payment.
+    pub issued: Amount,
+    pub created_at: SystemTime,
+}
+
+impl LightningAddressUser {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.username.is_empty()
+            || !self.username.chars().all(|c| {
+                c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_' | '.')
+            })
+        {
+            return Err(format!(
+                "Username {} may only contain a-z, 0-9, '-', '_' and '.'",
+                self.username
+            ));
+        }
+
+        if self.min_sendable_msat == 0 || self.max_sendable_msat < self.min_sendable_msat {
+            return Err("The sendable amounts are not a valid range".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Amount the gateway still has to issue to the user as ecash
+    pub fn balance(&self) -> Amount {
+        self.credited.saturating_sub(self.issued)
+    }
+
+    /// Metadata of the pay request, which the invoices commit to with their
+    /// description hash
+    pub fn metadata(&self, domain: &str) -> String {
+        json!([
+            ["text/plain", self.description],
+            ["text/identifier", format!("{}@{domain}", self.username)],
+        ])
+        .to_string()
+    }
+
+    /// URL the payer's wallet verifies the payment of `invoice` at
+    pub fn verify_url(&self, domain: &str, invoice: &Bolt11Invoice) -> String {
+        format!(
+            "https://{domain}/lnurlp/{}/verify/{}",
+            self.username,
+            invoice.payment_hash()
+        )
+    }
+
+    pub fn pay_request(&self, domain: &str) -> PayRequest {
+        PayRequest {
+            callback: format!("https://{domain}/lnurlp/{}/callback", self.username),
+            max_sendable: self.max_sendable_msat,
+            min_sendable: self.min_sendable_msat,
+            metadata: self.metadata(domain),
+            tag: "payRequest".to_string(),
+        }
+    }
+}
+
+/// Invoice created for a payment to a lightning address that has not been
+/// paid yet
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable)]
+pub struct LightningAddressInvoice {
+    pub username: String,
+    pub invoice: Bolt11Invoice,
+    /// Preimage the payment to the invoice is settled with
+    pub preimage: [u8; 32],
+}
+
+/// Ecash that is being issued to the user of a lightning address. It is
+/// recorded before the notes are spent, so that notes spent right before the
+/// gateway stopped are still found in the operation log of the federation.
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable)]
+pub struct LightningAddressIssuance {
+    pub federation_id: FederationId,
+    /// Amount already added to `issued` of the user
+    pub amount: Amount,
+    pub started_at: SystemTime,
+}
+
+/// Ecash issued to the user of a lightning address. It is kept until the
+/// operator acknowledges that it was passed on to the user.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LightningAddressNotes {
+    pub operation_id: OperationId,
+    pub notes: OOBNotes,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PayRequest {
+    pub callback: String,
+    pub max_sendable: u64,
+    pub min_sendable: u64,
+    pub metadata: String,
+    pub tag: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PayCallbackQuery {
+    /// The query parameter the payer's wallet sends the amount in
+    pub amount: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PayRequestInvoice {
+    pub pr: Bolt11Invoice,
+    pub routes: Vec<String>,
+    /// URL to verify the payment at, if the service supports LUD-21
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verify: Option<String>,
+}
+
+/// Response of the verify URL of an invoice (LUD-21)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyResponse {
+    pub status: String,
+    pub settled: bool,
+    /// Hex encoded preimage, only known once the invoice is settled
+    pub preimage: Option<String>,
+    pub pr: Bolt11Invoice,
+}
+
+impl VerifyResponse {
+    pub fn new(invoice: Bolt11Invoice, preimage: Option<[u8; 32]>) -> Self {
+        VerifyResponse {
+            status: "OK".to_string(),
+            settled: preimage.is_some(),
+            preimage: preimage.map(hex::encode),
+            pr: invoice,
+        }
+    }
+}
+
+/// Error response of LNURL requests, which is returned with status 200
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LnurlError {
+    pub status: String,
+    pub reason: String,
+}
+
+impl LnurlError {
+    pub fn new(reason: impl Into<String>) -> Self {
+        LnurlError {
+            status: "ERROR".to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Service that receives payments of its users via LNURL-pay (LUD-06) and
+/// lightning addresses (LUD-16)
+#[async_trait]
+pub trait LnurlPayService: Send + Sync + 'static {
+    /// Returns the pay request of the user
+    async fn pay_request(&self, username: &str) -> Result<PayRequest, LnurlError>;
+
+    /// Creates an invoice of `amount_msat` for a payment to the user
+    async fn pay_callback(
+        &self,
+        username: &str,
+        amount_msat: u64,
+    ) -> Result<PayRequestInvoice, LnurlError>;
+}
+
+/// Service that lets payers verify that their payment to one of its invoices
+/// was settled (LUD-21)
+#[async_trait]
+pub trait LnurlVerifyService: Send + Sync + 'static {
+    async fn verify(
+        &self,
+        username: &str,
+        payment_hash: sha256::Hash,
+    ) -> Result<VerifyResponse, LnurlError>;
+}
+
+/// Builds the un-authenticated routes of a LNURL server, so that services
+/// only implement the protocols they support and share the protocol handling.
+/// Errors of the services are returned in the LNURL format with status 200,
+/// so that the payer's wallet can show them.
+#[derive(Debug, Default)]
+pub struct LnurlRouter {
+    router: Router,
+}
+
+impl LnurlRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serves the pay requests and invoices of `service`
+    pub fn pay<S: LnurlPayService>(self, service: Arc<S>) -> Self {
+        let routes = Router::new()
+            .route(LNURLP_ENDPOINT, get(pay_request::<S>))
+            .route(LNURLP_PAY_ENDPOINT, get(pay_request::<S>))
+            .route(LNURLP_CALLBACK_ENDPOINT, get(pay_callback::<S>))
+            .layer(Extension(service));
+
+        LnurlRouter {
+            router: self.router.merge(routes),
+        }
+    }
+
+    /// Serves the verify URLs of the invoices of `service`
+    pub fn verify<S: LnurlVerifyService>(self, service: Arc<S>) -> Self {
+        let routes = Router::new()
+            .route(LNURLP_VERIFY_ENDPOINT, get(verify::<S>))
+            .layer(Extension(service));
+
+        LnurlRouter {
+            router: self.router.merge(routes),
+        }
+    }
+
+    pub fn build(self) -> Router {
+        // Wallets request LNURLs from web pages of other origins
+        self.router.layer(CorsLayer::permissive())
+    }
+}
+
+fn lnurl_response<T: Serialize>(result: Result<T, LnurlError>) -> Json<Value> {
+    match result {
+        Ok(response) => Json(json!(response)),
+        Err(error) => Json(json!(error)),
+    }
+}
+
+#[instrument(skip_all, fields(%username))]
+async fn pay_request<S: LnurlPayService>(
+    Extension(service): Extension<Arc<S>>,
+    Path(username): Path<String>,
+) -> Json<Value> {
+    lnurl_response(service.pay_request(&username).await)
+}
+
+#[instrument(skip_all, fields(%username, ?query))]
+async fn pay_callback<S: LnurlPayService>(
+    Extension(service): Extension<Arc<S>>,
+    Path(username): Path<String>,
+    Query(query): Query<PayCallbackQuery>,
+) -> Json<Value> {
+    lnurl_response(service.pay_callback(&username, query.amount).await)
+}
+
+#[instrument(skip_all, fields(%username, %payment_hash))]
+async fn verify<S: LnurlVerifyService>(
+    Extension(service): Extension<Arc<S>>,
+    Path((username, payment_hash)): Path<(String, sha256::Hash)>,
+) -> Json<Value> {
+    lnurl_response(service.verify(&username, payment_hash).await)
+}
+
+/// Like `GatewayError::into_response`, only invalid requests are explained to
+/// the payer
+fn lnurl_error(error: GatewayError) -> LnurlError {
+    match error {
+        GatewayError::InvalidMetadata(reason) => LnurlError::new(reason),
+        error => {
+            error!("Error handling LNURL request: {error}");
+            LnurlError::new("An internal gateway error occurred")
+        }
+    }
+}
+
+#[async_trait]
+impl LnurlPayService for Gateway {
+    async fn pay_request(&self, username: &str) -> Result<PayRequest, LnurlError> {
+        self.handle_lnurl_pay_request(username)
+            .await
+            .map_err(lnurl_error)
+    }
+
+    async fn pay_callback(
+        &self,
+        username: &str,
+        amount_msat: u64,
+    ) -> Result<PayRequestInvoice, LnurlError> {
+        self.handle_lnurl_pay_callback(username, amount_msat)
+            .await
+            .map_err(lnurl_error)
+    }
+}
+
+#[async_trait]
+impl LnurlVerifyService for Gateway {
+    async fn verify(
+        &self,
+        username: &str,
+        payment_hash: sha256::Hash,
+    ) -> Result<VerifyResponse, LnurlError> {
+        self.handle_lnurl_verify(username, payment_hash)
+            .await
+            .map_err(lnurl_error)
+    }
+}
+
+/// Registers a lightning address for a connected federation. If the username
+/// is registered already, its settings are updated and the amounts credited
+/// and issued to it are kept.
+pub async fn register_lightning_address(
+    db: &Database,
+    RegisterLightningAddressPayload {
+        username,
+        federation_id,
+        description,
+        min_sendable_msat,
+        max_sendable_msat,
+    }: RegisterLightningAddressPayload,
+) -> Result<LightningAddressUser, GatewayError> {
+    let mut dbtx = db.begin_transaction().await;
+    let key = LightningAddressUserKey {
+        username: username.clone(),
+    };
+    let is_issuing = dbtx
+        .get_value(&LightningAddressIssuanceKey {
+            username: username.clone(),
+        })
+        .await
+        .is_some();
+    let (credited, issued, created_at) = match dbtx.get_value(&key).await {
+        Some(user)
+            if user.federation_id != federation_id
+                && (user.balance() != Amount::ZERO || is_issuing) =>
+        {
+            return Err(GatewayError::InvalidMetadata(format!(
+                "Lightning address {username} still has a balance in federation {}",
+                user.federation_id
+            )));
+        }
+        Some(user) => (user.credited, user.issued, user.created_at),
+        None => (Amount::ZERO, Amount::ZERO, fedimint_core::time::now()),
+    };
+
+    let user = LightningAddressUser {
+        username,
+        federation_id,
+        description,
+        min_sendable_msat,
+        max_sendable_msat,
+        credited,
+        issued,
+        created_at,
+    };
+    user.validate().map_err(GatewayError::InvalidMetadata)?;
+
+    dbtx.insert_entry(&key, &user).await;
+    dbtx.commit_tx().await;
+
+    info!(
+        "Registered lightning address {} for federation {federation_id}",
+        user.username
+    );
+
+    Ok(user)
+}
+
+/// Removes a lightning address together with its unpaid invoices. Fails while
+/// ecash is still to be issued to or claimed for the user.
+pub async fn remove_lightning_address(
+    db: &Database,
+    RemoveLightningAddressPayload { username }: RemoveLightningAddressPayload,
+) -> Result<(), GatewayError> {
+    let mut dbtx = db.begin_transaction().await;
+    let user = dbtx
+        .remove_entry(&LightningAddressUserKey {
+            username: username.clone(),
+        })
+        .await
+        .ok_or(GatewayError::InvalidMetadata(format!(
+            "No lightning address with username {username}"
+        )))?;
+
+    let has_unclaimed_notes = dbtx
+        .find_by_prefix(&LightningAddressNotesUserPrefix {
+            username: username.clone(),
+        })
+        .await
+        .next()
+        .await
+        .is_some();
+    let is_issuing = dbtx
+        .get_value(&LightningAddressIssuanceKey {
+            username: username.clone(),
+        })
+        .await
+        .is_some();
+    if user.balance() != Amount::ZERO || has_unclaimed_notes || is_issuing {
+        return Err(GatewayError::InvalidMetadata(format!(
+            "Claim the ecash of lightning address {username} before removing it"
+        )));
+    }
+
+    let invoices = dbtx
+        .find_by_prefix(&LightningAddressInvoiceKeyPrefix)
+        .await
+        .filter(|(_, invoice)| std::future::ready(invoice.username == username))
+        .map(|(key, _)| key)
+        .collect::<Vec<_>>()
+        .await;
+    for key in invoices {
+        dbtx.remove_entry(&key).await;
+    }
+    dbtx.commit_tx().await;
+
+    info!("Removed lightning address {username}");
+
+    Ok(())
+}
+
+pub async fn list_lightning_addresses(db: &Database) -> Vec<LightningAddressUser> {
+    db.begin_transaction_nc()
+        .await
+        .find_by_prefix(&LightningAddressUserKeyPrefix)
+        .await
+        .map(|(_, user)| user)
+        .collect::<Vec<_>>()
+        .await
+}
+
+/// Forgets the notes the operator acknowledged to have passed on to the user
+/// of a lightning address and returns the ones that were not acknowledged
+/// yet. Notes are only forgotten once acknowledged, so that a claim whose
+/// response is lost can be repeated.
+pub async fn claim_lightning_address(
+    db: &Database,
+    ClaimLightningAddressPayload {
+        username,
+        acknowledge,
+    }: ClaimLightningAddressPayload,
+) -> Result<Vec<LightningAddressNotes>, GatewayError> {
+    let mut dbtx = db.begin_transaction().await;
+    for operation_id in &acknowledge {
+        dbtx.remove_entry(&LightningAddressNotesKey {
+            username: username.clone(),
+            operation_id: *operation_id,
+        })
+        .await
+        .ok_or(GatewayError::InvalidMetadata(format!(
+            "No unclaimed notes of lightning address {username} with operation id {}",
+            operation_id.fmt_full()
+        )))?;
+    }
+
+    let notes = dbtx
+        .find_by_prefix(&LightningAddressNotesUserPrefix {
+            username: username.clone(),
+        })
+        .await
+        .map(|(key, notes)| LightningAddressNotes {
+            operation_id: key.operation_id,
+            notes,
+        })
+        .collect::<Vec<_>>()
+        .await;
+    dbtx.commit_tx().await;
+
+    info!(
+        "Acknowledged {} and claimed {} notes of lightning address {username}",
+        acknowledge.len(),
+        notes.len()
+    );
+
+    Ok(notes)
+}
+
+/// Issues the balance of a lightning address as ecash from the gateway's
+/// balance in the federation. The issuance is recorded before the notes are
+/// spent and the notes are kept until their claim is acknowledged.
+pub async fn issue_ecash(
+    client: &ClientHandleArc,
+    db: &Database,
+    username: &str,
+) -> Result<(), GatewayError> {
+    let issuance_key = LightningAddressIssuanceKey {
+        username: username.to_string(),
+    };
+    let user_key = LightningAddressUserKey {
+        username: username.to_string(),
+    };
+
+    let mut dbtx = db.begin_transaction().await;
+    if let Some(issuance) = dbtx.get_value(&issuance_key).await {
+        // The gateway stopped while issuing ecash to the user before
+        return recover_issuance(client, db, username, issuance).await;
+    }
+
+    let Some(mut user) = dbtx.get_value(&user_key).await else {
+        return Ok(());
+    };
+    let issuance = LightningAddressIssuance {
+        federation_id: user.federation_id,
+        amount: user.balance(),
+        started_at: fedimint_core::time::now(),
+    };
+    if issuance.amount == Amount::ZERO {
+        return Ok(());
+    }
+    user.issued += issuance.amount;
+    dbtx.insert_entry(&user_key, &user).await;
+    dbtx.insert_new_entry(&issuance_key, &issuance).await;
+    dbtx.commit_tx().await;
+
+    let spent = client
+        .get_first_module::<MintClientModule>()
+        .spend_notes(
+            issuance.amount,
+            LIGHTNING_ADDRESS_NOTES_EXPIRY,
+            false,
+            json!({ "lightning_address": username }),
+        )
+        .await;
+
+    match spent {
+        Ok((operation_id, notes)) => {
+            complete_issuance(db, username, &issuance, Some((operation_id, notes))).await;
+            Ok(())
+        }
+        Err(e) => {
+            complete_issuance(db, username, &issuance, None).await;
+            Err(e.into())
+        }
+    }
+}
+
+/// Completes an issuance the gateway stopped in the middle of, with the notes
+/// in the operation log of the federation if they were spent before it stopped
+async fn recover_issuance(
+    client: &ClientHandleArc,
+    db: &Database,
+    username: &str,
+    issuance: LightningAddressIssuance,
+) -> Result<(), GatewayError> {
+    let meta = json!({ "lightning_address": username });
+    let mut start_after = None;
+    let spent = 'operations: loop {
+        let operations = client
+            .operation_log()
+            .list_operations(100, start_after)
+            .await;
+        let Some((last, _)) = operations.last() else {
+            break None;
+        };
+        start_after = Some(*last);
+
+        for (key, entry) in operations {
+            if key.creation_time < issuance.started_at {
+                break 'operations None;
+            }
+
+            if entry.operation_module_kind() != MintCommonInit::KIND.as_str() {
+                continue;
+            }
+
+            let operation = entry.meta::<MintOperationMeta>();
+            if let MintOperationMetaVariant::SpendOOB { oob_notes, .. } = operation.variant {
+                if operation.extra_meta == meta {
+                    break 'operations Some((key.operation_id, oob_notes));
+                }
+            }
+        }
+    };
+
+    warn!(
+        "Recovering the issuance of {} to lightning address {username}, notes spent: {}",
+        issuance.amount,
+        spent.is_some()
+    );
+
+    complete_issuance(db, username, &issuance, spent).await;
+
+    Ok(())
+}
+
+/// Records the notes spent for an issuance, or gives the amount back to the
+/// user if no notes were spent
+async fn complete_issuance(
+    db: &Database,
+    username: &str,
+    issuance: &LightningAddressIssuance,
+    spent: Option<(OperationId, OOBNotes)>,
+) {
+    let user_key = LightningAddressUserKey {
+        username: username.to_string(),
+    };
+
+    let mut dbtx = db.begin_transaction().await;
+    dbtx.remove_entry(&LightningAddressIssuanceKey {
+        username: username.to_string(),
+    })
+    .await;
+    let mut user = dbtx.get_value(&user_key).await;
+
+    match spent {
+        Some((operation_id, notes)) => {
+            // The notes may exceed the amount by their change, which is
+            // deducted from the next payment
+            if let Some(user) = &mut user {
+                user.issued += notes.total_amount().saturating_sub(issuance.amount);
+            }
+            dbtx.insert_new_entry(
+                &LightningAddressNotesKey {
+                    username: username.to_string(),
+                    operation_id,
+                },
+                &notes,
+            )
+            .await;
+
+            info!(
+                "Issued {} to lightning address {username} in federation {}",
+                notes.total_amount(),
+                issuance.federation_id
+            );
+        }
+        None => {
+            if let Some(user) = &mut user {
+                user.issued = user.issued.saturating_sub(issuance.amount);
+            }
+        }
+    }
+
+    if let Some(user) = user {
+        dbtx.insert_entry(&user_key, &user).await;
+    }
+    dbtx.commit_tx().await;
+}
+
+/// Loads the user of a lightning address and the domain it is served for
+pub async fn load_lightning_address_user<'a>(
+    db: &Database,
+    domain: Option<&'a str>,
+    username: &str,
+) -> Result<(LightningAddressUser, &'a str), GatewayError> {
+    let domain = domain.ok_or(GatewayError::InvalidMetadata(
+        "Lightning addresses are not enabled".to_string(),
+    ))?;
+
+    let user = db
+        .begin_transaction_nc()
+        .await
+        .get_value(&LightningAddressUserKey {
+            username: username.to_string(),
+        })
+        .await
+        .ok_or(GatewayError::InvalidMetadata(format!(
+            "Unknown lightning address {username}@{domain}"
+        )))?;
+
+    Ok((user, domain))
+}
+
+/// Creates an invoice on the lightning node for a payment of `amount_msat` to
+/// a lightning address. Once it is paid, `settle_invoices` credits the payment
+/// to the user.
+pub async fn create_invoice(
+    lightning_context: &LightningContext,
+    db: &Database,
+    domain: &str,
+    user: LightningAddressUser,
+    amount_msat: u64,
+) -> Result<PayRequestInvoice, GatewayError> {
+    if amount_msat < user.min_sendable_msat || user.max_sendable_msat < amount_msat {
+        return Err(GatewayError::InvalidMetadata(format!(
+            "The amount has to be between {} and {} msat",
+            user.min_sendable_msat, user.max_sendable_msat
+        )));
+    }
+
+    let open_invoices = db
+        .begin_transaction_nc()
+        .await
+        .find_by_prefix(&LightningAddressInvoiceKeyPrefix)
+        .await
+        .filter(|(_, invoice)| std::future::ready(invoice.username == user.username))
+        .count()
+        .await;
+    if LIGHTNING_ADDRESS_MAX_OPEN_INVOICES <= open_invoices {
+        return Err(GatewayError::InvalidMetadata(
+            "Too many unpaid invoices for this lightning address, try again later".to_string(),
+        ));
+    }
+
+    let lnrpc = &lightning_context.lnrpc;
+    if !lnrpc.supports_invoice_lookups() {
+        return Err(GatewayError::LightningRpcError(
+            LightningRpcError::FailedToGetInvoice {
+                failure_reason: "The lightning node can't settle lightning address payments"
+                    .to_string(),
+            },
+        ));
+    }
+
+    let preimage: [u8; 32] = OsRng.gen();
+    let description_hash = sha256::Hash::hash(user.metadata(domain).as_bytes());
+    let response = lnrpc
+        .create_invoice(CreateInvoiceRequest {
+            payment_hash: sha256::Hash::hash(&preimage).to_byte_array().to_vec(),
+            amount_msat,
+            expiry_secs: LIGHTNING_ADDRESS_INVOICE_EXPIRY_SECS,
+            description: Some(Description::Hash(description_hash.to_byte_array().to_vec())),
+        })
+        .await?;
+    let invoice = Bolt11Invoice::from_str(&response.invoice)
+        .map_err(|e| GatewayError::LightningResponseParseError(anyhow!(e)))?;
+    let verify_url = user.verify_url(domain, &invoice);
+
+    let mut dbtx = db.begin_transaction().await;
+    dbtx.insert_new_entry(
+        &LightningAddressInvoiceKey {
+            payment_hash: *invoice.payment_hash(),
+        },
+        &LightningAddressInvoice {
+            username: user.username,
+            invoice: invoice.clone(),
+            preimage,
+        },
+    )
+    .await;
+    dbtx.commit_tx().await;
+
+    Ok(PayRequestInvoice {
+        verify: Some(verify_url),
+        pr: invoice,
+        routes: vec![],
+    })
+}
+
+/// Returns whether the invoice of a lightning address with `payment_hash` was
+/// paid, together with its preimage once it was
+pub async fn verify_invoice(
+    db: &Database,
+    username: &str,
+    payment_hash: sha256::Hash,
+) -> Result<VerifyResponse, GatewayError> {
+    let mut dbtx = db.begin_transaction_nc().await;
+    let paid = dbtx
+        .get_value(&LightningAddressPaymentKey { payment_hash })
+        .await;
+    let (invoice, settled) = match paid {
+        Some(invoice) => (Some(invoice), true),
+        None => (
+            dbtx.get_value(&LightningAddressInvoiceKey { payment_hash })
+                .await,
+            false,
+        ),
+    };
+
+    match invoice.filter(|invoice| invoice.username == username) {
+        Some(invoice) => Ok(VerifyResponse::new(
+            invoice.invoice,
+            settled.then_some(invoice.preimage),
+        )),
+        None => Err(GatewayError::InvalidMetadata(format!(
+            "Unknown invoice {payment_hash}"
+        ))),
+    }
+}
+
+/// Settles the accepted payments to the unpaid invoices of lightning
+/// addresses and credits them to the users of the addresses
+pub async fn settle_invoices(lightning_context: &LightningContext, db: &Database) {
+    let invoices = db
+        .begin_transaction_nc()
+        .await
+        .find_by_prefix(&LightningAddressInvoiceKeyPrefix)
+        .await
+        .collect::<Vec<_>>()
+        .await;
+
+    for (key, invoice) in invoices {
+        let state = match lightning_context
+            .lnrpc
+            .lookup_invoice(key.payment_hash)
+            .await
+        {
+            Ok(state) => state,
+            // The payer's HTLC has timed out long ago, so the invoice can't be
+            // settled anymore
+            Err(e)
+                if invoice.invoice.would_expire(
+                    duration_since_epoch().saturating_sub(LIGHTNING_ADDRESS_INVOICE_RETENTION),
+                ) =>
+            {
+                warn!(
+                    "Forgetting invoice {} that can't be looked up: {e}",
+                    key.payment_hash
+                );
+                let mut dbtx = db.begin_transaction().await;
+                dbtx.remove_entry(&key).await;
+                dbtx.commit_tx().await;
+                continue;
+            }
+            Err(e) => {
+                warn!("Failed to look up invoice {}: {e}", key.payment_hash);
+                continue;
+            }
+        };
+
+        let paid = match state {
+            // The lightning node cancels expired invoices itself
+            InvoiceState::Open if !invoice.invoice.is_expired() => continue,
+            InvoiceState::Open | InvoiceState::Canceled => false,
+            InvoiceState::Accepted => {
+                if let Err(e) = lightning_context
+                    .lnrpc
+                    .settle_hold_invoice(invoice.preimage)
+                    .await
+                {
+                    warn!("Failed to settle invoice {}: {e}", key.payment_hash);
+                    continue;
+                }
+
+                true
+            }
+            InvoiceState::Settled => true,
+        };
+
+        let mut dbtx = db.begin_transaction().await;
+        dbtx.remove_entry(&key).await;
+        if paid {
+            // Payers verify the payment with the preimage (LUD-21)
+            dbtx.insert_entry(
+                &LightningAddressPaymentKey {
+                    payment_hash: key.payment_hash,
+                },
+                &invoice,
+            )
+            .await;
+        }
+        let user_key = LightningAddressUserKey {
+            username: invoice.username.clone(),
+        };
+        // The user may have been removed in the meantime
+        if let Some(mut user) = dbtx.get_value(&user_key).await.filter(|_| paid) {
+            let amount =
+                Amount::from_msats(invoice.invoice.amount_milli_satoshis().unwrap_or_default());
+            user.credited += amount;
+            dbtx.insert_entry(&user_key, &user).await;
+
+            info!(
+                "Credited {amount} to lightning address {} in federation {}",
+                user.username, user.federation_id
+            );
+        }
+        dbtx.commit_tx().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use fedimint_core::config::FederationId;
+    use fedimint_core::core::OperationId;
+    use fedimint_core::db::mem_impl::MemDatabase;
+    use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
+    use fedimint_core::tiered_multi::TieredMulti;
+    use fedimint_core::Amount;
+    use fedimint_mint_client::OOBNotes;
+
+    use super::{
+        claim_lightning_address, register_lightning_address, remove_lightning_address,
+        LightningAddressUser, PayRequestInvoice, VerifyResponse,
+    };
+    use crate::db::LightningAddressNotesKey;
+    use crate::rpc::{
+        ClaimLightningAddressPayload, RegisterLightningAddressPayload,
+        RemoveLightningAddressPayload,
+    };
+
+    const INVOICE: &str = "lnbc1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpl2pkx2ctnv5sxxmmwwd5kgetjypeh2ursdae8g6twvus8g6rfwvs8qun0dfjkxaq9qrsgq357wnc5r2ueh7ck6q93dj32dlqnls087fxdwk8qakdyafkq3yap9us6v52vjjsrvywa6rt52cm9r9zqt8r2t7mlcwspyetp5h2tztugp9lfyql";
+
+    #[test]
+    fn pay_request_identifies_the_address() {
+        let user = LightningAddressUser {
+            username: "alice".to_string(),
+            federation_id: FederationId::dummy(),
+            description: "Tips for Alice".to_string(),
+            min_sendable_msat: 1_000,
+            max_sendable_msat: 100_000_000,
+            credited: Amount::ZERO,
+            issued: Amount::ZERO,
+            created_at: SystemTime::UNIX_EPOCH,
+        };
+        assert_eq!(user.validate(), Ok(()));
+
+        let pay_request = user.pay_request("example.com");
+        assert_eq!(
+            pay_request.callback,
+            "https://example.com/lnurlp/alice/callback"
+        );
+        assert_eq!(
+            pay_request.metadata,
+            r#"[["text/plain","Tips for Alice"],["text/identifier","alice@example.com"]]"#
+        );
+
+        let uppercase = LightningAddressUser {
+            username: "Alice".to_string(),
+            ..user.clone()
+        };
+        assert!(uppercase.validate().is_err());
+
+        let no_range = LightningAddressUser {
+            min_sendable_msat: 2_000,
+            max_sendable_msat: 1_000,
+            ..user
+        };
+        assert!(no_range.validate().is_err());
+    }
+
+    #[test]
+    fn verify_responses_reveal_the_preimage_once_settled() {
+        let invoice = INVOICE.parse::<lightning_invoice::Bolt11Invoice>().unwrap();
+
+        let pending = serde_json::to_value(VerifyResponse::new(invoice.clone(), None)).unwrap();
+        assert_eq!(
+            pending,
+            serde_json::json!({
+                "status": "OK",
+                "settled": false,
+                "preimage": null,
+                "pr": INVOICE,
+            })
+        );
+
+        let settled =
+            serde_json::to_value(VerifyResponse::new(invoice.clone(), Some([1; 32]))).unwrap();
+        assert_eq!(settled["settled"], true);
+        assert_eq!(settled["preimage"], "01".repeat(32));
+
+        let without_verify = serde_json::to_value(PayRequestInvoice {
+            pr: invoice,
+            routes: vec![],
+            verify: None,
+        })
+        .unwrap();
+        assert!(without_verify.get("verify").is_none());
+    }
+
+    #[test]
+    fn change_of_issued_notes_is_deducted_from_the_balance() {
+        let user = LightningAddressUser {
+            username: "alice".to_string(),
+            federation_id: FederationId::dummy(),
+            description: "Tips for Alice".to_string(),
+            min_sendable_msat: 1_000,
+            max_sendable_msat: 100_000_000,
+            credited: Amount::from_msats(5_000),
+            issued: Amount::from_msats(3_000),
+            created_at: SystemTime::UNIX_EPOCH,
+        };
+        assert_eq!(user.balance(), Amount::from_msats(2_000));
+
+        let overissued = LightningAddressUser {
+            issued: Amount::from_msats(5_120),
+            ..user
+        };
+        assert_eq!(overissued.balance(), Amount::ZERO);
+    }
+
+    #[tokio::test]
+    async fn claimed_notes_are_kept_until_acknowledged() {
+        let db = Database::new(MemDatabase::new(), Default::default());
+        register_lightning_address(
+            &db,
+            RegisterLightningAddressPayload {
+                username: "alice".to_string(),
+                federation_id: FederationId::dummy(),
+                description: "Tips for Alice".to_string(),
+                min_sendable_msat: 1_000,
+                max_sendable_msat: 100_000_000,
+            },
+        )
+        .await
+        .unwrap();
+
+        let operation_id = OperationId::new_random();
+        let mut dbtx = db.begin_transaction().await;
+        dbtx.insert_new_entry(
+            &LightningAddressNotesKey {
+                username: "alice".to_string(),
+                operation_id,
+            },
+            &OOBNotes::new(FederationId::dummy().to_prefix(), TieredMulti::default()),
+        )
+        .await;
+        dbtx.commit_tx().await;
+
+        let claim = |acknowledge| ClaimLightningAddressPayload {
+            username: "alice".to_string(),
+            acknowledge,
+        };
+        let remove = || RemoveLightningAddressPayload {
+            username: "alice".to_string(),
+        };
+
+        // A claim whose response got lost can be repeated
+        for _ in 0..2 {
+            let notes = claim_lightning_address(&db, claim(vec![])).await.unwrap();
+            assert_eq!(notes.len(), 1);
+            assert_eq!(notes[0].operation_id, operation_id);
+        }
+        assert!(remove_lightning_address(&db, remove()).await.is_err());
+
+        let notes = claim_lightning_address(&db, claim(vec![operation_id]))
+            .await
+            .unwrap();
+        assert!(notes.is_empty());
+        assert!(claim_lightning_address(&db, claim(vec![operation_id]))
+            .await
+            .is_err());
+        remove_lightning_address(&db, remove()).await.unwrap();
+    }
+}
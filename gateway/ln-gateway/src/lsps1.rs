@@ -0,0 +1,213 @@
+//! Client side of LSPS1 (bLIP-51), which lets the gateway buy a channel with
+//! inbound liquidity from a Lightning Service Provider. Messages are exchanged
+//! with the LSP as JSON-RPC over the gateway lightning node's peer connection.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context};
+use bitcoin_hashes::Hash;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::{secp256k1, Amount};
+use fedimint_ln_common::PrunedInvoice;
+use lightning_invoice::Bolt11Invoice;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::gateway_lnrpc::PayInvoiceRequest;
+use crate::lightning::ILnRpcClient;
+use crate::GatewayError;
+
+/// Maximum CLTV delta of the route paying for an order
+const LSPS1_PAYMENT_MAX_DELAY: u64 = 1008;
+
+/// An order for a channel from an LSP, as tracked in the gateway database
+#[derive(Debug, Clone, Eq, PartialEq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct Lsps1Order {
+    pub order_id: String,
+    pub lsp_node_id: secp256k1::PublicKey,
+    /// Inbound liquidity of the channel
+    pub lsp_balance_sat: u64,
+    /// Outbound liquidity of the channel, paid for on top of the LSP's fee
+    pub client_balance_sat: u64,
+    /// Number of blocks the LSP guarantees to keep the channel open
+    pub channel_expiry_blocks: u32,
+    /// Amount the gateway has to pay for the order
+    pub order_total_sat: u64,
+    /// Invoice paying for the order
+    pub invoice: String,
+    pub state: Lsps1OrderState,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Encodable, Decodable, Serialize, Deserialize)]
+pub enum Lsps1OrderState {
+    /// The order has been created by the LSP, but not yet paid for
+    Created,
+    /// The gateway paid the order's invoice, the LSP has yet to open the
+    /// channel
+    Paid,
+    /// The LSP opened the channel
+    Completed,
+    /// The LSP failed the order, a payment is refunded by the LSP
+    Failed,
+}
+
+#[derive(Debug, Deserialize)]
+struct OrderResponse {
+    order_id: String,
+    order_state: OrderResponseState,
+    payment: OrderPayment,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum OrderResponseState {
+    Created,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Deserialize)]
+struct OrderPayment {
+    bolt11: OrderBolt11Payment,
+}
+
+#[derive(Debug, Deserialize)]
+struct OrderBolt11Payment {
+    // LSPS encodes satoshi amounts as strings
+    order_total_sat: String,
+    invoice: String,
+}
+
+/// Requests the LSP's supported channel options and limits
+pub async fn get_info(
+    lnrpc: &dyn ILnRpcClient,
+    lsp_node_id: secp256k1::PublicKey,
+) -> Result<Value, GatewayError> {
+    Ok(lnrpc
+        .lsps_request(lsp_node_id, "lsps1.get_info", json!({}))
+        .await?)
+}
+
+/// Asks the LSP for a quote on a channel, which has to be paid before the LSP
+/// opens the channel
+pub async fn create_order(
+    lnrpc: &dyn ILnRpcClient,
+    lsp_node_id: secp256k1::PublicKey,
+    lsp_balance_sat: u64,
+    client_balance_sat: u64,
+    channel_expiry_blocks: u32,
+    announce_channel: bool,
+) -> Result<Lsps1Order, GatewayError> {
+    let params = json!({
+        "lsp_balance_sat": lsp_balance_sat.to_string(),
+        "client_balance_sat": client_balance_sat.to_string(),
+        "required_channel_confirmations": 0,
+        "funding_confirms_within_blocks": 6,
+        "channel_expiry_blocks": channel_expiry_blocks,
+        "announce_channel": announce_channel,
+    });
+    let response = parse_order(
+        lnrpc
+            .lsps_request(lsp_node_id, "lsps1.create_order", params)
+            .await?,
+    )?;
+
+    Ok(Lsps1Order {
+        order_id: response.order_id,
+        lsp_node_id,
+        lsp_balance_sat,
+        client_balance_sat,
+        channel_expiry_blocks,
+        order_total_sat: parse_sat(&response.payment.bolt11.order_total_sat)?,
+        invoice: response.payment.bolt11.invoice,
+        state: response
+            .order_state
+            .into_order_state(Lsps1OrderState::Created),
+    })
+}
+
+/// Queries the LSP for the current state of `order` and returns the updated
+/// order
+pub async fn refresh_order(
+    lnrpc: &dyn ILnRpcClient,
+    order: Lsps1Order,
+) -> Result<Lsps1Order, GatewayError> {
+    let response = parse_order(
+        lnrpc
+            .lsps_request(
+                order.lsp_node_id,
+                "lsps1.get_order",
+                json!({ "order_id": order.order_id }),
+            )
+            .await?,
+    )?;
+
+    Ok(Lsps1Order {
+        state: response.order_state.into_order_state(order.state),
+        ..order
+    })
+}
+
+/// Pays the invoice of `order` with the gateway's lightning node, paying at
+/// most `max_fee` in routing fees
+pub async fn pay_order(
+    lnrpc: &dyn ILnRpcClient,
+    order: &Lsps1Order,
+    max_fee: Amount,
+) -> Result<(), GatewayError> {
+    let invoice = Bolt11Invoice::from_str(&order.invoice)
+        .map_err(|e| GatewayError::LightningResponseParseError(anyhow!("Invalid invoice: {e}")))?;
+
+    // The order total comes from the LSP, so it may not fit into msats
+    let order_total_msat = order.order_total_sat.checked_mul(1000).ok_or_else(|| {
+        GatewayError::LightningResponseParseError(anyhow!("Order total is out of range"))
+    })?;
+    if invoice.amount_milli_satoshis() != Some(order_total_msat) {
+        return Err(GatewayError::LightningResponseParseError(anyhow!(
+            "Invoice amount does not match the order total"
+        )));
+    }
+
+    if lnrpc.supports_private_payments() {
+        let invoice =
+            PrunedInvoice::try_from(invoice).map_err(GatewayError::LightningResponseParseError)?;
+        lnrpc
+            .pay_private(invoice, LSPS1_PAYMENT_MAX_DELAY, max_fee)
+            .await?;
+    } else {
+        lnrpc
+            .pay(PayInvoiceRequest {
+                invoice: order.invoice.clone(),
+                max_delay: LSPS1_PAYMENT_MAX_DELAY,
+                max_fee_msat: max_fee.msats,
+                payment_hash: invoice.payment_hash().to_byte_array().to_vec(),
+            })
+            .await?;
+    }
+
+    Ok(())
+}
+
+impl OrderResponseState {
+    /// The LSP reports paid but not yet completed orders as created, so we
+    /// keep our own record of having paid until the order is completed.
+    fn into_order_state(self, current: Lsps1OrderState) -> Lsps1OrderState {
+        match self {
+            OrderResponseState::Created => current,
+            OrderResponseState::Completed => Lsps1OrderState::Completed,
+            OrderResponseState::Failed => Lsps1OrderState::Failed,
+        }
+    }
+}
+
+fn parse_order(response: Value) -> Result<OrderResponse, GatewayError> {
+    serde_json::from_value(response)
+        .context("Invalid LSPS1 order")
+        .map_err(GatewayError::LightningResponseParseError)
+}
+
+fn parse_sat(amount: &str) -> Result<u64, GatewayError> {
+    amount
+        .parse()
+        .map_err(|e| GatewayError::LightningResponseParseError(anyhow!("Invalid amount: {e}")))
+}
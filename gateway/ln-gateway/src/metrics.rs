@@ -0,0 +1,249 @@
+use bitcoin_hashes::sha256;
+use fedimint_core::config::FederationId;
+use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::Amount;
+use fedimint_metrics::prometheus::{
+    register_int_gauge_vec_with_registry, register_int_gauge_with_registry, IntCounterVec,
+    IntGauge, IntGaugeVec,
+};
+use fedimint_metrics::{opts, register_int_counter_vec_with_registry, REGISTRY};
+use once_cell::sync::Lazy;
+use rand::rngs::OsRng;
+use rand::Rng;
+use tokio::sync::broadcast;
+
+use crate::db::{FeesEarnedKey, PaymentLogKey, PaymentOutcomesKey};
+use crate::events::{GatewayEvent, PaymentDirection};
+use crate::payment_log::PaymentLogEntry;
+
+pub(crate) static LND_QUEUED_REQUESTS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec_with_registry!(
+        opts!(
+            "gateway_lnd_queued_requests",
+            "Number of requests to LND waiting for a free slot of their method's limiter"
+        ),
+        &["method"],
+        REGISTRY
+    )
+    .unwrap()
+});
+
+pub(crate) static GATEWAY_SEND_PAUSED: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge_with_registry!(
+        opts!(
+            "gateway_send_paused",
+            "Whether outgoing payments are paused because balances are below the reserves"
+        ),
+        REGISTRY
+    )
+    .unwrap()
+});
+
+pub(crate) static FEDERATION_ECASH_BALANCE_MSATS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec_with_registry!(
+        opts!(
+            "gateway_federation_ecash_balance_msats",
+            "Ecash balance of the gateway in a federation"
+        ),
+        &["federation_id"],
+        REGISTRY
+    )
+    .unwrap()
+});
+
+pub(crate) static FEDERATION_FEES_EARNED_MSATS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec_with_registry!(
+        opts!(
+            "gateway_federation_fees_earned_msats_total",
+            "Fees the gateway charged for successful payments of a federation"
+        ),
+        &["federation_id", "direction"],
+        REGISTRY
+    )
+    .unwrap()
+});
+
+pub(crate) static FEDERATION_PAYMENTS_IN_FLIGHT: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec_with_registry!(
+        opts!(
+            "gateway_federation_payments_in_flight",
+            "Number of payments of a federation the gateway is currently processing"
+        ),
+        &["federation_id", "direction"],
+        REGISTRY
+    )
+    .unwrap()
+});
+
+pub(crate) static FEDERATION_PAYMENTS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec_with_registry!(
+        opts!(
+            "gateway_federation_payments_total",
+            "Number of finished payments of a federation by outcome"
+        ),
+        &["federation_id", "direction", "outcome"],
+        REGISTRY
+    )
+    .unwrap()
+});
+
+pub(crate) static FEDERATION_INCOMING_LIMIT_VIOLATIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec_with_registry!(
+        opts!(
+            "gateway_federation_incoming_limit_violations_total",
+            "Number of incoming payments of a federation rejected by its incoming limits"
+        ),
+        &["federation_id", "limit"],
+        REGISTRY
+    )
+    .unwrap()
+});
+
+/// Returns the fees the gateway earned across all federations
+pub(crate) async fn total_fees_earned(gateway_db: &Database) -> Amount {
+    gateway_db
+        .begin_transaction_nc()
+        .await
+        .get_value(&FeesEarnedKey)
+        .await
+        .unwrap_or(Amount::ZERO)
+}
+
+/// Number of finished payments across all federations and how many of them
+/// failed
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Encodable, Decodable)]
+pub struct PaymentOutcomes {
+    pub payments: u64,
+    pub failed_payments: u64,
+}
+
+/// Returns the outcomes of the payments the gateway finished
+pub(crate) async fn total_payment_outcomes(gateway_db: &Database) -> PaymentOutcomes {
+    gateway_db
+        .begin_transaction_nc()
+        .await
+        .get_value(&PaymentOutcomesKey)
+        .await
+        .unwrap_or_default()
+}
+
+/// Counts a payment as in flight until it is dropped. Payments that are
+/// dropped without an outcome, like requests the gateway rejected before
+/// paying, are not counted as finished. Finished payments are also sent to the
+/// subscribers of the gateway's events, recorded in the payment log, and their
+/// outcomes and the fees they earned are added up in the gateway's database so
+/// they survive restarts.
+#[derive(Debug)]
+pub(crate) struct InFlightPayment {
+    gateway_db: Database,
+    federation_id: FederationId,
+    label: String,
+    direction: PaymentDirection,
+    payment_hash: sha256::Hash,
+    amount: Amount,
+    events: broadcast::Sender<GatewayEvent>,
+}
+
+impl InFlightPayment {
+    pub(crate) fn start(
+        gateway_db: Database,
+        federation_id: FederationId,
+        direction: PaymentDirection,
+        payment_hash: sha256::Hash,
+        amount: Amount,
+        events: broadcast::Sender<GatewayEvent>,
+    ) -> Self {
+        let payment = InFlightPayment {
+            gateway_db,
+            federation_id,
+            label: federation_id.to_string(),
+            direction,
+            payment_hash,
+            amount,
+            events,
+        };
+        FEDERATION_PAYMENTS_IN_FLIGHT
+            .with_label_values(&[payment.label.as_str(), direction.as_str()])
+            .inc();
+        payment
+    }
+
+    pub(crate) async fn succeeded(self, fee: Amount) {
+        let labels = [self.label.as_str(), self.direction.as_str()];
+        FEDERATION_FEES_EARNED_MSATS
+            .with_label_values(&labels)
+            .inc_by(fee.msats);
+        self.finished(true, fee).await;
+    }
+
+    pub(crate) async fn failed(self) {
+        self.finished(false, Amount::ZERO).await;
+    }
+
+    async fn finished(&self, success: bool, fee: Amount) {
+        let outcome = if success { "success" } else { "failure" };
+        FEDERATION_PAYMENTS_TOTAL
+            .with_label_values(&[self.label.as_str(), self.direction.as_str(), outcome])
+            .inc();
+
+        let entry = PaymentLogEntry {
+            finished_at: fedimint_core::time::now(),
+            federation_id: self.federation_id,
+            direction: self.direction,
+            payment_hash: self.payment_hash,
+            amount: self.amount,
+            fee,
+            success,
+        };
+        self.gateway_db
+            .autocommit(
+                |dbtx, _| {
+                    let entry = entry.clone();
+                    Box::pin(async move {
+                        dbtx.insert_new_entry(
+                            &PaymentLogKey {
+                                finished_at: entry.finished_at,
+                                nonce: OsRng.gen(),
+                            },
+                            &entry,
+                        )
+                        .await;
+
+                        let fees = dbtx.get_value(&FeesEarnedKey).await.unwrap_or(Amount::ZERO);
+                        dbtx.insert_entry(&FeesEarnedKey, &(fees + fee)).await;
+
+                        let mut outcomes = dbtx
+                            .get_value(&PaymentOutcomesKey)
+                            .await
+                            .unwrap_or_default();
+                        outcomes.payments += 1;
+                        if !success {
+                            outcomes.failed_payments += 1;
+                        }
+                        dbtx.insert_entry(&PaymentOutcomesKey, &outcomes).await;
+                        Ok::<(), ()>(())
+                    })
+                },
+                None,
+            )
+            .await
+            .expect("Recording the payment never fails");
+
+        // Fails if nobody is subscribed
+        let _ = self.events.send(GatewayEvent::Payment {
+            federation_id: self.federation_id,
+            direction: self.direction,
+            success,
+            fee,
+        });
+    }
+}
+
+impl Drop for InFlightPayment {
+    fn drop(&mut self) {
+        FEDERATION_PAYMENTS_IN_FLIGHT
+            .with_label_values(&[self.label.as_str(), self.direction.as_str()])
+            .dec();
+    }
+}
@@ -0,0 +1,260 @@
+//! Generates a Grafana dashboard and Prometheus alert rules for the metrics
+//! this gateway exports. The metric names are taken from the registered
+//! metrics, so the bundle can't drift from what the metrics API serves.
+
+use fedimint_metrics::prometheus::core::Collector;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::metrics::{
+    FEDERATION_ECASH_BALANCE_MSATS, FEDERATION_FEES_EARNED_MSATS, FEDERATION_PAYMENTS_IN_FLIGHT,
+    FEDERATION_PAYMENTS_TOTAL, GATEWAY_SEND_PAUSED, LND_QUEUED_REQUESTS,
+};
+
+/// Prefix `fedimint_metrics::REGISTRY` adds to the names of all metrics
+const REGISTRY_PREFIX: &str = "fm";
+
+/// Share of failed payments of a federation above which an alert fires
+const PAYMENT_FAILURE_RATE_THRESHOLD: f64 = 0.2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonitoringBundle {
+    /// Dashboard that can be imported into Grafana as is
+    pub grafana_dashboard: Value,
+    /// Prometheus alerting rules. JSON is valid YAML, so they can be saved as
+    /// a rules file directly.
+    pub prometheus_rules: Value,
+}
+
+fn metric_name(collector: &impl Collector) -> String {
+    format!("{REGISTRY_PREFIX}_{}", collector.desc()[0].fq_name)
+}
+
+pub fn monitoring_bundle() -> MonitoringBundle {
+    MonitoringBundle {
+        grafana_dashboard: grafana_dashboard(),
+        prometheus_rules: prometheus_rules(),
+    }
+}
+
+fn grafana_dashboard() -> Value {
+    let balance = metric_name(&*FEDERATION_ECASH_BALANCE_MSATS);
+    let fees = metric_name(&*FEDERATION_FEES_EARNED_MSATS);
+    let in_flight = metric_name(&*FEDERATION_PAYMENTS_IN_FLIGHT);
+    let payments = metric_name(&*FEDERATION_PAYMENTS_TOTAL);
+    let send_paused = metric_name(&*GATEWAY_SEND_PAUSED);
+    let queued_requests = metric_name(&*LND_QUEUED_REQUESTS);
+    let federation = r#"federation_id=~"$federation_id""#;
+
+    let panels = vec![
+        panel(
+            "Ecash balance",
+            "sat",
+            &format!("{balance}{{{federation}}} / 1000"),
+            "{{federation_id}}",
+        ),
+        panel(
+            "Fees earned per hour",
+            "sat",
+            &format!(
+                "sum by (federation_id, direction) (increase({fees}{{{federation}}}[1h])) / 1000"
+            ),
+            "{{federation_id}} {{direction}}",
+        ),
+        panel(
+            "Payments in flight",
+            "short",
+            &format!("sum by (federation_id, direction) ({in_flight}{{{federation}}})"),
+            "{{federation_id}} {{direction}}",
+        ),
+        panel(
+            "Payment success rate",
+            "percentunit",
+            &format!(
+                "sum by (federation_id, direction) (rate({payments}{{{federation}, \
+                 outcome=\"success\"}}[15m])) / sum by (federation_id, direction) \
+                 (rate({payments}{{{federation}}}[15m]))"
+            ),
+            "{{federation_id}} {{direction}}",
+        ),
+        panel("Outgoing payments paused", "bool", &send_paused, "paused"),
+        panel(
+            "Queued LND requests",
+            "short",
+            &queued_requests,
+            "{{method}}",
+        ),
+    ];
+
+    let panels = panels
+        .into_iter()
+        .enumerate()
+        .map(|(index, mut value)| {
+            value["id"] = json!(index + 1);
+            value["gridPos"] = json!({
+                "h": 8,
+                "w": 12,
+                "x": (index % 2) * 12,
+                "y": (index / 2) * 8,
+            });
+            value
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "title": "Fedimint Gateway",
+        "uid": "fedimint-gateway",
+        "tags": ["fedimint", "gateway"],
+        "timezone": "browser",
+        "schemaVersion": 39,
+        "refresh": "1m",
+        "time": { "from": "now-24h", "to": "now" },
+        "templating": {
+            "list": [
+                {
+                    "name": "datasource",
+                    "type": "datasource",
+                    "query": "prometheus",
+                },
+                {
+                    "name": "federation_id",
+                    "label": "Federation",
+                    "type": "query",
+                    "datasource": { "type": "prometheus", "uid": "${datasource}" },
+                    "query": format!("label_values({balance}, federation_id)"),
+                    "refresh": 2,
+                    "multi": true,
+                    "includeAll": true,
+                    "allValue": ".*",
+                }
+            ]
+        },
+        "panels": panels,
+    })
+}
+
+fn panel(title: &str, unit: &str, expr: &str, legend: &str) -> Value {
+    json!({
+        "type": "timeseries",
+        "title": title,
+        "datasource": { "type": "prometheus", "uid": "${datasource}" },
+        "fieldConfig": { "defaults": { "unit": unit }, "overrides": [] },
+        "targets": [
+            {
+                "refId": "A",
+                "expr": expr,
+                "legendFormat": legend,
+            }
+        ],
+    })
+}
+
+fn prometheus_rules() -> Value {
+    let balance = metric_name(&*FEDERATION_ECASH_BALANCE_MSATS);
+    let payments = metric_name(&*FEDERATION_PAYMENTS_TOTAL);
+    let send_paused = metric_name(&*GATEWAY_SEND_PAUSED);
+    let queued_requests = metric_name(&*LND_QUEUED_REQUESTS);
+
+    json!({
+        "groups": [
+            {
+                "name": "fedimint-gateway",
+                "rules": [
+                    {
+                        "alert": "GatewaySendPaused",
+                        "expr": format!("{send_paused} == 1"),
+                        "for": "5m",
+                        "labels": { "severity": "critical" },
+                        "annotations": {
+                            "summary": "Gateway paused outgoing payments",
+                            "description": "The balances of the lightning node are below the \
+                                configured reserves, so the gateway stopped quoting outgoing \
+                                payments.",
+                        },
+                    },
+                    {
+                        "alert": "GatewayFederationBalanceEmpty",
+                        "expr": format!("{balance} == 0"),
+                        "for": "30m",
+                        "labels": { "severity": "warning" },
+                        "annotations": {
+                            "summary": "Gateway has no ecash in federation \
+                                {{ $labels.federation_id }}",
+                            "description": "Without ecash the gateway can't fund incoming \
+                                payments to users of this federation.",
+                        },
+                    },
+                    {
+                        "alert": "GatewayPaymentFailureRateHigh",
+                        "expr": format!(
+                            "sum by (federation_id, direction) \
+                             (rate({payments}{{outcome=\"failure\"}}[30m])) / sum by \
+                             (federation_id, direction) (rate({payments}[30m])) > \
+                             {PAYMENT_FAILURE_RATE_THRESHOLD}"
+                        ),
+                        "for": "15m",
+                        "labels": { "severity": "warning" },
+                        "annotations": {
+                            "summary": "Many {{ $labels.direction }} payments of federation \
+                                {{ $labels.federation_id }} fail",
+                            "description": "More than 20% of the payments failed during the \
+                                last 30 minutes.",
+                        },
+                    },
+                    {
+                        "alert": "GatewayLndRequestsQueued",
+                        "expr": format!("{queued_requests} > 0"),
+                        "for": "10m",
+                        "labels": { "severity": "warning" },
+                        "annotations": {
+                            "summary": "Requests to LND are queueing for {{ $labels.method }}",
+                            "description": "The concurrency limit for requests to LND is \
+                                exhausted, consider increasing it.",
+                        },
+                    },
+                ],
+            }
+        ]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::monitoring_bundle;
+
+    #[test]
+    fn bundle_references_exported_metrics() {
+        let bundle = monitoring_bundle();
+        let dashboard = bundle.grafana_dashboard.to_string();
+        let rules = bundle.prometheus_rules.to_string();
+
+        for name in [
+            "fm_gateway_federation_ecash_balance_msats",
+            "fm_gateway_federation_fees_earned_msats_total",
+            "fm_gateway_federation_payments_in_flight",
+            "fm_gateway_federation_payments_total",
+            "fm_gateway_send_paused",
+            "fm_gateway_lnd_queued_requests",
+        ] {
+            assert!(
+                dashboard.contains(name),
+                "{name} is missing in the dashboard"
+            );
+        }
+
+        for name in [
+            "fm_gateway_federation_ecash_balance_msats",
+            "fm_gateway_federation_payments_total",
+            "fm_gateway_send_paused",
+            "fm_gateway_lnd_queued_requests",
+        ] {
+            assert!(rules.contains(name), "{name} is missing in the alert rules");
+        }
+
+        let num_panels = bundle.grafana_dashboard["panels"]
+            .as_array()
+            .expect("panels is an array")
+            .len();
+        assert_eq!(num_panels, 6);
+    }
+}
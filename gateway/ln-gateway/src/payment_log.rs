@@ -0,0 +1,405 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+use bitcoin_hashes::sha256;
+use clap::{Args, ValueEnum};
+use fedimint_core::config::FederationId;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::Amount;
+use serde::{Deserialize, Serialize};
+
+use crate::events::PaymentDirection;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Columns of the CSV export, one row per payment
+const CSV_HEADER: &str =
+    "finished_at,federation_id,direction,payment_hash,amount_msat,fee_msat,success";
+
+/// Payment the gateway finished, recorded for bookkeeping
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct PaymentLogEntry {
+    pub finished_at: SystemTime,
+    pub federation_id: FederationId,
+    pub direction: PaymentDirection,
+    pub payment_hash: sha256::Hash,
+    /// Amount of the lightning payment, zero for LNv1 invoices without an
+    /// amount
+    pub amount: Amount,
+    /// Fee earned by the gateway, zero for failed payments
+    pub fee: Amount,
+    pub success: bool,
+}
+
+/// Outcome of a finished payment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentStatus {
+    Succeeded,
+    Failed,
+}
+
+/// Filters of a payment log query, unset filters match every payment
+#[derive(Debug, Clone, Default, PartialEq, Eq, Args, Serialize, Deserialize)]
+pub struct PaymentLogFilter {
+    #[clap(long)]
+    pub federation_id: Option<FederationId>,
+
+    #[clap(long)]
+    pub direction: Option<PaymentDirection>,
+
+    #[clap(long)]
+    pub status: Option<PaymentStatus>,
+
+    /// Smallest amount of the lightning payment
+    #[clap(long)]
+    pub min_amount: Option<Amount>,
+
+    /// Largest amount of the lightning payment
+    #[clap(long)]
+    pub max_amount: Option<Amount>,
+
+    #[clap(long)]
+    pub payment_hash: Option<sha256::Hash>,
+}
+
+impl PaymentLogFilter {
+    pub fn matches(&self, entry: &PaymentLogEntry) -> bool {
+        let status = if entry.success {
+            PaymentStatus::Succeeded
+        } else {
+            PaymentStatus::Failed
+        };
+
+        self.federation_id
+            .is_none_or(|federation_id| federation_id == entry.federation_id)
+            && self
+                .direction
+                .is_none_or(|direction| direction == entry.direction)
+            && self.status.is_none_or(|expected| expected == status)
+            && self.min_amount.is_none_or(|min| min <= entry.amount)
+            && self.max_amount.is_none_or(|max| entry.amount <= max)
+            && self
+                .payment_hash
+                .is_none_or(|payment_hash| payment_hash == entry.payment_hash)
+    }
+}
+
+/// Length of the periods payments are grouped by. Periods start at midnight
+/// UTC, weeks on Monday.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SummaryPeriod {
+    Day,
+    Week,
+    Month,
+}
+
+impl SummaryPeriod {
+    /// Returns the start of the period `time` falls into
+    pub fn start_of(self, time: SystemTime) -> SystemTime {
+        let days = time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            / SECONDS_PER_DAY;
+        let start = match self {
+            SummaryPeriod::Day => days,
+            // The Unix epoch was a Thursday
+            SummaryPeriod::Week => days.saturating_sub((days + 3) % 7),
+            SummaryPeriod::Month => days - (civil_from_days(days).2 - 1),
+        };
+
+        SystemTime::UNIX_EPOCH + Duration::from_secs(start * SECONDS_PER_DAY)
+    }
+}
+
+/// Returns the year, month and day of the month of the day `days` after the
+/// Unix epoch, see
+/// <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months counted from March, so that the leap day is the last day of the year
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// Formats the time as an RFC 3339 timestamp in UTC, e.g.
+/// `2024-03-15T13:20:00Z`
+fn format_utc(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (year, month, day) = civil_from_days(secs / SECONDS_PER_DAY);
+    let secs_of_day = secs % SECONDS_PER_DAY;
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
+/// Format of the exported payment log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentLogFormat {
+    /// Comma separated values with a header row and RFC 3339 timestamps
+    Csv,
+    /// One JSON object per line, as the entries are returned by the API
+    JsonLines,
+}
+
+impl PaymentLogFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            PaymentLogFormat::Csv => "text/csv",
+            PaymentLogFormat::JsonLines => "application/jsonl",
+        }
+    }
+}
+
+/// Writes the payments in the format, one payment per line
+pub fn export(entries: &[PaymentLogEntry], format: PaymentLogFormat) -> String {
+    let lines = entries.iter().map(|entry| match format {
+        // None of the fields can contain a comma or quote, so they need no
+        // escaping
+        PaymentLogFormat::Csv => format!(
+            "{},{},{},{},{},{},{}",
+            format_utc(entry.finished_at),
+            entry.federation_id,
+            entry.direction.as_str(),
+            entry.payment_hash,
+            entry.amount.msats,
+            entry.fee.msats,
+            entry.success
+        ),
+        PaymentLogFormat::JsonLines => {
+            serde_json::to_string(entry).expect("Serializing a payment never fails")
+        }
+    });
+
+    let header = match format {
+        PaymentLogFormat::Csv => Some(CSV_HEADER.to_string()),
+        PaymentLogFormat::JsonLines => None,
+    };
+
+    header
+        .into_iter()
+        .chain(lines)
+        .map(|line| line + "\n")
+        .collect()
+}
+
+/// Payments of a federation that finished within one period
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentSummaryBucket {
+    pub period_start: SystemTime,
+    pub federation_id: FederationId,
+    /// Successful incoming payments
+    pub incoming_payments: u64,
+    /// Successful outgoing payments
+    pub outgoing_payments: u64,
+    /// Failed payments in either direction
+    pub failed_payments: u64,
+    pub incoming_volume: Amount,
+    pub outgoing_volume: Amount,
+    pub fees_earned: Amount,
+}
+
+/// Groups the payments by federation and the period they finished in. The
+/// buckets are ordered by period, periods without payments are left out.
+pub fn summarize<'a>(
+    entries: impl IntoIterator<Item = &'a PaymentLogEntry>,
+    period: SummaryPeriod,
+) -> Vec<PaymentSummaryBucket> {
+    let mut buckets = BTreeMap::new();
+    for entry in entries {
+        let period_start = period.start_of(entry.finished_at);
+        let bucket = buckets
+            .entry((period_start, entry.federation_id))
+            .or_insert_with(|| PaymentSummaryBucket {
+                period_start,
+                federation_id: entry.federation_id,
+                incoming_payments: 0,
+                outgoing_payments: 0,
+                failed_payments: 0,
+                incoming_volume: Amount::ZERO,
+                outgoing_volume: Amount::ZERO,
+                fees_earned: Amount::ZERO,
+            });
+
+        match (entry.success, entry.direction) {
+            (false, _) => bucket.failed_payments += 1,
+            (true, PaymentDirection::Incoming) => {
+                bucket.incoming_payments += 1;
+                bucket.incoming_volume += entry.amount;
+            }
+            (true, PaymentDirection::Outgoing) => {
+                bucket.outgoing_payments += 1;
+                bucket.outgoing_volume += entry.amount;
+            }
+        }
+        bucket.fees_earned += entry.fee;
+    }
+
+    buckets.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use bitcoin_hashes::{sha256, Hash};
+    use fedimint_core::config::FederationId;
+    use fedimint_core::Amount;
+
+    use super::{
+        export, summarize, PaymentLogEntry, PaymentLogFilter, PaymentLogFormat, PaymentStatus,
+        SummaryPeriod,
+    };
+    use crate::events::PaymentDirection;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn periods_start_at_midnight_monday_and_the_first_of_the_month() {
+        // Friday, 2024-03-15 13:20 UTC
+        let time = at(1_710_460_800 + 48_000);
+
+        assert_eq!(SummaryPeriod::Day.start_of(time), at(1_710_460_800));
+        // Monday, 2024-03-11
+        assert_eq!(SummaryPeriod::Week.start_of(time), at(1_710_115_200));
+        // 2024-03-01
+        assert_eq!(SummaryPeriod::Month.start_of(time), at(1_709_251_200));
+        // The leap day 2024-02-29 belongs to February
+        assert_eq!(
+            SummaryPeriod::Month.start_of(at(1_709_164_800)),
+            at(1_706_745_600)
+        );
+    }
+
+    #[test]
+    fn groups_payments_by_period_and_federation() {
+        let entry = |finished_at, direction, amount, fee, success| PaymentLogEntry {
+            finished_at: at(finished_at),
+            federation_id: FederationId::dummy(),
+            direction,
+            payment_hash: sha256::Hash::hash(&[0]),
+            amount: Amount::from_msats(amount),
+            fee: Amount::from_msats(fee),
+            success,
+        };
+        let entries = [
+            // 2024-03-15
+            entry(1_710_460_800, PaymentDirection::Incoming, 10_000, 10, true),
+            entry(1_710_460_900, PaymentDirection::Outgoing, 20_000, 20, true),
+            entry(1_710_461_000, PaymentDirection::Outgoing, 30_000, 0, false),
+            // 2024-03-16
+            entry(1_710_547_200, PaymentDirection::Incoming, 40_000, 40, true),
+        ];
+
+        let days = summarize(&entries, SummaryPeriod::Day);
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].period_start, at(1_710_460_800));
+        assert_eq!(days[0].incoming_payments, 1);
+        assert_eq!(days[0].outgoing_payments, 1);
+        assert_eq!(days[0].failed_payments, 1);
+        assert_eq!(days[0].outgoing_volume, Amount::from_msats(20_000));
+        assert_eq!(days[0].fees_earned, Amount::from_msats(30));
+
+        let weeks = summarize(&entries, SummaryPeriod::Week);
+        assert_eq!(weeks.len(), 1);
+        assert_eq!(weeks[0].incoming_volume, Amount::from_msats(50_000));
+        assert_eq!(weeks[0].fees_earned, Amount::from_msats(70));
+    }
+
+    #[test]
+    fn exports_payments_as_csv() {
+        let entry = PaymentLogEntry {
+            // 2024-02-29 13:20:05 UTC
+            finished_at: at(1_709_164_800 + 48_005),
+            federation_id: FederationId::dummy(),
+            direction: PaymentDirection::Outgoing,
+            payment_hash: sha256::Hash::hash(&[0]),
+            amount: Amount::from_msats(20_000),
+            fee: Amount::from_msats(20),
+            success: true,
+        };
+
+        let csv = export(&[entry.clone()], PaymentLogFormat::Csv);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[1],
+            format!(
+                "2024-02-29T13:20:05Z,{},outgoing,{},20000,20,true",
+                FederationId::dummy(),
+                sha256::Hash::hash(&[0])
+            )
+        );
+
+        let json_lines = export(&[entry.clone()], PaymentLogFormat::JsonLines);
+        assert_eq!(
+            serde_json::from_str::<PaymentLogEntry>(json_lines.trim_end()).unwrap(),
+            entry
+        );
+    }
+
+    #[test]
+    fn filters_match_all_of_their_conditions() {
+        let entry = PaymentLogEntry {
+            finished_at: at(1_710_460_800),
+            federation_id: FederationId::dummy(),
+            direction: PaymentDirection::Incoming,
+            payment_hash: sha256::Hash::hash(&[0]),
+            amount: Amount::from_msats(20_000),
+            fee: Amount::from_msats(20),
+            success: true,
+        };
+
+        assert!(PaymentLogFilter::default().matches(&entry));
+        assert!(PaymentLogFilter {
+            federation_id: Some(FederationId::dummy()),
+            direction: Some(PaymentDirection::Incoming),
+            status: Some(PaymentStatus::Succeeded),
+            min_amount: Some(Amount::from_msats(20_000)),
+            max_amount: Some(Amount::from_msats(20_000)),
+            payment_hash: Some(sha256::Hash::hash(&[0])),
+        }
+        .matches(&entry));
+
+        assert!(!PaymentLogFilter {
+            status: Some(PaymentStatus::Failed),
+            ..PaymentLogFilter::default()
+        }
+        .matches(&entry));
+        assert!(!PaymentLogFilter {
+            max_amount: Some(Amount::from_msats(19_999)),
+            ..PaymentLogFilter::default()
+        }
+        .matches(&entry));
+        assert!(!PaymentLogFilter {
+            payment_hash: Some(sha256::Hash::hash(&[1])),
+            ..PaymentLogFilter::default()
+        }
+        .matches(&entry));
+    }
+}
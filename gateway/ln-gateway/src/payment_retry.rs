@@ -0,0 +1,167 @@
+use std::time::SystemTime;
+
+use clap::Args;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::Amount;
+use serde::{Deserialize, Serialize};
+
+use crate::lightning::LightningRpcError;
+
+/// Number of attempts recorded per payment at most
+pub const MAX_RECORDED_ATTEMPTS: usize = 100;
+
+/// Retries of outgoing LNv2 payments that failed with a retryable error. The
+/// first attempt may only spend part of the payment's fee budget, which rises
+/// linearly to the full budget for the last attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Args, Encodable, Decodable, Serialize, Deserialize)]
+pub struct PaymentRetryPolicy {
+    /// Maximum number of attempts per payment, including the first one
+    #[clap(long)]
+    pub max_attempts: u32,
+
+    /// Share of the fee budget in percent the first attempt may spend
+    #[clap(long, default_value_t = 100)]
+    pub initial_fee_budget_percent: u64,
+
+    /// Ask the lightning node to prefer a different route for every retry
+    #[clap(long)]
+    pub randomize_paths: bool,
+}
+
+impl Default for PaymentRetryPolicy {
+    /// A single attempt with the full fee budget
+    fn default() -> Self {
+        PaymentRetryPolicy {
+            max_attempts: 1,
+            initial_fee_budget_percent: 100,
+            randomize_paths: false,
+        }
+    }
+}
+
+impl PaymentRetryPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_attempts == 0 {
+            return Err("At least one attempt is required".to_string());
+        }
+
+        if self.initial_fee_budget_percent > 100 {
+            return Err(format!(
+                "Initial fee budget of {}% exceeds the fee budget",
+                self.initial_fee_budget_percent
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the fees attempt `attempt`, counting from zero, may spend out of
+    /// the payment's `fee_budget`
+    pub fn attempt_fee_budget(&self, attempt: u32, fee_budget: Amount) -> Amount {
+        let percent = if self.max_attempts <= 1 {
+            100
+        } else {
+            let escalation = (100 - self.initial_fee_budget_percent) * u64::from(attempt)
+                / u64::from(self.max_attempts - 1);
+            (self.initial_fee_budget_percent + escalation).min(100)
+        };
+
+        Amount::from_msats(
+            (u128::from(fee_budget.msats) * u128::from(percent) / 100)
+                .try_into()
+                .expect("At most the fee budget"),
+        )
+    }
+
+    /// Returns true if another attempt may succeed after `error`
+    pub fn is_retryable(error: &LightningRpcError) -> bool {
+        matches!(
+            error,
+            LightningRpcError::FailedPayment { .. }
+                | LightningRpcError::NoRoute { .. }
+                | LightningRpcError::FailedToConnect
+        )
+    }
+}
+
+/// An attempt to pay an invoice, recorded per payment hash
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct PaymentAttempt {
+    pub started_at: SystemTime,
+    pub fee_budget: Amount,
+    pub randomized_path: bool,
+    /// Set if the attempt failed
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use fedimint_core::Amount;
+
+    use super::PaymentRetryPolicy;
+    use crate::lightning::LightningRpcError;
+
+    #[test]
+    fn fee_budget_escalates_to_the_full_budget() {
+        let policy = PaymentRetryPolicy {
+            max_attempts: 3,
+            initial_fee_budget_percent: 50,
+            randomize_paths: true,
+        };
+
+        let budget = Amount::from_msats(10_000);
+        assert_eq!(
+            policy.attempt_fee_budget(0, budget),
+            Amount::from_msats(5_000)
+        );
+        assert_eq!(
+            policy.attempt_fee_budget(1, budget),
+            Amount::from_msats(7_500)
+        );
+        assert_eq!(policy.attempt_fee_budget(2, budget), budget);
+    }
+
+    #[test]
+    fn default_policy_spends_the_full_budget_at_once() {
+        let budget = Amount::from_msats(10_000);
+        assert_eq!(
+            PaymentRetryPolicy::default().attempt_fee_budget(0, budget),
+            budget
+        );
+    }
+
+    #[test]
+    fn retries_only_payment_and_connection_failures() {
+        assert!(PaymentRetryPolicy::is_retryable(
+            &LightningRpcError::FailedPayment {
+                failure_reason: "FailureReasonNoRoute".to_string(),
+            }
+        ));
+        assert!(PaymentRetryPolicy::is_retryable(
+            &LightningRpcError::NoRoute {
+                failure_reason: "FailureReasonNoRoute".to_string(),
+            }
+        ));
+        assert!(!PaymentRetryPolicy::is_retryable(
+            &LightningRpcError::FailedToGetNodeInfo {
+                failure_reason: String::new(),
+            }
+        ));
+    }
+
+    #[test]
+    fn validate_requires_at_least_one_attempt() {
+        let policy = PaymentRetryPolicy {
+            max_attempts: 3,
+            initial_fee_budget_percent: 50,
+            randomize_paths: true,
+        };
+        assert_eq!(policy.validate(), Ok(()));
+
+        let no_attempts = PaymentRetryPolicy {
+            max_attempts: 0,
+            ..policy
+        };
+        assert!(no_attempts.validate().is_err());
+    }
+}
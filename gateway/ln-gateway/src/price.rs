@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use fedimint_core::util::SafeUrl;
+use fedimint_core::Amount;
+use serde::{Deserialize, Serialize};
+
+/// Time between two updates of the fiat prices
+pub const PRICE_UPDATE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Provider of the fiat prices unless another URL is configured
+pub const DEFAULT_PRICE_ORACLE_URL: &str = "https://mempool.space/api/v1/prices";
+
+/// Source of the price of bitcoin in fiat currencies
+#[async_trait]
+pub trait PriceOracle: Debug + Send + Sync {
+    /// Returns the price of one bitcoin in each of `currencies`, keyed by the
+    /// currency code
+    async fn fetch_prices(&self, currencies: &[String]) -> anyhow::Result<BTreeMap<String, f64>>;
+}
+
+/// Fetches the prices from a URL that returns a JSON object with the price of
+/// one bitcoin per currency code, e.g. `{"USD": 65000, "EUR": 60000}`. Other
+/// fields are ignored.
+#[derive(Debug, Clone)]
+pub struct HttpPriceOracle {
+    url: SafeUrl,
+    client: reqwest::Client,
+}
+
+impl HttpPriceOracle {
+    pub fn new(url: SafeUrl) -> Self {
+        HttpPriceOracle {
+            url,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl PriceOracle for HttpPriceOracle {
+    async fn fetch_prices(&self, currencies: &[String]) -> anyhow::Result<BTreeMap<String, f64>> {
+        let response = self
+            .client
+            .get(self.url.clone().to_unsafe())
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<serde_json::Map<String, serde_json::Value>>()
+            .await
+            .context("Price oracle returned invalid JSON")?;
+
+        parse_prices(&response, currencies)
+    }
+}
+
+fn parse_prices(
+    response: &serde_json::Map<String, serde_json::Value>,
+    currencies: &[String],
+) -> anyhow::Result<BTreeMap<String, f64>> {
+    currencies
+        .iter()
+        .map(|currency| {
+            let price = response
+                .get(currency)
+                .and_then(serde_json::Value::as_f64)
+                .ok_or_else(|| anyhow!("Price oracle returned no price for {currency}"))?;
+            Ok((currency.clone(), price))
+        })
+        .collect()
+}
+
+/// Prices of bitcoin in the fiat currencies configured for the gateway
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FiatPrices {
+    /// Price of one bitcoin per currency code
+    pub prices: BTreeMap<String, f64>,
+    pub updated_at: SystemTime,
+}
+
+impl FiatPrices {
+    /// Returns the value of `amount` in every currency, for display only
+    pub fn convert(&self, amount: Amount) -> BTreeMap<String, f64> {
+        let btc = amount.msats as f64 / 100_000_000_000.0;
+
+        self.prices
+            .iter()
+            .map(|(currency, price)| (currency.clone(), btc * price))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::time::SystemTime;
+
+    use fedimint_core::Amount;
+    use serde_json::json;
+
+    use super::{parse_prices, FiatPrices};
+
+    #[test]
+    fn converts_amounts_to_fiat() {
+        let response = json!({"time": 1_700_000_000, "USD": 60_000, "EUR": 55_000.5});
+        let response = response.as_object().expect("Is an object");
+
+        let prices = parse_prices(response, &["USD".to_string(), "EUR".to_string()])
+            .expect("Prices are present");
+        assert_eq!(
+            prices,
+            BTreeMap::from([("EUR".to_string(), 55_000.5), ("USD".to_string(), 60_000.0)])
+        );
+        assert!(parse_prices(response, &["JPY".to_string()]).is_err());
+
+        let fiat_prices = FiatPrices {
+            prices,
+            updated_at: SystemTime::UNIX_EPOCH,
+        };
+        let usd = fiat_prices.convert(Amount::from_sats(50_000))["USD"];
+        assert!((usd - 30.0).abs() < 1e-9);
+    }
+}
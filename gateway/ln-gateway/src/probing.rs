@@ -0,0 +1,213 @@
+//! Periodic probing of configured destinations through the gateway's lightning
+//! node. Probes are payments the destination can't claim, so they test the
+//! liquidity of a route without moving funds. The results are kept in the
+//! gateway database for a week, so operators can see routes degrading before
+//! payments of users fail.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::time::duration_since_epoch;
+use fedimint_core::{secp256k1, Amount};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::db::{ProbeRecordKey, ProbeRecordKeyPrefix};
+use crate::lightning::ILnRpcClient;
+
+pub const DEFAULT_PROBE_INTERVAL_SECS: u64 = 600;
+
+/// Probe results older than this are removed from the database
+const PROBE_RECORD_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Maximum routing fee of a probe in parts per million of its amount
+const PROBE_MAX_FEE_PPM: u64 = 10_000;
+
+/// Minimum routing fee limit of a probe, so small probes can use routes with
+/// base fees
+const PROBE_MIN_MAX_FEE: Amount = Amount::from_sats(10);
+
+/// A destination the gateway regularly probes with a fixed amount
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ProbeTarget {
+    pub destination: secp256k1::PublicKey,
+    pub amount: Amount,
+}
+
+impl ProbeTarget {
+    fn max_fee(&self) -> Amount {
+        let proportional = Amount::from_msats(self.amount.msats * PROBE_MAX_FEE_PPM / 1_000_000);
+        proportional.max(PROBE_MIN_MAX_FEE)
+    }
+}
+
+impl FromStr for ProbeTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (destination, amount_msat) = s
+            .split_once(',')
+            .context("Wrong format, please provide: <node id>,<amount msat>")?;
+
+        Ok(ProbeTarget {
+            destination: destination.parse()?,
+            amount: Amount::from_msats(amount_msat.parse()?),
+        })
+    }
+}
+
+impl Display for ProbeTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} msat to {}", self.amount.msats, self.destination)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct ProbeRecord {
+    /// Whether the probe reached the destination
+    pub reachable: bool,
+    /// Routing fee of the route that reached the destination
+    pub fee_msat: Option<u64>,
+    /// Time until the probe reached the destination or failed
+    pub latency_ms: u64,
+}
+
+/// Recorded probes of one target
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiquidityHealth {
+    pub destination: secp256k1::PublicKey,
+    pub amount_msat: u64,
+    /// Share of the recorded probes that reached the destination, `None` if
+    /// there are no records yet
+    pub success_rate: Option<f64>,
+    /// Map of unix timestamp in seconds -> probe result
+    pub history: BTreeMap<u64, ProbeRecord>,
+}
+
+/// Probes all `targets` once, records the results and removes records that
+/// are past their retention
+pub async fn probe_targets(lnrpc: &dyn ILnRpcClient, db: &Database, targets: &[ProbeTarget]) {
+    for target in targets {
+        let start = Instant::now();
+        let result = match lnrpc
+            .probe(target.destination, target.amount, target.max_fee())
+            .await
+        {
+            Ok(result) => result,
+            Err(e) => {
+                warn!("Failed to probe {target}: {e}");
+                continue;
+            }
+        };
+
+        debug!("Probed {target}: {result:?}");
+
+        let mut dbtx = db.begin_transaction().await;
+        dbtx.insert_entry(
+            &ProbeRecordKey {
+                destination: target.destination,
+                amount_msat: target.amount.msats,
+                probed_at: duration_since_epoch().as_secs(),
+            },
+            &ProbeRecord {
+                reachable: result.reachable,
+                fee_msat: result.fee.map(|fee| fee.msats),
+                latency_ms: start.elapsed().as_millis() as u64,
+            },
+        )
+        .await;
+        dbtx.commit_tx().await;
+    }
+
+    let cutoff = duration_since_epoch()
+        .saturating_sub(PROBE_RECORD_RETENTION)
+        .as_secs();
+    let mut dbtx = db.begin_transaction().await;
+    let expired = dbtx
+        .find_by_prefix(&ProbeRecordKeyPrefix)
+        .await
+        .filter_map(|(key, _)| async move { (key.probed_at < cutoff).then_some(key) })
+        .collect::<Vec<_>>()
+        .await;
+    for key in expired {
+        dbtx.remove_entry(&key).await;
+    }
+    dbtx.commit_tx().await;
+}
+
+/// Groups the recorded probes by target. Configured targets without records
+/// are included, as are targets that were removed from the configuration but
+/// still have records.
+#[allow(clippy::cast_precision_loss)]
+pub async fn liquidity_health(db: &Database, targets: &[ProbeTarget]) -> Vec<LiquidityHealth> {
+    let mut histories: BTreeMap<ProbeTarget, BTreeMap<u64, ProbeRecord>> = targets
+        .iter()
+        .map(|target| (*target, BTreeMap::new()))
+        .collect();
+
+    let mut dbtx = db.begin_transaction_nc().await;
+    let records = dbtx
+        .find_by_prefix(&ProbeRecordKeyPrefix)
+        .await
+        .collect::<Vec<_>>()
+        .await;
+    for (key, record) in records {
+        let target = ProbeTarget {
+            destination: key.destination,
+            amount: Amount::from_msats(key.amount_msat),
+        };
+        histories
+            .entry(target)
+            .or_default()
+            .insert(key.probed_at, record);
+    }
+
+    histories
+        .into_iter()
+        .map(|(target, history)| {
+            let num_reachable = history.values().filter(|record| record.reachable).count();
+            LiquidityHealth {
+                destination: target.destination,
+                amount_msat: target.amount.msats,
+                success_rate: (!history.is_empty())
+                    .then(|| num_reachable as f64 / history.len() as f64),
+                history,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use fedimint_core::Amount;
+
+    use super::{ProbeTarget, PROBE_MIN_MAX_FEE};
+
+    #[test]
+    fn parses_probe_targets() {
+        let target: ProbeTarget =
+            "02eadbd9e7557375161df8b646776a547c5cbc2e95b3071ec81553f8ec2cea3b8c,5000000"
+                .parse()
+                .expect("valid target");
+        assert_eq!(target.amount, Amount::from_msats(5_000_000));
+        assert_eq!(target.max_fee(), Amount::from_msats(50_000));
+
+        let small_target = ProbeTarget {
+            amount: Amount::from_msats(1_000),
+            ..target
+        };
+        assert_eq!(small_target.max_fee(), PROBE_MIN_MAX_FEE);
+
+        assert!(
+            "02eadbd9e7557375161df8b646776a547c5cbc2e95b3071ec81553f8ec2cea3b8c"
+                .parse::<ProbeTarget>()
+                .is_err()
+        );
+    }
+}
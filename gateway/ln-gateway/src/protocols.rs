@@ -0,0 +1,61 @@
+use fedimint_core::encoding::{Decodable, Encodable};
+use serde::{Deserialize, Serialize};
+
+/// Versions of the lightning protocol the gateway serves for a federation.
+/// For a disabled version the gateway neither registers with the federation
+/// nor intercepts HTLCs or pays invoices on its behalf.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct LightningProtocols {
+    pub lnv1: bool,
+    pub lnv2: bool,
+}
+
+impl Default for LightningProtocols {
+    /// Both versions are served
+    fn default() -> Self {
+        LightningProtocols {
+            lnv1: true,
+            lnv2: true,
+        }
+    }
+}
+
+impl LightningProtocols {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.lnv1 && !self.lnv2 {
+            return Err(
+                "At least one protocol has to be enabled, leave the federation instead".to_string(),
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == LightningProtocols::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LightningProtocols;
+
+    #[test]
+    fn requires_one_protocol() {
+        assert!(LightningProtocols::default().is_default());
+        assert_eq!(LightningProtocols::default().validate(), Ok(()));
+
+        let lnv2_only = LightningProtocols {
+            lnv1: false,
+            lnv2: true,
+        };
+        assert_eq!(lnv2_only.validate(), Ok(()));
+        assert!(!lnv2_only.is_default());
+
+        let none = LightningProtocols {
+            lnv1: false,
+            lnv2: false,
+        };
+        assert!(none.validate().is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+use bitcoin::Txid;
+use fedimint_core::config::FederationId;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::Amount;
+use serde::{Deserialize, Serialize};
+
+/// Time between two checks whether the ecash balances are within their bands
+pub const REBALANCE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Number of actions kept in the rebalancing log
+pub const REBALANCE_LOG_SIZE: usize = 1_000;
+
+/// Ecash is only withdrawn once the excess reaches this amount, so the on-chain
+/// fees stay small compared to the withdrawn amount
+const MIN_WITHDRAWAL_SATS: u64 = 10_000;
+
+/// Range the gateway keeps its ecash balance in a federation within. Ecash
+/// above the band is withdrawn to the lightning node's on-chain wallet, and
+/// for a balance below the band a deposit is requested, both bringing the
+/// balance back to the middle of the band.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct RebalanceBand {
+    pub min_balance_msat: u64,
+    pub max_balance_msat: u64,
+}
+
+impl RebalanceBand {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_balance_msat < self.min_balance_msat {
+            return Err("The maximum balance is below the minimum".to_string());
+        }
+
+        Ok(())
+    }
+
+    fn target_msat(&self) -> u64 {
+        self.min_balance_msat + (self.max_balance_msat - self.min_balance_msat) / 2
+    }
+
+    /// Returns the action that brings `balance` back into the band, if it is
+    /// outside of it
+    pub fn action(&self, balance: Amount) -> Option<RebalanceAction> {
+        if self.max_balance_msat < balance.msats {
+            let excess_sats = (balance.msats - self.target_msat()) / 1000;
+            return (excess_sats >= MIN_WITHDRAWAL_SATS).then(|| RebalanceAction::Withdraw {
+                amount: bitcoin::Amount::from_sat(excess_sats),
+            });
+        }
+
+        if balance.msats < self.min_balance_msat {
+            return Some(RebalanceAction::TopUp {
+                amount: Amount::from_msats(self.target_msat() - balance.msats),
+            });
+        }
+
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub enum RebalanceAction {
+    /// Withdraw ecash to the lightning node's on-chain wallet
+    Withdraw {
+        #[serde(with = "bitcoin::amount::serde::as_sat")]
+        amount: bitcoin::Amount,
+    },
+    /// Deposit bitcoin into the federation
+    TopUp { amount: Amount },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub enum RebalanceOutcome {
+    Withdrawn {
+        txid: Txid,
+    },
+    /// The operator has to send the amount to the federation's deposit
+    /// address, since the lightning node's wallet can't be spent from
+    DepositRequested {
+        address: String,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+/// An action taken to keep the ecash balance of a federation within its band
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct RebalanceRecord {
+    pub federation_id: FederationId,
+    pub timestamp: SystemTime,
+    pub balance: Amount,
+    pub action: RebalanceAction,
+    pub outcome: RebalanceOutcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RebalanceStatus {
+    /// Set if the operator paused rebalancing
+    pub paused: bool,
+    pub bands: BTreeMap<FederationId, RebalanceBand>,
+    /// The most recent actions, newest first
+    pub log: Vec<RebalanceRecord>,
+}
+
+#[cfg(test)]
+mod tests {
+    use fedimint_core::Amount;
+
+    use super::{RebalanceAction, RebalanceBand};
+
+    #[test]
+    fn rebalances_to_the_middle_of_the_band() {
+        let band = RebalanceBand {
+            min_balance_msat: 1_000_000_000,
+            max_balance_msat: 3_000_000_000,
+        };
+        assert_eq!(band.validate(), Ok(()));
+
+        assert_eq!(band.action(Amount::from_msats(1_000_000_000)), None);
+        assert_eq!(band.action(Amount::from_msats(3_000_000_000)), None);
+        assert_eq!(
+            band.action(Amount::from_msats(3_500_000_000)),
+            Some(RebalanceAction::Withdraw {
+                amount: bitcoin::Amount::from_sat(1_500_000),
+            })
+        );
+        assert_eq!(
+            band.action(Amount::from_msats(400_000_000)),
+            Some(RebalanceAction::TopUp {
+                amount: Amount::from_msats(1_600_000_000),
+            })
+        );
+
+        // Too little to be worth the on-chain fees
+        let narrow = RebalanceBand {
+            min_balance_msat: 1_000_000_000,
+            max_balance_msat: 1_000_000_000,
+        };
+        assert_eq!(narrow.action(Amount::from_msats(1_009_999_999)), None);
+
+        let inverted = RebalanceBand {
+            min_balance_msat: 2,
+            max_balance_msat: 1,
+        };
+        assert!(inverted.validate().is_err());
+    }
+}
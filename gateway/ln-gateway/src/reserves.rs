@@ -0,0 +1,133 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tracing::{info, warn};
+
+use crate::lightning::LightningBalances;
+use crate::metrics::GATEWAY_SEND_PAUSED;
+
+/// Funds the lightning node keeps to pay the fees of outgoing payments. Below
+/// them the gateway stops quoting outgoing payments instead of failing them
+/// mid-flight. `None` means the balance is not checked.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BalanceReserves {
+    /// Minimum confirmed balance of the on-chain wallet
+    pub min_onchain_balance_sats: Option<u64>,
+    /// Minimum sum of the local balances of all channels
+    pub min_lightning_balance_sats: Option<u64>,
+}
+
+impl BalanceReserves {
+    pub fn is_enabled(&self) -> bool {
+        self.min_onchain_balance_sats.is_some() || self.min_lightning_balance_sats.is_some()
+    }
+
+    /// Describes which balance is below its reserve, if any
+    fn shortfall(&self, balances: &LightningBalances) -> Option<String> {
+        if let Some(min_sats) = self.min_onchain_balance_sats {
+            if balances.onchain_balance_sats < min_sats {
+                return Some(format!(
+                    "on-chain balance of {} sats is below the reserve of {min_sats} sats",
+                    balances.onchain_balance_sats
+                ));
+            }
+        }
+
+        if let Some(min_sats) = self.min_lightning_balance_sats {
+            if balances.lightning_balance_msats / 1000 < min_sats {
+                return Some(format!(
+                    "lightning balance of {} sats is below the reserve of {min_sats} sats",
+                    balances.lightning_balance_msats / 1000
+                ));
+            }
+        }
+
+        None
+    }
+}
+
+/// Tracks whether outgoing payments are paused because the balances of the
+/// lightning node fell below the `BalanceReserves`.
+#[derive(Debug, Default)]
+pub struct SendPause {
+    reserves: BalanceReserves,
+    paused: AtomicBool,
+}
+
+impl SendPause {
+    pub fn new(reserves: BalanceReserves) -> Self {
+        Self {
+            reserves,
+            paused: AtomicBool::new(false),
+        }
+    }
+
+    pub fn reserves(&self) -> BalanceReserves {
+        self.reserves
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Pauses or resumes outgoing payments depending on `balances` and alerts
+    /// the operator when that changes.
+    pub fn update(&self, balances: &LightningBalances) {
+        let shortfall = self.reserves.shortfall(balances);
+        let was_paused = self.paused.swap(shortfall.is_some(), Ordering::Relaxed);
+        GATEWAY_SEND_PAUSED.set(i64::from(shortfall.is_some()));
+
+        match (was_paused, shortfall) {
+            (false, Some(shortfall)) => {
+                warn!("Pausing outgoing payments, the {shortfall}");
+            }
+            (true, None) => info!("Resuming outgoing payments, balances are above the reserves"),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BalanceReserves, SendPause};
+    use crate::lightning::LightningBalances;
+
+    #[test]
+    fn pauses_below_reserves() {
+        let pause = SendPause::new(BalanceReserves {
+            min_onchain_balance_sats: Some(10_000),
+            min_lightning_balance_sats: Some(50_000),
+        });
+        assert!(!pause.is_paused());
+
+        pause.update(&LightningBalances {
+            onchain_balance_sats: 10_000,
+            lightning_balance_msats: 50_000_000,
+        });
+        assert!(!pause.is_paused());
+
+        pause.update(&LightningBalances {
+            onchain_balance_sats: 9_999,
+            lightning_balance_msats: 50_000_000,
+        });
+        assert!(pause.is_paused());
+
+        pause.update(&LightningBalances {
+            onchain_balance_sats: 10_000,
+            lightning_balance_msats: 49_999_999,
+        });
+        assert!(pause.is_paused());
+
+        pause.update(&LightningBalances {
+            onchain_balance_sats: 20_000,
+            lightning_balance_msats: 60_000_000,
+        });
+        assert!(!pause.is_paused());
+
+        let unlimited = SendPause::new(BalanceReserves::default());
+        unlimited.update(&LightningBalances {
+            onchain_balance_sats: 0,
+            lightning_balance_msats: 0,
+        });
+        assert!(!unlimited.is_paused());
+    }
+}
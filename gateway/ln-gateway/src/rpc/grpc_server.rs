@@ -0,0 +1,420 @@
+//! gRPC version of the gateway's administration API, so operators can
+//! integrate the gateway into infrastructure tooling. Requests are
+//! authenticated like the REST API, with the gateway password or an API token
+//! of a sufficient scope as bearer token. Without a TLS certificate the server
+//! only listens on loopback addresses, so bearer tokens are never sent over
+//! the network in plaintext.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{ensure, Context};
+use fedimint_core::config::FederationId;
+use fedimint_core::task::TaskGroup;
+use fedimint_core::{secp256k1, Amount, BitcoinAmountOrAll};
+use fedimint_ln_common::gateway_endpoint_constants::{
+    CONNECT_FED_ENDPOINT, LEAVE_FED_ENDPOINT, PAY_KEYSEND_ENDPOINT, WITHDRAW_ENDPOINT,
+};
+use hex::ToHex;
+use serde_json::json;
+use tonic::transport::{Identity, Server, ServerTlsConfig};
+use tonic::{Request, Response, Status};
+use tracing::{error, info};
+
+use super::rpc_server::hash_password;
+use super::{
+    ConnectFedPayload, FederationInfo, LeaveFedPayload, PayKeysendPayload, WithdrawPayload,
+    SPENDING_PASSWORD_HEADER,
+};
+use crate::api_token::ApiTokenScope;
+use crate::audit::{AuditActor, AuditEntry};
+use crate::gateway_admin::gateway_admin_server::{GatewayAdmin, GatewayAdminServer};
+use crate::gateway_admin::{
+    self, Channel, ConnectFederationRequest, FederationBalance, GetBalancesRequest,
+    GetBalancesResponse, GetInfoRequest, GetInfoResponse, LeaveFederationRequest,
+    ListChannelsRequest, ListChannelsResponse, PayKeysendRequest, PayKeysendResponse, RoutingFees,
+    WithdrawRequest, WithdrawResponse,
+};
+use crate::{Gateway, GatewayError};
+
+/// PEM encoded certificate and key the gRPC admin server authenticates itself
+/// with
+#[derive(Debug, Clone)]
+pub struct GrpcTls {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+/// Checks that the gRPC admin server on `listen` only serves plaintext on
+/// loopback addresses
+pub fn validate_grpc_listen(listen: SocketAddr, tls: Option<&GrpcTls>) -> anyhow::Result<()> {
+    ensure!(
+        tls.is_some() || listen.ip().is_loopback(),
+        "The gRPC admin API requires a TLS certificate to listen on {listen}"
+    );
+
+    Ok(())
+}
+
+/// Spawns the gRPC admin server on `listen`, which runs until the task group
+/// shuts down.
+pub fn run_grpc_server(
+    gateway: Arc<Gateway>,
+    listen: SocketAddr,
+    tls: Option<&GrpcTls>,
+    task_group: &TaskGroup,
+) -> anyhow::Result<()> {
+    validate_grpc_listen(listen, tls)?;
+
+    let mut builder = Server::builder();
+    if let Some(tls) = tls {
+        let cert = std::fs::read(&tls.cert_path).with_context(|| {
+            format!(
+                "Failed to read gRPC TLS certificate {}",
+                tls.cert_path.display()
+            )
+        })?;
+        let key = std::fs::read(&tls.key_path)
+            .with_context(|| format!("Failed to read gRPC TLS key {}", tls.key_path.display()))?;
+        builder = builder
+            .tls_config(ServerTlsConfig::new().identity(Identity::from_pem(cert, key)))
+            .context("Invalid gRPC TLS configuration")?;
+    }
+
+    let handle = task_group.make_handle();
+    let shutdown_rx = handle.make_shutdown_rx();
+    let server = builder
+        .add_service(GatewayAdminServer::new(GatewayAdminService { gateway }))
+        .serve_with_shutdown(listen, async {
+            shutdown_rx.await;
+        });
+
+    task_group.spawn("Gateway gRPC Admin Server", |_| async {
+        if let Err(e) = server.await {
+            error!("Error running gatewayd gRPC admin server: {e:?}");
+        } else {
+            info!("Successfully shutdown gRPC admin server");
+        }
+    });
+
+    info!("Successfully started gRPC admin server on {listen}");
+
+    Ok(())
+}
+
+struct GatewayAdminService {
+    gateway: Arc<Gateway>,
+}
+
+impl GatewayAdminService {
+    /// Validates that the bearer token in the `authorization` metadata matches
+    /// the gateway's password or is an API token with at least the
+    /// `required_scope`, and returns who sent the request
+    async fn authenticate<T>(
+        &self,
+        request: &Request<T>,
+        required_scope: ApiTokenScope,
+    ) -> Result<AuditActor, Status> {
+        let gateway_config = self
+            .gateway
+            .clone_gateway_config()
+            .await
+            .ok_or_else(|| Status::failed_precondition("The gateway is not configured"))?;
+
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| Status::unauthenticated("Missing bearer token"))?;
+
+        if hash_password(token, gateway_config.password_salt) == gateway_config.hashed_password {
+            return Ok(AuditActor::Password);
+        }
+
+        let Some(api_token) = self.gateway.api_token(token).await else {
+            return Err(Status::unauthenticated("Invalid bearer token"));
+        };
+
+        if !api_token.scope.allows(required_scope) {
+            return Err(Status::permission_denied(format!(
+                "The API token requires the {required_scope:?} scope"
+            )));
+        }
+
+        Ok(AuditActor::ApiToken {
+            token_hash: api_token.token_hash,
+            name: api_token.name,
+        })
+    }
+
+    /// Validates the `spending-password` metadata for requests that move funds
+    /// out of the gateway, if a spending password has been set, unless the
+    /// request is a lightning payment of `payment_amount` within its payment
+    /// threshold. Rejected requests are recorded in the audit log, like those
+    /// of the REST API.
+    async fn verify_spending_password<T>(
+        &self,
+        request: &Request<T>,
+        actor: &AuditActor,
+        action: &str,
+        parameters: &serde_json::Value,
+        payment_amount: Option<Amount>,
+    ) -> Result<(), Status> {
+        let spending_password = request
+            .metadata()
+            .get(SPENDING_PASSWORD_HEADER)
+            .and_then(|value| value.to_str().ok());
+
+        if self
+            .gateway
+            .verify_spending_password(spending_password, payment_amount)
+            .await
+        {
+            return Ok(());
+        }
+
+        self.record_audit_entry(actor.clone(), action, parameters.clone(), false)
+            .await;
+
+        Err(Status::unauthenticated("Invalid spending password"))
+    }
+
+    /// Records a mutating request in the audit log under the name of the
+    /// equivalent REST endpoint
+    async fn record_audit_entry(
+        &self,
+        actor: AuditActor,
+        action: &str,
+        parameters: serde_json::Value,
+        success: bool,
+    ) {
+        self.gateway
+            .record_audit_entry(AuditEntry {
+                recorded_at: fedimint_core::time::now(),
+                actor,
+                action: action.to_string(),
+                parameters: parameters.to_string(),
+                success,
+            })
+            .await;
+    }
+}
+
+impl From<GatewayError> for Status {
+    fn from(error: GatewayError) -> Self {
+        match error {
+            GatewayError::Disconnected => Status::unavailable(error.to_string()),
+            error => Status::internal(error.to_string()),
+        }
+    }
+}
+
+fn parse_federation_id(federation_id: &str) -> Result<FederationId, Status> {
+    FederationId::from_str(federation_id)
+        .map_err(|e| Status::invalid_argument(format!("Invalid federation id: {e}")))
+}
+
+impl TryFrom<PayKeysendRequest> for PayKeysendPayload {
+    type Error = Status;
+
+    fn try_from(request: PayKeysendRequest) -> Result<Self, Status> {
+        Ok(PayKeysendPayload {
+            node_id: secp256k1::PublicKey::from_str(&request.node_id)
+                .map_err(|e| Status::invalid_argument(format!("Invalid node id: {e}")))?,
+            amount: Amount::from_msats(request.amount_msat),
+            max_fee: Amount::from_msats(request.max_fee_msat),
+            tlv_records: request.tlv_records.into_iter().collect(),
+        })
+    }
+}
+
+impl TryFrom<WithdrawRequest> for WithdrawPayload {
+    type Error = Status;
+
+    fn try_from(request: WithdrawRequest) -> Result<Self, Status> {
+        Ok(WithdrawPayload {
+            federation_id: parse_federation_id(&request.federation_id)?,
+            amount: request.amount_sats.map_or(BitcoinAmountOrAll::All, |sats| {
+                BitcoinAmountOrAll::Amount(bitcoin::Amount::from_sat(sats))
+            }),
+            address: bitcoin::Address::from_str(&request.address)
+                .map_err(|e| Status::invalid_argument(format!("Invalid address: {e}")))?,
+        })
+    }
+}
+
+impl From<FederationInfo> for gateway_admin::FederationInfo {
+    fn from(info: FederationInfo) -> Self {
+        gateway_admin::FederationInfo {
+            federation_id: info.federation_id.to_string(),
+            balance_msat: info.balance_msat.msats,
+            channel_id: info.channel_id,
+            routing_fees: info.routing_fees.map(|fees| RoutingFees {
+                base_msat: fees.base_msat,
+                proportional_millionths: fees.proportional_millionths,
+            }),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl GatewayAdmin for GatewayAdminService {
+    async fn get_info(
+        &self,
+        request: Request<GetInfoRequest>,
+    ) -> Result<Response<GetInfoResponse>, Status> {
+        self.authenticate(&request, ApiTokenScope::ReadOnly).await?;
+        let info = self.gateway.handle_get_info().await?;
+
+        Ok(Response::new(GetInfoResponse {
+            version_hash: info.version_hash,
+            gateway_id: info.gateway_id.to_string(),
+            gateway_state: info.gateway_state,
+            lightning_pub_key: info.lightning_pub_key,
+            lightning_alias: info.lightning_alias,
+            network: info.network.map(|network| network.to_string()),
+            block_height: info.block_height,
+            synced_to_chain: info.synced_to_chain,
+            federations: info.federations.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn get_balances(
+        &self,
+        request: Request<GetBalancesRequest>,
+    ) -> Result<Response<GetBalancesResponse>, Status> {
+        self.authenticate(&request, ApiTokenScope::ReadOnly).await?;
+        let info = self.gateway.handle_get_info().await?;
+
+        let lightning_balances = match self.gateway.get_lightning_context().await {
+            Ok(context) => context.lnrpc.get_balances().await.ok(),
+            Err(_) => None,
+        };
+
+        Ok(Response::new(GetBalancesResponse {
+            federation_balances: info
+                .federations
+                .into_iter()
+                .map(|federation| FederationBalance {
+                    federation_id: federation.federation_id.to_string(),
+                    balance_msat: federation.balance_msat.msats,
+                })
+                .collect(),
+            onchain_balance_sats: lightning_balances.map(|balances| balances.onchain_balance_sats),
+            lightning_balance_msats: lightning_balances
+                .map(|balances| balances.lightning_balance_msats),
+        }))
+    }
+
+    async fn list_channels(
+        &self,
+        request: Request<ListChannelsRequest>,
+    ) -> Result<Response<ListChannelsResponse>, Status> {
+        self.authenticate(&request, ApiTokenScope::ReadOnly).await?;
+        let channels = self.gateway.handle_list_active_channels_msg().await?;
+
+        Ok(Response::new(ListChannelsResponse {
+            channels: channels
+                .into_iter()
+                .map(|channel| Channel {
+                    remote_pubkey: channel.remote_pubkey,
+                    channel_size_sats: channel.channel_size_sats,
+                    outbound_liquidity_sats: channel.outbound_liquidity_sats,
+                    inbound_liquidity_sats: channel.inbound_liquidity_sats,
+                    short_channel_id: channel.short_channel_id,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn connect_federation(
+        &self,
+        request: Request<ConnectFederationRequest>,
+    ) -> Result<Response<gateway_admin::FederationInfo>, Status> {
+        let actor = self.authenticate(&request, ApiTokenScope::Admin).await?;
+        let invite_code = request.into_inner().invite_code;
+        let result = self
+            .gateway
+            .handle_connect_federation(ConnectFedPayload {
+                invite_code: invite_code.clone(),
+            })
+            .await;
+        self.record_audit_entry(
+            actor,
+            CONNECT_FED_ENDPOINT,
+            json!({ "invite_code": invite_code }),
+            result.is_ok(),
+        )
+        .await;
+
+        Ok(Response::new(result?.into()))
+    }
+
+    async fn leave_federation(
+        &self,
+        request: Request<LeaveFederationRequest>,
+    ) -> Result<Response<gateway_admin::FederationInfo>, Status> {
+        let actor = self.authenticate(&request, ApiTokenScope::Admin).await?;
+        let federation_id = parse_federation_id(&request.into_inner().federation_id)?;
+        let result = self
+            .gateway
+            .handle_leave_federation(LeaveFedPayload { federation_id })
+            .await;
+        self.record_audit_entry(
+            actor,
+            LEAVE_FED_ENDPOINT,
+            json!({ "federation_id": federation_id }),
+            result.is_ok(),
+        )
+        .await;
+
+        Ok(Response::new(result?.into()))
+    }
+
+    async fn pay_keysend(
+        &self,
+        request: Request<PayKeysendRequest>,
+    ) -> Result<Response<PayKeysendResponse>, Status> {
+        let actor = self.authenticate(&request, ApiTokenScope::Payments).await?;
+        let payload = PayKeysendPayload::try_from(request.get_ref().clone())?;
+        let parameters = json!(payload);
+        self.verify_spending_password(
+            &request,
+            &actor,
+            PAY_KEYSEND_ENDPOINT,
+            &parameters,
+            Some(payload.amount),
+        )
+        .await?;
+
+        let result = self.gateway.handle_pay_keysend_msg(payload).await;
+        self.record_audit_entry(actor, PAY_KEYSEND_ENDPOINT, parameters, result.is_ok())
+            .await;
+
+        Ok(Response::new(PayKeysendResponse {
+            preimage: result?.0.encode_hex(),
+        }))
+    }
+
+    async fn withdraw(
+        &self,
+        request: Request<WithdrawRequest>,
+    ) -> Result<Response<WithdrawResponse>, Status> {
+        let actor = self.authenticate(&request, ApiTokenScope::Admin).await?;
+        let payload = WithdrawPayload::try_from(request.get_ref().clone())?;
+        let parameters = json!(payload);
+        self.verify_spending_password(&request, &actor, WITHDRAW_ENDPOINT, &parameters, None)
+            .await?;
+
+        let result = self.gateway.handle_withdraw_msg(payload).await;
+        self.record_audit_entry(actor, WITHDRAW_ENDPOINT, parameters, result.is_ok())
+            .await;
+
+        Ok(Response::new(WithdrawResponse {
+            txid: result?.to_string(),
+        }))
+    }
+}
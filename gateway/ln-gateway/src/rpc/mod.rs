@@ -1,20 +1,43 @@
+pub mod grpc_server;
 pub mod rpc_client;
 pub mod rpc_server;
 
 use std::collections::BTreeMap;
 use std::str::FromStr;
+use std::time::SystemTime;
 
 use bitcoin::address::NetworkUnchecked;
 use bitcoin::{Address, Network};
+use bitcoin_hashes::sha256;
 use fedimint_core::config::{ClientConfig, FederationId, JsonClientConfig};
+use fedimint_core::core::OperationId;
+use fedimint_core::util::SafeUrl;
 use fedimint_core::{secp256k1, Amount, BitcoinAmountOrAll};
 use fedimint_ln_common::config::parse_routing_fees;
 use fedimint_ln_common::{route_hints, serde_option_routing_fees};
 use lightning_invoice::RoutingFees;
 use serde::{Deserialize, Serialize};
 
+use crate::alerts::AlertPolicy;
+use crate::api_token::ApiTokenScope;
+use crate::dynamic_fees::DynamicFeePolicy;
+use crate::incoming_limits::IncomingLimits;
+use crate::lightning::{ChannelPolicy, MissionControlPair, OpenChannelOptions};
+use crate::liquidity_cap::LiquidityCap;
+use crate::payment_log::{PaymentLogFilter, PaymentLogFormat, SummaryPeriod};
+use crate::payment_retry::PaymentRetryPolicy;
+use crate::price::FiatPrices;
+use crate::protocols::LightningProtocols;
+use crate::rebalance::RebalanceBand;
+use crate::snapshot::EncryptedSnapshot;
+use crate::sweep::SweepPolicy;
+
 pub const V1_API_ENDPOINT: &str = "v1";
 
+/// Header carrying the spending password for requests that move funds out of
+/// the gateway
+pub const SPENDING_PASSWORD_HEADER: &str = "spending-password";
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ConnectFedPayload {
     pub invite_code: String,
@@ -68,6 +91,22 @@ pub struct FederationInfo {
     pub config: ClientConfig,
     pub channel_id: Option<u64>,
     pub routing_fees: Option<FederationRoutingFees>,
+    /// Limits on the incoming payments the gateway accepts for the federation
+    #[serde(default)]
+    pub incoming_limits: IncomingLimits,
+    /// Maximum ecash balance the gateway holds in the federation
+    #[serde(default)]
+    pub liquidity_cap: Option<LiquidityCap>,
+    /// Protocol versions the gateway serves for the federation
+    #[serde(default)]
+    pub protocols: LightningProtocols,
+    /// Value of the balance in the configured fiat currencies
+    #[serde(default)]
+    pub balance_fiat: BTreeMap<String, f64>,
+    /// Set while the gateway doesn't service the federation after repeated
+    /// payment failures
+    #[serde(default)]
+    pub suspended_until: Option<SystemTime>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -91,6 +130,9 @@ pub struct GatewayInfo {
     // should be able to remove it once 0.4.0 is released.
     #[serde(default)]
     pub synced_to_chain: bool,
+    /// Prices of bitcoin in the configured fiat currencies, if already fetched
+    #[serde(default)]
+    pub fiat_prices: Option<FiatPrices>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -122,6 +164,12 @@ impl From<RoutingFees> for FederationRoutingFees {
     }
 }
 
+impl std::fmt::Display for FederationRoutingFees {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{}", self.base_msat, self.proportional_millionths)
+    }
+}
+
 impl FromStr for FederationRoutingFees {
     type Err = anyhow::Error;
 
@@ -143,6 +191,387 @@ pub struct SetConfigurationPayload {
     pub per_federation_routing_fees: Option<Vec<(FederationId, FederationRoutingFees)>>,
 }
 
+/// The gateway's fee, routing, liquidity and alerting policies. It can be
+/// exported from one gateway and imported on another, so that several gateways
+/// run identical policies. Settings missing from an imported policy, e.g.
+/// because it was exported by an older gateway, are left unchanged.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct GatewayPolicy {
+    pub num_route_hints: u32,
+    pub routing_fees: FederationRoutingFees,
+    pub per_federation_routing_fees: BTreeMap<FederationId, FederationRoutingFees>,
+    #[serde(default)]
+    pub per_federation_incoming_limits: BTreeMap<FederationId, IncomingLimits>,
+    #[serde(default)]
+    pub per_federation_liquidity_caps: BTreeMap<FederationId, LiquidityCap>,
+    #[serde(default)]
+    pub per_federation_rebalance_bands: BTreeMap<FederationId, RebalanceBand>,
+    #[serde(default)]
+    pub per_federation_protocols: BTreeMap<FederationId, LightningProtocols>,
+    #[serde(default)]
+    pub dynamic_fee_policy: Option<DynamicFeePolicy>,
+    #[serde(default)]
+    pub payment_retry_policy: Option<PaymentRetryPolicy>,
+    #[serde(default)]
+    pub alert_policy: Option<AlertPolicy>,
+    #[serde(default)]
+    pub sweep_policy: Option<SweepPolicy>,
+}
+
+impl GatewayPolicy {
+    /// Lists the settings that differ between `self` and `other`
+    pub fn diff(&self, other: &GatewayPolicy) -> Vec<PolicyChange> {
+        let mut changes = Vec::new();
+
+        if self.num_route_hints != other.num_route_hints {
+            changes.push(PolicyChange {
+                setting: "num_route_hints".to_string(),
+                current: Some(self.num_route_hints.to_string()),
+                imported: other.num_route_hints.to_string(),
+            });
+        }
+
+        if self.routing_fees != other.routing_fees {
+            changes.push(PolicyChange {
+                setting: "routing_fees".to_string(),
+                current: Some(self.routing_fees.to_string()),
+                imported: other.routing_fees.to_string(),
+            });
+        }
+
+        for (federation_id, fees) in &other.per_federation_routing_fees {
+            let current = self.per_federation_routing_fees.get(federation_id);
+            if current != Some(fees) {
+                changes.push(PolicyChange {
+                    setting: format!("routing_fees[{federation_id}]"),
+                    current: current.map(ToString::to_string),
+                    imported: fees.to_string(),
+                });
+            }
+        }
+
+        diff_per_federation(
+            &mut changes,
+            "incoming_limits",
+            &self.per_federation_incoming_limits,
+            &other.per_federation_incoming_limits,
+        );
+        diff_per_federation(
+            &mut changes,
+            "liquidity_cap",
+            &self.per_federation_liquidity_caps,
+            &other.per_federation_liquidity_caps,
+        );
+        diff_per_federation(
+            &mut changes,
+            "rebalance_band",
+            &self.per_federation_rebalance_bands,
+            &other.per_federation_rebalance_bands,
+        );
+        diff_per_federation(
+            &mut changes,
+            "protocols",
+            &self.per_federation_protocols,
+            &other.per_federation_protocols,
+        );
+
+        diff_setting(
+            &mut changes,
+            "dynamic_fee_policy",
+            &self.dynamic_fee_policy,
+            &other.dynamic_fee_policy,
+        );
+        diff_setting(
+            &mut changes,
+            "payment_retry_policy",
+            &self.payment_retry_policy,
+            &other.payment_retry_policy,
+        );
+        diff_setting(
+            &mut changes,
+            "alert_policy",
+            &self.alert_policy,
+            &other.alert_policy,
+        );
+        diff_setting(
+            &mut changes,
+            "sweep_policy",
+            &self.sweep_policy,
+            &other.sweep_policy,
+        );
+
+        changes
+    }
+}
+
+/// Records a change of the setting `name` for every federation whose value in
+/// `imported` differs from the one in `current`
+fn diff_per_federation<T: Serialize + PartialEq>(
+    changes: &mut Vec<PolicyChange>,
+    name: &str,
+    current: &BTreeMap<FederationId, T>,
+    imported: &BTreeMap<FederationId, T>,
+) {
+    for (federation_id, value) in imported {
+        let current = current.get(federation_id);
+        if current != Some(value) {
+            changes.push(PolicyChange {
+                setting: format!("{name}[{federation_id}]"),
+                current: current.map(policy_value),
+                imported: policy_value(value),
+            });
+        }
+    }
+}
+
+/// Records a change of the setting `name` if the imported value differs from
+/// the current one
+fn diff_setting<T: Serialize + PartialEq>(
+    changes: &mut Vec<PolicyChange>,
+    name: &str,
+    current: &Option<T>,
+    imported: &Option<T>,
+) {
+    if current != imported {
+        changes.push(PolicyChange {
+            setting: name.to_string(),
+            current: current.as_ref().map(policy_value),
+            imported: imported
+                .as_ref()
+                .map_or_else(|| "none".to_string(), policy_value),
+        });
+    }
+}
+
+fn policy_value<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("Policies can be serialized to JSON")
+}
+
+/// A setting that is changed by importing a `GatewayPolicy`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PolicyChange {
+    pub setting: String,
+    pub current: Option<String>,
+    pub imported: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportPolicyPayload {
+    pub policy: GatewayPolicy,
+    /// Only validate the policy and return the changes it would make
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SetDynamicFeePolicyPayload {
+    /// The new policy, `None` stops adjusting the routing fees
+    pub policy: Option<DynamicFeePolicy>,
+    /// Only validate the policy and return the fee adjustments it would make
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RegisterLightningAddressPayload {
+    pub username: String,
+    pub federation_id: FederationId,
+    pub description: String,
+    pub min_sendable_msat: u64,
+    pub max_sendable_msat: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RemoveLightningAddressPayload {
+    pub username: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ClaimLightningAddressPayload {
+    pub username: String,
+    /// Operations of the notes that were passed on to the user and can be
+    /// forgotten by the gateway
+    #[serde(default)]
+    pub acknowledge: Vec<OperationId>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SetLightningProtocolsPayload {
+    pub federation_id: FederationId,
+    pub protocols: LightningProtocols,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SetLiquidityCapPayload {
+    pub federation_id: FederationId,
+    /// The new cap, `None` removes it
+    pub cap: Option<LiquidityCap>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CreateInvoiceBatchPayload {
+    /// An invoice is created for every amount
+    pub amounts: Vec<Amount>,
+    pub description: String,
+    pub expiry_secs: u32,
+    /// Passed back in the notifications of the batch
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// Url to notify about every paid invoice
+    pub webhook_url: Option<SafeUrl>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CreateApiTokenPayload {
+    /// Describes what the token is used for
+    pub name: String,
+    pub scope: ApiTokenScope,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RevokeApiTokenPayload {
+    pub token_hash: sha256::Hash,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SetPaymentRetryPolicyPayload {
+    /// The new policy, `None` attempts every payment once
+    pub policy: Option<PaymentRetryPolicy>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PaymentAttemptsPayload {
+    pub payment_hash: sha256::Hash,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PaymentLogPayload {
+    #[serde(default)]
+    pub filter: PaymentLogFilter,
+    /// Only payments that finished at or after this time are returned
+    pub start: Option<SystemTime>,
+    /// Only payments that finished before this time are returned
+    pub end: Option<SystemTime>,
+    /// Number of matching payments to skip, newest first
+    #[serde(default)]
+    pub offset: usize,
+    /// Maximum number of payments to return, all if not set
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PaymentSummaryPayload {
+    pub period: SummaryPeriod,
+    /// Only payments that finished at or after this time are summarized
+    pub start: Option<SystemTime>,
+    /// Only payments that finished before this time are summarized
+    pub end: Option<SystemTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExportPaymentLogPayload {
+    pub format: PaymentLogFormat,
+    /// Only payments that finished at or after this time are exported
+    pub start: Option<SystemTime>,
+    /// Only payments that finished before this time are exported
+    pub end: Option<SystemTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SetAlertPolicyPayload {
+    /// The new policy, `None` stops raising alerts
+    pub policy: Option<AlertPolicy>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SetSweepPolicyPayload {
+    /// The new policy, `None` stops sweeping
+    pub policy: Option<SweepPolicy>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SetRebalanceBandPayload {
+    pub federation_id: FederationId,
+    /// The new band, `None` stops rebalancing the federation
+    pub band: Option<RebalanceBand>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SetDrainingPayload {
+    pub draining: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CreateSnapshotPayload {
+    /// Password the snapshot is encrypted with
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CreateSwapPayload {
+    /// Amount the lightning node receives
+    pub amount_sats: u64,
+    /// Maximum the swap provider may charge on top of the amount
+    pub max_fee_sats: u64,
+    /// Fee rate of the lockup transaction, estimated by the node if not set
+    pub sat_per_vbyte: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CreateReverseSwapPayload {
+    /// Amount the lightning node pays
+    pub amount_sats: u64,
+    /// Maximum of the swap provider's fee and the routing fee together, which
+    /// are deducted from the amount received on-chain
+    pub max_fee_sats: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RefundSwapPayload {
+    pub id: String,
+    /// Fee rate of the refund transaction, estimated by the gateway's chain
+    /// source if not set
+    pub sat_per_vbyte: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RestoreSnapshotPayload {
+    pub snapshot: EncryptedSnapshot,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SetRebalancingPausedPayload {
+    pub paused: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SetIncomingLimitsPayload {
+    pub federation_id: FederationId,
+    /// The new limits, which replace the current ones
+    pub limits: IncomingLimits,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SetSpendingPasswordPayload {
+    /// The new spending password, `None` removes it
+    pub spending_password: Option<String>,
+    /// Lightning payments up to this amount don't require the spending
+    /// password. Without a threshold every payment requires it.
+    #[serde(default)]
+    pub payment_threshold: Option<Amount>,
+}
+
+impl std::fmt::Debug for SetSpendingPasswordPayload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SetSpendingPasswordPayload")
+            .field(
+                "spending_password",
+                &self.spending_password.as_ref().map(|_| "<redacted>"),
+            )
+            .field("payment_threshold", &self.payment_threshold)
+            .finish()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GetFundingAddressPayload;
 
@@ -152,6 +581,128 @@ pub struct OpenChannelPayload {
     pub host: String,
     pub channel_size_sats: u64,
     pub push_amount_sats: u64,
+    #[serde(default)]
+    pub options: OpenChannelOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConnectPeerPayload {
+    pub pubkey: secp256k1::PublicKey,
+    pub host: String,
+    /// Reconnect to the peer whenever the connection is lost, including after
+    /// restarts of the gateway
+    #[serde(default)]
+    pub persistent: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DisconnectPeerPayload {
+    pub pubkey: secp256k1::PublicKey,
+}
+
+/// A peer that the gateway's lightning node is connected to, or that the
+/// gateway keeps it connected to
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PeerStatus {
+    pub pubkey: secp256k1::PublicKey,
+    /// Address of the current connection, or the configured host if the peer
+    /// is disconnected
+    pub address: Option<String>,
+    pub connected: bool,
+    pub persistent: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QueryRoutePayload {
+    pub destination: secp256k1::PublicKey,
+    pub amount: Amount,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UpdateChannelPolicyPayload {
+    pub short_channel_id: u64,
+    pub policy: ChannelPolicy,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportMissionControlPayload {
+    pub pairs: Vec<MissionControlPair>,
+    /// Override the lightning node's own history even if it is more recent
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// Summary of the routing history of the gateway's lightning node
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MissionControlStats {
+    /// Number of node pairs with routing history
+    pub num_pairs: usize,
+    /// Number of node pairs whose most recent payment attempt failed
+    pub num_failing_pairs: usize,
+    pub last_failure_time: Option<u64>,
+    pub last_success_time: Option<u64>,
+}
+
+impl MissionControlStats {
+    pub fn from_pairs(pairs: &[MissionControlPair]) -> Self {
+        let non_zero = |time: u64| (time != 0).then_some(time);
+
+        MissionControlStats {
+            num_pairs: pairs.len(),
+            num_failing_pairs: pairs
+                .iter()
+                .filter(|pair| pair.fail_time > pair.success_time)
+                .count(),
+            last_failure_time: pairs
+                .iter()
+                .filter_map(|pair| non_zero(pair.fail_time))
+                .max(),
+            last_success_time: pairs
+                .iter()
+                .filter_map(|pair| non_zero(pair.success_time))
+                .max(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Lsps1GetInfoPayload {
+    pub lsp_node_id: secp256k1::PublicKey,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Lsps1CreateOrderPayload {
+    pub lsp_node_id: secp256k1::PublicKey,
+    /// Inbound liquidity to buy
+    pub lsp_balance_sat: u64,
+    /// Outbound liquidity to buy on top of the inbound liquidity
+    #[serde(default)]
+    pub client_balance_sat: u64,
+    /// Number of blocks the LSP has to keep the channel open
+    pub channel_expiry_blocks: u32,
+    #[serde(default)]
+    pub announce_channel: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Lsps1PayOrderPayload {
+    pub order_id: String,
+    /// Maximum routing fee for paying the order
+    pub max_fee: Amount,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Lsps1OrderPayload {
+    pub order_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PayKeysendPayload {
+    pub node_id: secp256k1::PublicKey,
+    pub amount: Amount,
+    pub max_fee: Amount,
+    /// Custom records attached to the payment, keyed by TLV type
+    pub tlv_records: BTreeMap<u64, Vec<u8>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -3,10 +3,30 @@ use bitcoin::Address;
 use fedimint_core::util::SafeUrl;
 use fedimint_core::{Amount, TransactionId};
 use fedimint_ln_common::gateway_endpoint_constants::{
-    BACKUP_ENDPOINT, BALANCE_ENDPOINT, CLOSE_CHANNELS_WITH_PEER_ENDPOINT, CONFIGURATION_ENDPOINT,
-    CONNECT_FED_ENDPOINT, GATEWAY_INFO_ENDPOINT, GATEWAY_INFO_POST_ENDPOINT,
-    GET_FUNDING_ADDRESS_ENDPOINT, LEAVE_FED_ENDPOINT, LIST_ACTIVE_CHANNELS_ENDPOINT,
-    OPEN_CHANNEL_ENDPOINT, RESTORE_ENDPOINT, SET_CONFIGURATION_ENDPOINT, WITHDRAW_ENDPOINT,
+    ALERT_POLICY_ENDPOINT, AUDIT_LOG_ENDPOINT, BACKUP_ENDPOINT, BALANCE_ENDPOINT,
+    CLAIM_LIGHTNING_ADDRESS_ENDPOINT, CLOSE_CHANNELS_WITH_PEER_ENDPOINT, CONFIGURATION_ENDPOINT,
+    CONNECT_FED_ENDPOINT, CONNECT_PEER_ENDPOINT, CREATE_API_TOKEN_ENDPOINT,
+    CREATE_INVOICE_BATCH_ENDPOINT, CREATE_REVERSE_SWAP_ENDPOINT, CREATE_SNAPSHOT_ENDPOINT,
+    CREATE_SWAP_ENDPOINT, DISCONNECT_PEER_ENDPOINT, DRAIN_STATUS_ENDPOINT,
+    EXPORT_MISSION_CONTROL_ENDPOINT, EXPORT_PAYMENT_LOG_ENDPOINT, EXPORT_POLICY_ENDPOINT,
+    GATEWAY_INFO_ENDPOINT, GATEWAY_INFO_POST_ENDPOINT, GET_FUNDING_ADDRESS_ENDPOINT,
+    IMPORT_MISSION_CONTROL_ENDPOINT, IMPORT_POLICY_ENDPOINT, LEAVE_FED_ENDPOINT,
+    LIQUIDITY_HEALTH_ENDPOINT, LIQUIDITY_HISTORY_ENDPOINT, LIST_ACTIVE_CHANNELS_ENDPOINT,
+    LIST_API_TOKENS_ENDPOINT, LIST_INVOICE_BATCHES_ENDPOINT, LIST_LIGHTNING_ADDRESSES_ENDPOINT,
+    LIST_PEERS_ENDPOINT, LIST_REVERSE_SWAPS_ENDPOINT, LIST_SWAPS_ENDPOINT,
+    LSPS1_CREATE_ORDER_ENDPOINT, LSPS1_GET_INFO_ENDPOINT, LSPS1_GET_ORDER_ENDPOINT,
+    LSPS1_PAY_ORDER_ENDPOINT, MISSION_CONTROL_STATS_ENDPOINT, MONITORING_BUNDLE_ENDPOINT,
+    OPEN_CHANNEL_ENDPOINT, PAYMENT_ATTEMPTS_ENDPOINT, PAYMENT_LOG_ENDPOINT,
+    PAYMENT_RETRY_POLICY_ENDPOINT, PAYMENT_SUMMARY_ENDPOINT, PAY_KEYSEND_ENDPOINT,
+    QUERY_ROUTE_ENDPOINT, REBALANCE_STATUS_ENDPOINT, REFUND_SWAP_ENDPOINT,
+    REGISTER_LIGHTNING_ADDRESS_ENDPOINT, REMOVE_LIGHTNING_ADDRESS_ENDPOINT,
+    RESET_MISSION_CONTROL_ENDPOINT, RESTORE_ENDPOINT, RESTORE_SNAPSHOT_ENDPOINT,
+    REVOKE_API_TOKEN_ENDPOINT, SET_ALERT_POLICY_ENDPOINT, SET_CONFIGURATION_ENDPOINT,
+    SET_DRAINING_ENDPOINT, SET_DYNAMIC_FEE_POLICY_ENDPOINT, SET_INCOMING_LIMITS_ENDPOINT,
+    SET_LIGHTNING_PROTOCOLS_ENDPOINT, SET_LIQUIDITY_CAP_ENDPOINT,
+    SET_PAYMENT_RETRY_POLICY_ENDPOINT, SET_REBALANCE_BAND_ENDPOINT,
+    SET_REBALANCING_PAUSED_ENDPOINT, SET_SPENDING_PASSWORD_ENDPOINT, SET_SWEEP_POLICY_ENDPOINT,
+    SWEEP_POLICY_ENDPOINT, UPDATE_CHANNEL_POLICY_ENDPOINT, WITHDRAW_ENDPOINT,
 };
 use reqwest::{Method, StatusCode};
 use serde::de::DeserializeOwned;
@@ -14,11 +34,40 @@ use serde::Serialize;
 use thiserror::Error;
 
 use super::{
-    BackupPayload, BalancePayload, CloseChannelsWithPeerPayload, ConfigPayload, ConnectFedPayload,
-    DepositAddressPayload, FederationInfo, GatewayFedConfig, GatewayInfo, GetFundingAddressPayload,
-    LeaveFedPayload, OpenChannelPayload, RestorePayload, SetConfigurationPayload, WithdrawPayload,
+    BackupPayload, BalancePayload, ClaimLightningAddressPayload, CloseChannelsWithPeerPayload,
+    ConfigPayload, ConnectFedPayload, ConnectPeerPayload, CreateApiTokenPayload,
+    CreateInvoiceBatchPayload, CreateReverseSwapPayload, CreateSnapshotPayload, CreateSwapPayload,
+    DepositAddressPayload, DisconnectPeerPayload, ExportPaymentLogPayload, FederationInfo,
+    GatewayFedConfig, GatewayInfo, GatewayPolicy, GetFundingAddressPayload,
+    ImportMissionControlPayload, ImportPolicyPayload, LeaveFedPayload, Lsps1CreateOrderPayload,
+    Lsps1GetInfoPayload, Lsps1OrderPayload, Lsps1PayOrderPayload, MissionControlStats,
+    OpenChannelPayload, PayKeysendPayload, PaymentAttemptsPayload, PaymentLogPayload,
+    PaymentSummaryPayload, PeerStatus, PolicyChange, QueryRoutePayload, RefundSwapPayload,
+    RegisterLightningAddressPayload, RemoveLightningAddressPayload, RestorePayload,
+    RestoreSnapshotPayload, RevokeApiTokenPayload, SetAlertPolicyPayload, SetConfigurationPayload,
+    SetDrainingPayload, SetDynamicFeePolicyPayload, SetIncomingLimitsPayload,
+    SetLightningProtocolsPayload, SetLiquidityCapPayload, SetPaymentRetryPolicyPayload,
+    SetRebalanceBandPayload, SetRebalancingPausedPayload, SetSpendingPasswordPayload,
+    SetSweepPolicyPayload, UpdateChannelPolicyPayload, WithdrawPayload, SPENDING_PASSWORD_HEADER,
 };
-use crate::lightning::ChannelInfo;
+use crate::alerts::AlertPolicy;
+use crate::api_token::{ApiToken, CreatedApiToken};
+use crate::audit::AuditEntry;
+use crate::drain::DrainStatus;
+use crate::dynamic_fees::FeeAdjustment;
+use crate::history::LiquiditySample;
+use crate::invoice_batch::InvoiceBatch;
+use crate::lightning::{ChannelInfo, MissionControlPair, RouteEstimate};
+use crate::lnurl::{LightningAddressNotes, LightningAddressUser};
+use crate::lsps1::Lsps1Order;
+use crate::monitoring::MonitoringBundle;
+use crate::payment_log::{PaymentLogEntry, PaymentSummaryBucket};
+use crate::payment_retry::{PaymentAttempt, PaymentRetryPolicy};
+use crate::probing::LiquidityHealth;
+use crate::rebalance::RebalanceStatus;
+use crate::snapshot::EncryptedSnapshot;
+use crate::swap::{ReverseSwapInfo, SwapInfo};
+use crate::sweep::SweepPolicy;
 use crate::CloseChannelsWithPeerResponse;
 
 pub struct GatewayRpcClient {
@@ -29,6 +78,9 @@ pub struct GatewayRpcClient {
     client: reqwest::Client,
     /// Optional gateway password
     password: Option<String>,
+    /// Optional spending password, required for requests that move funds out
+    /// of the gateway if one is set
+    spending_password: Option<String>,
 }
 
 impl GatewayRpcClient {
@@ -37,11 +89,18 @@ impl GatewayRpcClient {
             base_url: versioned_api,
             client: reqwest::Client::new(),
             password,
+            spending_password: None,
         }
     }
 
     pub fn with_password(&self, password: Option<String>) -> Self {
         GatewayRpcClient::new(self.base_url.clone(), password)
+            .with_spending_password(self.spending_password.clone())
+    }
+
+    pub fn with_spending_password(mut self, spending_password: Option<String>) -> Self {
+        self.spending_password = spending_password;
+        self
     }
 
     pub async fn get_info(&self) -> GatewayRpcResult<GatewayInfo> {
@@ -142,6 +201,380 @@ impl GatewayRpcClient {
         self.call_post(url, payload).await
     }
 
+    pub async fn export_policy(&self) -> GatewayRpcResult<GatewayPolicy> {
+        let url = self
+            .base_url
+            .join(EXPORT_POLICY_ENDPOINT)
+            .expect("invalid base url");
+        self.call_get(url).await
+    }
+
+    pub async fn import_policy(
+        &self,
+        payload: ImportPolicyPayload,
+    ) -> GatewayRpcResult<Vec<PolicyChange>> {
+        let url = self
+            .base_url
+            .join(IMPORT_POLICY_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn set_dynamic_fee_policy(
+        &self,
+        payload: SetDynamicFeePolicyPayload,
+    ) -> GatewayRpcResult<Vec<FeeAdjustment>> {
+        let url = self
+            .base_url
+            .join(SET_DYNAMIC_FEE_POLICY_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn set_incoming_limits(
+        &self,
+        payload: SetIncomingLimitsPayload,
+    ) -> GatewayRpcResult<()> {
+        let url = self
+            .base_url
+            .join(SET_INCOMING_LIMITS_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn register_lightning_address(
+        &self,
+        payload: RegisterLightningAddressPayload,
+    ) -> GatewayRpcResult<LightningAddressUser> {
+        let url = self
+            .base_url
+            .join(REGISTER_LIGHTNING_ADDRESS_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn remove_lightning_address(
+        &self,
+        payload: RemoveLightningAddressPayload,
+    ) -> GatewayRpcResult<()> {
+        let url = self
+            .base_url
+            .join(REMOVE_LIGHTNING_ADDRESS_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn claim_lightning_address(
+        &self,
+        payload: ClaimLightningAddressPayload,
+    ) -> GatewayRpcResult<Vec<LightningAddressNotes>> {
+        let url = self
+            .base_url
+            .join(CLAIM_LIGHTNING_ADDRESS_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn list_lightning_addresses(&self) -> GatewayRpcResult<Vec<LightningAddressUser>> {
+        let url = self
+            .base_url
+            .join(LIST_LIGHTNING_ADDRESSES_ENDPOINT)
+            .expect("invalid base url");
+        self.call_get(url).await
+    }
+
+    pub async fn set_lightning_protocols(
+        &self,
+        payload: SetLightningProtocolsPayload,
+    ) -> GatewayRpcResult<()> {
+        let url = self
+            .base_url
+            .join(SET_LIGHTNING_PROTOCOLS_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn set_liquidity_cap(&self, payload: SetLiquidityCapPayload) -> GatewayRpcResult<()> {
+        let url = self
+            .base_url
+            .join(SET_LIQUIDITY_CAP_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn set_rebalance_band(
+        &self,
+        payload: SetRebalanceBandPayload,
+    ) -> GatewayRpcResult<()> {
+        let url = self
+            .base_url
+            .join(SET_REBALANCE_BAND_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn set_draining(&self, payload: SetDrainingPayload) -> GatewayRpcResult<DrainStatus> {
+        let url = self
+            .base_url
+            .join(SET_DRAINING_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn create_snapshot(
+        &self,
+        payload: CreateSnapshotPayload,
+    ) -> GatewayRpcResult<EncryptedSnapshot> {
+        let url = self
+            .base_url
+            .join(CREATE_SNAPSHOT_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn restore_snapshot(&self, payload: RestoreSnapshotPayload) -> GatewayRpcResult<()> {
+        let url = self
+            .base_url
+            .join(RESTORE_SNAPSHOT_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn drain_status(&self) -> GatewayRpcResult<DrainStatus> {
+        let url = self
+            .base_url
+            .join(DRAIN_STATUS_ENDPOINT)
+            .expect("invalid base url");
+        self.call_get(url).await
+    }
+
+    pub async fn set_rebalancing_paused(
+        &self,
+        payload: SetRebalancingPausedPayload,
+    ) -> GatewayRpcResult<()> {
+        let url = self
+            .base_url
+            .join(SET_REBALANCING_PAUSED_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn rebalance_status(&self) -> GatewayRpcResult<RebalanceStatus> {
+        let url = self
+            .base_url
+            .join(REBALANCE_STATUS_ENDPOINT)
+            .expect("invalid base url");
+        self.call_get(url).await
+    }
+
+    pub async fn create_invoice_batch(
+        &self,
+        payload: CreateInvoiceBatchPayload,
+    ) -> GatewayRpcResult<InvoiceBatch> {
+        let url = self
+            .base_url
+            .join(CREATE_INVOICE_BATCH_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn list_invoice_batches(&self) -> GatewayRpcResult<Vec<InvoiceBatch>> {
+        let url = self
+            .base_url
+            .join(LIST_INVOICE_BATCHES_ENDPOINT)
+            .expect("invalid base url");
+        self.call_get(url).await
+    }
+
+    pub async fn create_swap(&self, payload: CreateSwapPayload) -> GatewayRpcResult<SwapInfo> {
+        let url = self
+            .base_url
+            .join(CREATE_SWAP_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn create_reverse_swap(
+        &self,
+        payload: CreateReverseSwapPayload,
+    ) -> GatewayRpcResult<ReverseSwapInfo> {
+        let url = self
+            .base_url
+            .join(CREATE_REVERSE_SWAP_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn list_reverse_swaps(&self) -> GatewayRpcResult<Vec<ReverseSwapInfo>> {
+        let url = self
+            .base_url
+            .join(LIST_REVERSE_SWAPS_ENDPOINT)
+            .expect("invalid base url");
+        self.call_get(url).await
+    }
+
+    pub async fn refund_swap(&self, payload: RefundSwapPayload) -> GatewayRpcResult<SwapInfo> {
+        let url = self
+            .base_url
+            .join(REFUND_SWAP_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn list_swaps(&self) -> GatewayRpcResult<Vec<SwapInfo>> {
+        let url = self
+            .base_url
+            .join(LIST_SWAPS_ENDPOINT)
+            .expect("invalid base url");
+        self.call_get(url).await
+    }
+
+    pub async fn set_sweep_policy(&self, payload: SetSweepPolicyPayload) -> GatewayRpcResult<()> {
+        let url = self
+            .base_url
+            .join(SET_SWEEP_POLICY_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn sweep_policy(&self) -> GatewayRpcResult<Option<SweepPolicy>> {
+        let url = self
+            .base_url
+            .join(SWEEP_POLICY_ENDPOINT)
+            .expect("invalid base url");
+        self.call_get(url).await
+    }
+
+    pub async fn set_alert_policy(&self, payload: SetAlertPolicyPayload) -> GatewayRpcResult<()> {
+        let url = self
+            .base_url
+            .join(SET_ALERT_POLICY_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn alert_policy(&self) -> GatewayRpcResult<Option<AlertPolicy>> {
+        let url = self
+            .base_url
+            .join(ALERT_POLICY_ENDPOINT)
+            .expect("invalid base url");
+        self.call_get(url).await
+    }
+
+    pub async fn set_payment_retry_policy(
+        &self,
+        payload: SetPaymentRetryPolicyPayload,
+    ) -> GatewayRpcResult<()> {
+        let url = self
+            .base_url
+            .join(SET_PAYMENT_RETRY_POLICY_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn payment_retry_policy(&self) -> GatewayRpcResult<Option<PaymentRetryPolicy>> {
+        let url = self
+            .base_url
+            .join(PAYMENT_RETRY_POLICY_ENDPOINT)
+            .expect("invalid base url");
+        self.call_get(url).await
+    }
+
+    pub async fn payment_attempts(
+        &self,
+        payload: PaymentAttemptsPayload,
+    ) -> GatewayRpcResult<Vec<PaymentAttempt>> {
+        let url = self
+            .base_url
+            .join(PAYMENT_ATTEMPTS_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn payment_log(
+        &self,
+        payload: PaymentLogPayload,
+    ) -> GatewayRpcResult<Vec<PaymentLogEntry>> {
+        let url = self
+            .base_url
+            .join(PAYMENT_LOG_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn payment_summary(
+        &self,
+        payload: PaymentSummaryPayload,
+    ) -> GatewayRpcResult<Vec<PaymentSummaryBucket>> {
+        let url = self
+            .base_url
+            .join(PAYMENT_SUMMARY_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    /// Returns the exported payment log as is, since it is not JSON
+    pub async fn export_payment_log(
+        &self,
+        payload: ExportPaymentLogPayload,
+    ) -> GatewayRpcResult<String> {
+        let url = self
+            .base_url
+            .join(EXPORT_PAYMENT_LOG_ENDPOINT)
+            .expect("invalid base url");
+        Ok(self
+            .send(Method::POST, url, Some(payload))
+            .await?
+            .text()
+            .await?)
+    }
+
+    pub async fn create_api_token(
+        &self,
+        payload: CreateApiTokenPayload,
+    ) -> GatewayRpcResult<CreatedApiToken> {
+        let url = self
+            .base_url
+            .join(CREATE_API_TOKEN_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn list_api_tokens(&self) -> GatewayRpcResult<Vec<ApiToken>> {
+        let url = self
+            .base_url
+            .join(LIST_API_TOKENS_ENDPOINT)
+            .expect("invalid base url");
+        self.call_get(url).await
+    }
+
+    pub async fn audit_log(&self) -> GatewayRpcResult<Vec<AuditEntry>> {
+        let url = self
+            .base_url
+            .join(AUDIT_LOG_ENDPOINT)
+            .expect("invalid base url");
+        self.call_get(url).await
+    }
+
+    pub async fn revoke_api_token(&self, payload: RevokeApiTokenPayload) -> GatewayRpcResult<()> {
+        let url = self
+            .base_url
+            .join(REVOKE_API_TOKEN_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn set_spending_password(
+        &self,
+        payload: SetSpendingPasswordPayload,
+    ) -> GatewayRpcResult<()> {
+        let url = self
+            .base_url
+            .join(SET_SPENDING_PASSWORD_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
     pub async fn get_funding_address(
         &self,
         payload: GetFundingAddressPayload,
@@ -172,6 +605,15 @@ impl GatewayRpcClient {
         self.call_post(url, payload).await
     }
 
+    /// Returns the hex encoded preimage of the keysend payment
+    pub async fn pay_keysend(&self, payload: PayKeysendPayload) -> GatewayRpcResult<String> {
+        let url = self
+            .base_url
+            .join(PAY_KEYSEND_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
     pub async fn list_active_channels(&self) -> GatewayRpcResult<Vec<ChannelInfo>> {
         let url = self
             .base_url
@@ -180,16 +622,174 @@ impl GatewayRpcClient {
         self.call_get(url).await
     }
 
+    pub async fn connect_peer(&self, payload: ConnectPeerPayload) -> GatewayRpcResult<()> {
+        let url = self
+            .base_url
+            .join(CONNECT_PEER_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn disconnect_peer(&self, payload: DisconnectPeerPayload) -> GatewayRpcResult<()> {
+        let url = self
+            .base_url
+            .join(DISCONNECT_PEER_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn list_peers(&self) -> GatewayRpcResult<Vec<PeerStatus>> {
+        let url = self
+            .base_url
+            .join(LIST_PEERS_ENDPOINT)
+            .expect("invalid base url");
+        self.call_get(url).await
+    }
+
+    pub async fn query_route(&self, payload: QueryRoutePayload) -> GatewayRpcResult<RouteEstimate> {
+        let url = self
+            .base_url
+            .join(QUERY_ROUTE_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn liquidity_health(&self) -> GatewayRpcResult<Vec<LiquidityHealth>> {
+        let url = self
+            .base_url
+            .join(LIQUIDITY_HEALTH_ENDPOINT)
+            .expect("invalid base url");
+        self.call_get(url).await
+    }
+
+    pub async fn liquidity_history(&self) -> GatewayRpcResult<Vec<LiquiditySample>> {
+        let url = self
+            .base_url
+            .join(LIQUIDITY_HISTORY_ENDPOINT)
+            .expect("invalid base url");
+        self.call_get(url).await
+    }
+
+    pub async fn monitoring_bundle(&self) -> GatewayRpcResult<MonitoringBundle> {
+        let url = self
+            .base_url
+            .join(MONITORING_BUNDLE_ENDPOINT)
+            .expect("invalid base url");
+        self.call_get(url).await
+    }
+
+    pub async fn update_channel_policy(
+        &self,
+        payload: UpdateChannelPolicyPayload,
+    ) -> GatewayRpcResult<()> {
+        let url = self
+            .base_url
+            .join(UPDATE_CHANNEL_POLICY_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn lsps1_get_info(
+        &self,
+        payload: Lsps1GetInfoPayload,
+    ) -> GatewayRpcResult<serde_json::Value> {
+        let url = self
+            .base_url
+            .join(LSPS1_GET_INFO_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn lsps1_create_order(
+        &self,
+        payload: Lsps1CreateOrderPayload,
+    ) -> GatewayRpcResult<Lsps1Order> {
+        let url = self
+            .base_url
+            .join(LSPS1_CREATE_ORDER_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn lsps1_pay_order(
+        &self,
+        payload: Lsps1PayOrderPayload,
+    ) -> GatewayRpcResult<Lsps1Order> {
+        let url = self
+            .base_url
+            .join(LSPS1_PAY_ORDER_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn lsps1_get_order(
+        &self,
+        payload: Lsps1OrderPayload,
+    ) -> GatewayRpcResult<Lsps1Order> {
+        let url = self
+            .base_url
+            .join(LSPS1_GET_ORDER_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn export_mission_control(&self) -> GatewayRpcResult<Vec<MissionControlPair>> {
+        let url = self
+            .base_url
+            .join(EXPORT_MISSION_CONTROL_ENDPOINT)
+            .expect("invalid base url");
+        self.call_get(url).await
+    }
+
+    pub async fn import_mission_control(
+        &self,
+        payload: ImportMissionControlPayload,
+    ) -> GatewayRpcResult<()> {
+        let url = self
+            .base_url
+            .join(IMPORT_MISSION_CONTROL_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
+    pub async fn reset_mission_control(&self) -> GatewayRpcResult<()> {
+        let url = self
+            .base_url
+            .join(RESET_MISSION_CONTROL_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, ()).await
+    }
+
+    pub async fn mission_control_stats(&self) -> GatewayRpcResult<MissionControlStats> {
+        let url = self
+            .base_url
+            .join(MISSION_CONTROL_STATS_ENDPOINT)
+            .expect("invalid base url");
+        self.call_get(url).await
+    }
+
     async fn call<P: Serialize, T: DeserializeOwned>(
         &self,
         method: Method,
         url: SafeUrl,
         payload: Option<P>,
     ) -> Result<T, GatewayRpcError> {
+        Ok(self.send(method, url, payload).await?.json().await?)
+    }
+
+    async fn send<P: Serialize>(
+        &self,
+        method: Method,
+        url: SafeUrl,
+        payload: Option<P>,
+    ) -> Result<reqwest::Response, GatewayRpcError> {
         let mut builder = self.client.request(method, url.to_unsafe());
         if let Some(password) = self.password.clone() {
             builder = builder.bearer_auth(password);
         }
+        if let Some(spending_password) = self.spending_password.as_ref() {
+            builder = builder.header(SPENDING_PASSWORD_HEADER, spending_password);
+        }
         if let Some(payload) = payload {
             builder = builder
                 .json(&payload)
@@ -198,7 +798,7 @@ impl GatewayRpcClient {
         let response = builder.send().await?;
 
         match response.status() {
-            StatusCode::OK => Ok(response.json().await?),
+            StatusCode::OK => Ok(response),
             status => Err(GatewayRpcError::BadStatus(status)),
         }
     }
@@ -1,8 +1,10 @@
 use std::sync::Arc;
 
-use axum::extract::Request;
-use axum::http::{header, StatusCode};
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, Method, StatusCode};
 use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::response::IntoResponse;
 use axum::routing::{get, post};
 use axum::{Extension, Json, Router};
@@ -13,35 +15,76 @@ use fedimint_core::encoding::Encodable;
 use fedimint_core::task::TaskGroup;
 use fedimint_ln_client::pay::PayInvoicePayload;
 use fedimint_ln_common::gateway_endpoint_constants::{
-    ADDRESS_ENDPOINT, BACKUP_ENDPOINT, BALANCE_ENDPOINT, CLOSE_CHANNELS_WITH_PEER_ENDPOINT,
-    CONFIGURATION_ENDPOINT, CONNECT_FED_ENDPOINT, CREATE_BOLT11_INVOICE_V2_ENDPOINT,
-    GATEWAY_INFO_ENDPOINT, GATEWAY_INFO_POST_ENDPOINT, GET_FUNDING_ADDRESS_ENDPOINT,
-    GET_GATEWAY_ID_ENDPOINT, LEAVE_FED_ENDPOINT, LIST_ACTIVE_CHANNELS_ENDPOINT,
-    OPEN_CHANNEL_ENDPOINT, PAY_INVOICE_ENDPOINT, RESTORE_ENDPOINT, ROUTING_INFO_V2_ENDPOINT,
-    SEND_PAYMENT_V2_ENDPOINT, SET_CONFIGURATION_ENDPOINT, WITHDRAW_ENDPOINT,
+    ADDRESS_ENDPOINT, ALERT_POLICY_ENDPOINT, AUDIT_LOG_ENDPOINT, BACKUP_ENDPOINT, BALANCE_ENDPOINT,
+    CLAIM_LIGHTNING_ADDRESS_ENDPOINT, CLOSE_CHANNELS_WITH_PEER_ENDPOINT, CONFIGURATION_ENDPOINT,
+    CONNECT_FED_ENDPOINT, CONNECT_PEER_ENDPOINT, CREATE_API_TOKEN_ENDPOINT,
+    CREATE_BOLT11_INVOICE_V2_ENDPOINT, CREATE_INVOICE_BATCH_ENDPOINT, CREATE_REVERSE_SWAP_ENDPOINT,
+    CREATE_SNAPSHOT_ENDPOINT, CREATE_SWAP_ENDPOINT, DISCONNECT_PEER_ENDPOINT,
+    DRAIN_STATUS_ENDPOINT, EVENTS_ENDPOINT, EXPORT_MISSION_CONTROL_ENDPOINT,
+    EXPORT_PAYMENT_LOG_ENDPOINT, EXPORT_POLICY_ENDPOINT, GATEWAY_INFO_ENDPOINT,
+    GATEWAY_INFO_POST_ENDPOINT, GET_FUNDING_ADDRESS_ENDPOINT, GET_GATEWAY_ID_ENDPOINT,
+    IMPORT_MISSION_CONTROL_ENDPOINT, IMPORT_POLICY_ENDPOINT, LEAVE_FED_ENDPOINT,
+    LIQUIDITY_HEALTH_ENDPOINT, LIQUIDITY_HISTORY_ENDPOINT, LIST_ACTIVE_CHANNELS_ENDPOINT,
+    LIST_API_TOKENS_ENDPOINT, LIST_INVOICE_BATCHES_ENDPOINT, LIST_LIGHTNING_ADDRESSES_ENDPOINT,
+    LIST_PEERS_ENDPOINT, LIST_REVERSE_SWAPS_ENDPOINT, LIST_SWAPS_ENDPOINT,
+    LSPS1_CREATE_ORDER_ENDPOINT, LSPS1_GET_INFO_ENDPOINT, LSPS1_GET_ORDER_ENDPOINT,
+    LSPS1_PAY_ORDER_ENDPOINT, MISSION_CONTROL_STATS_ENDPOINT, MONITORING_BUNDLE_ENDPOINT,
+    OPEN_CHANNEL_ENDPOINT, PAYMENT_ATTEMPTS_ENDPOINT, PAYMENT_LOG_ENDPOINT,
+    PAYMENT_RETRY_POLICY_ENDPOINT, PAYMENT_SUMMARY_ENDPOINT, PAY_INVOICE_ENDPOINT,
+    PAY_KEYSEND_ENDPOINT, QUERY_ROUTE_ENDPOINT, REBALANCE_STATUS_ENDPOINT, REFUND_SWAP_ENDPOINT,
+    REGISTER_LIGHTNING_ADDRESS_ENDPOINT, REMOVE_LIGHTNING_ADDRESS_ENDPOINT,
+    RESET_MISSION_CONTROL_ENDPOINT, RESTORE_ENDPOINT, RESTORE_SNAPSHOT_ENDPOINT,
+    REVOKE_API_TOKEN_ENDPOINT, ROUTING_INFO_V2_ENDPOINT, SEND_PAYMENT_V2_ENDPOINT,
+    SET_ALERT_POLICY_ENDPOINT, SET_CONFIGURATION_ENDPOINT, SET_DRAINING_ENDPOINT,
+    SET_DYNAMIC_FEE_POLICY_ENDPOINT, SET_INCOMING_LIMITS_ENDPOINT,
+    SET_LIGHTNING_PROTOCOLS_ENDPOINT, SET_LIQUIDITY_CAP_ENDPOINT,
+    SET_PAYMENT_RETRY_POLICY_ENDPOINT, SET_REBALANCE_BAND_ENDPOINT,
+    SET_REBALANCING_PAUSED_ENDPOINT, SET_SPENDING_PASSWORD_ENDPOINT, SET_SWEEP_POLICY_ENDPOINT,
+    SWEEP_POLICY_ENDPOINT, UPDATE_CHANNEL_POLICY_ENDPOINT, WITHDRAW_ENDPOINT,
 };
 use fedimint_lnv2_client::{CreateBolt11InvoicePayload, SendPaymentPayload};
+use futures::StreamExt;
 use hex::ToHex;
 use serde_json::{json, Value};
 use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
 use tower_http::cors::CorsLayer;
 use tracing::{error, info, instrument};
 
 use super::{
-    BackupPayload, BalancePayload, CloseChannelsWithPeerPayload, ConnectFedPayload,
-    DepositAddressPayload, GetFundingAddressPayload, InfoPayload, LeaveFedPayload,
-    OpenChannelPayload, RestorePayload, SetConfigurationPayload, WithdrawPayload, V1_API_ENDPOINT,
+    BackupPayload, BalancePayload, ClaimLightningAddressPayload, CloseChannelsWithPeerPayload,
+    ConnectFedPayload, ConnectPeerPayload, CreateApiTokenPayload, CreateInvoiceBatchPayload,
+    CreateReverseSwapPayload, CreateSnapshotPayload, CreateSwapPayload, DepositAddressPayload,
+    DisconnectPeerPayload, ExportPaymentLogPayload, GetFundingAddressPayload,
+    ImportMissionControlPayload, ImportPolicyPayload, InfoPayload, LeaveFedPayload,
+    Lsps1CreateOrderPayload, Lsps1GetInfoPayload, Lsps1OrderPayload, Lsps1PayOrderPayload,
+    OpenChannelPayload, PayKeysendPayload, PaymentAttemptsPayload, PaymentLogPayload,
+    PaymentSummaryPayload, QueryRoutePayload, RefundSwapPayload, RegisterLightningAddressPayload,
+    RemoveLightningAddressPayload, RestorePayload, RestoreSnapshotPayload, RevokeApiTokenPayload,
+    SetAlertPolicyPayload, SetConfigurationPayload, SetDrainingPayload, SetDynamicFeePolicyPayload,
+    SetIncomingLimitsPayload, SetLightningProtocolsPayload, SetLiquidityCapPayload,
+    SetPaymentRetryPolicyPayload, SetRebalanceBandPayload, SetRebalancingPausedPayload,
+    SetSpendingPasswordPayload, SetSweepPolicyPayload, UpdateChannelPolicyPayload, WithdrawPayload,
+    SPENDING_PASSWORD_HEADER, V1_API_ENDPOINT,
 };
+use crate::alerts::AlertPolicy;
+use crate::api_token::ApiTokenScope;
+use crate::audit::{redact_parameters, AuditActor, AuditEntry};
+use crate::lnurl::LnurlRouter;
+use crate::monitoring::monitoring_bundle;
 use crate::rpc::ConfigPayload;
 use crate::{Gateway, GatewayError};
 
 /// Creates the webserver's routes and spawns the webserver in a separate task.
 pub async fn run_webserver(gateway: Arc<Gateway>, task_group: &TaskGroup) -> anyhow::Result<()> {
     let v1_routes = v1_routes(gateway.clone());
+    let lnurl_routes = lnurl_routes(gateway.clone());
     let api_v1 = Router::new()
         .nest(&format!("/{V1_API_ENDPOINT}"), v1_routes.clone())
         // Backwards compatibility: Continue supporting gateway APIs without versioning
-        .merge(v1_routes);
+        .merge(v1_routes)
+        // Lightning addresses are served at the root of their domain
+        .merge(lnurl_routes);
 
     let handle = task_group.make_handle();
     let shutdown_rx = handle.make_shutdown_rx();
@@ -71,8 +114,10 @@ fn extract_bearer_token(request: &Request) -> Result<String, StatusCode> {
         let auth_str = header_value
             .to_str()
             .map_err(|_| StatusCode::UNAUTHORIZED)?;
-        let token = auth_str.trim_start_matches("Bearer ").to_string();
-        return Ok(token);
+        let token = auth_str
+            .strip_prefix("Bearer ")
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+        return Ok(token.to_string());
     }
 
     Err(StatusCode::UNAUTHORIZED)
@@ -80,8 +125,10 @@ fn extract_bearer_token(request: &Request) -> Result<String, StatusCode> {
 
 /// Middleware to authenticate an incoming request. Routes that are
 /// authenticated with this middleware always require a Bearer token to be
-/// supplied in the Authorization header.
+/// supplied in the Authorization header, which is either the gateway password
+/// or an API token with at least the `required_scope`.
 async fn auth_middleware(
+    State(required_scope): State<ApiTokenScope>,
     Extension(gateway): Extension<Arc<Gateway>>,
     request: Request,
     next: Next,
@@ -93,7 +140,15 @@ async fn auth_middleware(
         .ok_or(StatusCode::NOT_FOUND)?;
     let gateway_hashed_password = gateway_config.hashed_password;
     let password_salt = gateway_config.password_salt;
-    authenticate(gateway_hashed_password, password_salt, request, next).await
+    authenticate(
+        &gateway,
+        gateway_hashed_password,
+        password_salt,
+        required_scope,
+        request,
+        next,
+    )
+    .await
 }
 
 /// Middleware to authenticate an incoming request. Routes that are
@@ -102,34 +157,146 @@ async fn auth_middleware(
 /// enforces that a Bearer token must be supplied in the Authorization header.
 async fn auth_after_config_middleware(
     Extension(gateway): Extension<Arc<Gateway>>,
-    request: Request,
+    mut request: Request,
     next: Next,
 ) -> Result<impl IntoResponse, StatusCode> {
     // If the gateway's config has not been set, allow the request to continue, so
     // that the gateway can be configured
     let gateway_config = gateway.clone_gateway_config().await;
     if gateway_config.is_none() {
+        request.extensions_mut().insert(AuditActor::Unauthenticated);
         return Ok(next.run(request).await);
     }
 
     // Otherwise, validate that the Bearer token matches the gateway's hashed
-    // password
+    // password. Read-only API tokens may query the gateway's info and
+    // configuration.
     let gateway_config = gateway_config.expect("Already validated the gateway config is not none");
     let gateway_hashed_password = gateway_config.hashed_password;
     let password_salt = gateway_config.password_salt;
-    authenticate(gateway_hashed_password, password_salt, request, next).await
+    let required_scope = if request.method() == Method::GET {
+        ApiTokenScope::ReadOnly
+    } else {
+        ApiTokenScope::Admin
+    };
+    authenticate(
+        &gateway,
+        gateway_hashed_password,
+        password_salt,
+        required_scope,
+        request,
+        next,
+    )
+    .await
 }
 
-/// Validate that the Bearer token matches the gateway's hashed password
+/// Validate that the Bearer token matches the gateway's hashed password or is
+/// an API token with at least the `required_scope`
 async fn authenticate(
+    gateway: &Gateway,
     gateway_hashed_password: sha256::Hash,
     password_salt: [u8; 16],
-    request: Request,
+    required_scope: ApiTokenScope,
+    mut request: Request,
     next: Next,
 ) -> Result<axum::response::Response, StatusCode> {
     let token = extract_bearer_token(&request)?;
     let hashed_password = hash_password(&token, password_salt);
     if gateway_hashed_password == hashed_password {
+        request.extensions_mut().insert(AuditActor::Password);
+        request.extensions_mut().insert(ApiTokenScope::Admin);
+        return Ok(next.run(request).await);
+    }
+
+    if let Some(api_token) = gateway
+        .api_token(&token)
+        .await
+        .filter(|api_token| api_token.scope.allows(required_scope))
+    {
+        request.extensions_mut().insert(api_token.scope);
+        request.extensions_mut().insert(AuditActor::ApiToken {
+            token_hash: api_token.token_hash,
+            name: api_token.name,
+        });
+        return Ok(next.run(request).await);
+    }
+
+    Err(StatusCode::UNAUTHORIZED)
+}
+
+/// Requests to these endpoints only read the gateway's state, even though
+/// they are sent as POST requests
+const UNAUDITED_ENDPOINTS: &[&str] = &[
+    ADDRESS_ENDPOINT,
+    GATEWAY_INFO_POST_ENDPOINT,
+    GET_FUNDING_ADDRESS_ENDPOINT,
+    LSPS1_GET_INFO_ENDPOINT,
+    LSPS1_GET_ORDER_ENDPOINT,
+];
+
+/// Largest request body the audit middleware buffers, matching the limit
+/// axum's `Json` extractor applies by default
+const MAX_AUDITED_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+/// Middleware that records mutating requests in the audit log, together with
+/// who authenticated them. Has to run after the authentication middleware but
+/// before the spending password middleware, so that requests rejected for a
+/// wrong spending password are recorded as well.
+async fn audit_middleware(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, StatusCode> {
+    let action = request.uri().path().to_string();
+    if request.method() == Method::GET || UNAUDITED_ENDPOINTS.contains(&action.as_str()) {
+        return Ok(next.run(request).await);
+    }
+
+    let actor = request
+        .extensions()
+        .get::<AuditActor>()
+        .cloned()
+        .unwrap_or(AuditActor::Unauthenticated);
+    let (parts, body) = request.into_parts();
+    let body = axum::body::to_bytes(body, MAX_AUDITED_BODY_SIZE)
+        .await
+        .map_err(|_| StatusCode::PAYLOAD_TOO_LARGE)?;
+    let parameters = redact_parameters(&body);
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    gateway
+        .record_audit_entry(AuditEntry {
+            recorded_at: fedimint_core::time::now(),
+            actor,
+            action,
+            parameters,
+            success: response.status().is_success(),
+        })
+        .await;
+
+    Ok(response)
+}
+
+/// Middleware for routes that move funds out of the gateway. If a spending
+/// password has been set, it must be supplied in the `spending-password`
+/// header in addition to the Bearer token.
+async fn spending_password_middleware(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    request: Request,
+    next: Next,
+) -> Result<impl IntoResponse, StatusCode> {
+    let spending_password = request
+        .headers()
+        .get(SPENDING_PASSWORD_HEADER)
+        .map(|header_value| header_value.to_str())
+        .transpose()
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    if gateway
+        .verify_spending_password(spending_password, None)
+        .await
+    {
         return Ok(next.run(request).await);
     }
 
@@ -138,7 +305,10 @@ async fn authenticate(
 
 /// Gateway Webserver Routes. The gateway supports three types of routes
 /// - Always Authenticated: these routes always require a Bearer token. Used by
-///   gateway administrators.
+///   gateway administrators. Routes that move funds out of the gateway
+///   additionally require the spending password, if one is set. Besides the
+///   gateway password, API tokens are accepted for the routes their scope
+///   covers.
 /// - Authenticated after config: these routes are unauthenticated before
 ///   configuring the gateway to allow the user
 /// to set a password. After setting the password, they become authenticated.
@@ -157,23 +327,139 @@ fn v1_routes(gateway: Arc<Gateway>) -> Router {
             post(create_bolt11_invoice_v2),
         );
 
-    // Authenticated, public routes used for gateway administration
-    let always_authenticated_routes = Router::new()
+    // Authenticated routes that only read the gateway's state, e.g. for
+    // monitoring
+    let read_only_routes = Router::new()
         .route(BALANCE_ENDPOINT, post(balance))
+        .route(LIST_ACTIVE_CHANNELS_ENDPOINT, get(list_active_channels))
+        .route(LIST_PEERS_ENDPOINT, get(list_peers))
+        .route(QUERY_ROUTE_ENDPOINT, post(query_route))
+        .route(LIQUIDITY_HEALTH_ENDPOINT, get(liquidity_health))
+        .route(LIQUIDITY_HISTORY_ENDPOINT, get(liquidity_history))
+        .route(MONITORING_BUNDLE_ENDPOINT, get(monitoring))
+        .route(REBALANCE_STATUS_ENDPOINT, get(rebalance_status))
+        .route(LIST_INVOICE_BATCHES_ENDPOINT, get(list_invoice_batches))
+        .route(SWEEP_POLICY_ENDPOINT, get(sweep_policy))
+        .route(ALERT_POLICY_ENDPOINT, get(alert_policy))
+        .route(EXPORT_POLICY_ENDPOINT, get(export_policy))
+        .route(EXPORT_MISSION_CONTROL_ENDPOINT, get(export_mission_control))
+        .route(EXPORT_PAYMENT_LOG_ENDPOINT, post(export_payment_log))
+        .route(MISSION_CONTROL_STATS_ENDPOINT, get(mission_control_stats))
+        .route(PAYMENT_RETRY_POLICY_ENDPOINT, get(payment_retry_policy))
+        .route(PAYMENT_ATTEMPTS_ENDPOINT, post(payment_attempts))
+        .route(PAYMENT_LOG_ENDPOINT, post(payment_log))
+        .route(PAYMENT_SUMMARY_ENDPOINT, post(payment_summary))
+        .route(DRAIN_STATUS_ENDPOINT, get(drain_status))
+        .route(EVENTS_ENDPOINT, get(events))
+        .route(
+            LIST_LIGHTNING_ADDRESSES_ENDPOINT,
+            get(list_lightning_addresses),
+        )
+        .layer(middleware::from_fn_with_state(
+            ApiTokenScope::ReadOnly,
+            auth_middleware,
+        ));
+
+    // Authenticated routes for receiving and sending payments. Payments above
+    // the threshold of the spending password additionally require it, see
+    // `pay_keysend`.
+    let payment_routes = Router::new()
+        .route(PAY_KEYSEND_ENDPOINT, post(pay_keysend))
         .route(ADDRESS_ENDPOINT, post(address))
-        .route(WITHDRAW_ENDPOINT, post(withdraw))
+        .route(CREATE_INVOICE_BATCH_ENDPOINT, post(create_invoice_batch))
+        .layer(middleware::from_fn(audit_middleware))
+        .layer(middleware::from_fn_with_state(
+            ApiTokenScope::Payments,
+            auth_middleware,
+        ));
+
+    // Authenticated, public routes used for gateway administration
+    let always_authenticated_routes = Router::new()
         .route(CONNECT_FED_ENDPOINT, post(connect_fed))
         .route(LEAVE_FED_ENDPOINT, post(leave_fed))
         .route(BACKUP_ENDPOINT, post(backup))
         .route(RESTORE_ENDPOINT, post(restore))
         .route(GET_FUNDING_ADDRESS_ENDPOINT, post(get_funding_address))
-        .route(OPEN_CHANNEL_ENDPOINT, post(open_channel))
         .route(
             CLOSE_CHANNELS_WITH_PEER_ENDPOINT,
             post(close_channels_with_peer),
         )
-        .route(LIST_ACTIVE_CHANNELS_ENDPOINT, get(list_active_channels))
-        .layer(middleware::from_fn(auth_middleware));
+        .route(CONNECT_PEER_ENDPOINT, post(connect_peer))
+        .route(DISCONNECT_PEER_ENDPOINT, post(disconnect_peer))
+        .route(SET_INCOMING_LIMITS_ENDPOINT, post(set_incoming_limits))
+        .route(
+            SET_LIGHTNING_PROTOCOLS_ENDPOINT,
+            post(set_lightning_protocols),
+        )
+        .route(SET_LIQUIDITY_CAP_ENDPOINT, post(set_liquidity_cap))
+        .route(SET_REBALANCE_BAND_ENDPOINT, post(set_rebalance_band))
+        .route(
+            SET_REBALANCING_PAUSED_ENDPOINT,
+            post(set_rebalancing_paused),
+        )
+        .route(UPDATE_CHANNEL_POLICY_ENDPOINT, post(update_channel_policy))
+        .route(SET_DRAINING_ENDPOINT, post(set_draining))
+        .route(RESTORE_SNAPSHOT_ENDPOINT, post(restore_snapshot))
+        .route(
+            SET_PAYMENT_RETRY_POLICY_ENDPOINT,
+            post(set_payment_retry_policy),
+        )
+        .route(SET_ALERT_POLICY_ENDPOINT, post(set_alert_policy))
+        .route(
+            SET_DYNAMIC_FEE_POLICY_ENDPOINT,
+            post(set_dynamic_fee_policy),
+        )
+        .route(LSPS1_GET_INFO_ENDPOINT, post(lsps1_get_info))
+        .route(LSPS1_CREATE_ORDER_ENDPOINT, post(lsps1_create_order))
+        .route(LSPS1_GET_ORDER_ENDPOINT, post(lsps1_get_order))
+        .route(
+            IMPORT_MISSION_CONTROL_ENDPOINT,
+            post(import_mission_control),
+        )
+        .route(RESET_MISSION_CONTROL_ENDPOINT, post(reset_mission_control))
+        .route(CREATE_API_TOKEN_ENDPOINT, post(create_api_token))
+        .route(LIST_API_TOKENS_ENDPOINT, get(list_api_tokens))
+        .route(REVOKE_API_TOKEN_ENDPOINT, post(revoke_api_token))
+        .route(
+            REGISTER_LIGHTNING_ADDRESS_ENDPOINT,
+            post(register_lightning_address),
+        )
+        .route(
+            REMOVE_LIGHTNING_ADDRESS_ENDPOINT,
+            post(remove_lightning_address),
+        )
+        .route(LIST_SWAPS_ENDPOINT, get(list_swaps))
+        .route(LIST_REVERSE_SWAPS_ENDPOINT, get(list_reverse_swaps))
+        .route(REFUND_SWAP_ENDPOINT, post(refund_swap))
+        .route(AUDIT_LOG_ENDPOINT, get(audit_log))
+        .layer(middleware::from_fn(audit_middleware))
+        .layer(middleware::from_fn_with_state(
+            ApiTokenScope::Admin,
+            auth_middleware,
+        ));
+
+    // Authenticated routes that move funds out of the gateway
+    let spending_routes = Router::new()
+        .route(WITHDRAW_ENDPOINT, post(withdraw))
+        .route(OPEN_CHANNEL_ENDPOINT, post(open_channel))
+        .route(LSPS1_PAY_ORDER_ENDPOINT, post(lsps1_pay_order))
+        .route(SET_SPENDING_PASSWORD_ENDPOINT, post(set_spending_password))
+        .route(SET_SWEEP_POLICY_ENDPOINT, post(set_sweep_policy))
+        // The imported policy may include the sweep policy
+        .route(IMPORT_POLICY_ENDPOINT, post(import_policy))
+        .route(CREATE_SWAP_ENDPOINT, post(create_swap))
+        .route(CREATE_REVERSE_SWAP_ENDPOINT, post(create_reverse_swap))
+        .route(CREATE_SNAPSHOT_ENDPOINT, post(create_snapshot))
+        .route(
+            CLAIM_LIGHTNING_ADDRESS_ENDPOINT,
+            post(claim_lightning_address),
+        )
+        .layer(middleware::from_fn(spending_password_middleware))
+        .layer(middleware::from_fn(audit_middleware))
+        .layer(middleware::from_fn_with_state(
+            ApiTokenScope::Admin,
+            auth_middleware,
+        ));
 
     // Routes that are un-authenticated before gateway configuration, then become
     // authenticated after a password has been set.
@@ -183,16 +469,33 @@ fn v1_routes(gateway: Arc<Gateway>) -> Router {
         // FIXME: deprecated >= 0.3.0
         .route(GATEWAY_INFO_POST_ENDPOINT, post(handle_post_info))
         .route(GATEWAY_INFO_ENDPOINT, get(info))
+        .layer(middleware::from_fn(audit_middleware))
         .layer(middleware::from_fn(auth_after_config_middleware));
 
     Router::new()
         .merge(public_routes)
+        .merge(read_only_routes)
+        .merge(payment_routes)
         .merge(always_authenticated_routes)
+        .merge(spending_routes)
         .merge(authenticated_after_config_routes)
         .layer(Extension(gateway))
         .layer(CorsLayer::permissive())
 }
 
+/// Un-authenticated LNURL routes of the lightning addresses served by the
+/// gateway. Empty unless a lightning address domain is configured.
+fn lnurl_routes(gateway: Arc<Gateway>) -> Router {
+    if gateway.lightning_address_domain.is_none() {
+        return Router::new();
+    }
+
+    LnurlRouter::new()
+        .pay(gateway.clone())
+        .verify(gateway)
+        .build()
+}
+
 /// Creates a password hash by appending a 4 byte salt to the plaintext
 /// password.
 pub fn hash_password(plaintext_password: &str, salt: [u8; 16]) -> sha256::Hash {
@@ -332,6 +635,421 @@ async fn set_configuration(
     Ok(Json(json!(())))
 }
 
+/// Set the limits on incoming payments of a federation
+#[instrument(skip_all, err, fields(?payload))]
+async fn set_incoming_limits(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<SetIncomingLimitsPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    gateway.handle_set_incoming_limits_msg(payload).await?;
+    Ok(Json(json!(())))
+}
+
+/// Set the protocol versions the gateway serves for a federation
+#[instrument(skip_all, err, fields(?payload))]
+async fn set_lightning_protocols(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<SetLightningProtocolsPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    gateway.handle_set_lightning_protocols_msg(payload).await?;
+    Ok(Json(json!(())))
+}
+
+/// Set or remove the maximum ecash balance of a federation
+#[instrument(skip_all, err, fields(?payload))]
+async fn set_liquidity_cap(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<SetLiquidityCapPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    gateway.handle_set_liquidity_cap_msg(payload).await?;
+    Ok(Json(json!(())))
+}
+
+/// Set or remove the range a federation's ecash balance is kept within
+#[instrument(skip_all, err, fields(?payload))]
+async fn set_rebalance_band(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<SetRebalanceBandPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    gateway.handle_set_rebalance_band_msg(payload).await?;
+    Ok(Json(json!(())))
+}
+
+/// Pause or resume rebalancing of all federations
+#[instrument(skip_all, err, fields(?payload))]
+async fn set_rebalancing_paused(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<SetRebalancingPausedPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    gateway.handle_set_rebalancing_paused_msg(payload).await;
+    Ok(Json(json!(())))
+}
+
+/// Start or stop draining the gateway
+#[instrument(skip_all, err, fields(?payload))]
+async fn set_draining(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<SetDrainingPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let status = gateway.handle_set_draining_msg(payload).await;
+    Ok(Json(json!(status)))
+}
+
+/// Show the payments and operations the gateway still has to complete
+#[instrument(skip_all, err)]
+async fn drain_status(
+    Extension(gateway): Extension<Arc<Gateway>>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let status = gateway.handle_drain_status_msg().await;
+    Ok(Json(json!(status)))
+}
+
+/// Create an encrypted snapshot of the databases of a drained gateway
+#[instrument(skip_all, err)]
+async fn create_snapshot(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<CreateSnapshotPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let snapshot = gateway.handle_create_snapshot_msg(payload).await?;
+    Ok(Json(json!(snapshot)))
+}
+
+/// Restore a snapshot before the gateway joins a federation
+#[instrument(skip_all, err)]
+async fn restore_snapshot(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<RestoreSnapshotPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    gateway.handle_restore_snapshot_msg(payload).await?;
+    Ok(Json(json!(())))
+}
+
+/// Show the rebalancing bands and the most recent rebalancing actions
+#[instrument(skip_all, err)]
+async fn rebalance_status(
+    Extension(gateway): Extension<Arc<Gateway>>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let status = gateway.handle_rebalance_status_msg().await;
+    Ok(Json(json!(status)))
+}
+
+/// Create invoices on the lightning node for every amount of the batch
+#[instrument(skip_all, err, fields(?payload))]
+async fn create_invoice_batch(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<CreateInvoiceBatchPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let batch = gateway.handle_create_invoice_batch_msg(payload).await?;
+    Ok(Json(json!(batch)))
+}
+
+/// List the invoice batches and the state of their invoices, newest first
+#[instrument(skip_all, err)]
+async fn list_invoice_batches(
+    Extension(gateway): Extension<Arc<Gateway>>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let batches = gateway.handle_list_invoice_batches_msg().await;
+    Ok(Json(json!(batches)))
+}
+
+/// Stream the balance, channel and payment events of the gateway as
+/// server-sent events, starting with the current balances and channels
+#[instrument(skip_all, err)]
+async fn events(
+    Extension(gateway): Extension<Arc<Gateway>>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let mut receiver = gateway.subscribe_events();
+    let current = gateway.state_events().await;
+
+    let stream = async_stream::stream! {
+        for event in current {
+            yield event;
+        }
+
+        loop {
+            match receiver.recv().await {
+                Ok(event) => yield event,
+                // A subscriber that doesn't keep up misses some events
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+    .map(|event| Event::default().json_data(event));
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Swap on-chain funds of the lightning node into its channels
+#[instrument(skip_all, err, fields(?payload))]
+async fn create_swap(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<CreateSwapPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let swap = gateway.handle_create_swap_msg(payload).await?;
+    Ok(Json(json!(swap)))
+}
+
+/// List the swaps, newest first
+#[instrument(skip_all, err)]
+async fn list_swaps(
+    Extension(gateway): Extension<Arc<Gateway>>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let swaps = gateway.handle_list_swaps_msg().await;
+    Ok(Json(json!(swaps)))
+}
+
+/// Swap funds of the lightning node's channels into its on-chain wallet
+#[instrument(skip_all, err, fields(?payload))]
+async fn create_reverse_swap(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<CreateReverseSwapPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let swap = gateway.handle_create_reverse_swap_msg(payload).await?;
+    Ok(Json(json!(swap)))
+}
+
+/// List the reverse swaps, newest first
+#[instrument(skip_all, err)]
+async fn list_reverse_swaps(
+    Extension(gateway): Extension<Arc<Gateway>>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let swaps = gateway.handle_list_reverse_swaps_msg().await;
+    Ok(Json(json!(swaps)))
+}
+
+/// Send the lockup of a failed swap back to the on-chain wallet of the
+/// lightning node
+#[instrument(skip_all, err, fields(?payload))]
+async fn refund_swap(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<RefundSwapPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let swap = gateway.handle_refund_swap_msg(payload).await?;
+    Ok(Json(json!(swap)))
+}
+
+/// Set or remove the policy for sweeping on-chain funds to cold storage
+#[instrument(skip_all, err, fields(?payload))]
+async fn set_sweep_policy(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<SetSweepPolicyPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    gateway.handle_set_sweep_policy_msg(payload).await?;
+    Ok(Json(json!(())))
+}
+
+#[instrument(skip_all, err)]
+async fn sweep_policy(
+    Extension(gateway): Extension<Arc<Gateway>>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let policy = gateway.handle_sweep_policy_msg().await;
+    Ok(Json(json!(policy)))
+}
+
+#[instrument(skip_all, err)]
+async fn audit_log(
+    Extension(gateway): Extension<Arc<Gateway>>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let entries = gateway.handle_audit_log_msg().await;
+    Ok(Json(json!(entries)))
+}
+
+/// Set or remove the conditions the gateway raises alerts for
+#[instrument(skip_all, err, fields(?payload))]
+async fn set_alert_policy(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<SetAlertPolicyPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    gateway.handle_set_alert_policy_msg(payload).await?;
+    Ok(Json(json!(())))
+}
+
+/// Show the alert policy. The webhook URL is redacted for non-admin callers.
+#[instrument(skip_all, err)]
+async fn alert_policy(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Extension(scope): Extension<ApiTokenScope>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let mut policy = gateway.handle_alert_policy_msg().await;
+    if !scope.allows(ApiTokenScope::Admin) {
+        policy = policy.map(AlertPolicy::redacted);
+    }
+    Ok(Json(json!(policy)))
+}
+
+/// Set or remove the policy for retrying outgoing LNv2 payments
+#[instrument(skip_all, err, fields(?payload))]
+async fn set_payment_retry_policy(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<SetPaymentRetryPolicyPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    gateway.handle_set_payment_retry_policy_msg(payload).await?;
+    Ok(Json(json!(())))
+}
+
+#[instrument(skip_all, err)]
+async fn payment_retry_policy(
+    Extension(gateway): Extension<Arc<Gateway>>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let policy = gateway.handle_payment_retry_policy_msg().await;
+    Ok(Json(json!(policy)))
+}
+
+/// List the attempts to pay an outgoing LNv2 payment
+#[instrument(skip_all, err, fields(?payload))]
+async fn payment_attempts(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<PaymentAttemptsPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let attempts = gateway.handle_payment_attempts_msg(payload).await;
+    Ok(Json(json!(attempts)))
+}
+
+/// Search the payments the gateway finished, newest first
+#[instrument(skip_all, err, fields(?payload))]
+async fn payment_log(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<PaymentLogPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let entries = gateway.handle_payment_log_msg(payload).await;
+    Ok(Json(json!(entries)))
+}
+
+/// Group the payments the gateway finished by federation and period
+#[instrument(skip_all, err, fields(?payload))]
+async fn payment_summary(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<PaymentSummaryPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let buckets = gateway.handle_payment_summary_msg(payload).await;
+    Ok(Json(json!(buckets)))
+}
+
+/// Download the payments the gateway finished as CSV or JSON lines, e.g. for
+/// bookkeeping
+#[instrument(skip_all, err, fields(?payload))]
+async fn export_payment_log(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<ExportPaymentLogPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let content_type = payload.format.content_type();
+    let body = gateway.handle_export_payment_log_msg(payload).await;
+    Ok(([(header::CONTENT_TYPE, content_type)], body))
+}
+
+/// Issue an API token, which is only returned in this response
+#[instrument(skip_all, err, fields(?payload))]
+async fn create_api_token(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<CreateApiTokenPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let created = gateway.handle_create_api_token_msg(payload).await?;
+    Ok(Json(json!(created)))
+}
+
+#[instrument(skip_all, err)]
+async fn list_api_tokens(
+    Extension(gateway): Extension<Arc<Gateway>>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let tokens = gateway.handle_list_api_tokens_msg().await;
+    Ok(Json(json!(tokens)))
+}
+
+#[instrument(skip_all, err, fields(?payload))]
+async fn revoke_api_token(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<RevokeApiTokenPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    gateway.handle_revoke_api_token_msg(payload).await?;
+    Ok(Json(json!(())))
+}
+
+/// Register a lightning address or update its settings
+#[instrument(skip_all, err, fields(?payload))]
+async fn register_lightning_address(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<RegisterLightningAddressPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let user = gateway
+        .handle_register_lightning_address_msg(payload)
+        .await?;
+    Ok(Json(json!(user)))
+}
+
+#[instrument(skip_all, err, fields(?payload))]
+async fn remove_lightning_address(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<RemoveLightningAddressPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    gateway.handle_remove_lightning_address_msg(payload).await?;
+    Ok(Json(json!(())))
+}
+
+/// Hand out the ecash issued to the user of a lightning address that was not
+/// acknowledged yet
+#[instrument(skip_all, err, fields(?payload))]
+async fn claim_lightning_address(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<ClaimLightningAddressPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let notes = gateway.handle_claim_lightning_address_msg(payload).await?;
+    Ok(Json(json!(notes)))
+}
+
+#[instrument(skip_all, err)]
+async fn list_lightning_addresses(
+    Extension(gateway): Extension<Arc<Gateway>>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let users = gateway.handle_list_lightning_addresses_msg().await;
+    Ok(Json(json!(users)))
+}
+
+/// Set or remove the spending password
+#[instrument(skip_all, err, fields(?payload))]
+async fn set_spending_password(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<SetSpendingPasswordPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    gateway.handle_set_spending_password_msg(payload).await?;
+    Ok(Json(json!(())))
+}
+
+/// Export the gateway's fee and routing policy. The webhook URL of the alert
+/// policy is redacted for non-admin callers.
+#[instrument(skip_all, err)]
+async fn export_policy(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Extension(scope): Extension<ApiTokenScope>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let mut policy = gateway.handle_export_policy_msg().await?;
+    if !scope.allows(ApiTokenScope::Admin) {
+        policy.alert_policy = policy.alert_policy.map(AlertPolicy::redacted);
+    }
+    Ok(Json(json!(policy)))
+}
+
+/// Import a fee and routing policy exported from another gateway
+#[instrument(skip_all, err, fields(?payload))]
+async fn import_policy(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<ImportPolicyPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let changes = gateway.handle_import_policy_msg(payload).await?;
+    Ok(Json(json!(changes)))
+}
+
+/// Set or remove the bounds within which the routing fees are adjusted
+/// automatically
+#[instrument(skip_all, err, fields(?payload))]
+async fn set_dynamic_fee_policy(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<SetDynamicFeePolicyPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let adjustments = gateway.handle_set_dynamic_fee_policy_msg(payload).await?;
+    Ok(Json(json!(adjustments)))
+}
+
 #[instrument(skip_all, err)]
 async fn get_funding_address(
     Extension(gateway): Extension<Arc<Gateway>>,
@@ -359,6 +1077,26 @@ async fn close_channels_with_peer(
     Ok(Json(json!(response)))
 }
 
+#[instrument(skip_all, err, fields(?payload))]
+async fn pay_keysend(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    headers: HeaderMap,
+    Json(payload): Json<PayKeysendPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let spending_password = headers
+        .get(SPENDING_PASSWORD_HEADER)
+        .and_then(|header_value| header_value.to_str().ok());
+    if !gateway
+        .verify_spending_password(spending_password, Some(payload.amount))
+        .await
+    {
+        return Err(GatewayError::InvalidSpendingPassword);
+    }
+
+    let preimage = gateway.handle_pay_keysend_msg(payload).await?;
+    Ok(Json(json!(preimage.0.encode_hex::<String>())))
+}
+
 #[instrument(skip_all, err)]
 async fn list_active_channels(
     Extension(gateway): Extension<Arc<Gateway>>,
@@ -367,6 +1105,137 @@ async fn list_active_channels(
     Ok(Json(json!(channels)))
 }
 
+#[instrument(skip_all, err, fields(?payload))]
+async fn connect_peer(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<ConnectPeerPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    gateway.handle_connect_peer_msg(payload).await?;
+    Ok(Json(json!(())))
+}
+
+#[instrument(skip_all, err, fields(?payload))]
+async fn disconnect_peer(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<DisconnectPeerPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    gateway.handle_disconnect_peer_msg(payload).await?;
+    Ok(Json(json!(())))
+}
+
+#[instrument(skip_all, err)]
+async fn list_peers(
+    Extension(gateway): Extension<Arc<Gateway>>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let peers = gateway.handle_list_peers_msg().await?;
+    Ok(Json(json!(peers)))
+}
+
+#[instrument(skip_all, err, fields(?payload))]
+async fn query_route(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<QueryRoutePayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let route = gateway.handle_query_route_msg(payload).await?;
+    Ok(Json(json!(route)))
+}
+
+#[instrument(skip_all)]
+async fn liquidity_health(Extension(gateway): Extension<Arc<Gateway>>) -> impl IntoResponse {
+    let health = gateway.handle_liquidity_health_msg().await;
+    Json(json!(health))
+}
+
+/// List the hourly samples of the gateway's liquidity and earned fees
+#[instrument(skip_all)]
+async fn liquidity_history(Extension(gateway): Extension<Arc<Gateway>>) -> impl IntoResponse {
+    let samples = gateway.handle_liquidity_history_msg().await;
+    Json(json!(samples))
+}
+
+#[instrument(skip_all)]
+async fn monitoring() -> impl IntoResponse {
+    Json(json!(monitoring_bundle()))
+}
+
+#[instrument(skip_all, err, fields(?payload))]
+async fn update_channel_policy(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<UpdateChannelPolicyPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    gateway.handle_update_channel_policy_msg(payload).await?;
+    Ok(Json(json!(())))
+}
+
+#[instrument(skip_all, err, fields(?payload))]
+async fn lsps1_get_info(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<Lsps1GetInfoPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let info = gateway.handle_lsps1_get_info_msg(payload).await?;
+    Ok(Json(info))
+}
+
+#[instrument(skip_all, err, fields(?payload))]
+async fn lsps1_create_order(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<Lsps1CreateOrderPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let order = gateway.handle_lsps1_create_order_msg(payload).await?;
+    Ok(Json(json!(order)))
+}
+
+#[instrument(skip_all, err, fields(?payload))]
+async fn lsps1_pay_order(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<Lsps1PayOrderPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let order = gateway.handle_lsps1_pay_order_msg(payload).await?;
+    Ok(Json(json!(order)))
+}
+
+#[instrument(skip_all, err, fields(?payload))]
+async fn lsps1_get_order(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<Lsps1OrderPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let order = gateway.handle_lsps1_get_order_msg(payload).await?;
+    Ok(Json(json!(order)))
+}
+
+#[instrument(skip_all, err)]
+async fn export_mission_control(
+    Extension(gateway): Extension<Arc<Gateway>>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let pairs = gateway.handle_export_mission_control_msg().await?;
+    Ok(Json(json!(pairs)))
+}
+
+#[instrument(skip_all, err)]
+async fn import_mission_control(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<ImportMissionControlPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    gateway.handle_import_mission_control_msg(payload).await?;
+    Ok(Json(json!(())))
+}
+
+#[instrument(skip_all, err)]
+async fn reset_mission_control(
+    Extension(gateway): Extension<Arc<Gateway>>,
+) -> Result<impl IntoResponse, GatewayError> {
+    gateway.handle_reset_mission_control_msg().await?;
+    Ok(Json(json!(())))
+}
+
+#[instrument(skip_all, err)]
+async fn mission_control_stats(
+    Extension(gateway): Extension<Arc<Gateway>>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let stats = gateway.handle_mission_control_stats_msg().await?;
+    Ok(Json(json!(stats)))
+}
+
 #[instrument(skip_all, err)]
 async fn get_gateway_id(
     Extension(gateway): Extension<Arc<Gateway>>,
@@ -0,0 +1,107 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use fedimint_core::config::FederationId;
+
+/// Values recorded per federation during a sliding window of time, e.g. the
+/// amounts of recent payments. Values older than the window are dropped
+/// before the values of a federation are accessed.
+///
+/// The values are only kept in memory, a restart starts with empty windows.
+#[derive(Debug)]
+pub struct SlidingWindow<T> {
+    window: Duration,
+    // Map of federation -> time and value of the recorded entries, oldest first
+    entries: Mutex<BTreeMap<FederationId, VecDeque<(Instant, T)>>>,
+}
+
+impl<T> SlidingWindow<T> {
+    pub fn new(window: Duration) -> Self {
+        SlidingWindow {
+            window,
+            entries: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Records `value` for `federation_id` at `now`
+    pub fn record(&self, federation_id: FederationId, value: T, now: Instant) {
+        self.with_entries(federation_id, now, |entries| {
+            entries.push_back((now, value));
+        });
+    }
+
+    /// Calls `f` with the entries of `federation_id` recorded within the
+    /// window before `now`, oldest first. `f` may record or clear entries.
+    pub fn with_entries<R>(
+        &self,
+        federation_id: FederationId,
+        now: Instant,
+        f: impl FnOnce(&mut VecDeque<(Instant, T)>) -> R,
+    ) -> R {
+        let mut entries = self.entries.lock().expect("poisoned");
+        let federation_entries = entries.entry(federation_id).or_default();
+
+        while federation_entries
+            .front()
+            .is_some_and(|(recorded_at, _)| now.duration_since(*recorded_at) >= self.window)
+        {
+            federation_entries.pop_front();
+        }
+
+        f(federation_entries)
+    }
+
+    /// Forgets the entries of a federation the gateway left
+    pub fn remove(&self, federation_id: FederationId) {
+        self.entries
+            .lock()
+            .expect("poisoned")
+            .remove(&federation_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use bitcoin_hashes::{sha256, Hash};
+    use fedimint_core::config::FederationId;
+
+    use super::SlidingWindow;
+
+    fn sum(window: &SlidingWindow<u64>, federation_id: FederationId, now: Instant) -> u64 {
+        window.with_entries(federation_id, now, |entries| {
+            entries.iter().map(|(_, value)| value).sum()
+        })
+    }
+
+    #[test]
+    fn drops_entries_older_than_the_window() {
+        let window = SlidingWindow::new(Duration::from_secs(60));
+        let federation_id = FederationId::dummy();
+        let other_federation_id = FederationId(sha256::Hash::from_byte_array([1; 32]));
+        let start = Instant::now();
+
+        window.record(federation_id, 1, start);
+        window.record(federation_id, 2, start + Duration::from_secs(30));
+        window.record(other_federation_id, 4, start);
+        assert_eq!(
+            sum(&window, federation_id, start + Duration::from_secs(59)),
+            3
+        );
+
+        assert_eq!(
+            sum(&window, federation_id, start + Duration::from_secs(60)),
+            2
+        );
+        assert_eq!(
+            sum(&window, federation_id, start + Duration::from_secs(90)),
+            0
+        );
+        assert_eq!(sum(&window, other_federation_id, start), 4);
+
+        window.remove(other_federation_id);
+        assert_eq!(sum(&window, other_federation_id, start), 0);
+    }
+}
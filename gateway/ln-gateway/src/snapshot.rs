@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+use anyhow::Context;
+use fedimint_core::config::FederationId;
+use fedimint_core::db::{Database, DatabaseVersion, IDatabaseTransactionOpsCore};
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::module::registry::ModuleDecoderRegistry;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+/// Raw key-value pairs of a database
+pub type DatabaseEntries = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// Copy of the gateway's database and the databases of its federation clients,
+/// used to move a gateway to new hardware without recovering every federation.
+///
+/// The gateway doesn't derive the client secrets from a mnemonic, they are
+/// stored in the clients' databases and hence part of the snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable)]
+pub struct GatewaySnapshot {
+    pub created_at: SystemTime,
+    /// Version of the gateway's database when the snapshot was created
+    pub database_version: DatabaseVersion,
+    pub gateway: DatabaseEntries,
+    pub federations: BTreeMap<FederationId, DatabaseEntries>,
+}
+
+/// A [`GatewaySnapshot`] encrypted with a password
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptedSnapshot {
+    pub salt: String,
+    /// Hex encoded ciphertext, prefixed with the nonce
+    pub ciphertext: String,
+}
+
+impl GatewaySnapshot {
+    pub fn encrypt(&self, password: &str) -> anyhow::Result<EncryptedSnapshot> {
+        let salt = fedimint_aead::random_salt();
+        let key = fedimint_aead::get_encryption_key(password, &salt)?;
+        let ciphertext = fedimint_aead::encrypt(self.consensus_encode_to_vec(), &key)?;
+
+        Ok(EncryptedSnapshot {
+            salt,
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+
+    pub fn decrypt(snapshot: &EncryptedSnapshot, password: &str) -> anyhow::Result<Self> {
+        let key = fedimint_aead::get_encryption_key(password, &snapshot.salt)?;
+        let mut ciphertext =
+            hex::decode(&snapshot.ciphertext).context("Ciphertext is not hex encoded")?;
+        let plaintext = fedimint_aead::decrypt(&mut ciphertext, &key)
+            .context("Wrong password or corrupted snapshot")?;
+
+        // Snapshots may exceed the default decoding limit
+        Ok(Self::consensus_decode_from_finite_reader(
+            &mut std::io::Cursor::new(plaintext),
+            &ModuleDecoderRegistry::default(),
+        )?)
+    }
+}
+
+/// Reads all entries of `db` within a single transaction, so they are
+/// consistent
+pub async fn read_entries(db: &Database) -> anyhow::Result<DatabaseEntries> {
+    let mut dbtx = db.begin_transaction_nc().await;
+    let entries = dbtx.raw_find_by_prefix(&[]).await?.collect().await;
+
+    Ok(entries)
+}
+
+/// Replaces all entries of `db` with `entries`
+pub async fn write_entries(db: &Database, entries: &DatabaseEntries) -> anyhow::Result<()> {
+    let mut dbtx = db.begin_transaction().await;
+
+    let existing_keys = dbtx
+        .raw_find_by_prefix(&[])
+        .await?
+        .map(|(key, _)| key)
+        .collect::<Vec<_>>()
+        .await;
+    for key in existing_keys {
+        dbtx.raw_remove_entry(&key).await?;
+    }
+
+    for (key, value) in entries {
+        dbtx.raw_insert_bytes(key, value).await?;
+    }
+
+    dbtx.commit_tx_result().await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::time::SystemTime;
+
+    use fedimint_core::config::FederationId;
+    use fedimint_core::db::DatabaseVersion;
+
+    use super::GatewaySnapshot;
+
+    #[test]
+    fn snapshot_requires_password() {
+        let snapshot = GatewaySnapshot {
+            created_at: SystemTime::UNIX_EPOCH,
+            database_version: DatabaseVersion(1),
+            gateway: vec![(vec![0x07], vec![1, 2, 3])],
+            federations: BTreeMap::from([(FederationId::dummy(), vec![(vec![0x2f], vec![4])])]),
+        };
+
+        let encrypted = snapshot.encrypt("password").expect("Can encrypt");
+        assert_eq!(
+            GatewaySnapshot::decrypt(&encrypted, "password").expect("Can decrypt"),
+            snapshot
+        );
+        assert!(GatewaySnapshot::decrypt(&encrypted, "wrong password").is_err());
+    }
+}
@@ -205,7 +205,6 @@ impl CompleteHtlcState {
                     };
 
                     lightning_context
-                        .lnrpc
                         .complete_htlc(htlc)
                         .await
                         .map_err(|_| CompleteHtlcError::FailedToCompleteHtlc)?;
@@ -0,0 +1,1849 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, ensure, Context};
+use bitcoin::blockdata::opcodes::all::{
+    OP_CHECKSIG, OP_CHECKSIGVERIFY, OP_CLTV, OP_EQUALVERIFY, OP_HASH160, OP_SIZE,
+};
+use bitcoin::script::Builder;
+use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bitcoin::taproot::{LeafVersion, TapLeafHash, TaprootBuilder, TaprootSpendInfo};
+use bitcoin::{
+    absolute, Address, Network, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness,
+};
+use bitcoin_hashes::{ripemd160, sha256, Hash, HashEngine};
+use fedimint_bitcoind::DynBitcoindRpc;
+use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::runtime::spawn;
+use fedimint_core::secp256k1::{
+    KeyPair, Message, PublicKey, Scalar, Secp256k1, SecretKey, XOnlyPublicKey,
+};
+use fedimint_core::util::SafeUrl;
+use futures::StreamExt;
+use lightning_invoice::Bolt11Invoice;
+use rand::rngs::OsRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::db::{ReverseSwapKey, ReverseSwapKeyPrefix, SwapKey, SwapKeyPrefix};
+use crate::gateway_lnrpc::create_invoice_request::Description;
+use crate::gateway_lnrpc::{CreateInvoiceRequest, PayInvoiceRequest};
+use crate::lightning::{InvoiceState, LightningRpcError};
+use crate::rpc::{CreateReverseSwapPayload, CreateSwapPayload, RefundSwapPayload};
+use crate::{fetch_lightning_node_info, GatewayError, LightningContext, Result};
+
+/// Time between two checks of the swaps that are in flight
+pub const SWAP_UPDATE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time the swap provider has to pay the invoice of a swap
+pub const SWAP_INVOICE_EXPIRY_SECS: u32 = 24 * 60 * 60;
+
+/// Blocks after which the lockup of a swap has to be refundable at the latest,
+/// so a provider can't lock the funds up indefinitely
+pub const MAX_SWAP_TIMEOUT_BLOCKS: u64 = 7 * 144;
+
+/// Blocks before the provider can refund the lockup of a reverse swap that the
+/// gateway needs at least to claim it
+pub const MIN_REVERSE_SWAP_TIMEOUT_BLOCKS: u64 = 36;
+
+/// Maximum number of blocks the payment of a reverse swap's invoice may be
+/// locked up for, which lasts until the gateway claimed the lockup
+const REVERSE_SWAP_PAYMENT_MAX_DELAY: u64 = 1008;
+
+/// Blocks the claims and refunds of lockups should confirm within, which their
+/// fee rate is estimated for
+const CONFIRMATION_TARGET: u16 = 6;
+
+/// Client of a Boltz-style swap provider. The gateway uses submarine swaps to
+/// turn the on-chain funds of its lightning node into outbound liquidity: it
+/// sends the funds to a lockup address and the provider pays an invoice of the
+/// node in return, which reveals the preimage the provider claims the lockup
+/// with.
+#[derive(Debug, Clone)]
+pub struct SwapClient {
+    url: SafeUrl,
+    client: reqwest::Client,
+}
+
+/// Swap as created by the swap provider
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmarineSwap {
+    pub id: String,
+    pub address: String,
+    /// Amount to send to the lockup address, including the provider's fees
+    pub expected_amount: u64,
+    pub timeout_block_height: u64,
+    /// Key the provider claims the lockup with
+    pub claim_public_key: String,
+}
+
+/// Reverse swap as created by the swap provider
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReverseSwap {
+    pub id: String,
+    /// Hold invoice the provider settles once the lockup is claimed
+    pub invoice: String,
+    pub lockup_address: String,
+    /// Amount the provider sends to the lockup address
+    pub onchain_amount: u64,
+    pub timeout_block_height: u64,
+    /// Key the provider refunds the lockup with
+    pub refund_public_key: String,
+}
+
+/// Fees the swap provider currently charges for reverse swaps
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReverseSwapFees {
+    pub percentage: f64,
+    pub miner_fees: ReverseSwapMinerFees,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ReverseSwapMinerFees {
+    /// Fees for the provider's lockup transaction
+    pub lockup: u64,
+    /// Estimated fees for the gateway's claim transaction
+    pub claim: u64,
+}
+
+impl ReverseSwapFees {
+    /// Returns the fee the provider deducts from the lightning payment of
+    /// `amount_sats`, or `None` if it is out of range
+    pub fn fee_sats(&self, amount_sats: u64) -> Option<u64> {
+        ((amount_sats as f64 * self.percentage / 100.0).ceil() as u64)
+            .checked_add(self.miner_fees.lockup)
+    }
+}
+
+/// Fees the swap provider currently charges for submarine swaps
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SwapFees {
+    pub percentage: f64,
+    /// Fees for the provider's claim transaction
+    pub miner_fees: u64,
+}
+
+impl SwapFees {
+    /// Returns the fee the provider charges on top of `amount_sats`, or `None`
+    /// if it is out of range
+    pub fn fee_sats(&self, amount_sats: u64) -> Option<u64> {
+        ((amount_sats as f64 * self.percentage / 100.0).ceil() as u64).checked_add(self.miner_fees)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SwapPair<F> {
+    fees: F,
+}
+
+#[derive(Debug, Deserialize)]
+struct SwapStatusResponse {
+    status: String,
+}
+
+impl SwapClient {
+    pub fn new(url: SafeUrl) -> Self {
+        SwapClient {
+            url,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn endpoint(&self, path: &str) -> String {
+        format!("{}/{path}", self.url.as_str().trim_end_matches('/'))
+    }
+
+    /// Returns the fees the provider quotes for swaps from on-chain bitcoin to
+    /// lightning
+    pub async fn submarine_swap_fees(&self) -> anyhow::Result<SwapFees> {
+        let mut pairs = self
+            .client
+            .get(self.endpoint("swap/submarine"))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<BTreeMap<String, BTreeMap<String, SwapPair<SwapFees>>>>()
+            .await
+            .context("Swap provider returned invalid fees")?;
+
+        pairs
+            .get_mut("BTC")
+            .and_then(|pairs| pairs.remove("BTC"))
+            .map(|pair| pair.fees)
+            .context("Swap provider doesn't offer swaps from BTC to lightning")
+    }
+
+    /// Creates a swap that pays `invoice` once the lockup is confirmed. The
+    /// lockup can be refunded with the secret key of `refund_public_key`
+    /// after the timeout.
+    pub async fn create_submarine_swap(
+        &self,
+        invoice: &Bolt11Invoice,
+        refund_public_key: PublicKey,
+    ) -> anyhow::Result<SubmarineSwap> {
+        self.client
+            .post(self.endpoint("swap/submarine"))
+            .json(&json!({
+                "from": "BTC",
+                "to": "BTC",
+                "invoice": invoice.to_string(),
+                "refundPublicKey": refund_public_key.to_string(),
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .context("Swap provider returned an invalid swap")
+    }
+
+    /// Returns the fees the provider quotes for swaps from lightning to
+    /// on-chain bitcoin
+    pub async fn reverse_swap_fees(&self) -> anyhow::Result<ReverseSwapFees> {
+        let mut pairs = self
+            .client
+            .get(self.endpoint("swap/reverse"))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<BTreeMap<String, BTreeMap<String, SwapPair<ReverseSwapFees>>>>()
+            .await
+            .context("Swap provider returned invalid fees")?;
+
+        pairs
+            .get_mut("BTC")
+            .and_then(|pairs| pairs.remove("BTC"))
+            .map(|pair| pair.fees)
+            .context("Swap provider doesn't offer swaps from lightning to BTC")
+    }
+
+    /// Creates a reverse swap that locks up on-chain funds once the invoice
+    /// of `amount_sats` is paid. The lockup can be claimed with the preimage
+    /// of `payment_hash` and the secret key of `claim_public_key`, which
+    /// settles the invoice.
+    pub async fn create_reverse_swap(
+        &self,
+        amount_sats: u64,
+        payment_hash: &sha256::Hash,
+        claim_public_key: PublicKey,
+    ) -> anyhow::Result<ReverseSwap> {
+        self.client
+            .post(self.endpoint("swap/reverse"))
+            .json(&json!({
+                "from": "BTC",
+                "to": "BTC",
+                "invoiceAmount": amount_sats,
+                "preimageHash": payment_hash.to_string(),
+                "claimPublicKey": claim_public_key.to_string(),
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .context("Swap provider returned an invalid reverse swap")
+    }
+
+    /// Returns the status of the swap as reported by the swap provider, e.g.
+    /// `transaction.mempool`
+    pub async fn swap_status(&self, id: &str) -> anyhow::Result<String> {
+        let response = self
+            .client
+            .get(self.endpoint(&format!("swap/{id}")))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<SwapStatusResponse>()
+            .await
+            .context("Swap provider returned an invalid status")?;
+
+        Ok(response.status)
+    }
+}
+
+impl ReverseSwap {
+    /// Checks that the provider's invoice pays for the preimage of
+    /// `payment_hash` and `amount_sats` and that the lockup address commits to
+    /// the gateway's claim key and a timeout that leaves time to claim the
+    /// lockup. Returns the invoice and the provider's refund key.
+    pub fn verify(
+        &self,
+        payment_hash: &sha256::Hash,
+        amount_sats: u64,
+        claim_public_key: &PublicKey,
+        block_height: u64,
+        network: Network,
+    ) -> anyhow::Result<(Bolt11Invoice, PublicKey)> {
+        let invoice = Bolt11Invoice::from_str(&self.invoice).context("Invalid invoice")?;
+        ensure!(
+            invoice.payment_hash() == payment_hash,
+            "The invoice doesn't pay for the preimage"
+        );
+        ensure!(
+            invoice.amount_milli_satoshis() == amount_sats.checked_mul(1000),
+            "The invoice isn't for {amount_sats} sats"
+        );
+        ensure!(
+            block_height + MIN_REVERSE_SWAP_TIMEOUT_BLOCKS <= self.timeout_block_height
+                && self.timeout_block_height <= block_height + MAX_SWAP_TIMEOUT_BLOCKS,
+            "The timeout at block {} leaves no time to claim the lockup",
+            self.timeout_block_height
+        );
+
+        let refund_public_key =
+            PublicKey::from_str(&self.refund_public_key).context("Invalid refund public key")?;
+        let address = SwapTree::new(
+            SwapKind::Reverse,
+            payment_hash,
+            claim_public_key,
+            &refund_public_key,
+            self.timeout_block_height,
+        )?
+        .address(network);
+        ensure!(
+            address.to_string() == self.lockup_address,
+            "The lockup address {} doesn't commit to the swap",
+            self.lockup_address
+        );
+
+        Ok((invoice, refund_public_key))
+    }
+}
+
+impl SubmarineSwap {
+    /// Rebuilds the swap tree from the payment hash of the invoice, the
+    /// provider's claim key, the gateway's refund key and the timeout, and
+    /// returns the lockup address if the provider's address commits to it.
+    /// Otherwise the provider could spend the lockup without paying the
+    /// invoice.
+    pub fn verify_lockup_address(
+        &self,
+        payment_hash: &sha256::Hash,
+        refund_public_key: &PublicKey,
+        network: Network,
+    ) -> anyhow::Result<Address> {
+        let claim_public_key =
+            PublicKey::from_str(&self.claim_public_key).context("Invalid claim public key")?;
+        let address = SwapTree::new(
+            SwapKind::Submarine,
+            payment_hash,
+            &claim_public_key,
+            refund_public_key,
+            self.timeout_block_height,
+        )?
+        .address(network);
+
+        ensure!(
+            address.to_string() == self.address,
+            "The lockup address {} doesn't commit to the swap",
+            self.address
+        );
+
+        Ok(address)
+    }
+}
+
+/// Direction of a swap, which determines whether the provider holds the claim
+/// or the refund key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapKind {
+    /// The gateway locks up on-chain funds for a lightning payment
+    Submarine,
+    /// The provider locks up on-chain funds for a lightning payment
+    Reverse,
+}
+
+/// Taproot tree of a swap. The claimer can spend the lockup with the preimage
+/// of the payment hash, the refunder after the timeout, and both together can
+/// spend it with the aggregate of their keys.
+struct SwapTree {
+    claim_leaf: ScriptBuf,
+    refund_leaf: ScriptBuf,
+    spend_info: TaprootSpendInfo,
+}
+
+impl SwapTree {
+    fn new(
+        kind: SwapKind,
+        payment_hash: &sha256::Hash,
+        claim_public_key: &PublicKey,
+        refund_public_key: &PublicKey,
+        timeout_block_height: u64,
+    ) -> anyhow::Result<Self> {
+        // Like the provider's script, the claim leaf of reverse swaps only
+        // accepts preimages of 32 bytes, the only ones that settle a payment
+        let builder = match kind {
+            SwapKind::Submarine => Builder::new(),
+            SwapKind::Reverse => Builder::new()
+                .push_opcode(OP_SIZE)
+                .push_int(32)
+                .push_opcode(OP_EQUALVERIFY),
+        };
+        let claim_leaf = builder
+            .push_opcode(OP_HASH160)
+            .push_slice(ripemd160::Hash::hash(payment_hash.as_byte_array()).to_byte_array())
+            .push_opcode(OP_EQUALVERIFY)
+            .push_x_only_key(&XOnlyPublicKey::from(*claim_public_key))
+            .push_opcode(OP_CHECKSIG)
+            .into_script();
+        let refund_leaf = Builder::new()
+            .push_x_only_key(&XOnlyPublicKey::from(*refund_public_key))
+            .push_opcode(OP_CHECKSIGVERIFY)
+            .push_int(i64::try_from(timeout_block_height).context("Invalid timeout")?)
+            .push_opcode(OP_CLTV)
+            .into_script();
+
+        // The provider's key comes first in the aggregate
+        let internal_key = match kind {
+            SwapKind::Submarine => aggregate_keys(&[*claim_public_key, *refund_public_key])?,
+            SwapKind::Reverse => aggregate_keys(&[*refund_public_key, *claim_public_key])?,
+        };
+        let secp = Secp256k1::verification_only();
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(1, claim_leaf.clone())
+            .and_then(|builder| builder.add_leaf(1, refund_leaf.clone()))
+            .context("Invalid swap tree")?
+            .finalize(&secp, internal_key)
+            .map_err(|_| anyhow!("Incomplete swap tree"))?;
+
+        Ok(SwapTree {
+            claim_leaf,
+            refund_leaf,
+            spend_info,
+        })
+    }
+
+    fn address(&self, network: Network) -> Address {
+        Address::p2tr_tweaked(self.spend_info.output_key(), network)
+    }
+
+    fn lockup_script(&self) -> ScriptBuf {
+        ScriptBuf::new_v1_p2tr_tweaked(self.spend_info.output_key())
+    }
+
+    /// Builds the transaction that sends the lockup funded by `lockup_tx` to
+    /// `destination` via `leaf`, which is unlocked by a signature of `key`
+    /// and `arguments`. The lockup has to be at least `min_amount_sats`. The
+    /// transaction signals replaceability, so a stuck spend can be replaced
+    /// with a higher fee rate.
+    #[allow(clippy::too_many_arguments)]
+    fn spend(
+        &self,
+        leaf: &ScriptBuf,
+        key: &KeyPair,
+        arguments: &[&[u8]],
+        lock_time: absolute::LockTime,
+        lockup_tx: &Transaction,
+        min_amount_sats: u64,
+        destination: &Address,
+        sat_per_vbyte: u64,
+    ) -> anyhow::Result<Transaction> {
+        let lockup_script = self.lockup_script();
+        let (vout, lockup) = lockup_tx
+            .output
+            .iter()
+            .enumerate()
+            .find(|(_, output)| output.script_pubkey == lockup_script)
+            .context("The transaction doesn't fund the lockup address")?;
+        ensure!(
+            min_amount_sats <= lockup.value,
+            "The lockup of {} sats is less than the agreed {min_amount_sats} sats",
+            lockup.value
+        );
+
+        let mut transaction = Transaction {
+            version: 2,
+            lock_time,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(
+                    lockup_tx.txid(),
+                    u32::try_from(vout).expect("Output index fits into u32"),
+                ),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: lockup.value,
+                script_pubkey: destination.script_pubkey(),
+            }],
+        };
+
+        // The signature has a fixed size, so the size of the signed transaction
+        // doesn't change when the fee is deducted
+        self.sign(&mut transaction, lockup, leaf, key, arguments)?;
+        let fee = (transaction.vsize() as u64)
+            .checked_mul(sat_per_vbyte)
+            .context("The fee rate is out of range")?;
+        transaction.output[0].value = lockup
+            .value
+            .checked_sub(fee)
+            .filter(|value| destination.script_pubkey().dust_value().to_sat() <= *value)
+            .context("The lockup doesn't cover the fee")?;
+        self.sign(&mut transaction, lockup, leaf, key, arguments)?;
+
+        Ok(transaction)
+    }
+
+    /// Signs the only input of `transaction`, which spends `lockup` via
+    /// `leaf`
+    fn sign(
+        &self,
+        transaction: &mut Transaction,
+        lockup: &TxOut,
+        leaf: &ScriptBuf,
+        key: &KeyPair,
+        arguments: &[&[u8]],
+    ) -> anyhow::Result<()> {
+        let leaf_hash = TapLeafHash::from_script(leaf, LeafVersion::TapScript);
+        let sighash = SighashCache::new(&*transaction).taproot_script_spend_signature_hash(
+            0,
+            &Prevouts::All(&[lockup]),
+            leaf_hash,
+            TapSighashType::Default,
+        )?;
+        let message = Message::from_slice(sighash.as_byte_array()).expect("Sighash has 32 bytes");
+        let signature = bitcoin::taproot::Signature {
+            sig: Secp256k1::signing_only().sign_schnorr_no_aux_rand(&message, key),
+            hash_ty: TapSighashType::Default,
+        };
+        let control_block = self
+            .spend_info
+            .control_block(&(leaf.clone(), LeafVersion::TapScript))
+            .context("The leaf is not part of the swap tree")?;
+
+        let mut witness = Witness::new();
+        witness.push(signature.to_vec());
+        for argument in arguments {
+            witness.push(argument);
+        }
+        witness.push(leaf.as_bytes());
+        witness.push(control_block.serialize());
+        transaction.input[0].witness = witness;
+
+        Ok(())
+    }
+}
+
+/// Aggregates the keys in the given order as specified by MuSig2 (BIP-327)
+fn aggregate_keys(keys: &[PublicKey]) -> anyhow::Result<XOnlyPublicKey> {
+    let secp = Secp256k1::verification_only();
+    let list_hash = tagged_hash(
+        "KeyAgg list",
+        &keys
+            .iter()
+            .flat_map(PublicKey::serialize)
+            .collect::<Vec<u8>>(),
+    );
+    let second_key = keys.iter().find(|key| **key != keys[0]);
+
+    let mut tweaked_keys = keys.iter().map(|key| {
+        if Some(key) == second_key {
+            return Ok(*key);
+        }
+
+        let coefficient = tagged_hash(
+            "KeyAgg coefficient",
+            &[list_hash.as_byte_array().as_slice(), &key.serialize()[..]].concat(),
+        );
+        let coefficient = Scalar::from_be_bytes(coefficient.to_byte_array())
+            .map_err(|_| anyhow::anyhow!("Key aggregation coefficient out of range"))?;
+
+        key.mul_tweak(&secp, &coefficient)
+            .context("Invalid key aggregation coefficient")
+    });
+
+    let first = tweaked_keys.next().context("No keys to aggregate")??;
+    let aggregate = tweaked_keys.try_fold(first, |aggregate, key| {
+        aggregate
+            .combine(&key?)
+            .context("Keys cancel each other out")
+    })?;
+
+    Ok(aggregate.x_only_public_key().0)
+}
+
+fn tagged_hash(tag: &str, message: &[u8]) -> sha256::Hash {
+    let tag_hash = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag_hash.as_byte_array());
+    engine.input(tag_hash.as_byte_array());
+    engine.input(message);
+    sha256::Hash::from_engine(engine)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub enum SwapState {
+    /// Waiting for the lockup to confirm and the provider to pay the invoice
+    Pending,
+    /// The invoice was paid
+    Completed,
+    /// The provider won't pay the invoice, the lockup is refunded with the
+    /// refund key once the timeout is reached
+    Failed,
+    /// The lockup was sent back to the on-chain wallet of the lightning node
+    Refunded,
+    /// The lockup was never sent, so there is nothing to refund
+    NotFunded,
+}
+
+impl SwapState {
+    /// Returns the final state of a swap the provider reports `status` for,
+    /// if the provider gave up on it
+    pub fn from_failed_status(status: &str) -> Option<Self> {
+        match status {
+            "swap.expired" | "invoice.failedToPay" | "transaction.lockupFailed" => {
+                Some(SwapState::Failed)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub enum ReverseSwapState {
+    /// Waiting for the payment of the invoice, the lockup and its claim to
+    /// confirm
+    Pending,
+    /// The claim of the lockup to the on-chain wallet of the lightning node
+    /// confirmed
+    Claimed,
+    /// The provider didn't lock up the funds, the payment is returned
+    Failed,
+}
+
+impl ReverseSwapState {
+    /// Returns the final state of a reverse swap the provider reports
+    /// `status` for, if the provider gave up on it
+    pub fn from_failed_status(status: &str) -> Option<Self> {
+        match status {
+            "swap.expired" | "invoice.expired" | "transaction.failed" | "transaction.refunded" => {
+                Some(ReverseSwapState::Failed)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Reverse swap from the channels of the lightning node to its on-chain
+/// wallet
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable)]
+pub struct ReverseSwapRecord {
+    pub id: String,
+    /// Amount paid by the lightning node
+    pub amount_sats: u64,
+    /// Invoice of the provider
+    pub invoice: Bolt11Invoice,
+    /// Preimage the lockup is claimed with, which settles the invoice
+    pub preimage: [u8; 32],
+    pub lockup_address: String,
+    /// Amount the provider sends to the lockup address
+    pub onchain_amount_sats: u64,
+    /// Block height after which the provider can refund the lockup
+    pub timeout_block_height: u64,
+    /// Key to claim the lockup with. It never leaves the gateway's database.
+    pub claim_secret_key: SecretKey,
+    /// Key the provider refunds the lockup with
+    pub refund_public_key: PublicKey,
+    /// Transaction that claims the lockup, which is rebroadcast until it
+    /// confirms
+    pub claim_transaction: Option<Transaction>,
+    /// Status last reported by the swap provider
+    pub status: String,
+    pub state: ReverseSwapState,
+    pub created_at: SystemTime,
+}
+
+/// Reverse swap as returned by the API, without the preimage and the claim
+/// key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReverseSwapInfo {
+    pub id: String,
+    pub amount_sats: u64,
+    pub invoice: Bolt11Invoice,
+    pub lockup_address: String,
+    pub onchain_amount_sats: u64,
+    pub timeout_block_height: u64,
+    pub claim_txid: Option<bitcoin::Txid>,
+    pub status: String,
+    pub state: ReverseSwapState,
+    pub created_at: SystemTime,
+}
+
+impl ReverseSwapRecord {
+    fn tree(&self, claim_key: &KeyPair) -> anyhow::Result<SwapTree> {
+        SwapTree::new(
+            SwapKind::Reverse,
+            self.invoice.payment_hash(),
+            &claim_key.public_key(),
+            &self.refund_public_key,
+            self.timeout_block_height,
+        )
+    }
+
+    /// Returns the output script of the lockup address
+    pub fn lockup_script(&self) -> anyhow::Result<ScriptBuf> {
+        let claim_key = KeyPair::from_secret_key(&Secp256k1::new(), &self.claim_secret_key);
+        Ok(self.tree(&claim_key)?.lockup_script())
+    }
+
+    /// Builds the transaction that sends the lockup funded by `lockup_tx` to
+    /// `destination` via the claim leaf of the swap tree, which reveals the
+    /// preimage to the provider. A lockup of less than the agreed on-chain
+    /// amount is rejected, since the preimage settles the full payment.
+    pub fn claim_transaction(
+        &self,
+        lockup_tx: &Transaction,
+        destination: &Address,
+        sat_per_vbyte: u64,
+    ) -> anyhow::Result<Transaction> {
+        let claim_key = KeyPair::from_secret_key(&Secp256k1::new(), &self.claim_secret_key);
+        let tree = self.tree(&claim_key)?;
+
+        tree.spend(
+            &tree.claim_leaf,
+            &claim_key,
+            &[&self.preimage],
+            absolute::LockTime::ZERO,
+            lockup_tx,
+            self.onchain_amount_sats,
+            destination,
+            sat_per_vbyte,
+        )
+    }
+}
+
+impl From<ReverseSwapRecord> for ReverseSwapInfo {
+    fn from(record: ReverseSwapRecord) -> Self {
+        ReverseSwapInfo {
+            id: record.id,
+            amount_sats: record.amount_sats,
+            invoice: record.invoice,
+            lockup_address: record.lockup_address,
+            onchain_amount_sats: record.onchain_amount_sats,
+            timeout_block_height: record.timeout_block_height,
+            claim_txid: record.claim_transaction.as_ref().map(Transaction::txid),
+            status: record.status,
+            state: record.state,
+            created_at: record.created_at,
+        }
+    }
+}
+
+/// Submarine swap from the on-chain wallet of the lightning node to its
+/// channels
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable)]
+pub struct SwapRecord {
+    pub id: String,
+    /// Amount received by the lightning node
+    pub amount_sats: u64,
+    pub invoice: Bolt11Invoice,
+    /// Preimage the payment to the invoice is settled with
+    pub preimage: [u8; 32],
+    pub lockup_address: String,
+    /// Amount sent to the lockup address
+    pub lockup_amount_sats: u64,
+    pub lockup_txid: Option<bitcoin::Txid>,
+    /// Block height after which the lockup can be refunded
+    pub timeout_block_height: u64,
+    /// Key the provider claims the lockup with
+    pub claim_public_key: PublicKey,
+    /// Key to refund the lockup with if the swap fails. It never leaves the
+    /// gateway's database.
+    pub refund_secret_key: SecretKey,
+    /// Last transaction that refunded the lockup
+    pub refund_txid: Option<bitcoin::Txid>,
+    /// Status last reported by the swap provider
+    pub status: String,
+    pub state: SwapState,
+    pub created_at: SystemTime,
+}
+
+/// Swap as returned by the API, without the preimage and the refund key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwapInfo {
+    pub id: String,
+    pub amount_sats: u64,
+    pub invoice: Bolt11Invoice,
+    pub lockup_address: String,
+    pub lockup_amount_sats: u64,
+    pub lockup_txid: Option<bitcoin::Txid>,
+    pub timeout_block_height: u64,
+    pub refund_txid: Option<bitcoin::Txid>,
+    pub status: String,
+    pub state: SwapState,
+    pub created_at: SystemTime,
+}
+
+impl SwapRecord {
+    fn tree(&self, refund_key: &KeyPair) -> anyhow::Result<SwapTree> {
+        SwapTree::new(
+            SwapKind::Submarine,
+            self.invoice.payment_hash(),
+            &self.claim_public_key,
+            &refund_key.public_key(),
+            self.timeout_block_height,
+        )
+    }
+
+    /// Returns the output script of the lockup address
+    pub fn lockup_script(&self) -> anyhow::Result<ScriptBuf> {
+        let refund_key = KeyPair::from_secret_key(&Secp256k1::new(), &self.refund_secret_key);
+        Ok(self.tree(&refund_key)?.lockup_script())
+    }
+
+    /// Builds the transaction that sends the lockup funded by `lockup_tx` to
+    /// `destination` via the refund leaf of the swap tree, which can't be
+    /// mined before the timeout
+    pub fn refund_transaction(
+        &self,
+        lockup_tx: &Transaction,
+        destination: &Address,
+        sat_per_vbyte: u64,
+    ) -> anyhow::Result<Transaction> {
+        let refund_key = KeyPair::from_secret_key(&Secp256k1::new(), &self.refund_secret_key);
+        let tree = self.tree(&refund_key)?;
+        let lock_time = u32::try_from(self.timeout_block_height)
+            .ok()
+            .and_then(|height| absolute::LockTime::from_height(height).ok())
+            .context("Invalid timeout")?;
+
+        tree.spend(
+            &tree.refund_leaf,
+            &refund_key,
+            &[],
+            lock_time,
+            lockup_tx,
+            self.lockup_amount_sats,
+            destination,
+            sat_per_vbyte,
+        )
+    }
+}
+
+impl From<SwapRecord> for SwapInfo {
+    fn from(record: SwapRecord) -> Self {
+        SwapInfo {
+            id: record.id,
+            amount_sats: record.amount_sats,
+            invoice: record.invoice,
+            lockup_address: record.lockup_address,
+            lockup_amount_sats: record.lockup_amount_sats,
+            lockup_txid: record.lockup_txid,
+            timeout_block_height: record.timeout_block_height,
+            refund_txid: record.refund_txid,
+            status: record.status,
+            state: record.state,
+            created_at: record.created_at,
+        }
+    }
+}
+
+/// Creates a submarine swap that sends `amount_sats` plus the swap
+/// provider's fee from the on-chain wallet of the lightning node to the
+/// provider, which pays an invoice of the node in return once the lockup
+/// is confirmed. The lockup address is only funded if it commits to the
+/// invoice's payment hash, the gateway's refund key and a timeout in the
+/// near future.
+pub async fn create_swap(
+    swap_client: &SwapClient,
+    lightning_context: &LightningContext,
+    db: &Database,
+    CreateSwapPayload {
+        amount_sats,
+        max_fee_sats,
+        sat_per_vbyte,
+    }: CreateSwapPayload,
+) -> Result<SwapInfo> {
+    if !lightning_context.lnrpc.supports_invoice_lookups() {
+        return Err(GatewayError::LightningRpcError(
+            LightningRpcError::FailedToGetInvoice {
+                failure_reason: "The lightning node can't settle swap invoices".to_string(),
+            },
+        ));
+    }
+
+    let fees = swap_client.submarine_swap_fees().await?;
+    let quoted_fee_sats = fees
+        .fee_sats(amount_sats)
+        .ok_or_else(|| GatewayError::InvalidMetadata("The swap fee is out of range".to_string()))?;
+    let quoted_amount_sats = amount_sats.checked_add(quoted_fee_sats).ok_or_else(|| {
+        GatewayError::InvalidMetadata("The swap amount is out of range".to_string())
+    })?;
+    let amount_msat = amount_sats.checked_mul(1000).ok_or_else(|| {
+        GatewayError::InvalidMetadata("The swap amount is out of range".to_string())
+    })?;
+    if max_fee_sats < quoted_fee_sats {
+        return Err(GatewayError::InvalidMetadata(format!(
+            "The swap fee of {quoted_fee_sats} sats exceeds the maximum of {max_fee_sats} sats"
+        )));
+    }
+
+    let preimage: [u8; 32] = OsRng.gen();
+    let response = lightning_context
+        .lnrpc
+        .create_invoice(CreateInvoiceRequest {
+            payment_hash: sha256::Hash::hash(&preimage).to_byte_array().to_vec(),
+            amount_msat,
+            expiry_secs: SWAP_INVOICE_EXPIRY_SECS,
+            description: Some(Description::Direct("Submarine swap".to_string())),
+        })
+        .await?;
+    let invoice = Bolt11Invoice::from_str(&response.invoice)
+        .map_err(|e| GatewayError::LightningResponseParseError(anyhow!(e)))?;
+
+    let refund_key = KeyPair::new(&Secp256k1::new(), &mut OsRng);
+    let swap = swap_client
+        .create_submarine_swap(&invoice, refund_key.public_key())
+        .await?;
+
+    if quoted_amount_sats < swap.expected_amount {
+        return Err(GatewayError::InvalidMetadata(format!(
+            "The swap provider expects {} sats instead of the quoted {quoted_amount_sats} sats",
+            swap.expected_amount
+        )));
+    }
+
+    let (_, _, _, block_height, _) =
+        fetch_lightning_node_info(lightning_context.lnrpc.clone()).await?;
+    let block_height = u64::from(block_height);
+    if swap.timeout_block_height <= block_height
+        || block_height + MAX_SWAP_TIMEOUT_BLOCKS < swap.timeout_block_height
+    {
+        return Err(GatewayError::InvalidMetadata(format!(
+            "The swap timeout at block {} is not within {MAX_SWAP_TIMEOUT_BLOCKS} blocks",
+            swap.timeout_block_height
+        )));
+    }
+
+    let lockup_address = swap
+        .verify_lockup_address(
+            invoice.payment_hash(),
+            &refund_key.public_key(),
+            lightning_context.lightning_network,
+        )
+        .map_err(|e| GatewayError::InvalidMetadata(format!("Invalid swap: {e}")))?;
+
+    let mut record = SwapRecord {
+        id: swap.id,
+        amount_sats,
+        invoice,
+        preimage,
+        lockup_address: swap.address,
+        lockup_amount_sats: swap.expected_amount,
+        lockup_txid: None,
+        timeout_block_height: swap.timeout_block_height,
+        claim_public_key: PublicKey::from_str(&swap.claim_public_key)
+            .expect("Verified with the lockup address"),
+        refund_secret_key: refund_key.secret_key(),
+        refund_txid: None,
+        status: "swap.created".to_string(),
+        state: SwapState::Pending,
+        created_at: fedimint_core::time::now(),
+    };
+    let key = SwapKey {
+        id: record.id.clone(),
+    };
+
+    // The refund key is persisted before any funds are sent
+    let mut dbtx = db.begin_transaction().await;
+    dbtx.insert_new_entry(&key, &record).await;
+    dbtx.commit_tx().await;
+
+    let sent = lightning_context
+        .lnrpc
+        .send_onchain(
+            lockup_address,
+            bitcoin::Amount::from_sat(record.lockup_amount_sats),
+            sat_per_vbyte,
+        )
+        .await;
+    match &sent {
+        Ok(txid) => record.lockup_txid = Some(*txid),
+        Err(e) => {
+            warn!("Failed to fund the lockup of swap {}: {e}", record.id);
+            record.state = SwapState::NotFunded;
+        }
+    }
+
+    let mut dbtx = db.begin_transaction().await;
+    dbtx.insert_entry(&key, &record).await;
+    dbtx.commit_tx().await;
+
+    let txid = sent?;
+    info!(
+        "Created swap {} sending {} sats to {} in {txid}",
+        record.id, record.lockup_amount_sats, record.lockup_address
+    );
+
+    Ok(record.into())
+}
+
+/// Returns all swaps, newest first
+pub async fn list_swaps(db: &Database) -> Vec<SwapInfo> {
+    let mut swaps = db
+        .begin_transaction_nc()
+        .await
+        .find_by_prefix(&SwapKeyPrefix)
+        .await
+        .map(|(_, swap)| SwapInfo::from(swap))
+        .collect::<Vec<_>>()
+        .await;
+    swaps.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    swaps
+}
+
+/// Settles the invoices the swap provider paid, updates the status of the
+/// pending swaps and refunds the failed swaps once their timeout is reached.
+/// Refunds need a chain source, without one failed swaps stay unrefunded.
+pub async fn update_swaps(
+    swap_client: &SwapClient,
+    bitcoin_rpc: Option<&DynBitcoindRpc>,
+    lightning_context: &LightningContext,
+    db: &Database,
+) {
+    let swaps = db
+        .begin_transaction_nc()
+        .await
+        .find_by_prefix(&SwapKeyPrefix)
+        .await
+        .filter(|(_, swap)| {
+            std::future::ready(matches!(swap.state, SwapState::Pending | SwapState::Failed))
+        })
+        .collect::<Vec<_>>()
+        .await;
+    if swaps.is_empty() {
+        return;
+    }
+
+    let block_height = match fetch_lightning_node_info(lightning_context.lnrpc.clone()).await {
+        Ok((_, _, _, block_height, _)) => u64::from(block_height),
+        Err(e) => {
+            warn!("Failed to get the block height to update the swaps: {e}");
+            return;
+        }
+    };
+
+    for (key, mut swap) in swaps {
+        if swap.state == SwapState::Failed {
+            if swap.timeout_block_height <= block_height {
+                let Some(bitcoin_rpc) = bitcoin_rpc else {
+                    warn!("No chain source to refund swap {} with", swap.id);
+                    continue;
+                };
+
+                match refund(
+                    bitcoin_rpc,
+                    lightning_context,
+                    &mut swap,
+                    block_height,
+                    None,
+                )
+                .await
+                {
+                    Ok(_) => {
+                        let mut dbtx = db.begin_transaction().await;
+                        dbtx.insert_entry(&key, &swap).await;
+                        dbtx.commit_tx().await;
+                    }
+                    Err(e) => warn!("Failed to refund swap {}: {e}", swap.id),
+                }
+            }
+
+            continue;
+        }
+
+        let payment_hash = *swap.invoice.payment_hash();
+        match lightning_context.lnrpc.lookup_invoice(payment_hash).await {
+            Ok(InvoiceState::Accepted) => {
+                match lightning_context
+                    .lnrpc
+                    .settle_hold_invoice(swap.preimage)
+                    .await
+                {
+                    Ok(_) => swap.state = SwapState::Completed,
+                    Err(e) => {
+                        warn!("Failed to settle invoice of swap {}: {e}", swap.id)
+                    }
+                }
+            }
+            Ok(InvoiceState::Settled) => swap.state = SwapState::Completed,
+            Ok(InvoiceState::Open | InvoiceState::Canceled) => {}
+            Err(e) => warn!("Failed to look up invoice of swap {}: {e}", swap.id),
+        }
+
+        if swap.state == SwapState::Completed {
+            info!("Swap {} received {} sats", swap.id, swap.amount_sats);
+        } else {
+            match swap_client.swap_status(&swap.id).await {
+                Ok(status) => {
+                    if let Some(state) = SwapState::from_failed_status(&status) {
+                        warn!(
+                            "Swap {} failed with status {status}, its lockup is refunded \
+                             after block {}",
+                            swap.id, swap.timeout_block_height
+                        );
+                        swap.state = state;
+                    }
+                    swap.status = status;
+                }
+                Err(e) => warn!("Failed to get the status of swap {}: {e:?}", swap.id),
+            }
+
+            // The provider can't claim the lockup without the payment anymore
+            if swap.state == SwapState::Pending && swap.timeout_block_height <= block_height {
+                warn!(
+                    "Swap {} timed out at block {}, its lockup is refunded",
+                    swap.id, swap.timeout_block_height
+                );
+                swap.state = SwapState::Failed;
+            }
+        }
+
+        let mut dbtx = db.begin_transaction().await;
+        dbtx.insert_entry(&key, &swap).await;
+        dbtx.commit_tx().await;
+    }
+}
+
+/// Creates a reverse swap that pays `amount_sats` from the channels of the
+/// lightning node to the provider, which locks up the amount minus its fee
+/// on-chain in return. Once the lockup is confirmed, `update_reverse_swaps`
+/// claims it to the on-chain wallet of the node, which reveals the preimage
+/// that settles the payment. The invoice is only paid if it commits to the
+/// gateway's preimage and the lockup address to the gateway's claim key. The
+/// provider's fee and the routing fee together may not exceed `max_fee_sats`.
+pub async fn create_reverse_swap(
+    swap_client: &SwapClient,
+    lightning_context: &LightningContext,
+    db: &Database,
+    CreateReverseSwapPayload {
+        amount_sats,
+        max_fee_sats,
+    }: CreateReverseSwapPayload,
+) -> Result<ReverseSwapInfo> {
+    let fees = swap_client.reverse_swap_fees().await?;
+    let quoted_fee_sats = fees
+        .fee_sats(amount_sats)
+        .ok_or_else(|| GatewayError::InvalidMetadata("The swap fee is out of range".to_string()))?;
+    let Some(max_routing_fee_sats) = max_fee_sats.checked_sub(quoted_fee_sats) else {
+        return Err(GatewayError::InvalidMetadata(format!(
+            "The swap fee of {quoted_fee_sats} sats exceeds the maximum of {max_fee_sats} sats"
+        )));
+    };
+    let min_onchain_amount_sats = amount_sats.checked_sub(quoted_fee_sats).ok_or_else(|| {
+        GatewayError::InvalidMetadata("The swap amount doesn't cover the fee".to_string())
+    })?;
+
+    let preimage: [u8; 32] = OsRng.gen();
+    let payment_hash = sha256::Hash::hash(&preimage);
+    let claim_key = KeyPair::new(&Secp256k1::new(), &mut OsRng);
+    let swap = swap_client
+        .create_reverse_swap(amount_sats, &payment_hash, claim_key.public_key())
+        .await?;
+
+    if swap.onchain_amount < min_onchain_amount_sats {
+        return Err(GatewayError::InvalidMetadata(format!(
+            "The swap provider locks up {} sats instead of the quoted {min_onchain_amount_sats} \
+             sats",
+            swap.onchain_amount
+        )));
+    }
+
+    let (_, _, _, block_height, _) =
+        fetch_lightning_node_info(lightning_context.lnrpc.clone()).await?;
+    let (invoice, refund_public_key) = swap
+        .verify(
+            &payment_hash,
+            amount_sats,
+            &claim_key.public_key(),
+            u64::from(block_height),
+            lightning_context.lightning_network,
+        )
+        .map_err(|e| GatewayError::InvalidMetadata(format!("Invalid reverse swap: {e}")))?;
+
+    let record = ReverseSwapRecord {
+        id: swap.id,
+        amount_sats,
+        invoice,
+        preimage,
+        lockup_address: swap.lockup_address,
+        onchain_amount_sats: swap.onchain_amount,
+        timeout_block_height: swap.timeout_block_height,
+        claim_secret_key: claim_key.secret_key(),
+        refund_public_key,
+        claim_transaction: None,
+        status: "swap.created".to_string(),
+        state: ReverseSwapState::Pending,
+        created_at: fedimint_core::time::now(),
+    };
+    let key = ReverseSwapKey {
+        id: record.id.clone(),
+    };
+
+    // The preimage and the claim key are persisted before the invoice is paid
+    let mut dbtx = db.begin_transaction().await;
+    dbtx.insert_new_entry(&key, &record).await;
+    dbtx.commit_tx().await;
+
+    // The payment only completes once the lockup is claimed
+    let lnrpc = lightning_context.lnrpc.clone();
+    let db = db.clone();
+    let invoice = record.invoice.clone();
+    spawn("pay reverse swap invoice", async move {
+        let paid = lnrpc
+            .pay(PayInvoiceRequest {
+                invoice: invoice.to_string(),
+                max_delay: REVERSE_SWAP_PAYMENT_MAX_DELAY,
+                max_fee_msat: max_routing_fee_sats.saturating_mul(1000),
+                payment_hash: invoice.payment_hash().to_byte_array().to_vec(),
+            })
+            .await;
+
+        if let Err(e) = paid {
+            warn!("Failed to pay the invoice of reverse swap {}: {e}", key.id);
+            let mut dbtx = db.begin_transaction().await;
+            if let Some(mut swap) = dbtx
+                .get_value(&key)
+                .await
+                .filter(|swap| swap.state == ReverseSwapState::Pending)
+            {
+                swap.state = ReverseSwapState::Failed;
+                dbtx.insert_entry(&key, &swap).await;
+            }
+            dbtx.commit_tx().await;
+        }
+    });
+
+    info!(
+        "Created reverse swap {} paying {amount_sats} sats for a lockup of {} sats",
+        record.id, record.onchain_amount_sats
+    );
+
+    Ok(record.into())
+}
+
+/// Returns all reverse swaps, newest first
+pub async fn list_reverse_swaps(db: &Database) -> Vec<ReverseSwapInfo> {
+    let mut swaps = db
+        .begin_transaction_nc()
+        .await
+        .find_by_prefix(&ReverseSwapKeyPrefix)
+        .await
+        .map(|(_, swap)| ReverseSwapInfo::from(swap))
+        .collect::<Vec<_>>()
+        .await;
+    swaps.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    swaps
+}
+
+/// Updates the status of the pending reverse swaps and claims their lockups
+/// to the on-chain wallet of the lightning node once they are confirmed.
+/// Unconfirmed lockups are not claimed, since the claim reveals the preimage
+/// the provider settles the payment with even if the lockup is double spent.
+pub async fn update_reverse_swaps(
+    swap_client: &SwapClient,
+    bitcoin_rpc: &DynBitcoindRpc,
+    lightning_context: &LightningContext,
+    db: &Database,
+) {
+    let swaps = db
+        .begin_transaction_nc()
+        .await
+        .find_by_prefix(&ReverseSwapKeyPrefix)
+        .await
+        .filter(|(_, swap)| std::future::ready(swap.state == ReverseSwapState::Pending))
+        .collect::<Vec<_>>()
+        .await;
+
+    for (key, mut swap) in swaps {
+        match swap_client.swap_status(&swap.id).await {
+            Ok(status) => {
+                if let Some(state) = ReverseSwapState::from_failed_status(&status) {
+                    warn!("Reverse swap {} failed with status {status}", swap.id);
+                    swap.state = state;
+                }
+                swap.status = status;
+            }
+            Err(e) => warn!(
+                "Failed to get the status of reverse swap {}: {e:?}",
+                swap.id
+            ),
+        }
+
+        if swap.state == ReverseSwapState::Pending {
+            match claim(bitcoin_rpc, lightning_context, &mut swap).await {
+                Ok(Some(txid)) => info!(
+                    "Claimed {} sats of reverse swap {} in {txid}",
+                    swap.onchain_amount_sats, swap.id
+                ),
+                Ok(None) => {}
+                Err(e) => warn!("Failed to claim reverse swap {}: {e}", swap.id),
+            }
+        }
+
+        let mut dbtx = db.begin_transaction().await;
+        dbtx.insert_entry(&key, &swap).await;
+        dbtx.commit_tx().await;
+    }
+}
+
+/// Sends the lockup of a reverse swap to the on-chain wallet of the lightning
+/// node once it is confirmed, and returns the claim once that is confirmed as
+/// well. Until then the claim is rebroadcast on every call. The lockup, its
+/// confirmation and the fee rate come from the gateway's own chain source and
+/// the claim is broadcast through it, since the provider learns the preimage
+/// from the claim and could otherwise fake the lockup or make the claim burn
+/// it as fees.
+async fn claim(
+    bitcoin_rpc: &DynBitcoindRpc,
+    lightning_context: &LightningContext,
+    swap: &mut ReverseSwapRecord,
+) -> anyhow::Result<Option<bitcoin::Txid>> {
+    if let Some(transaction) = &swap.claim_transaction {
+        let txid = transaction.txid();
+        if bitcoin_rpc.get_tx_block_height(&txid).await?.is_some() {
+            swap.state = ReverseSwapState::Claimed;
+            return Ok(Some(txid));
+        }
+
+        bitcoin_rpc.submit_transaction(transaction.clone()).await;
+        return Ok(None);
+    }
+
+    let lockup_script = swap.lockup_script()?;
+    bitcoin_rpc.watch_script_history(&lockup_script).await?;
+
+    let mut lockup_tx = None;
+    for transaction in bitcoin_rpc.get_script_history(&lockup_script).await? {
+        let funds_lockup = transaction
+            .output
+            .iter()
+            .any(|output| output.script_pubkey == lockup_script);
+        if funds_lockup
+            && bitcoin_rpc
+                .get_tx_block_height(&transaction.txid())
+                .await?
+                .is_some()
+        {
+            lockup_tx = Some(transaction);
+            break;
+        }
+    }
+    let Some(lockup_tx) = lockup_tx else {
+        return Ok(None);
+    };
+
+    let destination =
+        Address::from_str(&lightning_context.lnrpc.get_funding_address().await?.address)?
+            .require_network(lightning_context.lightning_network)?;
+    let sat_per_vbyte = estimate_sat_per_vbyte(bitcoin_rpc).await?;
+
+    let transaction = swap.claim_transaction(&lockup_tx, &destination, sat_per_vbyte)?;
+    info!(
+        "Broadcasting claim {} of reverse swap {}",
+        transaction.txid(),
+        swap.id
+    );
+    bitcoin_rpc.submit_transaction(transaction.clone()).await;
+    swap.claim_transaction = Some(transaction);
+
+    Ok(None)
+}
+
+/// Refunds the lockup of a swap, see `refund`. A swap that was refunded
+/// already is refunded again, e.g. with a higher fee rate if the previous
+/// refund is stuck.
+pub async fn refund_swap(
+    bitcoin_rpc: &DynBitcoindRpc,
+    lightning_context: &LightningContext,
+    db: &Database,
+    RefundSwapPayload { id, sat_per_vbyte }: RefundSwapPayload,
+) -> Result<SwapInfo> {
+    let key = SwapKey { id };
+    let mut swap = db
+        .begin_transaction_nc()
+        .await
+        .get_value(&key)
+        .await
+        .ok_or_else(|| GatewayError::InvalidMetadata(format!("Unknown swap {}", key.id)))?;
+
+    let (_, _, _, block_height, _) =
+        fetch_lightning_node_info(lightning_context.lnrpc.clone()).await?;
+    refund(
+        bitcoin_rpc,
+        lightning_context,
+        &mut swap,
+        u64::from(block_height),
+        sat_per_vbyte,
+    )
+    .await?;
+
+    let mut dbtx = db.begin_transaction().await;
+    dbtx.insert_entry(&key, &swap).await;
+    dbtx.commit_tx().await;
+
+    Ok(swap.into())
+}
+
+/// Sends the lockup of a swap whose timeout was reached back to the on-chain
+/// wallet of the lightning node. The invoice of the swap is canceled first, so
+/// the provider can't pay it anymore once it can't claim the lockup. The
+/// lockup is looked up and the refund is broadcast via the gateway's chain
+/// source, whose estimate is used without a fee rate.
+async fn refund(
+    bitcoin_rpc: &DynBitcoindRpc,
+    lightning_context: &LightningContext,
+    swap: &mut SwapRecord,
+    block_height: u64,
+    sat_per_vbyte: Option<u64>,
+) -> Result<bitcoin::Txid> {
+    let Some(lockup_txid) = swap.lockup_txid else {
+        return Err(GatewayError::InvalidMetadata(format!(
+            "The lockup of swap {} was never sent",
+            swap.id
+        )));
+    };
+
+    if swap.state == SwapState::Completed {
+        return Err(GatewayError::InvalidMetadata(format!(
+            "Swap {} was completed",
+            swap.id
+        )));
+    }
+
+    if block_height < swap.timeout_block_height {
+        return Err(GatewayError::InvalidMetadata(format!(
+            "The lockup of swap {} can be refunded after block {}",
+            swap.id, swap.timeout_block_height
+        )));
+    }
+
+    let payment_hash = *swap.invoice.payment_hash();
+    match lightning_context.lnrpc.lookup_invoice(payment_hash).await? {
+        InvoiceState::Open => {
+            lightning_context
+                .lnrpc
+                .cancel_hold_invoice(payment_hash)
+                .await?;
+        }
+        InvoiceState::Canceled => {}
+        InvoiceState::Accepted | InvoiceState::Settled => {
+            return Err(GatewayError::InvalidMetadata(format!(
+                "The invoice of swap {} was paid",
+                swap.id
+            )));
+        }
+    }
+
+    let lockup_script = swap.lockup_script()?;
+    bitcoin_rpc.watch_script_history(&lockup_script).await?;
+    let Some(lockup_tx) = bitcoin_rpc
+        .get_script_history(&lockup_script)
+        .await?
+        .into_iter()
+        .find(|transaction| transaction.txid() == lockup_txid)
+    else {
+        return Err(GatewayError::InvalidMetadata(format!(
+            "The chain source doesn't know the lockup transaction {lockup_txid} of swap {}",
+            swap.id
+        )));
+    };
+
+    let destination =
+        Address::from_str(&lightning_context.lnrpc.get_funding_address().await?.address)
+            .and_then(|address| address.require_network(lightning_context.lightning_network))
+            .map_err(|e| GatewayError::LightningResponseParseError(e.into()))?;
+    let sat_per_vbyte = match sat_per_vbyte {
+        Some(sat_per_vbyte) => sat_per_vbyte,
+        None => estimate_sat_per_vbyte(bitcoin_rpc).await?,
+    };
+
+    let transaction = swap
+        .refund_transaction(&lockup_tx, &destination, sat_per_vbyte)
+        .map_err(|e| {
+            GatewayError::InvalidMetadata(format!("Can't refund swap {}: {e}", swap.id))
+        })?;
+    let txid = transaction.txid();
+    bitcoin_rpc.submit_transaction(transaction.clone()).await;
+    info!(
+        "Refunded {} sats of swap {} to {destination} in {txid}",
+        transaction.output[0].value, swap.id
+    );
+
+    swap.state = SwapState::Refunded;
+    swap.refund_txid = Some(txid);
+
+    Ok(txid)
+}
+
+/// Returns the fee rate the chain source estimates for a transaction to
+/// confirm within `CONFIRMATION_TARGET` blocks
+async fn estimate_sat_per_vbyte(bitcoin_rpc: &DynBitcoindRpc) -> anyhow::Result<u64> {
+    let fee_rate = bitcoin_rpc
+        .get_fee_rate(CONFIRMATION_TARGET)
+        .await?
+        .context("The chain source has no fee estimate yet")?;
+    Ok(fee_rate.sats_per_kvb.div_ceil(1000))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+    use bitcoin::taproot::{LeafVersion, TapLeafHash};
+    use bitcoin::{absolute, Address, Network, Transaction, TxOut};
+    use bitcoin_hashes::{ripemd160, sha256, Hash};
+    use fedimint_core::secp256k1::{
+        schnorr, Message, PublicKey, Secp256k1, SecretKey, XOnlyPublicKey,
+    };
+    use lightning_invoice::Bolt11Invoice;
+    use serde_json::json;
+
+    use super::{
+        ReverseSwapRecord, ReverseSwapState, SubmarineSwap, SwapFees, SwapKind, SwapRecord,
+        SwapState, SwapTree,
+    };
+
+    const INVOICE: &str = "lnbc1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpl2pkx2ctnv5sxxmmwwd5kgetjypeh2ursdae8g6twvus8g6rfwvs8qun0dfjkxaq9qrsgq357wnc5r2ueh7ck6q93dj32dlqnls087fxdwk8qakdyafkq3yap9us6v52vjjsrvywa6rt52cm9r9zqt8r2t7mlcwspyetp5h2tztugp9lfyql";
+
+    #[test]
+    fn parses_provider_responses() {
+        let swap: SubmarineSwap = serde_json::from_value(json!({
+            "id": "bx2kiWwNVZzr",
+            "bip21": "bitcoin:bcrt1p...?amount=0.00100391",
+            "address": "bcrt1pq7ud5zcg",
+            "claimPublicKey": "02b3c5f1",
+            "timeoutBlockHeight": 1_024,
+            "acceptZeroConf": false,
+            "expectedAmount": 100_391,
+        }))
+        .expect("Valid swap");
+        assert_eq!(swap.id, "bx2kiWwNVZzr");
+        assert_eq!(swap.expected_amount, 100_391);
+        assert_eq!(swap.timeout_block_height, 1_024);
+
+        let fees: SwapFees = serde_json::from_value(json!({
+            "percentage": 0.1,
+            "minerFees": 258,
+        }))
+        .expect("Valid fees");
+        assert_eq!(fees.fee_sats(100_000), Some(358));
+        assert_eq!(fees.fee_sats(100_001), Some(359));
+        assert_eq!(
+            SwapFees {
+                miner_fees: u64::MAX,
+                ..fees
+            }
+            .fee_sats(100_000),
+            None
+        );
+
+        assert_eq!(SwapState::from_failed_status("transaction.mempool"), None);
+        assert_eq!(
+            SwapState::from_failed_status("swap.expired"),
+            Some(SwapState::Failed)
+        );
+    }
+
+    #[test]
+    fn rejects_lockup_addresses_not_committing_to_the_swap() {
+        let secp = Secp256k1::new();
+        let key =
+            |byte| PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&[byte; 32]).unwrap());
+        let payment_hash = sha256::Hash::hash(&[42; 32]);
+
+        let address = SwapTree::new(SwapKind::Submarine, &payment_hash, &key(1), &key(2), 1_024)
+            .expect("Valid swap")
+            .address(Network::Regtest);
+        let swap = SubmarineSwap {
+            id: "bx2kiWwNVZzr".to_string(),
+            address: address.to_string(),
+            expected_amount: 100_391,
+            timeout_block_height: 1_024,
+            claim_public_key: key(1).to_string(),
+        };
+
+        assert_eq!(
+            swap.verify_lockup_address(&payment_hash, &key(2), Network::Regtest)
+                .expect("Address commits to the swap"),
+            address
+        );
+
+        // The provider would be able to refund the lockup itself
+        assert!(swap
+            .verify_lockup_address(&payment_hash, &key(3), Network::Regtest)
+            .is_err());
+
+        // The provider could claim the lockup without paying the invoice
+        assert!(swap
+            .verify_lockup_address(&sha256::Hash::hash(&[0; 32]), &key(2), Network::Regtest)
+            .is_err());
+
+        // The lockup would be refundable later than agreed
+        let later = SubmarineSwap {
+            timeout_block_height: 2_048,
+            ..swap
+        };
+        assert!(later
+            .verify_lockup_address(&payment_hash, &key(2), Network::Regtest)
+            .is_err());
+    }
+
+    #[test]
+    fn builds_the_claim_leaves_of_the_provider() {
+        // No swap response of the provider is available offline, so the leaves
+        // are checked against the scripts of its swap trees byte by byte
+        let secp = Secp256k1::new();
+        let key =
+            |byte| PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&[byte; 32]).unwrap());
+        let payment_hash = sha256::Hash::hash(&[42; 32]);
+        let hash160 = ripemd160::Hash::hash(payment_hash.as_byte_array());
+        let claim_key = XOnlyPublicKey::from(key(1)).serialize();
+
+        // OP_HASH160 <hash160> OP_EQUALVERIFY <claim key> OP_CHECKSIG
+        let mut submarine_leaf = vec![0xa9, 0x14];
+        submarine_leaf.extend_from_slice(hash160.as_byte_array());
+        submarine_leaf.extend_from_slice(&[0x88, 0x20]);
+        submarine_leaf.extend_from_slice(&claim_key);
+        submarine_leaf.push(0xac);
+
+        // OP_SIZE 32 OP_EQUALVERIFY followed by the submarine claim leaf
+        let mut reverse_leaf = vec![0x82, 0x01, 0x20, 0x88];
+        reverse_leaf.extend_from_slice(&submarine_leaf);
+
+        let submarine_tree =
+            SwapTree::new(SwapKind::Submarine, &payment_hash, &key(1), &key(2), 1_024).unwrap();
+        assert_eq!(submarine_tree.claim_leaf.to_bytes(), submarine_leaf);
+        let reverse_tree =
+            SwapTree::new(SwapKind::Reverse, &payment_hash, &key(1), &key(2), 1_024).unwrap();
+        assert_eq!(reverse_tree.claim_leaf.to_bytes(), reverse_leaf);
+
+        // <refund key> OP_CHECKSIGVERIFY <timeout> OP_CHECKLOCKTIMEVERIFY
+        let mut refund_leaf = vec![0x20];
+        refund_leaf.extend_from_slice(&XOnlyPublicKey::from(key(2)).serialize());
+        refund_leaf.extend_from_slice(&[0xad, 0x02, 0x00, 0x04, 0xb1]);
+        assert_eq!(submarine_tree.refund_leaf.to_bytes(), refund_leaf);
+        assert_eq!(reverse_tree.refund_leaf.to_bytes(), refund_leaf);
+    }
+
+    #[test]
+    fn refunds_the_lockup_after_the_timeout() {
+        let secp = Secp256k1::new();
+        let invoice = INVOICE.parse::<Bolt11Invoice>().unwrap();
+        let claim_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let refund_key = SecretKey::from_slice(&[2; 32]).unwrap();
+        let swap = SwapRecord {
+            id: "bx2kiWwNVZzr".to_string(),
+            amount_sats: 100_000,
+            invoice: invoice.clone(),
+            preimage: [0; 32],
+            lockup_address: String::new(),
+            lockup_amount_sats: 100_391,
+            lockup_txid: None,
+            timeout_block_height: 1_024,
+            claim_public_key: claim_key.public_key(&secp),
+            refund_secret_key: refund_key,
+            refund_txid: None,
+            status: "swap.expired".to_string(),
+            state: SwapState::Failed,
+            created_at: SystemTime::UNIX_EPOCH,
+        };
+
+        let tree = SwapTree::new(
+            SwapKind::Submarine,
+            invoice.payment_hash(),
+            &swap.claim_public_key,
+            &refund_key.public_key(&secp),
+            swap.timeout_block_height,
+        )
+        .unwrap();
+        let destination = Address::p2tr(
+            &secp,
+            claim_key.x_only_public_key(&secp).0,
+            None,
+            Network::Regtest,
+        );
+        let lockup = TxOut {
+            value: 100_391,
+            script_pubkey: tree.address(Network::Regtest).script_pubkey(),
+        };
+        let lockup_tx = Transaction {
+            version: 2,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![
+                TxOut {
+                    value: 5_000,
+                    script_pubkey: destination.script_pubkey(),
+                },
+                lockup.clone(),
+            ],
+        };
+
+        let refund = swap
+            .refund_transaction(&lockup_tx, &destination, 2)
+            .expect("Lockup covers the fee");
+        assert_eq!(
+            refund.lock_time,
+            absolute::LockTime::from_height(1_024).unwrap()
+        );
+        assert_eq!(refund.input[0].previous_output.txid, lockup_tx.txid());
+        assert_eq!(refund.input[0].previous_output.vout, 1);
+        assert_eq!(
+            refund.output[0].value,
+            lockup.value - 2 * refund.vsize() as u64
+        );
+
+        // The witness spends the refund leaf with a valid signature of the
+        // refund key
+        let witness = refund.input[0].witness.to_vec();
+        assert_eq!(witness.len(), 3);
+        assert_eq!(witness[1], tree.refund_leaf.to_bytes());
+        let sighash = SighashCache::new(&refund)
+            .taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(&[&lockup]),
+                TapLeafHash::from_script(&tree.refund_leaf, LeafVersion::TapScript),
+                TapSighashType::Default,
+            )
+            .unwrap();
+        secp.verify_schnorr(
+            &schnorr::Signature::from_slice(&witness[0]).unwrap(),
+            &Message::from_slice(sighash.as_byte_array()).unwrap(),
+            &refund_key.x_only_public_key(&secp).0,
+        )
+        .expect("Valid refund signature");
+
+        assert!(swap
+            .refund_transaction(&lockup_tx, &destination, 1_000)
+            .is_err());
+        let other_tx = Transaction {
+            output: vec![lockup_tx.output[0].clone()],
+            ..lockup_tx
+        };
+        assert!(swap.refund_transaction(&other_tx, &destination, 2).is_err());
+    }
+
+    #[test]
+    fn claims_the_lockup_of_reverse_swaps_with_the_preimage() {
+        let secp = Secp256k1::new();
+        let preimage = [42; 32];
+        let claim_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let refund_key = SecretKey::from_slice(&[2; 32]).unwrap();
+        let invoice = INVOICE.parse::<Bolt11Invoice>().unwrap();
+        let swap = ReverseSwapRecord {
+            id: "ZwLlDWCZpkSS".to_string(),
+            amount_sats: 100_000,
+            invoice,
+            preimage,
+            lockup_address: String::new(),
+            onchain_amount_sats: 99_500,
+            timeout_block_height: 1_024,
+            claim_secret_key: claim_key,
+            refund_public_key: refund_key.public_key(&secp),
+            claim_transaction: None,
+            status: "transaction.confirmed".to_string(),
+            state: ReverseSwapState::Pending,
+            created_at: SystemTime::UNIX_EPOCH,
+        };
+
+        let tree = SwapTree::new(
+            SwapKind::Reverse,
+            swap.invoice.payment_hash(),
+            &claim_key.public_key(&secp),
+            &swap.refund_public_key,
+            swap.timeout_block_height,
+        )
+        .unwrap();
+        let lockup = TxOut {
+            value: 99_500,
+            script_pubkey: tree.address(Network::Regtest).script_pubkey(),
+        };
+        let lockup_tx = Transaction {
+            version: 2,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![lockup.clone()],
+        };
+        let destination = Address::p2tr(
+            &secp,
+            refund_key.x_only_public_key(&secp).0,
+            None,
+            Network::Regtest,
+        );
+
+        let claim = swap
+            .claim_transaction(&lockup_tx, &destination, 3)
+            .expect("Lockup covers the fee");
+        assert_eq!(claim.lock_time, absolute::LockTime::ZERO);
+        assert_eq!(
+            claim.output[0].value,
+            lockup.value - 3 * claim.vsize() as u64
+        );
+
+        let witness = claim.input[0].witness.to_vec();
+        assert_eq!(witness.len(), 4);
+        assert_eq!(witness[1], preimage.to_vec());
+        assert_eq!(witness[2], tree.claim_leaf.to_bytes());
+        let sighash = SighashCache::new(&claim)
+            .taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(&[&lockup]),
+                TapLeafHash::from_script(&tree.claim_leaf, LeafVersion::TapScript),
+                TapSighashType::Default,
+            )
+            .unwrap();
+        secp.verify_schnorr(
+            &schnorr::Signature::from_slice(&witness[0]).unwrap(),
+            &Message::from_slice(sighash.as_byte_array()).unwrap(),
+            &claim_key.x_only_public_key(&secp).0,
+        )
+        .expect("Valid claim signature");
+
+        // The preimage isn't revealed for a lockup of less than the agreed amount
+        let underfunded_tx = Transaction {
+            output: vec![TxOut {
+                value: 99_499,
+                ..lockup.clone()
+            }],
+            ..lockup_tx.clone()
+        };
+        assert!(swap
+            .claim_transaction(&underfunded_tx, &destination, 3)
+            .is_err());
+        assert_eq!(swap.lockup_script().unwrap(), lockup.script_pubkey);
+
+        // The provider's key comes first in the key aggregate of reverse swaps
+        let submarine_tree = SwapTree::new(
+            SwapKind::Submarine,
+            swap.invoice.payment_hash(),
+            &claim_key.public_key(&secp),
+            &swap.refund_public_key,
+            swap.timeout_block_height,
+        )
+        .unwrap();
+        assert_ne!(
+            submarine_tree.address(Network::Regtest),
+            tree.address(Network::Regtest)
+        );
+
+        assert_eq!(
+            ReverseSwapState::from_failed_status("transaction.confirmed"),
+            None
+        );
+        assert_eq!(
+            ReverseSwapState::from_failed_status("invoice.expired"),
+            Some(ReverseSwapState::Failed)
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::Address;
+use fedimint_core::encoding::{Decodable, Encodable};
+use serde::{Deserialize, Serialize};
+
+/// Time between two checks whether a sweep is due
+pub const SWEEP_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Funds are only swept once the excess reaches this amount, so the on-chain
+/// fees stay small compared to the swept amount
+const MIN_SWEEP_SATS: u64 = 10_000;
+
+/// Periodically sends the on-chain balance of the lightning node above
+/// `threshold_sats` to a cold storage address
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct SweepPolicy {
+    pub address: Address<NetworkUnchecked>,
+    /// On-chain balance that is kept in the lightning node's wallet, e.g. to
+    /// open channels
+    pub threshold_sats: u64,
+    /// Time between two sweeps
+    pub interval_secs: u64,
+    /// Fee rate of the sweep transaction, the lightning node estimates it if
+    /// not set
+    pub sat_per_vbyte: Option<u64>,
+}
+
+impl SweepPolicy {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+
+    /// Returns the amount to sweep from an on-chain balance of
+    /// `onchain_balance_sats`, if it is worth sweeping
+    pub fn sweep_amount(&self, onchain_balance_sats: u64) -> Option<bitcoin::Amount> {
+        let excess_sats = onchain_balance_sats.saturating_sub(self.threshold_sats);
+        (excess_sats >= MIN_SWEEP_SATS).then(|| bitcoin::Amount::from_sat(excess_sats))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::Address;
+
+    use super::SweepPolicy;
+
+    #[test]
+    fn sweeps_balance_above_threshold() {
+        let policy = SweepPolicy {
+            address: Address::from_str("bcrt1qsurq2ylgmwpkxflhsf4a7lzh3dnsd7cjkxwnff").unwrap(),
+            threshold_sats: 1_000_000,
+            interval_secs: 24 * 60 * 60,
+            sat_per_vbyte: None,
+        };
+
+        assert_eq!(policy.sweep_amount(500_000), None);
+        assert_eq!(policy.sweep_amount(1_009_999), None);
+        assert_eq!(
+            policy.sweep_amount(1_250_000),
+            Some(bitcoin::Amount::from_sat(250_000))
+        );
+    }
+}
@@ -1,10 +1,12 @@
+use std::time::Duration;
 use std::{ffi, iter};
 
 use anyhow::Context as _;
 use clap::Parser;
 use fedimint_core::core::OperationId;
+use fedimint_core::encoding::Encodable;
 use fedimint_core::Amount;
-use lightning_invoice::{Bolt11InvoiceDescription, Description};
+use lightning_invoice::{Bolt11Invoice, Bolt11InvoiceDescription, Description};
 use serde::{Deserialize, Serialize};
 use tracing::info;
 
@@ -42,6 +44,22 @@ enum Opts {
         #[clap(long, default_value = "false")]
         force_internal: bool,
     },
+    /// Log in to a LNURL-auth service
+    LnurlAuth {
+        /// LNURL of the login
+        lnurl: String,
+    },
+    /// Wait until the LNURL service that issued an invoice reports it as
+    /// settled, e.g. for a payment that finished in the background
+    LnurlVerify {
+        /// Invoice fetched from the LNURL service
+        invoice: Bolt11Invoice,
+        /// Verify URL the LNURL service returned with the invoice
+        verify_url: String,
+        /// Seconds to wait for the settlement
+        #[clap(long, default_value = "60")]
+        max_wait_secs: u64,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -91,7 +109,9 @@ pub(crate) async fn handle_cli_command(
             gateway_id,
             force_internal,
         } => {
-            let bolt11 = crate::get_invoice(&payment_info, amount, lnurl_comment).await?;
+            let lnurl_invoice =
+                crate::get_lnurl_invoice(&payment_info, amount, lnurl_comment).await?;
+            let bolt11 = lnurl_invoice.invoice.clone();
             info!("Paying invoice: {bolt11}");
             let ln_gateway = module.get_gateway(gateway_id, force_internal).await?;
 
@@ -116,14 +136,32 @@ pub(crate) async fn handle_cli_command(
                         "payment_type": payment_type.payment_type(),
                         "contract_id": contract_id,
                         "fee": fee,
+                        "verify_url": lnurl_invoice.verify_url,
                     }
                 }
             } else {
-                module
+                let response = module
                     .wait_for_ln_payment(payment_type, contract_id, false)
                     .await?
-                    .context("expected a response")?
+                    .context("expected a response")?;
+                lnurl_invoice.with_success_message(response)
             }
         }
+        Opts::LnurlAuth { lnurl } => {
+            let linking_key = crate::LnurlClient::default().auth(&lnurl, module).await?;
+            info!("Logged in with linking key {linking_key}");
+            serde_json::json!({ "linking_key": linking_key })
+        }
+        Opts::LnurlVerify {
+            invoice,
+            verify_url,
+            max_wait_secs,
+        } => {
+            let verify_url = reqwest::Url::parse(&verify_url).context("Invalid verify URL")?;
+            let preimage = crate::LnurlClient::default()
+                .wait_for_settlement(&invoice, &verify_url, Duration::from_secs(max_wait_secs))
+                .await?;
+            serde_json::json!({ "preimage": preimage.consensus_encode_to_hex() })
+        }
     })
 }
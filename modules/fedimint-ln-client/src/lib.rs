@@ -86,11 +86,15 @@ use pay::PayInvoicePayload;
 use rand::rngs::OsRng;
 use rand::seq::IteratorRandom as _;
 use rand::{CryptoRng, Rng, RngCore};
-use secp256k1::{All, PublicKey, Scalar, Secp256k1, Signing, ThirtyTwoByteHash, Verification};
+use secp256k1::{
+    ecdsa, All, PublicKey, Scalar, Secp256k1, Signing, ThirtyTwoByteHash, Verification,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use strum::IntoEnumIterator;
-use tracing::{debug, error, info};
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
 
 use crate::db::PaymentResultPrefix;
 use crate::incoming::{
@@ -334,6 +338,7 @@ impl ModuleInit for LightningClientInit {
 pub enum LightningChildKeys {
     RedeemKey = 0,
     PreimageAuthentication = 1,
+    LnurlAuth = 2,
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -392,6 +397,7 @@ pub struct LightningClientModule {
     secp: Secp256k1<All>,
     module_api: DynModuleApi,
     preimage_auth: KeyPair,
+    lnurl_auth_seed: [u8; 32],
     client_ctx: ClientContext<Self>,
     update_gateway_cache_merge: UpdateMerge,
     gateway_conn: Arc<dyn GatewayConnection + Send + Sync>,
@@ -463,6 +469,10 @@ impl LightningClientModule {
                 .module_root_secret()
                 .child_key(ChildId(LightningChildKeys::PreimageAuthentication as u64))
                 .to_secp_key(&secp),
+            lnurl_auth_seed: args
+                .module_root_secret()
+                .child_key(ChildId(LightningChildKeys::LnurlAuth as u64))
+                .to_random_bytes(),
             secp,
             client_ctx: args.context(),
             update_gateway_cache_merge: UpdateMerge::default(),
@@ -1857,54 +1867,600 @@ fn tweak_user_secret_key<Ctx: Verification + Signing>(
     KeyPair::from_secret_key(secp, &sk_tweaked)
 }
 
-/// Get LN invoice with given settings
+/// Time a LNURL service has to respond to a request by default
+pub const LNURL_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Times a failed request to a LNURL service is retried by default
+pub const LNURL_REQUEST_RETRIES: usize = 2;
+
+/// Requests that are sent to the same LNURL service at once by default
+pub const LNURL_MAX_REQUESTS_PER_HOST: usize = 4;
+
+/// Get LN invoice with given settings, see [`LnurlClient::get_invoice`]
 pub async fn get_invoice(
     info: &str,
     amount: Option<Amount>,
     lnurl_comment: Option<String>,
 ) -> anyhow::Result<Bolt11Invoice> {
-    let info = info.trim();
-    match lightning_invoice::Bolt11Invoice::from_str(info) {
-        Ok(invoice) => {
-            debug!("Parsed parameter as bolt11 invoice: {invoice}");
-            match (invoice.amount_milli_satoshis(), amount) {
-                (Some(_), Some(_)) => {
-                    bail!("Amount specified in both invoice and command line")
+    LnurlClient::default()
+        .get_invoice(info, amount, lnurl_comment)
+        .await
+}
+
+/// Get LN invoice with given settings together with its success action, see
+/// [`LnurlClient::get_lnurl_invoice`]
+pub async fn get_lnurl_invoice(
+    info: &str,
+    amount: Option<Amount>,
+    lnurl_comment: Option<String>,
+) -> anyhow::Result<LnurlInvoice> {
+    LnurlClient::default()
+        .get_lnurl_invoice(info, amount, lnurl_comment)
+        .await
+}
+
+/// Signs the challenges of LNURL-auth (LUD-04) logins. Services identify the
+/// user by the linking key of their domain, so it has to stay the same across
+/// logins to a domain but must differ between domains.
+pub trait LnurlAuthSigner {
+    /// Signs the `k1` challenge of the service at `domain` and returns the
+    /// domain's linking key together with the signature
+    fn sign_challenge(&self, domain: &str, k1: &[u8; 32]) -> (PublicKey, ecdsa::Signature);
+}
+
+impl LnurlAuthSigner for LightningClientModule {
+    fn sign_challenge(&self, domain: &str, k1: &[u8; 32]) -> (PublicKey, ecdsa::Signature) {
+        sign_lnurl_auth_challenge(&self.secp, &self.lnurl_auth_seed, domain, k1)
+    }
+}
+
+/// Signs `k1` with the linking key of `domain`, which is derived from `seed`
+/// as the HMAC-SHA256 of the domain
+pub fn sign_lnurl_auth_challenge<C: Signing>(
+    secp: &Secp256k1<C>,
+    seed: &[u8; 32],
+    domain: &str,
+    k1: &[u8; 32],
+) -> (PublicKey, ecdsa::Signature) {
+    let mut engine = HmacEngine::<sha256::Hash>::new(seed);
+    engine.input(domain.as_bytes());
+    let linking_key = KeyPair::from_seckey_slice(secp, &Hmac::from_engine(engine).to_byte_array())
+        .expect("Hash is a valid secret key with overwhelming probability");
+
+    let message = secp256k1::Message::from_slice(k1).expect("k1 has 32 bytes");
+    (
+        linking_key.public_key(),
+        secp.sign_ecdsa(&message, &linking_key.secret_key()),
+    )
+}
+
+/// Sends the requests to LNURL services. The HTTP client can be configured by
+/// the caller, e.g. with a proxy to route the requests through Tor or with a
+/// custom user agent. Clones share their limits on the requests per host, so
+/// a caller can give every flow its own clone with its own cancellation token.
+#[derive(Debug, Clone)]
+pub struct LnurlClient {
+    client: reqwest::Client,
+    request_timeout: Duration,
+    retries: usize,
+    max_requests_per_host: usize,
+    limiters: Arc<std::sync::Mutex<BTreeMap<String, Arc<Semaphore>>>>,
+    cancel: CancellationToken,
+}
+
+impl Default for LnurlClient {
+    fn default() -> Self {
+        LnurlClient::new(reqwest::Client::new())
+    }
+}
+
+impl LnurlClient {
+    pub fn new(client: reqwest::Client) -> Self {
+        LnurlClient {
+            client,
+            request_timeout: LNURL_REQUEST_TIMEOUT,
+            retries: LNURL_REQUEST_RETRIES,
+            max_requests_per_host: LNURL_MAX_REQUESTS_PER_HOST,
+            limiters: Arc::new(std::sync::Mutex::new(BTreeMap::new())),
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Sets the time a single attempt of a request may take
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Sets how often a failed request is retried, zero disables retries
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets how many requests are sent to the same host at once, further
+    /// requests wait for a slot. Clones created afterwards share the limit.
+    pub fn with_max_requests_per_host(mut self, max_requests_per_host: usize) -> Self {
+        self.max_requests_per_host = max_requests_per_host.max(1);
+        self.limiters = Arc::new(std::sync::Mutex::new(BTreeMap::new()));
+        self
+    }
+
+    /// Aborts all requests of this client once `cancel` is cancelled, e.g.
+    /// when the user abandons the payment they were sent for
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    fn limiter(&self, url: &reqwest::Url) -> Arc<Semaphore> {
+        self.limiters
+            .lock()
+            .expect("poisoned")
+            .entry(url.host_str().unwrap_or_default().to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.max_requests_per_host)))
+            .clone()
+    }
+
+    /// Sends a GET request to `url` and returns the JSON response
+    async fn request(&self, url: &reqwest::Url) -> anyhow::Result<serde_json::Value> {
+        let limiter = self.limiter(url);
+        let request = retry(
+            format!("LNURL request to {url}"),
+            backon::FibonacciBuilder::default()
+                .with_min_delay(Duration::from_millis(500))
+                .with_max_times(self.retries),
+            || async {
+                // The slot is released while waiting for a retry
+                let _permit = limiter.acquire().await.expect("Limiter is never closed");
+                let started_at = fedimint_core::time::now();
+                let response = timeout(self.request_timeout, async {
+                    let response = self.client.get(url.clone()).send().await?;
+                    if let Err(e) = response.error_for_status_ref() {
+                        // Some services answer LNURL errors with an error status, their
+                        // reason is passed on instead of retrying the request
+                        let body = response.json().await.unwrap_or_default();
+                        if lnurl_error_reason(&body).is_some() {
+                            return Ok(body);
+                        }
+                        return Err(anyhow::Error::from(e));
+                    }
+                    Ok(response.json().await?)
+                })
+                .await
+                .context("LNURL service did not respond in time")??;
+
+                debug!(
+                    "LNURL service at {} responded in {:?}",
+                    url.host_str().unwrap_or_default(),
+                    fedimint_core::time::now()
+                        .duration_since(started_at)
+                        .unwrap_or_default()
+                );
+
+                Ok(response)
+            },
+        );
+
+        tokio::select! {
+            response = request => response,
+            () = self.cancel.cancelled() => Err(anyhow!("LNURL request to {url} was cancelled")),
+        }
+    }
+
+    /// Logs in to the LNURL-auth (LUD-04) service `lnurl` refers to and
+    /// returns the linking key the service identifies us by
+    pub async fn auth(
+        &self,
+        lnurl: &str,
+        signer: &impl LnurlAuthSigner,
+    ) -> anyhow::Result<PublicKey> {
+        let Some(LnurlRequest::Auth(url)) = parse_lnurl(lnurl.trim())? else {
+            bail!("Not an LNURL-auth request: {lnurl}");
+        };
+        let (callback, linking_key) = lnurl_auth_callback(&url, signer)?;
+        let response = self.request(&callback).await?;
+        if let Some(reason) = lnurl_error_reason(&response) {
+            bail!("LNURL service refused the login: {reason}");
+        }
+
+        Ok(linking_key)
+    }
+
+    /// Returns the invoice to pay for `info`, which is either an invoice or a
+    /// LNURL-pay request to fetch an invoice of `amount` from
+    pub async fn get_invoice(
+        &self,
+        info: &str,
+        amount: Option<Amount>,
+        lnurl_comment: Option<String>,
+    ) -> anyhow::Result<Bolt11Invoice> {
+        Ok(self
+            .get_lnurl_invoice(info, amount, lnurl_comment)
+            .await?
+            .invoice)
+    }
+
+    /// Like [`LnurlClient::get_invoice`], but also returns the URL to verify
+    /// the payment with and the success action if the invoice was fetched from
+    /// a LNURL service
+    pub async fn get_lnurl_invoice(
+        &self,
+        info: &str,
+        amount: Option<Amount>,
+        lnurl_comment: Option<String>,
+    ) -> anyhow::Result<LnurlInvoice> {
+        let info = info.trim();
+        // Payment links prefix invoices and LNURLs with the URI scheme
+        let info = match info.get(..10) {
+            Some(scheme) if scheme.eq_ignore_ascii_case("lightning:") => &info[10..],
+            _ => info,
+        };
+        match lightning_invoice::Bolt11Invoice::from_str(info) {
+            Ok(invoice) => {
+                debug!("Parsed parameter as bolt11 invoice: {invoice}");
+                match (invoice.amount_milli_satoshis(), amount) {
+                    (Some(_), Some(_)) => {
+                        bail!("Amount specified in both invoice and command line")
+                    }
+                    (None, _) => {
+                        bail!("We don't support invoices without an amount")
+                    }
+                    _ => {}
+                };
+                Ok(LnurlInvoice {
+                    invoice,
+                    verify_url: None,
+                    success_action: None,
+                })
+            }
+            Err(e) => {
+                let url = match parse_lnurl(info)? {
+                    Some(LnurlRequest::Pay(url) | LnurlRequest::Unspecified(url)) => url,
+                    Some(request) => bail!("Only LNURL-pay is supported, not {request:?}"),
+                    None => bail!("Invalid invoice or lnurl: {e:?}"),
+                };
+                debug!("Parsed parameter as lnurl: {url}");
+                let amount = amount.context("When using a lnurl, an amount must be specified")?;
+                let url = reqwest::Url::parse(&url).context("Invalid LNURL")?;
+                let response = self.request(&url).await?;
+                if let Some(reason) = lnurl_error_reason(&response) {
+                    bail!("LNURL service refused the payment request: {reason}");
                 }
-                (None, _) => {
-                    bail!("We don't support invoices without an amount")
+                let response = lnurl::decode_ln_url_response_from_json(response)?;
+                match response {
+                    lnurl::LnUrlResponse::LnUrlPayResponse(response) => {
+                        let callback =
+                            lnurl_pay_callback(&response, amount, lnurl_comment.as_deref())?;
+                        let response = self.request(&callback).await?;
+                        if let Some(reason) = lnurl_error_reason(&response) {
+                            bail!("LNURL service refused to create an invoice: {reason}");
+                        }
+                        let response = serde_json::from_value::<LnurlPayCallbackResponse>(response)
+                            .context("LNURL service returned no invoice")?;
+                        let invoice = Bolt11Invoice::from_str(&response.pr)?;
+                        ensure!(
+                            invoice.amount_milli_satoshis() == Some(amount.msats),
+                            "LNURL service returned an invoice of {:?} msat instead of {}",
+                            invoice.amount_milli_satoshis(),
+                            amount.msats
+                        );
+                        let verify_url = response
+                            .verify
+                            .map(|verify| reqwest::Url::parse(&verify))
+                            .transpose()
+                            .context("Invalid LNURL verify URL")?;
+                        Ok(LnurlInvoice {
+                            invoice,
+                            verify_url,
+                            success_action: response
+                                .success_action
+                                .map(lnurl::pay::SuccessAction::from_params),
+                        })
+                    }
+                    other => {
+                        bail!("Unexpected response from lnurl: {other:?}");
+                    }
                 }
-                _ => {}
-            };
-            Ok(invoice)
+            }
         }
-        Err(e) => {
-            let lnurl = if info.to_lowercase().starts_with("lnurl") {
-                lnurl::lnurl::LnUrl::from_str(info)?
-            } else if info.contains('@') {
-                lnurl::lightning_address::LightningAddress::from_str(info)?.lnurl()
-            } else {
-                bail!("Invalid invoice or lnurl: {e:?}");
-            };
-            debug!("Parsed parameter as lnurl: {lnurl:?}");
-            let amount = amount.context("When using a lnurl, an amount must be specified")?;
-            let async_client = lnurl::AsyncClient::from_client(reqwest::Client::new());
-            let response = async_client.make_request(&lnurl.url).await?;
-            match response {
-                lnurl::LnUrlResponse::LnUrlPayResponse(response) => {
-                    let invoice = async_client
-                        .get_invoice(&response, amount.msats, None, lnurl_comment.as_deref())
-                        .await?;
-                    let invoice = Bolt11Invoice::from_str(invoice.invoice())?;
-                    assert_eq!(invoice.amount_milli_satoshis(), Some(amount.msats));
-                    Ok(invoice)
-                }
-                other => {
-                    bail!("Unexpected response from lnurl: {other:?}");
+    }
+
+    /// Polls the LUD-21 verify URL of a paid invoice with increasing delays
+    /// until the LNURL service reports the invoice as settled, and returns the
+    /// preimage once it is checked against the invoice's payment hash. Failed
+    /// requests are retried until `max_wait` elapsed.
+    pub async fn wait_for_settlement(
+        &self,
+        invoice: &Bolt11Invoice,
+        verify_url: &reqwest::Url,
+        max_wait: Duration,
+    ) -> Result<Preimage, LnurlVerifyError> {
+        let poll = async {
+            let mut delays = backon::BackoffBuilder::build(
+                &backon::FibonacciBuilder::default()
+                    .with_min_delay(Duration::from_secs(1))
+                    .with_max_delay(Duration::from_secs(30))
+                    .with_max_times(usize::MAX),
+            );
+            loop {
+                match self.verify(verify_url).await {
+                    Ok(LnurlVerifyResponse::Settled { preimage }) => {
+                        return verify_preimage(invoice, &preimage);
+                    }
+                    Ok(LnurlVerifyResponse::Pending) => {}
+                    Ok(LnurlVerifyResponse::Error { reason }) => {
+                        return Err(LnurlVerifyError::Service(reason));
+                    }
+                    Err(e) => debug!("Failed to verify LNURL payment at {verify_url}: {e:?}"),
                 }
+                runtime::sleep(delays.next().unwrap_or(Duration::from_secs(30))).await;
+            }
+        };
+
+        tokio::select! {
+            result = timeout(max_wait, poll) => {
+                result.map_err(|_| LnurlVerifyError::Timeout(max_wait))?
+            }
+            () = self.cancel.cancelled() => Err(LnurlVerifyError::Cancelled),
+        }
+    }
+
+    async fn verify(&self, verify_url: &reqwest::Url) -> anyhow::Result<LnurlVerifyResponse> {
+        let response = self.request(verify_url).await?;
+        if let Some(reason) = lnurl_error_reason(&response) {
+            return Ok(LnurlVerifyResponse::Error { reason });
+        }
+
+        match (&response["settled"], &response["preimage"]) {
+            (serde_json::Value::Bool(true), serde_json::Value::String(preimage)) => {
+                Ok(LnurlVerifyResponse::Settled {
+                    preimage: preimage.clone(),
+                })
+            }
+            (serde_json::Value::Bool(_), _) => Ok(LnurlVerifyResponse::Pending),
+            _ => bail!("LNURL service returned an invalid verify response: {response}"),
+        }
+    }
+}
+
+/// Invoice to pay, fetched from a LNURL service unless it was given directly
+#[derive(Debug, Clone)]
+pub struct LnurlInvoice {
+    pub invoice: Bolt11Invoice,
+    /// LUD-21 URL to check whether the invoice was settled, set if the LNURL
+    /// service supports it
+    pub verify_url: Option<reqwest::Url>,
+    /// LUD-09 action to present to the user once the invoice is paid
+    pub success_action: Option<lnurl::pay::SuccessAction>,
+}
+
+impl LnurlInvoice {
+    /// Returns the message of the success action to present to the user after
+    /// paying the invoice. AES encrypted messages are decrypted with the
+    /// payment's `preimage`, unknown actions are ignored.
+    pub fn success_message(&self, preimage: &Preimage) -> anyhow::Result<Option<String>> {
+        let message = match &self.success_action {
+            Some(lnurl::pay::SuccessAction::Message(message)) => message.clone(),
+            Some(lnurl::pay::SuccessAction::Url { url, description }) => {
+                format!("{description}: {url}")
+            }
+            Some(lnurl::pay::SuccessAction::AES(params)) => {
+                let plaintext = params
+                    .decrypt(&preimage.0)
+                    .context("Failed to decrypt the LNURL success action")?;
+                format!("{}: {plaintext}", params.description)
             }
+            Some(lnurl::pay::SuccessAction::Unknown(_)) | None => return Ok(None),
+        };
+
+        Ok(Some(message))
+    }
+
+    /// Adds the message of the success action to the `response` of
+    /// [`LightningClientModule::wait_for_ln_payment`] for a successful payment
+    /// of the invoice. The payment succeeded regardless, so a message that
+    /// can't be decrypted is only logged.
+    pub fn with_success_message(&self, mut response: serde_json::Value) -> serde_json::Value {
+        let Some(preimage) = response["preimage"].as_str().and_then(|preimage| {
+            Preimage::consensus_decode_hex(preimage, &Default::default()).ok()
+        }) else {
+            return response;
+        };
+
+        match self.success_message(&preimage) {
+            Ok(Some(message)) => response["success_message"] = json!(message),
+            Ok(None) => {}
+            Err(e) => warn!("Failed to present the LNURL success action: {e:?}"),
+        }
+
+        response
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum LnurlVerifyError {
+    #[error("Invoice was not settled within {0:?}")]
+    Timeout(Duration),
+    #[error("LNURL service failed to verify the payment: {0}")]
+    Service(String),
+    #[error("LNURL service returned a preimage that doesn't match the invoice")]
+    InvalidPreimage,
+    #[error("Waiting for the settlement was cancelled")]
+    Cancelled,
+}
+
+/// Response of the callback of a LNURL-pay request
+#[derive(Debug, Deserialize)]
+struct LnurlPayCallbackResponse {
+    pr: String,
+    verify: Option<String>,
+    #[serde(rename = "successAction")]
+    success_action: Option<lnurl::pay::SuccessActionParams>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum LnurlVerifyResponse {
+    Settled { preimage: String },
+    Pending,
+    Error { reason: String },
+}
+
+/// Returns the reason if `response` is a LNURL error response
+fn lnurl_error_reason(response: &serde_json::Value) -> Option<String> {
+    match serde_json::from_value(response.clone()) {
+        Ok(lnurl::Response::Error { reason }) => Some(reason),
+        _ => None,
+    }
+}
+
+/// Returns the preimage if it is the hex encoded preimage of the invoice's
+/// payment hash
+fn verify_preimage(invoice: &Bolt11Invoice, preimage: &str) -> Result<Preimage, LnurlVerifyError> {
+    let preimage = <[u8; 32] as bitcoin::hashes::hex::FromHex>::from_hex(preimage)
+        .map_err(|_| LnurlVerifyError::InvalidPreimage)?;
+    if sha256::Hash::hash(&preimage) != *invoice.payment_hash() {
+        return Err(LnurlVerifyError::InvalidPreimage);
+    }
+
+    Ok(Preimage(preimage))
+}
+
+/// LNURL subprotocol a LNURL refers to, together with the URL of its first
+/// request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LnurlRequest {
+    /// LUD-06 pay request, including lightning addresses (LUD-16)
+    Pay(String),
+    /// LUD-03 withdraw request
+    Withdraw(String),
+    /// LUD-07 channel request
+    Channel(String),
+    /// LUD-04 auth request
+    Auth(String),
+    /// Bech32 LNURL whose subprotocol is only known from the `tag` of the
+    /// service's response
+    Unspecified(String),
+}
+
+impl LnurlRequest {
+    /// Classifies the URL of a bech32 LNURL, which only tells apart auth
+    /// requests by their `tag=login` query parameter
+    fn from_bech32(lnurl: &str) -> anyhow::Result<Self> {
+        let url = lnurl::lnurl::LnUrl::from_str(lnurl)?.url;
+        let is_auth = reqwest::Url::parse(&url)?
+            .query_pairs()
+            .any(|(name, value)| name == "tag" && value == "login");
+        Ok(if is_auth {
+            LnurlRequest::Auth(url)
+        } else {
+            LnurlRequest::Unspecified(url)
+        })
+    }
+}
+
+/// Parses the LNURL `info` refers to, or returns `None` if it is neither a
+/// LNURL nor a lightning address. Besides bech32 LNURLs, LUD-17 URLs
+/// (`lnurlp://`, `lnurlw://`, `lnurlc://` and `keyauth://`) and LUD-01
+/// fallback URLs carrying the LNURL in their `lightning` query parameter are
+/// accepted.
+pub fn parse_lnurl(info: &str) -> anyhow::Result<Option<LnurlRequest>> {
+    if let Some((scheme, rest)) = info.split_once("://") {
+        let scheme = scheme.to_lowercase();
+        if scheme == "http" || scheme == "https" {
+            let url = reqwest::Url::parse(info)?;
+            let Some((_, lnurl)) = url.query_pairs().find(|(name, _)| name == "lightning") else {
+                return Ok(None);
+            };
+            return LnurlRequest::from_bech32(&lnurl).map(Some);
         }
+
+        let request: fn(String) -> LnurlRequest = match scheme.as_str() {
+            "lnurlp" => LnurlRequest::Pay,
+            "lnurlw" => LnurlRequest::Withdraw,
+            "lnurlc" => LnurlRequest::Channel,
+            "keyauth" => LnurlRequest::Auth,
+            _ => return Ok(None),
+        };
+        // Onion services are requested over plain HTTP
+        let host = rest.split(['/', '?', ':']).next().unwrap_or_default();
+        let scheme = if host.to_lowercase().ends_with(".onion") {
+            "http"
+        } else {
+            "https"
+        };
+        return Ok(Some(request(format!("{scheme}://{rest}"))));
+    }
+
+    if info.to_lowercase().starts_with("lnurl") {
+        return LnurlRequest::from_bech32(info).map(Some);
     }
+
+    if info.contains('@') {
+        let address = lnurl::lightning_address::LightningAddress::from_str(info)?;
+        return Ok(Some(LnurlRequest::Pay(address.lnurl().url)));
+    }
+
+    Ok(None)
+}
+
+/// Returns the URL that completes the LNURL-auth login `url` refers to, signed
+/// by `signer`, together with the linking key it was signed with
+fn lnurl_auth_callback(
+    url: &str,
+    signer: &impl LnurlAuthSigner,
+) -> anyhow::Result<(reqwest::Url, PublicKey)> {
+    let mut url = reqwest::Url::parse(url)?;
+    let domain = url
+        .host_str()
+        .context("LNURL-auth URL has no domain")?
+        .to_string();
+    let k1 = url
+        .query_pairs()
+        .find(|(name, _)| name == "k1")
+        .map(|(_, k1)| <[u8; 32] as bitcoin::hashes::hex::FromHex>::from_hex(&k1))
+        .context("LNURL-auth URL has no k1 challenge")?
+        .context("Invalid LNURL-auth k1 challenge")?;
+
+    let (linking_key, signature) = signer.sign_challenge(&domain, &k1);
+    url.query_pairs_mut()
+        .append_pair("sig", &signature.to_string())
+        .append_pair("key", &linking_key.to_string());
+
+    Ok((url, linking_key))
+}
+
+/// Returns the URL to request the invoice of a LNURL-pay payment from. A
+/// comment is only sent if the service accepts comments of its length
+/// (LUD-12).
+fn lnurl_pay_callback(
+    pay: &lnurl::pay::PayResponse,
+    amount: Amount,
+    comment: Option<&str>,
+) -> anyhow::Result<reqwest::Url> {
+    ensure!(
+        pay.min_sendable <= amount.msats && amount.msats <= pay.max_sendable,
+        "The LNURL service only accepts amounts between {} and {} msat",
+        pay.min_sendable,
+        pay.max_sendable
+    );
+
+    let mut callback = reqwest::Url::parse(&pay.callback).context("Invalid LNURL callback")?;
+    callback
+        .query_pairs_mut()
+        .append_pair("amount", &amount.msats.to_string());
+
+    if let Some(comment) = comment {
+        let max_length = pay.comment_allowed.unwrap_or(0);
+        ensure!(max_length > 0, "The LNURL service doesn't accept comments");
+        ensure!(
+            comment.chars().count() <= max_length as usize,
+            "The LNURL service only accepts comments of up to {max_length} characters"
+        );
+        callback.query_pairs_mut().append_pair("comment", comment);
+    }
+
+    Ok(callback)
 }
 
 #[derive(Debug, Clone)]
@@ -2025,3 +2581,259 @@ impl GatewayConnection for MockGatewayConnection {
         Ok("00000000".to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bitcoin::hashes::{sha256, Hash};
+    use fedimint_core::Amount;
+    use fedimint_ln_common::contracts::Preimage;
+    use lightning_invoice::{Bolt11Invoice, Currency, InvoiceBuilder, PaymentSecret};
+    use lnurl::pay::{AesParams, PayResponse, SuccessAction};
+    use lnurl::Tag;
+    use secp256k1::{Secp256k1, SecretKey};
+    use serde_json::json;
+
+    use super::{
+        lnurl_auth_callback, lnurl_error_reason, lnurl_pay_callback, parse_lnurl,
+        sign_lnurl_auth_challenge, verify_preimage, LnurlAuthSigner, LnurlClient, LnurlInvoice,
+        LnurlRequest, LnurlVerifyError,
+    };
+
+    struct SeedSigner([u8; 32]);
+
+    impl LnurlAuthSigner for SeedSigner {
+        fn sign_challenge(
+            &self,
+            domain: &str,
+            k1: &[u8; 32],
+        ) -> (secp256k1::PublicKey, secp256k1::ecdsa::Signature) {
+            sign_lnurl_auth_challenge(&Secp256k1::new(), &self.0, domain, k1)
+        }
+    }
+
+    fn invoice(preimage: [u8; 32]) -> Bolt11Invoice {
+        let secp = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        InvoiceBuilder::new(Currency::Regtest)
+            .amount_milli_satoshis(10_000)
+            .description(String::new())
+            .payment_hash(sha256::Hash::hash(&preimage))
+            .payment_secret(PaymentSecret([0; 32]))
+            .duration_since_epoch(Duration::from_secs(1_700_000_000))
+            .min_final_cltv_expiry_delta(18)
+            .build_signed(|hash| secp.sign_ecdsa_recoverable(hash, &secret_key))
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_lnurl_forms() {
+        let url = "https://service.com/api?q=3fc3645b439ce8e7f2553a69e5267081d96dcd340693afabe04be7b0ccd178df";
+        let lnurl = "LNURL1DP68GURN8GHJ7UM9WFMXJCM99E3K7MF0V9CXJ0M385EKVCENXC6R2C35XVUKXEFCV5MKVV34X5EKZD3EV56NYD3HXQURZEPEXEJXXEPNXSCRVWFNV9NXZCN9XQ6XYEFHVGCXXCMYXYMNSERXFQ5FNS";
+        let unspecified = Some(LnurlRequest::Unspecified(url.to_string()));
+
+        assert_eq!(parse_lnurl(lnurl).unwrap(), unspecified);
+        assert_eq!(
+            parse_lnurl(&format!("https://wallet.com/pay?lightning={lnurl}")).unwrap(),
+            unspecified
+        );
+        assert_eq!(
+            parse_lnurl("lnurlp://service.com/api?q=1").unwrap(),
+            Some(LnurlRequest::Pay("https://service.com/api?q=1".to_string()))
+        );
+        assert_eq!(
+            parse_lnurl("lnurlp://service.onion/api").unwrap(),
+            Some(LnurlRequest::Pay("http://service.onion/api".to_string()))
+        );
+        assert_eq!(
+            parse_lnurl("lnurlp://service.ONION/api").unwrap(),
+            Some(LnurlRequest::Pay("http://service.ONION/api".to_string()))
+        );
+        assert_eq!(
+            parse_lnurl("alice@service.com").unwrap(),
+            Some(LnurlRequest::Pay(
+                "https://service.com/.well-known/lnurlp/alice".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_lnurl("lnurlw://service.com/withdraw").unwrap(),
+            Some(LnurlRequest::Withdraw(
+                "https://service.com/withdraw".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_lnurl("LNURLC://service.com/channel").unwrap(),
+            Some(LnurlRequest::Channel(
+                "https://service.com/channel".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_lnurl("keyauth://service.com/login?tag=login&k1=00").unwrap(),
+            Some(LnurlRequest::Auth(
+                "https://service.com/login?tag=login&k1=00".to_string()
+            ))
+        );
+        assert_eq!(parse_lnurl("https://service.com").unwrap(), None);
+        assert_eq!(parse_lnurl("not an lnurl").unwrap(), None);
+    }
+
+    #[test]
+    fn signs_lnurl_auth_challenge() {
+        let k1 = "e2af6254a8df433264fa23f67eb8188635d15ce883e8fc020989d5f82ae6f11e";
+        let url = format!("https://service.com/login?tag=login&k1={k1}");
+        let signer = SeedSigner([7; 32]);
+
+        let (callback, linking_key) = lnurl_auth_callback(&url, &signer).unwrap();
+        let query = callback
+            .query_pairs()
+            .into_owned()
+            .collect::<std::collections::BTreeMap<_, _>>();
+        assert_eq!(query["tag"], "login");
+        assert_eq!(query["k1"], k1);
+        assert_eq!(query["key"], linking_key.to_string());
+
+        let signature = secp256k1::ecdsa::Signature::from_der(
+            &<Vec<u8> as bitcoin::hashes::hex::FromHex>::from_hex(&query["sig"]).unwrap(),
+        )
+        .unwrap();
+        let message = secp256k1::Message::from_slice(
+            &<[u8; 32] as bitcoin::hashes::hex::FromHex>::from_hex(k1).unwrap(),
+        )
+        .unwrap();
+        Secp256k1::new()
+            .verify_ecdsa(&message, &signature, &linking_key)
+            .unwrap();
+
+        // The linking key only depends on the domain
+        let other_login = "https://service.com/other?tag=login&k1=00000000000000000000000000000000000000000000000000000000000000ff";
+        assert_eq!(
+            lnurl_auth_callback(other_login, &signer).unwrap().1,
+            linking_key
+        );
+        let other_domain = format!("https://other.com/login?tag=login&k1={k1}");
+        assert_ne!(
+            lnurl_auth_callback(&other_domain, &signer).unwrap().1,
+            linking_key
+        );
+
+        assert!(lnurl_auth_callback("https://service.com/login?tag=login", &signer).is_err());
+        assert!(lnurl_auth_callback("https://service.com/login?k1=00", &signer).is_err());
+    }
+
+    #[test]
+    fn appends_comment_only_if_allowed() {
+        let pay = PayResponse {
+            callback: "https://example.com/lnurlp/alice/callback".to_string(),
+            max_sendable: 100_000_000,
+            min_sendable: 1_000,
+            tag: Tag::PayRequest,
+            metadata: "[]".to_string(),
+            comment_allowed: Some(5),
+            allows_nostr: None,
+            nostr_pubkey: None,
+        };
+        let amount = Amount::from_msats(10_000);
+
+        assert_eq!(
+            lnurl_pay_callback(&pay, amount, Some("tipé!"))
+                .unwrap()
+                .as_str(),
+            "https://example.com/lnurlp/alice/callback?amount=10000&comment=tip%C3%A9%21"
+        );
+        assert!(lnurl_pay_callback(&pay, amount, Some("thanks")).is_err());
+        assert!(lnurl_pay_callback(&pay, Amount::from_msats(999), None).is_err());
+
+        let no_comments = PayResponse {
+            comment_allowed: None,
+            ..pay
+        };
+        assert_eq!(
+            lnurl_pay_callback(&no_comments, amount, None)
+                .unwrap()
+                .as_str(),
+            "https://example.com/lnurlp/alice/callback?amount=10000"
+        );
+        assert!(lnurl_pay_callback(&no_comments, amount, Some("tip")).is_err());
+    }
+
+    #[test]
+    fn reads_reason_of_lnurl_errors() {
+        assert_eq!(
+            lnurl_error_reason(&json!({"status": "ERROR", "reason": "Amount too low"})),
+            Some("Amount too low".to_string())
+        );
+        assert_eq!(
+            lnurl_error_reason(&json!({"pr": "lnbc1", "routes": []})),
+            None
+        );
+        assert_eq!(lnurl_error_reason(&json!({"status": "OK"})), None);
+    }
+
+    #[test]
+    fn checks_preimage_against_payment_hash() {
+        let preimage = [7; 32];
+        let invoice = invoice(preimage);
+
+        assert_eq!(
+            verify_preimage(&invoice, &"07".repeat(32)).unwrap().0,
+            preimage
+        );
+        assert_eq!(
+            verify_preimage(&invoice, &"08".repeat(32)),
+            Err(LnurlVerifyError::InvalidPreimage)
+        );
+        assert_eq!(
+            verify_preimage(&invoice, "not hex"),
+            Err(LnurlVerifyError::InvalidPreimage)
+        );
+    }
+
+    #[test]
+    fn decrypts_aes_success_action_with_preimage() {
+        let preimage = [7; 32];
+        let params = AesParams::new("Your voucher".to_string(), "ABC-123", &preimage).unwrap();
+        let lnurl_invoice = LnurlInvoice {
+            invoice: invoice(preimage),
+            verify_url: None,
+            success_action: Some(SuccessAction::AES(params)),
+        };
+
+        assert_eq!(
+            lnurl_invoice.success_message(&Preimage(preimage)).unwrap(),
+            Some("Your voucher: ABC-123".to_string())
+        );
+        assert!(lnurl_invoice.success_message(&Preimage([8; 32])).is_err());
+        assert_eq!(
+            lnurl_invoice.with_success_message(json!({ "preimage": "07".repeat(32) })),
+            json!({ "preimage": "07".repeat(32), "success_message": "Your voucher: ABC-123" })
+        );
+
+        let message_invoice = LnurlInvoice {
+            success_action: Some(SuccessAction::Message("Thanks!".to_string())),
+            ..lnurl_invoice
+        };
+        assert_eq!(
+            message_invoice.success_message(&Preimage([8; 32])).unwrap(),
+            Some("Thanks!".to_string())
+        );
+    }
+
+    #[test]
+    fn clones_share_the_limits_per_host() {
+        let client = LnurlClient::default().with_max_requests_per_host(2);
+        let clone = client.clone();
+        let url = |url: &str| reqwest::Url::parse(url).unwrap();
+
+        let limiter = client.limiter(&url("https://example.com/lnurlp/alice"));
+        assert_eq!(limiter.available_permits(), 2);
+        assert!(std::sync::Arc::ptr_eq(
+            &limiter,
+            &clone.limiter(&url("https://example.com/lnurlp/bob/callback"))
+        ));
+        assert!(!std::sync::Arc::ptr_eq(
+            &limiter,
+            &clone.limiter(&url("https://example.org/lnurlp/alice"))
+        ));
+    }
+}
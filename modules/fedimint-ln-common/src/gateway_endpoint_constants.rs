@@ -1,22 +1,79 @@
 /// Use `_` for word separator
 
 pub const ADDRESS_ENDPOINT: &str = "/address";
+pub const ALERT_POLICY_ENDPOINT: &str = "/alert_policy";
+pub const AUDIT_LOG_ENDPOINT: &str = "/audit_log";
 pub const BACKUP_ENDPOINT: &str = "/backup";
 pub const BALANCE_ENDPOINT: &str = "/balance";
+pub const CLAIM_LIGHTNING_ADDRESS_ENDPOINT: &str = "/claim_lightning_address";
 pub const CONFIGURATION_ENDPOINT: &str = "/config";
+pub const CONNECT_PEER_ENDPOINT: &str = "/connect_peer";
 pub const CONNECT_FED_ENDPOINT: &str = "/connect-fed"; // uses `-` for backwards compatibility
+pub const CREATE_API_TOKEN_ENDPOINT: &str = "/create_api_token";
 pub const CREATE_BOLT11_INVOICE_V2_ENDPOINT: &str = "/create_bolt11_invoice";
+pub const CREATE_INVOICE_BATCH_ENDPOINT: &str = "/create_invoice_batch";
+pub const CREATE_SNAPSHOT_ENDPOINT: &str = "/create_snapshot";
+pub const CREATE_REVERSE_SWAP_ENDPOINT: &str = "/create_reverse_swap";
+pub const CREATE_SWAP_ENDPOINT: &str = "/create_swap";
+pub const DISCONNECT_PEER_ENDPOINT: &str = "/disconnect_peer";
+pub const DRAIN_STATUS_ENDPOINT: &str = "/drain_status";
+pub const EVENTS_ENDPOINT: &str = "/events";
+pub const EXPORT_MISSION_CONTROL_ENDPOINT: &str = "/export_mission_control";
+pub const EXPORT_PAYMENT_LOG_ENDPOINT: &str = "/export_payment_log";
+pub const EXPORT_POLICY_ENDPOINT: &str = "/export_policy";
 pub const GATEWAY_INFO_ENDPOINT: &str = "/info";
 pub const GET_GATEWAY_ID_ENDPOINT: &str = "/id";
 pub const GATEWAY_INFO_POST_ENDPOINT: &str = "/info";
 pub const GET_FUNDING_ADDRESS_ENDPOINT: &str = "/get_funding_address";
+pub const IMPORT_MISSION_CONTROL_ENDPOINT: &str = "/import_mission_control";
+pub const IMPORT_POLICY_ENDPOINT: &str = "/import_policy";
 pub const LEAVE_FED_ENDPOINT: &str = "/leave-fed"; // uses `-` for backwards compatibility
+pub const LIQUIDITY_HEALTH_ENDPOINT: &str = "/liquidity_health";
+pub const LIQUIDITY_HISTORY_ENDPOINT: &str = "/liquidity_history";
+pub const LIST_API_TOKENS_ENDPOINT: &str = "/list_api_tokens";
 pub const LIST_ACTIVE_CHANNELS_ENDPOINT: &str = "/list_active_channels";
+pub const LIST_LIGHTNING_ADDRESSES_ENDPOINT: &str = "/list_lightning_addresses";
+pub const LIST_INVOICE_BATCHES_ENDPOINT: &str = "/list_invoice_batches";
+pub const LIST_PEERS_ENDPOINT: &str = "/list_peers";
+pub const LIST_REVERSE_SWAPS_ENDPOINT: &str = "/list_reverse_swaps";
+pub const LIST_SWAPS_ENDPOINT: &str = "/list_swaps";
+pub const LSPS1_CREATE_ORDER_ENDPOINT: &str = "/lsps1_create_order";
+pub const LSPS1_GET_INFO_ENDPOINT: &str = "/lsps1_get_info";
+pub const LSPS1_GET_ORDER_ENDPOINT: &str = "/lsps1_get_order";
+pub const LSPS1_PAY_ORDER_ENDPOINT: &str = "/lsps1_pay_order";
+pub const MISSION_CONTROL_STATS_ENDPOINT: &str = "/mission_control_stats";
+pub const MONITORING_BUNDLE_ENDPOINT: &str = "/monitoring_bundle";
 pub const OPEN_CHANNEL_ENDPOINT: &str = "/open_channel";
 pub const CLOSE_CHANNELS_WITH_PEER_ENDPOINT: &str = "/close_channels_with_peer";
+pub const REVOKE_API_TOKEN_ENDPOINT: &str = "/revoke_api_token";
 pub const ROUTING_INFO_V2_ENDPOINT: &str = "/routing_info";
+pub const PAYMENT_ATTEMPTS_ENDPOINT: &str = "/payment_attempts";
+pub const PAYMENT_LOG_ENDPOINT: &str = "/payment_log";
+pub const PAYMENT_RETRY_POLICY_ENDPOINT: &str = "/payment_retry_policy";
+pub const PAYMENT_SUMMARY_ENDPOINT: &str = "/payment_summary";
 pub const PAY_INVOICE_ENDPOINT: &str = "/pay_invoice";
+pub const PAY_KEYSEND_ENDPOINT: &str = "/pay_keysend";
+pub const QUERY_ROUTE_ENDPOINT: &str = "/query_route";
+pub const REBALANCE_STATUS_ENDPOINT: &str = "/rebalance_status";
+pub const REFUND_SWAP_ENDPOINT: &str = "/refund_swap";
+pub const REGISTER_LIGHTNING_ADDRESS_ENDPOINT: &str = "/register_lightning_address";
+pub const REMOVE_LIGHTNING_ADDRESS_ENDPOINT: &str = "/remove_lightning_address";
+pub const RESET_MISSION_CONTROL_ENDPOINT: &str = "/reset_mission_control";
 pub const RESTORE_ENDPOINT: &str = "/restore";
+pub const RESTORE_SNAPSHOT_ENDPOINT: &str = "/restore_snapshot";
 pub const SEND_PAYMENT_V2_ENDPOINT: &str = "/send_payment";
+pub const SET_ALERT_POLICY_ENDPOINT: &str = "/set_alert_policy";
 pub const SET_CONFIGURATION_ENDPOINT: &str = "/set_configuration";
+pub const SET_DRAINING_ENDPOINT: &str = "/set_draining";
+pub const SET_DYNAMIC_FEE_POLICY_ENDPOINT: &str = "/set_dynamic_fee_policy";
+pub const SET_INCOMING_LIMITS_ENDPOINT: &str = "/set_incoming_limits";
+pub const SET_LIGHTNING_PROTOCOLS_ENDPOINT: &str = "/set_lightning_protocols";
+pub const SET_LIQUIDITY_CAP_ENDPOINT: &str = "/set_liquidity_cap";
+pub const SET_PAYMENT_RETRY_POLICY_ENDPOINT: &str = "/set_payment_retry_policy";
+pub const SET_REBALANCE_BAND_ENDPOINT: &str = "/set_rebalance_band";
+pub const SET_REBALANCING_PAUSED_ENDPOINT: &str = "/set_rebalancing_paused";
+pub const SET_SPENDING_PASSWORD_ENDPOINT: &str = "/set_spending_password";
+pub const SET_SWEEP_POLICY_ENDPOINT: &str = "/set_sweep_policy";
+pub const SWEEP_POLICY_ENDPOINT: &str = "/sweep_policy";
+pub const UPDATE_CHANNEL_POLICY_ENDPOINT: &str = "/update_channel_policy";
 pub const WITHDRAW_ENDPOINT: &str = "/withdraw";
@@ -46,6 +46,7 @@ use secp256k1::{ecdh, KeyPair, PublicKey, Scalar, SecretKey};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tpe::{derive_agg_decryption_key, AggregateDecryptionKey};
+use tracing::debug;
 
 use crate::api::LnFederationApi;
 use crate::receive_sm::{ReceiveSMCommon, ReceiveSMState, ReceiveStateMachine};
@@ -175,6 +176,10 @@ pub struct RoutingInfo {
     pub receive_fee: PaymentFee,
     pub expiration_delta_default: u64,
     pub expiration_delta_minimum: u64,
+    /// The gateway temporarily doesn't accept outgoing payments, for example
+    /// because its lightning node is low on funds
+    #[serde(default)]
+    pub send_paused: bool,
 }
 
 #[derive(
@@ -384,6 +389,10 @@ impl LightningClientModule {
             .map_err(SendPaymentError::GatewayError)?
             .ok_or(SendPaymentError::UnknownFederation)?;
 
+        if payment_info.send_paused {
+            return Err(SendPaymentError::TemporarilyUnavailable);
+        }
+
         if !payment_info.send_fee_default.le(&payment_fee_limit) {
             return Err(SendPaymentError::PaymentFeeExceedsLimit(
                 payment_info.send_fee_default,
@@ -789,8 +798,12 @@ impl LightningClientModule {
 
         let agg_decryption_key = derive_agg_decryption_key(&self.cfg.tpe_agg_pk, &encryption_seed);
 
-        if !contract.verify_agg_decryption_key(&self.cfg.tpe_agg_pk, &agg_decryption_key) {
-            return None; // The decryption key is not derived from our pk
+        // The contract was not created by us, so its ciphertext is checked as well
+        if let Err(error) =
+            contract.check_agg_decryption_key(&self.cfg.tpe_agg_pk, &agg_decryption_key)
+        {
+            debug!(?error, "Incoming contract is not decryptable with our key");
+            return None;
         }
 
         contract.decrypt_preimage(&agg_decryption_key)?;
@@ -887,6 +900,8 @@ pub enum SendPaymentError {
     FederationError(String),
     #[error("We failed to finalize the funding transaction")]
     FinalizationError(String),
+    #[error("The gateway temporarily doesn't send payments")]
+    TemporarilyUnavailable,
 }
 
 #[derive(Error, Debug, Clone, Eq, PartialEq)]
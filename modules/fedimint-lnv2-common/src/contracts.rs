@@ -5,9 +5,10 @@ use secp256k1::schnorr::Signature;
 use secp256k1::{Message, PublicKey};
 use serde::{Deserialize, Serialize};
 use tpe::{
-    create_decryption_key_share, decrypt_preimage, encrypt_preimage, verify_agg_decryption_key,
-    verify_ciphertext, verify_decryption_key_share, AggregateDecryptionKey, AggregatePublicKey,
-    CipherText, DecryptionKeyShare, PublicKeyShare, SecretKeyShare,
+    check_agg_decryption_key, check_ciphertext, create_decryption_key_share, decrypt_preimage,
+    encrypt_preimage, verify_agg_decryption_key, verify_ciphertext, verify_decryption_key_share,
+    verify_decryption_key_shares, AggregateDecryptionKey, AggregatePublicKey, CipherText,
+    DecryptionKeyShare, PublicKeyShare, SecretKeyShare, TpeError,
 };
 
 use crate::ContractId;
@@ -70,6 +71,17 @@ impl IncomingContract {
         verify_ciphertext(&self.ciphertext, &self.commitment.consensus_hash())
     }
 
+    /// Checks that the ciphertext encrypts a preimage for this contract's
+    /// commitment
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TpeError::InvalidCiphertext`] if the ciphertext is not bound
+    /// to the commitment.
+    pub fn check(&self) -> Result<(), TpeError> {
+        check_ciphertext(&self.ciphertext, &self.commitment.consensus_hash())
+    }
+
     pub fn verify_decryption_share(
         &self,
         pk: &PublicKeyShare,
@@ -83,6 +95,15 @@ impl IncomingContract {
         )
     }
 
+    /// Verifies the decryption shares of multiple peers at once, which is
+    /// faster than verifying them individually if all of them are valid
+    pub fn verify_decryption_shares(
+        &self,
+        shares: &[(PublicKeyShare, DecryptionKeyShare)],
+    ) -> bool {
+        verify_decryption_key_shares(shares, &self.ciphertext, &self.commitment.consensus_hash())
+    }
+
     pub fn verify_agg_decryption_key(
         &self,
         agg_pk: &AggregatePublicKey,
@@ -96,6 +117,26 @@ impl IncomingContract {
         )
     }
 
+    /// Checks the ciphertext and then the aggregate decryption key for it
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TpeError::InvalidCiphertext`] if the ciphertext is not bound
+    /// to the commitment and [`TpeError::InvalidAggregate`] if the key does not
+    /// decrypt it.
+    pub fn check_agg_decryption_key(
+        &self,
+        agg_pk: &AggregatePublicKey,
+        agg_decryption_key: &AggregateDecryptionKey,
+    ) -> Result<(), TpeError> {
+        check_agg_decryption_key(
+            agg_pk,
+            agg_decryption_key,
+            &self.ciphertext,
+            &self.commitment.consensus_hash(),
+        )
+    }
+
     pub fn verify_preimage(&self, preimage: &[u8; 32]) -> bool {
         preimage.consensus_hash::<sha256::Hash>() == self.commitment.payment_hash
     }
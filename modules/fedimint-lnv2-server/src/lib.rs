@@ -55,6 +55,7 @@ use group::Curve;
 use rand::rngs::OsRng;
 use strum::IntoEnumIterator;
 use tpe::{AggregatePublicKey, PublicKeyShare, SecretKeyShare};
+use tracing::debug;
 
 use crate::db::{
     BlockCountVoteKey, BlockCountVotePrefix, DbKeyPrefix, GatewayKey, GatewayPrefix,
@@ -211,7 +212,7 @@ impl ServerModuleInit for LightningInit {
                             network: params.consensus.network,
                         },
                         private: LightningConfigPrivate {
-                            sk: sks[peer.to_usize()],
+                            sk: sks[peer.to_usize()].clone(),
                         },
                     }
                     .to_erased(),
@@ -421,6 +422,7 @@ impl ServerModule for Lightning {
                     .await
                     .ok_or(LightningInputError::UnknownContract)?;
 
+                // The ciphertext has already been checked in process_output
                 if !contract
                     .verify_agg_decryption_key(&self.cfg.consensus.tpe_agg_pk, agg_decryption_key)
                 {
@@ -466,9 +468,10 @@ impl ServerModule for Lightning {
                 LightningOutputOutcome::Outgoing
             }
             LightningOutputV0::Incoming(contract) => {
-                if !contract.verify() {
-                    return Err(LightningOutputError::InvalidContract);
-                }
+                contract.check().map_err(|e| {
+                    debug!("Rejecting invalid incoming contract: {e}");
+                    LightningOutputError::InvalidContract
+                })?;
 
                 if contract.commitment.expiration <= self.consensus_unix_time(dbtx).await {
                     return Err(LightningOutputError::ContractExpired);
@@ -0,0 +1,75 @@
+#!/usr/bin/env bash
+# Live status summary of the dev federation, shown as a pane by the mprocs and
+# tmuxinator setups. Refresh interval can be set with `FM_STATUS_INTERVAL`.
+
+set -euo pipefail
+
+eval "$(devimint env)"
+
+echo "Waiting for fedimint start"
+
+if [ "$(devimint wait)" = "ERROR" ]; then
+  echo "fedimint didn't start correctly"
+  exit 1
+fi
+
+eval "$(devimint env)"
+
+interval="${FM_STATUS_INTERVAL:-5}"
+
+# A dedicated client, so polling doesn't lock the database of the user's client
+status_client="fedimint-cli --data-dir $FM_CLIENT_BASE_DIR/status"
+if [ ! -e "$FM_CLIENT_BASE_DIR/status/client.db" ]; then
+  mkdir -p "$FM_CLIENT_BASE_DIR/status"
+  $status_client join-federation "$FM_INVITE_CODE" > /dev/null
+fi
+
+function federation_status() {
+  echo "== federation =="
+  for ((peer = 0; peer < FM_FED_SIZE; peer++)); do
+    if status="$($status_client dev api --peer-id "$peer" status 2> /dev/null)"; then
+      jq -r --arg peer "$peer" '.value
+        | "fedimintd-\($peer): \(.server), session \(.federation.session_count // "-"), \(.federation.peers_online // 0) peers online, \(.federation.peers_flagged // 0) flagged"' \
+        <<< "$status"
+    else
+      echo "fedimintd-$peer: unreachable"
+    fi
+  done
+}
+
+function gateway_status() {
+  echo "== gateways =="
+  for gateway in CLN LND; do
+    gateway_cli="FM_GWCLI_$gateway"
+    if info="$(${!gateway_cli} info 2> /dev/null)"; then
+      jq -r --arg gateway "$gateway" '"gateway-\($gateway): \(.gateway_state)",
+        (.federations[] | "  \(.federation_id): \(.balance_msat) msat")' <<< "$info"
+    else
+      echo "gateway-$gateway: unreachable"
+    fi
+  done
+}
+
+function latest_errors() {
+  echo "== latest errors =="
+  grep -h "ERROR" "$FM_LOGS_DIR"/*.log 2> /dev/null | tail -n 5 | cut -c -200 || true
+}
+
+while true; do
+  summary="$(
+    echo "bitcoind block height: $($FM_BTC_CLIENT getblockcount 2> /dev/null || echo unreachable)"
+    echo
+    federation_status
+    echo
+    gateway_status
+    echo
+    latest_errors
+  )"
+
+  clear
+  echo "$(date +%T), refreshing every ${interval}s"
+  echo
+  echo "$summary"
+
+  sleep "$interval"
+done